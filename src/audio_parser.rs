@@ -1,92 +1,502 @@
+//! Decoding audio files into the 16KHz mono samples whisper takes.
+
 use std::fs::File;
 use std::path::Path;
 
 use symphonia::core::audio::SampleBuffer;
-use symphonia::core::codecs::DecoderOptions;
+use symphonia::core::codecs::{DecoderOptions, CODEC_TYPE_NULL};
 use symphonia::core::errors::Error;
-use symphonia::core::formats::FormatOptions;
-use symphonia::core::io::MediaSourceStream;
-use symphonia::core::meta::MetadataOptions;
-use symphonia::core::probe::Hint;
+use symphonia::core::formats::{FormatOptions, FormatReader, Track};
+use symphonia::core::io::{MediaSource, MediaSourceStream, ReadOnlySource};
+use symphonia::core::meta::{MetadataOptions, MetadataRevision, StandardTagKey};
+use symphonia::core::probe::{Hint, ProbeResult};
 
-const WHISPER_SAMPLE_RATE: u32 = 16000;
+use crate::resample;
+use crate::warning::Warning;
 
-pub fn parse_audio_file(audio_path: &str) -> Vec<f32> {
-    // Create a media source. Note that the MediaSource trait is automatically implemented for File,
-    // among other types.
-    let file = Box::new(File::open(Path::new(&audio_path)).unwrap());
+/// The sample rate whisper models take, and the default target of the audio preprocessing.
+pub const WHISPER_SAMPLE_RATE: u32 = 16000;
 
-    // Create the media source stream using the boxed media source from above.
-    let mss = MediaSourceStream::new(file, Default::default());
+/// The audio formats decoded, those of the symphonia features enabled in Cargo.toml (its
+/// defaults, plus mp3, isomp4 and aac).
+pub const SUPPORTED_FORMATS: &[&str] = &[
+    "wav",
+    "mp3",
+    "flac",
+    "ogg (Vorbis, FLAC)",
+    "mkv/webm (Vorbis, FLAC, PCM, AAC)",
+    "mp4/m4a (AAC)",
+];
+
+/// Decode an audio file into whisper's 16KHz mono samples, resampling audio at any other
+/// rate. Panics where `try_parse_audio_file` fails.
+pub fn parse_audio_file(audio_path: impl AsRef<Path>) -> Vec<f32> {
+    expect_decoded(try_parse_audio_file(audio_path))
+}
+
+/// Decode an audio file like `parse_audio_file`, failing with `Error::UnsupportedFormat` on
+/// files in no supported format, and `Error::Io` on files that can't be read.
+pub fn try_parse_audio_file(audio_path: impl AsRef<Path>) -> Result<Vec<f32>, crate::error::Error> {
+    try_parse_audio_file_with_sample_rate(audio_path, WHISPER_SAMPLE_RATE)
+}
+
+/// The decoded audio, panicking with the error's message for the panicking variants of the
+/// `try_parse_*` functions.
+fn expect_decoded<T>(decoded: Result<T, crate::error::Error>) -> T {
+    decoded.unwrap_or_else(|err| panic!("{}", err))
+}
+
+/// What probing an audio file tells about it, without decoding the audio.
+#[derive(Debug, Clone, Default, PartialEq, Eq, serde::Serialize)]
+pub struct AudioInfo {
+    /// The sample rate the container declares, `None` when it declares none (or zero).
+    pub sample_rate: Option<u32>,
+    pub channels: Option<usize>,
+    /// The language the file declares, as written in it: an ID3 `TLAN` frame, a WAV `ILNG`
+    /// or Vorbis `LANGUAGE` tag, or the language of a Matroska audio track. Usually an
+    /// ISO 639 code, see `language::whisper_code`. symphonia doesn't read the language of
+    /// mp4 tracks.
+    pub language: Option<String>,
+}
+
+/// Probe the audio file at `audio_path` for its format and metadata, reading no more of it
+/// than the container headers. Panics where `try_probe_audio_info` fails.
+pub fn probe_audio_info(audio_path: impl AsRef<Path>) -> AudioInfo {
+    expect_decoded(try_probe_audio_info(audio_path))
+}
+
+/// Probe the audio file at `audio_path` like `probe_audio_info`, failing on unreadable
+/// files, unknown formats and files without an audio track.
+pub fn try_probe_audio_info(
+    audio_path: impl AsRef<Path>,
+) -> Result<AudioInfo, crate::error::Error> {
+    let audio_path = audio_path.as_ref();
+    let mut probed = try_probe_file(audio_path)?;
+    let track = audio_track(probed.format.as_ref(), &audio_path.display().to_string())?;
+    let track_language = track.language.clone();
+    let sample_rate = track.codec_params.sample_rate.filter(|&rate| rate != 0);
+    let channels = track.codec_params.channels.map(|channels| channels.count());
+
+    // tags read before the container (such as ID3) come first, then the container's own
+    let tagged_language = probed
+        .metadata
+        .get()
+        .and_then(|metadata| metadata.current().and_then(language_tag))
+        .or_else(|| probed.format.metadata().current().and_then(language_tag));
+    Ok(AudioInfo {
+        sample_rate,
+        channels,
+        language: tagged_language.or(track_language),
+    })
+}
+
+/// The value of the first non-empty language tag of `revision`.
+fn language_tag(revision: &MetadataRevision) -> Option<String> {
+    revision
+        .tags()
+        .iter()
+        .filter(|tag| tag.std_key == Some(StandardTagKey::Language))
+        .map(|tag| {
+            tag.value
+                .to_string()
+                .trim_end_matches('\0')
+                .trim()
+                .to_string()
+        })
+        .find(|language| !language.is_empty())
+}
+
+/// Decode an audio file into `audio`, replacing its contents but keeping its allocation, to
+/// decode many files without allocating a buffer for each. Panics where
+/// `try_parse_audio_file_into` fails.
+pub fn parse_audio_file_into(audio_path: impl AsRef<Path>, audio: &mut Vec<f32>) {
+    expect_decoded(try_parse_audio_file_into(audio_path, audio))
+}
+
+/// Decode an audio file into `audio` like `parse_audio_file_into`, failing like
+/// `try_parse_audio_file`. `audio` is left empty on failure.
+pub fn try_parse_audio_file_into(
+    audio_path: impl AsRef<Path>,
+    audio: &mut Vec<f32>,
+) -> Result<(), crate::error::Error> {
+    let audio_path = audio_path.as_ref();
+    let format = try_probe_file(audio_path)?.format;
+    audio.clear();
+    let decoded = decode_audio(
+        format,
+        &audio_path.display().to_string(),
+        WHISPER_SAMPLE_RATE,
+        false,
+        None,
+        audio,
+    );
+    if decoded.is_err() {
+        audio.clear();
+    }
+    decoded.map(|_| ())
+}
+
+/// The container format of a file as far as it is known, to help symphonia pick the format
+/// reader without relying on the file's extension. An empty hint leaves it to the content.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FormatHint {
+    /// A file extension such as `"mp3"`, without the dot.
+    pub extension: Option<String>,
+    /// A MIME type such as `"audio/ogg"`.
+    pub mime_type: Option<String>,
+}
+
+impl FormatHint {
+    pub fn extension(extension: impl Into<String>) -> Self {
+        FormatHint {
+            extension: Some(extension.into()),
+            mime_type: None,
+        }
+    }
+
+    pub fn mime_type(mime_type: impl Into<String>) -> Self {
+        FormatHint {
+            extension: None,
+            mime_type: Some(mime_type.into()),
+        }
+    }
+
+    fn to_hint(&self) -> Hint {
+        let mut hint = Hint::new();
+        if let Some(extension) = &self.extension {
+            hint.with_extension(extension);
+        }
+        if let Some(mime_type) = &self.mime_type {
+            hint.mime_type(mime_type);
+        }
+        hint
+    }
+}
+
+/// Decode an audio file like `parse_audio_file`, using `hint` instead of its extension to
+/// pick the container format, for extensionless or ambiguous files of a known format. When
+/// the hint is wrong the file is probed again without one. Panics where
+/// `try_parse_audio_file_with_hint` fails.
+pub fn parse_audio_file_with_hint(audio_path: impl AsRef<Path>, hint: &FormatHint) -> Vec<f32> {
+    expect_decoded(try_parse_audio_file_with_hint(audio_path, hint))
+}
+
+/// Decode an audio file with a format hint like `parse_audio_file_with_hint`, failing like
+/// `try_parse_audio_file`.
+pub fn try_parse_audio_file_with_hint(
+    audio_path: impl AsRef<Path>,
+    hint: &FormatHint,
+) -> Result<Vec<f32>, crate::error::Error> {
+    let audio_path = audio_path.as_ref();
+    let format = try_probe_file_with_hint(audio_path, hint)?.format;
+    let mut audio_data = Vec::new();
+    decode_audio(
+        format,
+        &audio_path.display().to_string(),
+        WHISPER_SAMPLE_RATE,
+        false,
+        None,
+        &mut audio_data,
+    )?;
+    Ok(audio_data)
+}
+
+/// Decode an audio file resampled to `sample_rate` instead of whisper's 16KHz, for pipelines
+/// feeding models with a non-standard sample rate. Panics where
+/// `try_parse_audio_file_with_sample_rate` fails.
+///
+/// `Transcriber` always decodes at `WHISPER_SAMPLE_RATE`, which its timestamps assume.
+pub fn parse_audio_file_with_sample_rate(
+    audio_path: impl AsRef<Path>,
+    sample_rate: u32,
+) -> Vec<f32> {
+    expect_decoded(try_parse_audio_file_with_sample_rate(
+        audio_path,
+        sample_rate,
+    ))
+}
+
+/// Decode an audio file at `sample_rate` like `parse_audio_file_with_sample_rate`, failing
+/// like `try_parse_audio_file`.
+pub fn try_parse_audio_file_with_sample_rate(
+    audio_path: impl AsRef<Path>,
+    sample_rate: u32,
+) -> Result<Vec<f32>, crate::error::Error> {
+    let audio_path = audio_path.as_ref();
+    let format = try_probe_file(audio_path)?.format;
+    let mut audio_data = Vec::new();
+    decode_audio(
+        format,
+        &audio_path.display().to_string(),
+        sample_rate,
+        false,
+        None,
+        &mut audio_data,
+    )?;
+    Ok(audio_data)
+}
+
+/// Decode an audio file like `try_parse_audio_file`, but fail with
+/// `Error::UnsupportedSampleRate` on audio that isn't sampled at 16KHz instead of resampling
+/// it, for callers who want no resampling losses.
+pub fn parse_audio_file_strict(
+    audio_path: impl AsRef<Path>,
+) -> Result<Vec<f32>, crate::error::Error> {
+    Ok(decode_audio_file(audio_path.as_ref(), true, &mut |_, _| ())?.0)
+}
+
+/// Open the audio file at `audio_path` and probe its container format.
+///
+/// The format is recognized by the file's content. The extension is only a hint, and when
+/// probing with it fails the file is probed again without one, so mislabeled files (an mp3
+/// named `.wav`) still decode. Files in no format symphonia reads fail with
+/// `Error::UnsupportedFormat`.
+fn try_probe_file(audio_path: &Path) -> Result<ProbeResult, crate::error::Error> {
+    let hint = match audio_path
+        .extension()
+        .and_then(|extension| extension.to_str())
+    {
+        Some(extension) => FormatHint::extension(extension),
+        None => FormatHint::default(),
+    };
+    try_probe_file_with_hint(audio_path, &hint)
+}
+
+/// Open and probe the audio file at `audio_path` with `hint` instead of its extension,
+/// probing again without a hint when that fails.
+fn try_probe_file_with_hint(
+    audio_path: &Path,
+    hint: &FormatHint,
+) -> Result<ProbeResult, crate::error::Error> {
+    // Note that the MediaSource trait is automatically implemented for File, among other types.
+    let open =
+        || -> std::io::Result<Box<dyn MediaSource>> { Ok(Box::new(File::open(audio_path)?)) };
+
+    match probe(open()?, &hint.to_hint()) {
+        Ok(probed) => Ok(probed),
+        Err(_) => probe(open()?, &Hint::new())
+            .map_err(|err| probe_failure(err, &audio_path.display().to_string())),
+    }
+}
 
-    // Create a hint to help the format registry guess what format reader is appropriate. In this
-    // example we'll leave it empty.
-    let hint = Hint::new();
+/// The error of a failed probe of `input`: `Error::UnsupportedFormat` when no format reader
+/// recognized it.
+fn probe_failure(err: Error, input: &str) -> crate::error::Error {
+    match err {
+        Error::IoError(err) => crate::error::Error::Io(err),
+        _ => crate::error::Error::UnsupportedFormat(input.to_string()),
+    }
+}
 
-    // Use the default options when reading and decoding.
+/// Probe the container format of `source`, `hint` helps picking the format reader.
+fn probe(source: Box<dyn MediaSource>, hint: &Hint) -> Result<ProbeResult, Error> {
+    // Create the media source stream using the boxed media source from above.
+    let mss = MediaSourceStream::new(source, Default::default());
+
+    // Use the default options when reading.
     let format_opts: FormatOptions = Default::default();
     let metadata_opts: MetadataOptions = Default::default();
-    let decoder_opts: DecoderOptions = Default::default();
 
     // Probe the media source stream for a format.
-    let probed = symphonia::default::get_probe()
-        .format(&hint, mss, &format_opts, &metadata_opts)
-        .unwrap();
+    symphonia::default::get_probe().format(hint, mss, &format_opts, &metadata_opts)
+}
 
-    // Get the format reader yielded by the probe operation.
-    let mut format = probed.format;
+/// A step run over decoded mono audio at its source rate, before it is resampled, with the
+/// samples and their rate.
+pub(crate) type BeforeResampling<'a> = &'a mut dyn FnMut(&mut [f32], u32);
 
-    // Get the default track.
-    let track = format.default_track().unwrap();
+/// Decode an in-memory audio file, `mime_type` is used as a hint for the container format.
+/// Panics where `try_parse_audio_bytes` fails.
+pub fn parse_audio_bytes(bytes: Vec<u8>, mime_type: Option<&str>) -> Vec<f32> {
+    expect_decoded(try_parse_audio_bytes(bytes, mime_type))
+}
 
-    if let Some(sample_rate) = track.codec_params.sample_rate {
-        if sample_rate != WHISPER_SAMPLE_RATE {
-            panic!(
-                "audio sample rate must be 16KHz, use {} to convert to mono,16KHz,f32 audio",
-                "ffmpeg -i <input_audio_file> -ac 1 -ar 16000 -sample_fmt fltp <output_audio_file>"
-            );
-        }
+/// Decode an in-memory audio file like `parse_audio_bytes`, failing with
+/// `Error::UnsupportedFormat` on audio in no supported format.
+pub fn try_parse_audio_bytes(
+    bytes: Vec<u8>,
+    mime_type: Option<&str>,
+) -> Result<Vec<f32>, crate::error::Error> {
+    parse_audio(Box::new(std::io::Cursor::new(bytes)), mime_type, None)
+}
+
+/// Decode an in-memory audio file like `try_parse_audio_bytes`, running `preprocess` over
+/// the decoded audio at its source rate, before it is resampled.
+pub(crate) fn decode_audio_bytes(
+    bytes: Vec<u8>,
+    mime_type: Option<&str>,
+    preprocess: BeforeResampling<'_>,
+) -> Result<Vec<f32>, crate::error::Error> {
+    parse_audio(
+        Box::new(std::io::Cursor::new(bytes)),
+        mime_type,
+        Some(preprocess),
+    )
+}
+
+/// Decode audio read from a stream as it arrives, without seeking. `mime_type` is used as a
+/// hint for the container format. Panics where `try_parse_audio_stream` fails.
+pub fn parse_audio_stream<R>(reader: R, mime_type: Option<&str>) -> Vec<f32>
+where
+    R: std::io::Read + Send + Sync + 'static,
+{
+    expect_decoded(try_parse_audio_stream(reader, mime_type))
+}
+
+/// Decode audio read from a stream like `parse_audio_stream`, failing with
+/// `Error::UnsupportedFormat` on audio in no supported format.
+pub fn try_parse_audio_stream<R>(
+    reader: R,
+    mime_type: Option<&str>,
+) -> Result<Vec<f32>, crate::error::Error>
+where
+    R: std::io::Read + Send + Sync + 'static,
+{
+    parse_audio(Box::new(ReadOnlySource::new(reader)), mime_type, None)
+}
+
+/// Decode audio read from a stream like `try_parse_audio_stream`, running `preprocess` over
+/// the decoded audio at its source rate, before it is resampled.
+pub(crate) fn decode_audio_stream<R>(
+    reader: R,
+    mime_type: Option<&str>,
+    preprocess: BeforeResampling<'_>,
+) -> Result<Vec<f32>, crate::error::Error>
+where
+    R: std::io::Read + Send + Sync + 'static,
+{
+    parse_audio(
+        Box::new(ReadOnlySource::new(reader)),
+        mime_type,
+        Some(preprocess),
+    )
+}
+
+fn parse_audio(
+    source: Box<dyn MediaSource>,
+    mime_type: Option<&str>,
+    preprocess: Option<BeforeResampling<'_>>,
+) -> Result<Vec<f32>, crate::error::Error> {
+    let mut hint = Hint::new();
+    if let Some(mime_type) = mime_type {
+        hint.mime_type(mime_type);
     }
+    let input = "the audio";
+    let probed = probe(source, &hint).map_err(|err| probe_failure(err, input))?;
+    let mut audio_data = Vec::new();
+    decode_audio(
+        probed.format,
+        input,
+        WHISPER_SAMPLE_RATE,
+        false,
+        preprocess,
+        &mut audio_data,
+    )?;
+    Ok(audio_data)
+}
+
+/// Decode an audio file at whisper's 16KHz like `parse_audio_file`, with a
+/// `Warning::SampleRateMismatch` when its header declares another sample rate than the
+/// decoded audio has. Files that can't be opened or are in an unsupported format fail
+/// instead of panicking, as do files not at 16KHz when `strict` forbids resampling them.
+/// `preprocess` runs over the decoded audio at its source rate, before it is resampled.
+pub(crate) fn decode_audio_file(
+    audio_path: &Path,
+    strict: bool,
+    preprocess: BeforeResampling<'_>,
+) -> Result<(Vec<f32>, Option<Warning>), crate::error::Error> {
+    let mut audio_data = Vec::new();
+    let warning = decode_audio(
+        try_probe_file(audio_path)?.format,
+        &audio_path.display().to_string(),
+        WHISPER_SAMPLE_RATE,
+        strict,
+        Some(preprocess),
+        &mut audio_data,
+    )?;
+    Ok((audio_data, warning))
+}
+
+/// Decode the audio track of `format`, appending its mono samples at `sample_rate` to
+/// `audio_data`. `input` names the audio in errors.
+///
+/// The sample rate is the one of the first decoded buffer, as headers may leave it out or
+/// get it wrong. A header disagreeing with the decoded audio is returned as a warning. Audio
+/// at `sample_rate` is decoded straight into `audio_data`, audio at any other rate is
+/// decoded whole and then resampled, or fails with `Error::UnsupportedSampleRate` when
+/// `strict`. `preprocess` runs over the decoded audio at its source rate, before it is
+/// resampled.
+fn decode_audio(
+    mut format: Box<dyn FormatReader>,
+    input: &str,
+    sample_rate: u32,
+    strict: bool,
+    preprocess: Option<BeforeResampling<'_>>,
+    audio_data: &mut Vec<f32>,
+) -> Result<Option<Warning>, crate::error::Error> {
+    let start = audio_data.len();
+    // Use the default options when decoding.
+    let decoder_opts: DecoderOptions = Default::default();
+
+    let track = audio_track(format.as_ref(), input)?;
+
+    // The sample rate declared by the container header. The decoded stream is what gets
+    // checked, this is only used to report headers that disagree with it. Some writers put a
+    // zero rate in mp4 headers when they leave it to the codec configuration.
+    let declared_sample_rate = track.codec_params.sample_rate.filter(|&rate| rate != 0);
+    let mut warning = None;
 
     if let Some(channels) = track.codec_params.channels {
         let channel_count = channels.count();
         if channel_count > 2 {
-            panic!(
-                "{} channels not supported, use {} to convert to mono,16KHz,f32 audio",
-                channel_count,
-                "ffmpeg -i <input_audio_file> -ac 1 -ar 16000 -sample_fmt fltp <output_audio_file>"
-            );
+            return Err(crate::error::Error::UnsupportedChannels(channel_count));
         }
     }
 
-    // Create a decoder for the track.
+    // Create a decoder for the track, there is none for codecs no enabled feature decodes.
     let mut decoder = symphonia::default::get_codecs()
         .make(&track.codec_params, &decoder_opts)
-        .unwrap();
+        .map_err(|_| crate::error::Error::UnsupportedFormat(input.to_string()))?;
 
     // Store the track identifier, we'll use it to filter packets.
     let track_id = track.id;
+    // the length the header declares, which streams cut short fall behind by more than
+    // encoder delay and padding
+    let declared_frames = track.codec_params.n_frames;
+    let frames_slack = declared_frames.map_or(0, |frames| {
+        (frames / 100).max(2 * track.codec_params.max_frames_per_packet.unwrap_or(0))
+    });
+    let mut decoded_frames = 0u64;
 
     let mut sample_buf = None;
+    // the rate of audio that needs resampling, decoded into `native` until then
+    let mut native_rate = None;
+    let mut native = Vec::new();
 
-    let mut audio_data: Vec<f32> = vec![];
     loop {
         // Get the next packet from the format reader.
         let packet = match format.next_packet() {
             Ok(packet) => packet,
             Err(Error::ResetRequired) => {
-                // The track list has been changed. Re-examine it and create a new set of decoders,
-                // then restart the decode loop. This is an advanced feature and it is not
-                // unreasonable to consider this "the end." As of v0.5.0, the only usage of this is
-                // for chained OGG physical streams.
-                unimplemented!();
+                // The track list has been changed, as of symphonia 0.5 only by chained Ogg
+                // physical streams. Decoding on would need new decoders for the new tracks.
+                return Err(crate::error::Error::AudioTruncated {
+                    decoded_secs: decoded_seconds(audio_data, sample_rate, &native, native_rate),
+                    reason: "the stream changes its tracks midway (a chained Ogg stream), which \
+                             isn't supported"
+                        .to_string(),
+                });
             }
-            Err(Error::IoError(_)) => {
+            // symphonia reports the end of the stream as an unexpected EOF
+            Err(Error::IoError(err)) if err.kind() == std::io::ErrorKind::UnexpectedEof => {
                 break;
             }
             Err(err) => {
                 // A unrecoverable error occured, halt decoding.
-                panic!("{}", err);
+                return Err(truncated(
+                    err,
+                    decoded_seconds(audio_data, sample_rate, &native, native_rate),
+                ));
             }
         };
         // If the packet does not belong to the selected track, skip it.
@@ -97,6 +507,7 @@ pub fn parse_audio_file(audio_path: &str) -> Vec<f32> {
         // Decode the packet into audio samples, ignoring any decode errors.
         match decoder.decode(&packet) {
             Ok(audio_buf) => {
+                decoded_frames += audio_buf.frames() as u64;
                 // The decoded audio samples may now be accessed via the audio buffer if per-channel
                 // slices of samples in their native decoded format is desired. Use-cases where
                 // the samples need to be accessed in an interleaved order or converted into
@@ -111,6 +522,25 @@ pub fn parse_audio_file(audio_path: &str) -> Vec<f32> {
                     // Get the audio buffer specification.
                     let spec = *audio_buf.spec();
 
+                    if spec.rate != sample_rate {
+                        if strict {
+                            return Err(crate::error::Error::UnsupportedSampleRate {
+                                sample_rate: spec.rate,
+                                required: sample_rate,
+                            });
+                        }
+                        native_rate = Some(spec.rate);
+                    }
+                    warning = match declared_sample_rate {
+                        Some(declared) if declared != spec.rate => {
+                            Some(Warning::SampleRateMismatch {
+                                declared,
+                                decoded: spec.rate,
+                            })
+                        }
+                        _ => None,
+                    };
+
                     // Get the capacity of the decoded buffer. Note: This is capacity, not length!
                     let duration = audio_buf.capacity() as u64;
 
@@ -121,22 +551,697 @@ pub fn parse_audio_file(audio_path: &str) -> Vec<f32> {
                 if let Some(buf) = &mut sample_buf {
                     let is_stereo = audio_buf.spec().channels.count() == 2;
                     buf.copy_interleaved_ref(audio_buf);
+                    let out = match native_rate {
+                        Some(_) => &mut native,
+                        None => &mut *audio_data,
+                    };
 
-                    let mut feed_buffer;
+                    // The samples may now be access via the `samples()` function. Stereo is
+                    // mixed down to mono by averaging the channels, as
+                    // `whisper_rs::convert_stereo_to_mono_audio` does.
                     if is_stereo {
-                        feed_buffer =
-                            whisper_rs::convert_stereo_to_mono_audio(&buf.samples()).unwrap();
+                        out.extend(
+                            buf.samples()
+                                .chunks_exact(2)
+                                .map(|frame| (frame[0] + frame[1]) / 2.0),
+                        );
                     } else {
-                        feed_buffer = buf.samples().to_vec();
+                        out.extend_from_slice(buf.samples());
                     }
-
-                    // The samples may now be access via the `samples()` function.
-                    audio_data.append(&mut feed_buffer);
                 }
             }
             Err(Error::DecodeError(_)) => (),
+            Err(Error::IoError(err)) if err.kind() != std::io::ErrorKind::UnexpectedEof => {
+                return Err(truncated(
+                    Error::IoError(err),
+                    decoded_seconds(audio_data, sample_rate, &native, native_rate),
+                ));
+            }
             Err(_) => break,
         }
     }
-    audio_data
+    if let Some(declared) = declared_frames {
+        if decoded_frames + frames_slack < declared {
+            return Err(crate::error::Error::AudioTruncated {
+                decoded_secs: decoded_seconds(audio_data, sample_rate, &native, native_rate),
+                reason: format!(
+                    "the stream ended after {} of the {} frames its header declares",
+                    decoded_frames, declared
+                ),
+            });
+        }
+    }
+    if let Some(native_rate) = native_rate {
+        if let Some(preprocess) = preprocess {
+            preprocess(&mut native, native_rate);
+        }
+        audio_data.extend(resample::resample(&native, native_rate, sample_rate));
+    } else if let Some(preprocess) = preprocess {
+        preprocess(&mut audio_data[start..], sample_rate);
+    }
+    Ok(warning)
+}
+
+/// The first audio track of `format`. Containers like mp4 and mkv also hold video and
+/// subtitle tracks, which have no codec symphonia can decode and may come first.
+fn audio_track<'a>(
+    format: &'a dyn FormatReader,
+    input: &str,
+) -> Result<&'a Track, crate::error::Error> {
+    format
+        .tracks()
+        .iter()
+        .find(|track| track.codec_params.codec != CODEC_TYPE_NULL)
+        .ok_or_else(|| crate::error::Error::NoAudioTrack(input.to_string()))
+}
+
+/// The error of reading the audio failing after `decoded` seconds, which would otherwise end
+/// it early and truncate the transcript without notice.
+fn truncated(err: Error, decoded: f32) -> crate::error::Error {
+    crate::error::Error::AudioTruncated {
+        decoded_secs: decoded,
+        reason: err.to_string(),
+    }
+}
+
+/// The seconds of audio decoded so far, into `audio_data` at `sample_rate` or into `native`
+/// at `native_rate` when it is resampled.
+fn decoded_seconds(
+    audio_data: &[f32],
+    sample_rate: u32,
+    native: &[f32],
+    native_rate: Option<u32>,
+) -> f32 {
+    match native_rate {
+        Some(rate) => native.len() as f32 / rate as f32,
+        None => audio_data.len() as f32 / sample_rate as f32,
+    }
+}
+
+/// The ffmpeg command converting audio to what the preprocessing takes.
+pub(crate) fn ffmpeg_command(sample_rate: u32) -> String {
+    format!(
+        "ffmpeg -i <input_audio_file> -ac 1 -ar {} -sample_fmt fltp <output_audio_file>",
+        sample_rate
+    )
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    /// Write a 16 bit PCM WAV file holding the given interleaved samples.
+    pub(crate) use crate::test_util::write_pcm_wav as write_test_wav;
+
+    /// `mp3` with its ID3 tag replaced by an ID3v2.3 tag holding a `TLAN` frame.
+    fn with_id3_language(mp3: &[u8], language: &str) -> Vec<u8> {
+        let syncsafe = |bytes: &[u8]| bytes.iter().fold(0, |size, &b| size << 7 | b as usize);
+        let frames = match &mp3[..3] {
+            b"ID3" => &mp3[10 + syncsafe(&mp3[6..10])..],
+            _ => mp3,
+        };
+
+        let mut tlan = b"TLAN".to_vec();
+        tlan.extend_from_slice(&(1 + language.len() as u32).to_be_bytes());
+        tlan.extend_from_slice(&[0, 0, 0]);
+        tlan.extend_from_slice(language.as_bytes());
+        let size = tlan.len();
+        let mut bytes = b"ID3\x03\0\0".to_vec();
+        bytes.extend([21, 14, 7, 0].map(|shift| (size >> shift & 0x7f) as u8));
+        bytes.extend(tlan);
+        bytes.extend_from_slice(frames);
+        bytes
+    }
+
+    #[test]
+    fn test_probe_audio_info_reads_language_tags() {
+        let dir = tempfile::tempdir().unwrap();
+        let wav = dir.path().join("interview.wav");
+        crate::test_util::write_wav_with_language(&wav, &[0.0; 16000], "deu");
+        let mp3 = dir.path().join("interview.mp3");
+        let original = std::fs::read("src/test_data/test.mp3").unwrap();
+        std::fs::write(&mp3, with_id3_language(&original, "fre")).unwrap();
+
+        let info = probe_audio_info(&wav);
+        assert_eq!(info.language.as_deref(), Some("deu"));
+        assert_eq!(info.sample_rate, Some(16000));
+        assert_eq!(info.channels, Some(1));
+        assert_eq!(probe_audio_info(&mp3).language.as_deref(), Some("fre"));
+        assert_eq!(probe_audio_info("src/test_data/test.mp3").language, None);
+        // the tag doesn't get in the way of decoding
+        assert_eq!(parse_audio_file(&wav).len(), 16000);
+    }
+
+    fn atom(kind: &[u8; 4], body: &[u8]) -> Vec<u8> {
+        let mut bytes = ((8 + body.len()) as u32).to_be_bytes().to_vec();
+        bytes.extend_from_slice(kind);
+        bytes.extend_from_slice(body);
+        bytes
+    }
+
+    /// An atom with a zero version and flags in front of its body.
+    fn full_atom(kind: &[u8; 4], body: &[u8]) -> Vec<u8> {
+        atom(kind, &[&[0; 4], body].concat())
+    }
+
+    /// A sample table whose `frames` all sit in one chunk at `offset`.
+    fn sample_table(entry: Vec<u8>, frames: &[&[u8]], duration: u32, offset: u32) -> Vec<u8> {
+        let count = frames.len() as u32;
+        let sizes: Vec<u8> = frames
+            .iter()
+            .flat_map(|frame| (frame.len() as u32).to_be_bytes())
+            .collect();
+        let (stts, stsc, stco) = if frames.is_empty() {
+            (vec![0; 4], vec![0; 4], vec![0; 4])
+        } else {
+            (
+                [1, count, duration].map(u32::to_be_bytes).concat(),
+                [1, 1, count, 1].map(u32::to_be_bytes).concat(),
+                [1, offset].map(u32::to_be_bytes).concat(),
+            )
+        };
+        atom(
+            b"stbl",
+            &[
+                full_atom(b"stsd", &[&1u32.to_be_bytes(), entry.as_slice()].concat()),
+                full_atom(b"stts", &stts),
+                full_atom(b"stsc", &stsc),
+                full_atom(
+                    b"stsz",
+                    &[[0, count].map(u32::to_be_bytes).concat(), sizes].concat(),
+                ),
+                full_atom(b"stco", &stco),
+            ]
+            .concat(),
+        )
+    }
+
+    fn track(id: u32, handler: &[u8; 4], timescale: u32, stbl: Vec<u8>) -> Vec<u8> {
+        let mut tkhd = [0, 0, id, 0, 0].map(u32::to_be_bytes).concat();
+        tkhd.resize(80, 0);
+        let mut mdhd = [0, 0, timescale, 0].map(u32::to_be_bytes).concat();
+        mdhd.resize(20, 0);
+        let hdlr = [&[0; 4], handler.as_slice(), &[0; 13]].concat();
+        atom(
+            b"trak",
+            &[
+                full_atom(b"tkhd", &tkhd),
+                atom(
+                    b"mdia",
+                    &[
+                        full_atom(b"mdhd", &mdhd),
+                        full_atom(b"hdlr", &hdlr),
+                        atom(b"minf", &stbl),
+                    ]
+                    .concat(),
+                ),
+            ]
+            .concat(),
+        )
+    }
+
+    /// Write an mp4 file with an empty video track followed by a 16KHz mono AAC-LC track of
+    /// `frames` silent frames, 1024 samples each.
+    pub(crate) fn write_test_mp4(path: &Path, frames: usize) {
+        write_test_mp4_declaring(path, frames, 16000);
+    }
+
+    /// Write an mp4 file like `write_test_mp4` whose header declares `declared_rate`, zero for
+    /// none, while its AAC configuration stays at 16KHz.
+    fn write_test_mp4_declaring(path: &Path, frames: usize, declared_rate: u32) {
+        // single channel element with no spectral data, then the end element
+        const SILENT_FRAME: &[u8] = &[0x00, 0x00, 0x00, 0x07];
+        // AAC-LC at 16KHz, mono
+        const AUDIO_SPECIFIC_CONFIG: [u8; 2] = [0x14, 0x08];
+
+        let ftyp = atom(b"ftyp", b"isom\0\0\x02\0isommp41");
+        let mut mvhd = [0, 0, 1000, 0, 0x0001_0000].map(u32::to_be_bytes).concat();
+        mvhd.resize(96, 0);
+
+        let video = track(
+            1,
+            b"vide",
+            1000,
+            sample_table(atom(b"avc1", &[0; 78]), &[], 0, 0),
+        );
+
+        let decoder_config = [
+            &[0x04, 17, 0x40, 0x15, 0, 0, 0][..],
+            &[0; 8],
+            &[0x05, 2],
+            &AUDIO_SPECIFIC_CONFIG,
+        ]
+        .concat();
+        let es_descriptor = [
+            &[0x03, (3 + decoder_config.len() + 3) as u8, 0, 1, 0][..],
+            &decoder_config,
+            &[0x06, 1, 0x02],
+        ]
+        .concat();
+        let mut mp4a = vec![0, 0, 0, 0, 0, 0, 0, 1];
+        mp4a.extend_from_slice(&[0; 8]);
+        mp4a.extend_from_slice(&[0, 1, 0, 16, 0, 0, 0, 0]);
+        mp4a.extend_from_slice(&(declared_rate << 16).to_be_bytes());
+        mp4a.extend_from_slice(&full_atom(b"esds", &es_descriptor));
+        let samples = vec![SILENT_FRAME; frames];
+
+        // the moov atom's size doesn't depend on the chunk offset, so build it twice
+        let moov = |offset: u32| {
+            let audio = track(
+                2,
+                b"soun",
+                16000,
+                sample_table(atom(b"mp4a", &mp4a), &samples, 1024, offset),
+            );
+            atom(
+                b"moov",
+                &[full_atom(b"mvhd", &mvhd), video.clone(), audio].concat(),
+            )
+        };
+        let offset = (ftyp.len() + moov(0).len() + 8) as u32;
+        let mdat = atom(b"mdat", &samples.concat());
+        std::fs::write(path, [ftyp, moov(offset), mdat].concat()).unwrap();
+    }
+
+    #[test]
+    fn test_parse_audio_file_16k_wav() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("tone.wav");
+        write_test_wav(&path, 16000, 1, &[1000; 16000]);
+
+        let audio = parse_audio_file(path.to_str().unwrap());
+
+        assert_eq!(audio.len(), 16000);
+    }
+
+    #[test]
+    fn test_try_parse_audio_fails_on_unsupported_audio() {
+        let dir = tempfile::tempdir().unwrap();
+        let text = dir.path().join("notes.txt");
+        std::fs::write(&text, "not audio at all, just some text").unwrap();
+        let six_channels = dir.path().join("surround.wav");
+        write_test_wav(&six_channels, 16000, 6, &[0; 6 * 1600]);
+
+        assert!(matches!(
+            try_parse_audio_file(&text),
+            Err(crate::error::Error::UnsupportedFormat(input)) if input.ends_with("notes.txt")
+        ));
+        assert!(matches!(
+            try_parse_audio_file_with_hint(&text, &FormatHint::extension("wav")),
+            Err(crate::error::Error::UnsupportedFormat(_))
+        ));
+        assert!(matches!(
+            try_parse_audio_bytes(b"not audio either".to_vec(), Some("audio/mpeg")),
+            Err(crate::error::Error::UnsupportedFormat(_))
+        ));
+        assert!(matches!(
+            try_probe_audio_info(dir.path().join("missing.wav")),
+            Err(crate::error::Error::Io(_))
+        ));
+        assert!(matches!(
+            try_parse_audio_file(&six_channels),
+            Err(crate::error::Error::UnsupportedChannels(6))
+        ));
+        let mut audio = vec![0.5; 10];
+        assert!(try_parse_audio_file_into(&six_channels, &mut audio).is_err());
+        assert!(audio.is_empty());
+    }
+
+    #[test]
+    fn test_parse_audio_file_into_reuses_buffer() {
+        let dir = tempfile::tempdir().unwrap();
+        let long = dir.path().join("long.wav");
+        let short = dir.path().join("short.wav");
+        write_test_wav(&long, 16000, 1, &[1000; 32000]);
+        write_test_wav(&short, 16000, 1, &[-1000; 8000]);
+        let mut audio = Vec::new();
+
+        parse_audio_file_into(&long, &mut audio);
+        let capacity = audio.capacity();
+        parse_audio_file_into(&short, &mut audio);
+
+        assert_eq!(audio, parse_audio_file(&short));
+        assert_eq!(audio.capacity(), capacity);
+    }
+
+    #[test]
+    fn test_parse_audio_file_mixes_stereo_down() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("stereo.wav");
+        write_test_wav(&path, 16000, 2, &[16384, 0].repeat(16000));
+
+        let audio = parse_audio_file(&path);
+
+        assert_eq!(audio.len(), 16000);
+        assert!(audio.iter().all(|sample| (sample - 0.25).abs() < 1e-6));
+    }
+
+    #[test]
+    fn test_parse_audio_file_ignores_misleading_extension() {
+        let dir = tempfile::tempdir().unwrap();
+        let mp3_named_wav = dir.path().join("speech.wav");
+        std::fs::copy("src/test_data/test.mp3", &mp3_named_wav).unwrap();
+        let wav_named_mp3 = dir.path().join("tone.mp3");
+        write_test_wav(&wav_named_mp3, 16000, 1, &[1000; 16000]);
+        let mp4_named_mp3 = dir.path().join("video.mp3");
+        write_test_mp4(&mp4_named_mp3, 4);
+
+        assert_eq!(
+            parse_audio_file(&mp3_named_wav),
+            parse_audio_file("src/test_data/test.mp3")
+        );
+        assert_eq!(parse_audio_file(&wav_named_mp3).len(), 16000);
+        assert_eq!(parse_audio_file(&mp4_named_mp3).len(), 4 * 1024);
+    }
+
+    #[test]
+    fn test_parse_audio_file_with_hint() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("upload");
+        std::fs::copy("src/test_data/test.mp3", &path).unwrap();
+        let expected = parse_audio_file("src/test_data/test.mp3");
+
+        assert_eq!(
+            parse_audio_file_with_hint(&path, &FormatHint::extension("mp3")),
+            expected
+        );
+        assert_eq!(
+            parse_audio_file_with_hint(&path, &FormatHint::mime_type("audio/mpeg")),
+            expected
+        );
+        // a wrong hint falls back to probing the content, as does no hint
+        assert_eq!(
+            parse_audio_file_with_hint(&path, &FormatHint::extension("wav")),
+            expected
+        );
+        assert_eq!(
+            parse_audio_file_with_hint(&path, &FormatHint::default()),
+            expected
+        );
+    }
+
+    #[test]
+    fn test_parse_audio_bytes_matches_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("tone.wav");
+        write_test_wav(&path, 16000, 1, &[1000; 16000]);
+
+        let audio = parse_audio_bytes(std::fs::read(&path).unwrap(), Some("audio/wav"));
+
+        assert_eq!(audio, parse_audio_file(path.to_str().unwrap()));
+    }
+
+    #[test]
+    fn test_parse_audio_file_without_frames() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("empty.wav");
+        write_test_wav(&path, 16000, 1, &[]);
+
+        assert!(parse_audio_file(path.to_str().unwrap()).is_empty());
+    }
+
+    #[test]
+    fn test_parse_audio_file_mp4_with_video_track() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("video.mp4");
+        write_test_mp4(&path, 16);
+
+        let audio = parse_audio_file(path.to_str().unwrap());
+
+        assert_eq!(audio.len(), 16 * 1024);
+        assert!(audio.iter().all(|sample| sample.abs() < 1e-3));
+    }
+
+    #[test]
+    fn test_decoded_sample_rate_is_trusted_over_the_header() {
+        let dir = tempfile::tempdir().unwrap();
+        let without_rate = dir.path().join("without_rate.mp4");
+        write_test_mp4_declaring(&without_rate, 4, 0);
+        let wrong_rate = dir.path().join("wrong_rate.mp4");
+        write_test_mp4_declaring(&wrong_rate, 4, 44100);
+
+        let (audio, warning) = decode_audio_file(&without_rate, false, &mut |_, _| ()).unwrap();
+        assert_eq!(audio.len(), 4 * 1024);
+        assert_eq!(warning, None);
+        assert_eq!(probe_audio_info(&without_rate).sample_rate, None);
+
+        let (audio, warning) = decode_audio_file(&wrong_rate, false, &mut |_, _| ()).unwrap();
+        assert_eq!(audio.len(), 4 * 1024);
+        assert_eq!(
+            warning,
+            Some(Warning::SampleRateMismatch {
+                declared: 44100,
+                decoded: 16000
+            })
+        );
+    }
+
+    #[test]
+    fn test_parse_audio_file_8k_wav_is_upsampled() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("tone.wav");
+        write_test_wav(&path, 8000, 1, &[1000; 8000]);
+
+        let audio = parse_audio_file(path.to_str().unwrap());
+
+        assert_eq!(audio.len(), 16000);
+        let level = 1000.0 / 32768.0;
+        assert!(audio[100..15900]
+            .iter()
+            .all(|sample| (sample - level).abs() < 1e-4));
+    }
+
+    #[test]
+    fn test_decode_audio_file_preprocesses_at_the_source_rate() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("tone.wav");
+        write_test_wav(&path, 8000, 1, &[1000; 8000]);
+        let mut preprocessed = Vec::new();
+
+        let (audio, _) = decode_audio_file(&path, false, &mut |audio, sample_rate| {
+            preprocessed.push((audio.len(), sample_rate));
+            audio.fill(0.0);
+        })
+        .unwrap();
+
+        assert_eq!(preprocessed, [(8000, 8000)]);
+        // the preprocessed audio is what gets resampled
+        assert_eq!(audio.len(), 16000);
+        assert!(audio.iter().all(|&sample| sample == 0.0));
+
+        write_test_wav(&path, 16000, 1, &[1000; 16000]);
+        preprocessed.clear();
+        decode_audio_file(&path, false, &mut |audio, sample_rate| {
+            preprocessed.push((audio.len(), sample_rate))
+        })
+        .unwrap();
+        assert_eq!(preprocessed, [(16000, 16000)]);
+    }
+
+    #[test]
+    fn test_parse_audio_file_48k_mp3_is_downsampled() {
+        let at_16k = parse_audio_file("src/test_data/test.mp3");
+        let at_48k = parse_audio_file("src/test_data/test_48k_mono.mp3");
+
+        assert_eq!(
+            probe_audio_info("src/test_data/test_48k_mono.mp3").sample_rate,
+            Some(48000)
+        );
+        // the same recording, give or take the encoder padding of the mp3 frames
+        let difference = at_48k.len().abs_diff(at_16k.len());
+        assert!(
+            difference < 2 * 1152,
+            "{} vs {}",
+            at_48k.len(),
+            at_16k.len()
+        );
+        let rms =
+            |audio: &[f32]| (audio.iter().map(|x| x * x).sum::<f32>() / audio.len() as f32).sqrt();
+        let (rms_16k, rms_48k) = (rms(&at_16k), rms(&at_48k));
+        assert!(
+            (rms_48k / rms_16k - 1.0).abs() < 0.1,
+            "{} vs {}",
+            rms_48k,
+            rms_16k
+        );
+    }
+
+    #[test]
+    fn test_parse_audio_file_strict_rejects_8k() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("tone.wav");
+        write_test_wav(&path, 8000, 1, &[1000; 8000]);
+
+        assert!(matches!(
+            parse_audio_file_strict(&path),
+            Err(crate::error::Error::UnsupportedSampleRate {
+                sample_rate: 8000,
+                required: 16000
+            })
+        ));
+    }
+
+    #[test]
+    fn test_parse_audio_file_strict_keeps_16k() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("tone.wav");
+        write_test_wav(&path, 16000, 1, &[1000; 16000]);
+
+        assert_eq!(
+            parse_audio_file_strict(&path).unwrap(),
+            parse_audio_file(&path)
+        );
+    }
+
+    #[test]
+    fn test_parse_audio_file_with_custom_sample_rate() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("tone.wav");
+        write_test_wav(&path, 8000, 1, &[1000; 8000]);
+
+        assert_eq!(parse_audio_file_with_sample_rate(&path, 8000).len(), 8000);
+        assert_eq!(parse_audio_file_with_sample_rate(&path, 24000).len(), 24000);
+    }
+
+    /// A reader serving `bytes`, then failing with `err` (or ending, for `None`) without
+    /// ever reaching their end.
+    struct CutReader {
+        bytes: std::io::Cursor<Vec<u8>>,
+        cut_at: u64,
+        err: Option<std::io::ErrorKind>,
+    }
+
+    impl std::io::Read for CutReader {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            let left = self.cut_at.saturating_sub(self.bytes.position()) as usize;
+            if left == 0 {
+                return match self.err {
+                    Some(kind) => Err(kind.into()),
+                    None => Ok(0),
+                };
+            }
+            let n = buf.len().min(left);
+            self.bytes.read(&mut buf[..n])
+        }
+    }
+
+    fn cut_wav(err: Option<std::io::ErrorKind>) -> CutReader {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("tone.wav");
+        write_test_wav(&path, 16000, 1, &[1000; 32000]);
+        CutReader {
+            bytes: std::io::Cursor::new(std::fs::read(&path).unwrap()),
+            cut_at: 44 + 32000,
+            err,
+        }
+    }
+
+    #[test]
+    fn test_parse_audio_stream_ending_early_is_truncated() {
+        let err = try_parse_audio_stream(cut_wav(None), Some("audio/wav")).unwrap_err();
+
+        assert!(matches!(
+            err,
+            crate::error::Error::AudioTruncated { decoded_secs, .. }
+                if decoded_secs > 0.9 && decoded_secs <= 1.0
+        ));
+        assert!(
+            err.to_string()
+                .contains("of the 32000 frames its header declares"),
+            "{}",
+            err
+        );
+    }
+
+    /// A format reader whose stream changes its tracks after `packets` packets, like a
+    /// chained Ogg stream.
+    struct ResetAfter {
+        inner: Box<dyn FormatReader>,
+        packets: usize,
+    }
+
+    impl FormatReader for ResetAfter {
+        fn try_new(
+            _: MediaSourceStream,
+            _: &FormatOptions,
+        ) -> symphonia::core::errors::Result<Self> {
+            unreachable!("built around a probed reader")
+        }
+
+        fn cues(&self) -> &[symphonia::core::formats::Cue] {
+            self.inner.cues()
+        }
+
+        fn metadata(&mut self) -> symphonia::core::meta::Metadata<'_> {
+            self.inner.metadata()
+        }
+
+        fn seek(
+            &mut self,
+            mode: symphonia::core::formats::SeekMode,
+            to: symphonia::core::formats::SeekTo,
+        ) -> symphonia::core::errors::Result<symphonia::core::formats::SeekedTo> {
+            self.inner.seek(mode, to)
+        }
+
+        fn tracks(&self) -> &[Track] {
+            self.inner.tracks()
+        }
+
+        fn next_packet(
+            &mut self,
+        ) -> symphonia::core::errors::Result<symphonia::core::formats::Packet> {
+            if self.packets == 0 {
+                return Err(Error::ResetRequired);
+            }
+            self.packets -= 1;
+            self.inner.next_packet()
+        }
+
+        fn into_inner(self: Box<Self>) -> MediaSourceStream {
+            self.inner.into_inner()
+        }
+    }
+
+    #[test]
+    fn test_decode_audio_stream_reset_is_an_error() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("tone.wav");
+        write_test_wav(&path, 16000, 1, &[1000; 32000]);
+        let format = Box::new(ResetAfter {
+            inner: try_probe_file(&path).unwrap().format,
+            packets: 2,
+        });
+
+        let err = decode_audio(
+            format,
+            "tone.wav",
+            WHISPER_SAMPLE_RATE,
+            false,
+            None,
+            &mut Vec::new(),
+        )
+        .unwrap_err();
+
+        assert!(matches!(err, crate::error::Error::AudioTruncated { .. }));
+        assert!(err.to_string().contains("chained Ogg stream"), "{}", err);
+    }
+
+    #[test]
+    fn test_parse_audio_stream_io_error_is_not_taken_for_the_end() {
+        let err = try_parse_audio_stream(
+            cut_wav(Some(std::io::ErrorKind::ConnectionReset)),
+            Some("audio/wav"),
+        )
+        .unwrap_err();
+
+        assert!(matches!(
+            err,
+            // decoding stops at the last whole packet before the cut, a second in
+            crate::error::Error::AudioTruncated { decoded_secs, .. }
+                if decoded_secs > 0.9 && decoded_secs <= 1.0
+        ));
+        assert!(err.to_string().contains("it would be truncated"));
+        assert!(err.to_string().contains("connection reset"));
+    }
 }
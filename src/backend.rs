@@ -0,0 +1,461 @@
+//! The seam between the transcriber and the model running inference.
+//!
+//! `WhisperBackend` runs whisper itself. Everything the transcriber reads from whisper goes
+//! through these traits, so tests (and the `test-util` feature) can swap in a scripted model.
+
+use std::ffi::{c_void, CStr};
+use std::os::raw::c_int;
+use std::panic::{self, AssertUnwindSafe};
+use std::path::{Path, PathBuf};
+
+use whisper_rs::{FullParams, WhisperError};
+
+use crate::callbacks::Hooks;
+use crate::system::ModelHyperparameters;
+
+/// A loaded model.
+pub(crate) trait InferenceBackend: Send + Sync {
+    /// Create a fresh state to run inference in, on the GPU when `use_gpu` is set and the
+    /// model was loaded there.
+    fn create_state(&self, use_gpu: bool) -> Result<Box<dyn InferenceState + '_>, WhisperError>;
+
+    /// The model type, such as "tiny" or "large".
+    fn model_type(&self) -> String;
+
+    fn is_multilingual(&self) -> bool;
+
+    fn n_vocab(&self) -> c_int;
+
+    #[cfg(feature = "mel-spectrogram")]
+    fn n_mels(&self) -> c_int;
+
+    /// The end of text token, ids from it on are special tokens.
+    fn token_eot(&self) -> c_int;
+
+    fn hyperparameters(&self) -> ModelHyperparameters;
+
+    /// The number of bands of the mel filterbank shipped in the model file, which whisper
+    /// turns audio into spectrograms with. `None` when it is unknown.
+    fn mel_filterbank_n_mels(&self) -> Option<c_int> {
+        None
+    }
+
+    /// The memory one inference state takes, in bytes, see `memory::MemoryBudget`.
+    fn state_memory_bytes(&self) -> u64 {
+        crate::memory::estimate_state_bytes(&self.model_type())
+    }
+
+    /// The file the model was loaded from, `None` for models not loaded from a file.
+    fn model_path(&self) -> Option<&Path> {
+        None
+    }
+}
+
+/// The state of one inference run, and the segments it decoded.
+pub(crate) trait InferenceState {
+    /// Run inference over 16KHz mono samples, replacing the previous run's segments.
+    ///
+    /// The run reports its progress and new segments to `hooks`, and stops early once a hook
+    /// failed.
+    fn full(
+        &mut self,
+        params: FullParams<'_, '_>,
+        audio: &[f32],
+        hooks: &mut Hooks<'_, '_>,
+    ) -> Result<(), WhisperError>;
+
+    fn n_segments(&self) -> Result<c_int, WhisperError>;
+
+    fn segment_text(&self, segment: c_int) -> Result<String, WhisperError>;
+
+    /// Append the segment's text to `text`, reusing its allocation where the backend can.
+    ///
+    /// whisper-rs only hands out segment text as new `String`s, so the whisper backend still
+    /// allocates a temporary one per segment.
+    fn segment_text_into(&self, segment: c_int, text: &mut String) -> Result<(), WhisperError> {
+        text.push_str(&self.segment_text(segment)?);
+        Ok(())
+    }
+
+    /// The segment start in centiseconds from the start of the run's audio.
+    fn segment_t0(&self, segment: c_int) -> Result<i64, WhisperError>;
+
+    /// The segment end in centiseconds from the start of the run's audio.
+    fn segment_t1(&self, segment: c_int) -> Result<i64, WhisperError>;
+
+    fn n_tokens(&self, segment: c_int) -> Result<c_int, WhisperError>;
+
+    fn token_id(&self, segment: c_int, token: c_int) -> Result<c_int, WhisperError>;
+
+    fn token_text(&self, segment: c_int, token: c_int) -> Result<String, WhisperError>;
+
+    fn token_data(&self, segment: c_int, token: c_int) -> Result<TokenData, WhisperError>;
+
+    /// The id of the language of the last run, detected when it was not given.
+    fn lang_id(&self) -> Result<c_int, WhisperError>;
+}
+
+/// The timing and probability of a decoded token.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct TokenData {
+    pub(crate) t0: i64,
+    pub(crate) t1: i64,
+    pub(crate) p: f32,
+}
+
+/// A whisper model, with the CPU copy of it retries fall back to loaded on first use.
+pub(crate) struct WhisperBackend {
+    ctx: whisper_rs::WhisperContext,
+    use_gpu: bool,
+    model_path: PathBuf,
+    mel_filterbank_n_mels: Option<c_int>,
+    cpu_ctx: std::sync::OnceLock<whisper_rs::WhisperContext>,
+}
+
+impl WhisperBackend {
+    /// Load the model at `model_path`, on the GPU when `use_gpu` is set and one is available.
+    pub(crate) fn load(model_path: &Path, use_gpu: bool) -> Result<WhisperBackend, WhisperError> {
+        Ok(WhisperBackend {
+            ctx: load_context(model_path, use_gpu)?,
+            use_gpu,
+            model_path: model_path.to_path_buf(),
+            mel_filterbank_n_mels: read_ggml_header(model_path)
+                .map(|header| header.filterbank_n_mels),
+            cpu_ctx: std::sync::OnceLock::new(),
+        })
+    }
+
+    fn context(&self, use_gpu: bool) -> Result<&whisper_rs::WhisperContext, WhisperError> {
+        if use_gpu || !self.use_gpu {
+            return Ok(&self.ctx);
+        }
+        if let Some(ctx) = self.cpu_ctx.get() {
+            return Ok(ctx);
+        }
+        let ctx = load_context(&self.model_path, false)?;
+        Ok(self.cpu_ctx.get_or_init(|| ctx))
+    }
+}
+
+/// Load a whisper context from the model file at `path`.
+///
+/// whisper opens the file with `fopen`, which takes UTF-8 on unix but the ANSI code page and
+/// at most 260 characters on Windows. Paths it can't open are read into memory and loaded
+/// from there instead, which briefly holds the model twice.
+fn load_context(path: &Path, use_gpu: bool) -> Result<whisper_rs::WhisperContext, WhisperError> {
+    let mut params = whisper_rs::WhisperContextParameters::default();
+    params.use_gpu(use_gpu);
+    match path.to_str() {
+        Some(path) if fopen_accepts(path) => {
+            whisper_rs::WhisperContext::new_with_params(path, params)
+        }
+        _ => {
+            let buffer = std::fs::read(path).map_err(|_| WhisperError::InitError)?;
+            whisper_rs::WhisperContext::new_from_buffer_with_params(&buffer, params)
+        }
+    }
+}
+
+/// The mel band counts declared in the header of a ggml model file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct GgmlHeader {
+    /// The mel bands the model takes.
+    pub(crate) n_mels: c_int,
+    /// The bands of the mel filterbank shipped in the file.
+    pub(crate) filterbank_n_mels: c_int,
+}
+
+/// Read the header of the ggml model file at `path`, `None` when the file can't be read or
+/// isn't a ggml model.
+///
+/// whisper.cpp doesn't expose the filterbank, so it is read from the file: the magic number,
+/// 11 hyperparameters (`n_mels` the tenth) and then the filterbank's band count, all 32-bit
+/// little-endian.
+pub(crate) fn read_ggml_header(path: &Path) -> Option<GgmlHeader> {
+    const GGML_MAGIC: u32 = 0x6767_6d6c;
+    const N_MELS_OFFSET: usize = 4 + 9 * 4;
+    const FILTERBANK_OFFSET: usize = 4 + 11 * 4;
+
+    let mut header = [0u8; FILTERBANK_OFFSET + 4];
+    std::io::Read::read_exact(&mut std::fs::File::open(path).ok()?, &mut header).ok()?;
+    let word = |offset: usize| {
+        u32::from_le_bytes(
+            header[offset..offset + 4]
+                .try_into()
+                .expect("a 4 byte slice"),
+        )
+    };
+    (word(0) == GGML_MAGIC).then(|| GgmlHeader {
+        n_mels: word(N_MELS_OFFSET) as c_int,
+        filterbank_n_mels: word(FILTERBANK_OFFSET) as c_int,
+    })
+}
+
+/// Whether `fopen` opens `path` on this platform.
+fn fopen_accepts(path: &str) -> bool {
+    const WINDOWS_MAX_PATH: usize = 260;
+    !cfg!(windows) || (path.is_ascii() && path.len() < WINDOWS_MAX_PATH)
+}
+
+impl InferenceBackend for WhisperBackend {
+    fn create_state(&self, use_gpu: bool) -> Result<Box<dyn InferenceState + '_>, WhisperError> {
+        Ok(Box::new(self.context(use_gpu)?.create_state()?))
+    }
+
+    fn model_type(&self) -> String {
+        self.ctx.model_type_readable().unwrap_or_default()
+    }
+
+    fn is_multilingual(&self) -> bool {
+        self.ctx.is_multilingual()
+    }
+
+    fn n_vocab(&self) -> c_int {
+        self.ctx.n_vocab()
+    }
+
+    #[cfg(feature = "mel-spectrogram")]
+    fn n_mels(&self) -> c_int {
+        self.ctx.model_n_mels()
+    }
+
+    fn model_path(&self) -> Option<&Path> {
+        Some(&self.model_path)
+    }
+
+    fn token_eot(&self) -> c_int {
+        self.ctx.token_eot()
+    }
+
+    fn hyperparameters(&self) -> ModelHyperparameters {
+        ModelHyperparameters {
+            model_type: self.model_type(),
+            n_vocab: self.ctx.model_n_vocab(),
+            n_audio_ctx: self.ctx.model_n_audio_ctx(),
+            n_audio_state: self.ctx.model_n_audio_state(),
+            n_audio_head: self.ctx.model_n_audio_head(),
+            n_audio_layer: self.ctx.model_n_audio_layer(),
+            n_text_ctx: self.ctx.model_n_text_ctx(),
+            n_text_state: self.ctx.model_n_text_state(),
+            n_text_head: self.ctx.model_n_text_head(),
+            n_text_layer: self.ctx.model_n_text_layer(),
+            n_mels: self.ctx.model_n_mels(),
+            ftype: self.ctx.model_ftype(),
+        }
+    }
+
+    fn mel_filterbank_n_mels(&self) -> Option<c_int> {
+        self.mel_filterbank_n_mels
+    }
+}
+
+impl InferenceState for whisper_rs::WhisperState<'_> {
+    fn full(
+        &mut self,
+        mut params: FullParams<'_, '_>,
+        audio: &[f32],
+        hooks: &mut Hooks<'_, '_>,
+    ) -> Result<(), WhisperError> {
+        if !hooks.is_empty() {
+            let user_data = hooks as *mut Hooks<'_, '_> as *mut c_void;
+            // SAFETY: the trampolines only use the user data as the hooks, which outlive the
+            // run, and never unwind into whisper
+            unsafe {
+                params.set_progress_callback(Some(progress_trampoline));
+                params.set_progress_callback_user_data(user_data);
+                params.set_new_segment_callback(Some(new_segment_trampoline));
+                params.set_new_segment_callback_user_data(user_data);
+                params.set_abort_callback(Some(abort_trampoline));
+                params.set_abort_callback_user_data(user_data);
+            }
+        }
+        whisper_rs::WhisperState::full(self, params, audio).map(|_| ())
+    }
+
+    fn n_segments(&self) -> Result<c_int, WhisperError> {
+        self.full_n_segments()
+    }
+
+    /// whisper splits text into byte level tokens, so a segment can end in the middle of a
+    /// multibyte character continued by the next segment. Such a character is kept whole in
+    /// the first segment, and any bytes still invalid become U+FFFD (reported as
+    /// `Warning::ReplacementCharacters`) rather than failing the transcription.
+    fn segment_text(&self, segment: c_int) -> Result<String, WhisperError> {
+        let bytes = self.full_get_segment_bytes(segment)?;
+        let previous = match continuation_prefix(&bytes) {
+            0 => None,
+            _ if segment == 0 => None,
+            _ => self.full_get_segment_bytes(segment - 1).ok(),
+        };
+        let next = match missing_tail(&bytes) {
+            0 => None,
+            _ if segment + 1 >= self.full_n_segments()? => None,
+            _ => self.full_get_segment_bytes(segment + 1).ok(),
+        };
+        Ok(stitch_utf8(previous.as_deref(), bytes, next.as_deref()))
+    }
+
+    fn segment_t0(&self, segment: c_int) -> Result<i64, WhisperError> {
+        self.full_get_segment_t0(segment)
+    }
+
+    fn segment_t1(&self, segment: c_int) -> Result<i64, WhisperError> {
+        self.full_get_segment_t1(segment)
+    }
+
+    fn n_tokens(&self, segment: c_int) -> Result<c_int, WhisperError> {
+        self.full_n_tokens(segment)
+    }
+
+    fn token_id(&self, segment: c_int, token: c_int) -> Result<c_int, WhisperError> {
+        self.full_get_token_id(segment, token)
+    }
+
+    fn token_text(&self, segment: c_int, token: c_int) -> Result<String, WhisperError> {
+        self.full_get_token_text(segment, token)
+    }
+
+    fn token_data(&self, segment: c_int, token: c_int) -> Result<TokenData, WhisperError> {
+        let data = self.full_get_token_data(segment, token)?;
+        Ok(TokenData {
+            t0: data.t0,
+            t1: data.t1,
+            p: data.p,
+        })
+    }
+
+    fn lang_id(&self) -> Result<c_int, WhisperError> {
+        self.full_lang_id_from_state()
+    }
+}
+
+unsafe extern "C" fn progress_trampoline(
+    _ctx: *mut whisper_rs_sys::whisper_context,
+    _state: *mut whisper_rs_sys::whisper_state,
+    progress: c_int,
+    user_data: *mut c_void,
+) {
+    let hooks = &mut *(user_data as *mut Hooks<'_, '_>);
+    hooks.progress(progress);
+}
+
+unsafe extern "C" fn new_segment_trampoline(
+    _ctx: *mut whisper_rs_sys::whisper_context,
+    state: *mut whisper_rs_sys::whisper_state,
+    n_new: c_int,
+    user_data: *mut c_void,
+) {
+    let hooks = &mut *(user_data as *mut Hooks<'_, '_>);
+    // reading the segments can't panic, this only guards against a bug doing so anyway
+    let _ = panic::catch_unwind(AssertUnwindSafe(|| {
+        let n_segments = whisper_rs_sys::whisper_full_n_segments_from_state(state);
+        for segment in (n_segments - n_new).max(0)..n_segments {
+            let text = whisper_rs_sys::whisper_full_get_segment_text_from_state(state, segment);
+            if text.is_null() {
+                continue;
+            }
+            hooks.segment(
+                whisper_rs_sys::whisper_full_get_segment_t0_from_state(state, segment),
+                whisper_rs_sys::whisper_full_get_segment_t1_from_state(state, segment),
+                &CStr::from_ptr(text).to_string_lossy(),
+            );
+        }
+    }));
+}
+
+/// The text of a segment's `bytes`, taking the start of a character split from the end of
+/// the `previous` segment's bytes away and completing one split into the `next` segment's.
+fn stitch_utf8(previous: Option<&[u8]>, mut bytes: Vec<u8>, next: Option<&[u8]>) -> String {
+    if let Some(previous) = previous {
+        let skip = missing_tail(previous).min(continuation_prefix(&bytes));
+        bytes.drain(..skip);
+    }
+    if let Some(next) = next {
+        let take = missing_tail(&bytes).min(continuation_prefix(next));
+        bytes.extend_from_slice(&next[..take]);
+    }
+    match String::from_utf8(bytes) {
+        Ok(text) => text,
+        Err(err) => String::from_utf8_lossy(err.as_bytes()).into_owned(),
+    }
+}
+
+/// The number of UTF-8 continuation bytes `bytes` starts with, at most 3.
+fn continuation_prefix(bytes: &[u8]) -> usize {
+    bytes
+        .iter()
+        .take(3)
+        .take_while(|&&byte| byte & 0xC0 == 0x80)
+        .count()
+}
+
+/// The number of bytes missing to complete the character `bytes` ends in the middle of.
+fn missing_tail(bytes: &[u8]) -> usize {
+    let start = bytes.len().saturating_sub(3);
+    let Some(lead) = (start..bytes.len())
+        .rev()
+        .find(|&i| bytes[i] & 0xC0 != 0x80)
+    else {
+        return 0;
+    };
+    let width: usize = match bytes[lead] {
+        0xC2..=0xDF => 2,
+        0xE0..=0xEF => 3,
+        0xF0..=0xF4 => 4,
+        _ => return 0,
+    };
+    width.saturating_sub(bytes.len() - lead)
+}
+
+unsafe extern "C" fn abort_trampoline(user_data: *mut c_void) -> bool {
+    (*(user_data as *const Hooks<'_, '_>)).failed()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stitch_utf8_keeps_split_characters_whole() {
+        // "中文" split inside "文" (e6 96 87)
+        let first = "中".bytes().chain([0xE6, 0x96]).collect::<Vec<u8>>();
+        let second = vec![0x87, b'!'];
+
+        assert_eq!(stitch_utf8(None, first.clone(), Some(&second)), "中文");
+        assert_eq!(stitch_utf8(Some(&first), second.clone(), None), "!");
+        assert_eq!(stitch_utf8(None, b"ok".to_vec(), Some(&second)), "ok");
+    }
+
+    #[test]
+    fn test_stitch_utf8_replaces_invalid_bytes() {
+        assert_eq!(stitch_utf8(None, vec![b'a', 0xE6, 0x96], None), "a\u{FFFD}");
+        assert_eq!(stitch_utf8(Some(b"a"), vec![0x87, b'b'], None), "\u{FFFD}b");
+        assert_eq!(missing_tail("中".as_bytes()), 0);
+        assert_eq!(missing_tail(&[0xF0, 0x9F]), 2);
+        assert_eq!(continuation_prefix(&[0x80, 0x80, 0x80, 0x80]), 3);
+    }
+
+    #[test]
+    fn test_read_ggml_header() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("ggml-large-v3.bin");
+        let mut header = 0x6767_6d6cu32.to_le_bytes().to_vec();
+        for value in [
+            51866, 1500, 1280, 20, 32, 448, 1280, 20, 32, 128, 1, 80, 201,
+        ] {
+            header.extend((value as u32).to_le_bytes());
+        }
+        std::fs::write(&path, &header).unwrap();
+
+        assert_eq!(
+            read_ggml_header(&path),
+            Some(GgmlHeader {
+                n_mels: 128,
+                filterbank_n_mels: 80
+            })
+        );
+        std::fs::write(&path, b"not a model").unwrap();
+        assert_eq!(read_ggml_header(&path), None);
+        assert_eq!(read_ggml_header(&dir.path().join("missing")), None);
+    }
+}
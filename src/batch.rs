@@ -0,0 +1,445 @@
+use std::panic::{self, AssertUnwindSafe};
+use std::path::{Path, PathBuf};
+
+use crate::audio_parser;
+use crate::error::Error;
+use crate::options::TranscribeOptions;
+use crate::postprocess;
+use crate::transcriber::{Preprocess, Transcriber, TranscriberOutput, SAMPLES_PER_CENTISECOND};
+
+/// File extensions picked up when transcribing a directory.
+const AUDIO_EXTENSIONS: [&str; 2] = ["mp3", "wav"];
+
+/// The outcome of transcribing every audio file in a directory.
+///
+/// A file failing to transcribe is recorded in `failures` and does not stop the batch.
+#[derive(Debug, Default)]
+pub struct BatchReport {
+    successes: Vec<(PathBuf, TranscriberOutput)>,
+    failures: Vec<(PathBuf, Box<dyn std::error::Error + Send + Sync>)>,
+}
+
+impl BatchReport {
+    /// The files that were transcribed, with their transcriptions.
+    pub fn get_successes(&self) -> &Vec<(PathBuf, TranscriberOutput)> {
+        &self.successes
+    }
+
+    /// The files that failed to transcribe, with the reason why.
+    pub fn get_failures(&self) -> &Vec<(PathBuf, Box<dyn std::error::Error + Send + Sync>)> {
+        &self.failures
+    }
+
+    /// Number of files transcribed successfully.
+    pub fn succeeded(&self) -> usize {
+        self.successes.len()
+    }
+
+    /// Number of files that failed to transcribe.
+    pub fn failed(&self) -> usize {
+        self.failures.len()
+    }
+}
+
+/// The input a segment of a multi-input transcription came from.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct SourceRef {
+    /// The position of the input among the transcribed inputs.
+    pub index: usize,
+    /// The input's path, written lossily to JSON when it isn't valid UTF-8.
+    #[serde(serialize_with = "serialize_path_lossy")]
+    pub path: PathBuf,
+    /// The chapter of the input the segment falls in, for inputs with chapters. No input
+    /// format read today carries chapters, so it is always `None`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub chapter: Option<String>,
+}
+
+impl SourceRef {
+    pub(crate) fn new(index: usize, path: PathBuf) -> SourceRef {
+        SourceRef {
+            index,
+            path,
+            chapter: None,
+        }
+    }
+}
+
+fn serialize_path_lossy<S: serde::Serializer>(
+    path: &Path,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(&path.to_string_lossy())
+}
+
+impl Transcriber {
+    /// Transcribe every mp3 and wav file directly inside `dir`, in file name order.
+    ///
+    /// Errors (and panics) while transcribing a file are collected in the report instead of
+    /// aborting the batch. Only failing to list the directory is returned as an error.
+    ///
+    /// # Arguments
+    ///
+    /// * `dir` - the directory holding the audio files.
+    /// * `options` - the options used for every file.
+    pub fn transcribe_dir(
+        &self,
+        dir: impl AsRef<Path>,
+        options: &TranscribeOptions,
+    ) -> Result<BatchReport, Error> {
+        let mut report = BatchReport::default();
+        for path in audio_files_in(dir.as_ref())? {
+            match self.transcribe_file_catching_panics(&path, options) {
+                Ok(mut output) => {
+                    let source = SourceRef::new(
+                        report.successes.len() + report.failures.len(),
+                        path.clone(),
+                    );
+                    for segment in &mut output.segments {
+                        segment.source = Some(source.clone());
+                    }
+                    report.successes.push((path, output))
+                }
+                Err(err) => report.failures.push((path, err)),
+            }
+        }
+        Ok(report)
+    }
+
+    /// Transcribe the audio files at `paths` one after the other as a single recording.
+    ///
+    /// Every segment records the file it came from in `TranscriberOutputSegment::get_source`,
+    /// the file holding the middle of the segment when it spans two files, which it is then
+    /// cut to along with its words. Timestamps count from the start of the first file, as if
+    /// the files played back to back. `options.concat_gap` of silence is put between the
+    /// files, and taken out of the timestamps again.
+    ///
+    /// # Arguments
+    ///
+    /// * `paths` - the audio files, in playing order.
+    /// * `options` - the options used to configure whisper.
+    pub fn transcribe_concat(
+        &self,
+        paths: &[impl AsRef<Path>],
+        options: &TranscribeOptions,
+    ) -> Result<TranscriberOutput, Box<dyn std::error::Error + Send + Sync>> {
+        self.check_options(options)?;
+        let gap_cs = (options.concat_gap.as_millis() / 10) as i64;
+        let mut audio_data = Vec::new();
+        // every file is gated at its own rate, before the files are joined
+        let mut preprocess = Preprocess::new(options);
+        // the centisecond each file ends at, in the joined audio with the gaps
+        let mut ends = Vec::with_capacity(paths.len());
+        for (index, path) in paths.iter().enumerate() {
+            if index > 0 {
+                audio_data.resize(
+                    audio_data.len() + gap_cs as usize * SAMPLES_PER_CENTISECOND,
+                    0.0,
+                );
+            }
+            audio_data.extend(
+                audio_parser::decode_audio_file(
+                    path.as_ref(),
+                    options.strict_sample_rate,
+                    &mut |audio, sample_rate| preprocess.apply(audio, sample_rate),
+                )?
+                .0,
+            );
+            ends.push((audio_data.len() / SAMPLES_PER_CENTISECOND) as i64);
+        }
+
+        let mut output = self.run_preprocessed(audio_data, preprocess, options)?;
+        for segment in &mut output.segments {
+            let middle = (segment.start_timestamp + segment.end_timestamp) / 2;
+            let index = ends
+                .iter()
+                .position(|&end| middle < end)
+                .unwrap_or(paths.len().saturating_sub(1));
+            segment.source = Some(SourceRef::new(index, paths[index].as_ref().to_path_buf()));
+            // a segment running into the gaps or the files around its own is kept within it,
+            // so taking the gaps out doesn't move it into another file
+            let file_start = match index {
+                0 => 0,
+                _ => ends[index - 1] + gap_cs,
+            };
+            let file_end = ends[index];
+            segment.start_timestamp = segment.start_timestamp.clamp(file_start, file_end);
+            segment.end_timestamp = segment
+                .end_timestamp
+                .clamp(segment.start_timestamp, file_end);
+            for word in &mut segment.words {
+                word.start_timestamp = word.start_timestamp.clamp(file_start, file_end);
+                word.end_timestamp = word.end_timestamp.clamp(word.start_timestamp, file_end);
+            }
+            if gap_cs > 0 && index > 0 {
+                postprocess::shift_segments(
+                    std::slice::from_mut(segment),
+                    -(index as i64 * gap_cs),
+                );
+            }
+        }
+        if let Some(duration) = &mut output.audio_duration {
+            *duration -= paths.len().saturating_sub(1) as i64 * gap_cs;
+        }
+        Ok(output)
+    }
+
+    fn transcribe_file_catching_panics(
+        &self,
+        path: &Path,
+        options: &TranscribeOptions,
+    ) -> Result<TranscriberOutput, Box<dyn std::error::Error + Send + Sync>> {
+        panic::catch_unwind(AssertUnwindSafe(|| {
+            self.transcribe_with_options(path, options)
+        }))
+        .unwrap_or_else(|payload| Err(panic_message(payload.as_ref()).into()))
+    }
+}
+
+/// The audio files directly inside `dir`, sorted by path.
+fn audio_files_in(dir: &Path) -> Result<Vec<PathBuf>, Error> {
+    let mut files = Vec::new();
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        let is_audio = path
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .is_some_and(|extension| {
+                AUDIO_EXTENSIONS
+                    .iter()
+                    .any(|audio| audio.eq_ignore_ascii_case(extension))
+            });
+        if is_audio && path.is_file() {
+            files.push(path);
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+pub(crate) fn panic_message(payload: &(dyn std::any::Any + Send)) -> String {
+    if let Some(message) = payload.downcast_ref::<&str>() {
+        message.to_string()
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        message.clone()
+    } else {
+        "transcription panicked".to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::audio_parser::tests::write_test_wav;
+    use crate::model_handler;
+    use crate::test_util::{MockBackend, MockSegment};
+
+    #[test]
+    fn test_audio_files_in_filters_and_sorts() {
+        let dir = tempfile::tempdir().unwrap();
+        for name in ["b.wav", "a.MP3", "notes.txt", "c.mp3"] {
+            std::fs::write(dir.path().join(name), b"").unwrap();
+        }
+        std::fs::create_dir(dir.path().join("nested.wav")).unwrap();
+
+        let files = audio_files_in(dir.path()).unwrap();
+
+        let names: Vec<&str> = files
+            .iter()
+            .map(|path| path.file_name().unwrap().to_str().unwrap())
+            .collect();
+        assert_eq!(names, vec!["a.MP3", "b.wav", "c.mp3"]);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_transcribe_dir_non_utf8_file_name() {
+        use std::os::unix::ffi::OsStrExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let name = std::ffi::OsStr::from_bytes(b"caf\xe9.wav");
+        write_test_wav(&dir.path().join(name), 16000, 1, &[0; 16000]);
+        let transcriber = Transcriber::with_mock_backend(MockBackend::new(vec![MockSegment::new(
+            0, 100, " Hi.",
+        )]));
+
+        let report = transcriber
+            .transcribe_dir(dir.path(), &TranscribeOptions::default())
+            .unwrap();
+
+        assert!(report.get_failures().is_empty());
+        assert_eq!(report.get_successes()[0].0.file_name(), Some(name));
+    }
+
+    #[test]
+    fn test_transcribe_concat_attributes_segments_to_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let first = dir.path().join("first.wav");
+        let second = dir.path().join("second.wav");
+        write_test_wav(&first, 16000, 1, &[0; 32000]);
+        write_test_wav(&second, 16000, 1, &[0; 48000]);
+        let transcriber = Transcriber::with_mock_backend(MockBackend::new(vec![
+            MockSegment::new(0, 150, " One."),
+            MockSegment::new(160, 199, " Two."),
+            MockSegment::new(180, 260, " Three."),
+            MockSegment::new(300, 500, " Four."),
+        ]));
+
+        let mut output = transcriber
+            .transcribe_concat(&[&first, &second], &TranscribeOptions::default())
+            .unwrap();
+
+        assert_eq!(output.get_audio_duration(), Some(500));
+        let sources: Vec<(usize, &Path)> = output
+            .get_segments()
+            .iter()
+            .map(|segment| {
+                let source = segment.get_source().unwrap();
+                (source.index, source.path.as_path())
+            })
+            .collect();
+        assert_eq!(
+            sources,
+            vec![
+                (0, first.as_path()),
+                (0, first.as_path()),
+                (1, second.as_path()),
+                (1, second.as_path()),
+            ]
+        );
+        assert!(output.to_json().contains(r#""source":{"index":1,"path":"#));
+
+        output.merge_segments(std::time::Duration::from_secs(5), 200, 1);
+        let texts: Vec<&str> = output.get_segments().iter().map(|s| s.get_text()).collect();
+        assert_eq!(texts, vec![" One. Two.", " Three. Four."]);
+    }
+
+    #[test]
+    fn test_transcribe_concat_takes_gaps_out_of_timestamps() {
+        let dir = tempfile::tempdir().unwrap();
+        let first = dir.path().join("first.wav");
+        let second = dir.path().join("second.wav");
+        write_test_wav(&first, 16000, 1, &[0; 32000]);
+        write_test_wav(&second, 16000, 1, &[0; 48000]);
+        // timed in the joined audio, with the second file starting after the gap at 2.5s
+        let transcriber = Transcriber::with_mock_backend(MockBackend::new(vec![
+            MockSegment::new(0, 150, " One."),
+            MockSegment::new(230, 300, " Two."),
+            MockSegment::new(300, 500, " Three."),
+        ]));
+        let options = TranscribeOptions {
+            concat_gap: std::time::Duration::from_millis(500),
+            ..Default::default()
+        };
+
+        let output = transcriber
+            .transcribe_concat(&[&first, &second], &options)
+            .unwrap();
+
+        assert_eq!(output.get_audio_duration(), Some(500));
+        let timed: Vec<(i64, i64, usize)> = output
+            .get_segments()
+            .iter()
+            .map(|segment| {
+                (
+                    *segment.get_start_timestamp(),
+                    *segment.get_end_timestamp(),
+                    segment.get_source().unwrap().index,
+                )
+            })
+            .collect();
+        assert_eq!(timed, vec![(0, 150, 0), (200, 250, 1), (250, 450, 1)]);
+    }
+
+    #[test]
+    fn test_transcribe_concat_keeps_segments_within_their_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let first = dir.path().join("first.wav");
+        let second = dir.path().join("second.wav");
+        write_test_wav(&first, 16000, 1, &[0; 32000]);
+        write_test_wav(&second, 16000, 1, &[0; 48000]);
+        // the first file ends at 2s, the second starts after the gap at 2.5s
+        let transcriber = Transcriber::with_mock_backend(MockBackend::new(vec![
+            MockSegment::new(100, 240, " One two."),
+            MockSegment::new(240, 400, " Three four."),
+        ]));
+        let options = TranscribeOptions {
+            concat_gap: std::time::Duration::from_millis(500),
+            word_timestamps: true,
+            ..Default::default()
+        };
+
+        let output = transcriber
+            .transcribe_concat(&[&first, &second], &options)
+            .unwrap();
+
+        let segments = output.get_segments();
+        assert_eq!(
+            (
+                *segments[0].get_start_timestamp(),
+                *segments[0].get_end_timestamp()
+            ),
+            (100, 200)
+        );
+        assert_eq!(segments[1].get_source().unwrap().index, 1);
+        assert_eq!(*segments[1].get_start_timestamp(), 200);
+        for segment in segments {
+            for word in segment.get_words() {
+                assert!(word.get_start_timestamp() >= segment.get_start_timestamp());
+                assert!(word.get_end_timestamp() <= segment.get_end_timestamp());
+            }
+        }
+        assert_eq!(*segments[0].get_words()[1].get_end_timestamp(), 200);
+    }
+
+    #[test]
+    fn test_transcribe_dir_sets_sources() {
+        let dir = tempfile::tempdir().unwrap();
+        write_test_wav(&dir.path().join("a.wav"), 16000, 1, &[0; 16000]);
+        write_test_wav(&dir.path().join("b.wav"), 16000, 1, &[0; 16000]);
+        let transcriber = Transcriber::with_mock_backend(MockBackend::new(vec![MockSegment::new(
+            0, 100, " Hi.",
+        )]));
+
+        let report = transcriber
+            .transcribe_dir(dir.path(), &TranscribeOptions::default())
+            .unwrap();
+
+        for (index, (path, output)) in report.get_successes().iter().enumerate() {
+            let source = output.get_segments()[0].get_source().unwrap();
+            assert_eq!((source.index, &source.path), (index, path));
+        }
+    }
+
+    #[test]
+    fn test_audio_files_in_missing_dir() {
+        assert!(matches!(
+            audio_files_in(Path::new("does/not/exist")),
+            Err(Error::Io(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn component_test_transcribe_dir_reports_failures() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::copy("src/test_data/test.mp3", dir.path().join("good.mp3")).unwrap();
+        std::fs::write(dir.path().join("bad.wav"), b"not a wav file").unwrap();
+
+        let tiny_model_handler = model_handler::ModelHandler::new("Tiny", "models").await;
+        let whisper_wrp = Transcriber::new(tiny_model_handler);
+
+        let report = whisper_wrp
+            .transcribe_dir(dir.path().to_str().unwrap(), &TranscribeOptions::default())
+            .unwrap();
+
+        assert_eq!(report.succeeded(), 1);
+        assert_eq!(report.failed(), 1);
+        assert!(report.get_failures()[0].0.ends_with("bad.wav"));
+        assert!(report.get_failures()[0]
+            .1
+            .to_string()
+            .contains("unsupported audio format"));
+
+        let _ = std::fs::remove_dir_all("models/");
+    }
+}
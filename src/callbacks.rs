@@ -0,0 +1,506 @@
+//! Callbacks run while whisper transcribes, see `Transcriber::transcribe_with_callbacks`.
+//!
+//! whisper calls them from C, where unwinding is undefined behavior, so every callback runs
+//! inside `catch_unwind`. A panic aborts the inference run and is returned from the
+//! transcription as `Error::CallbackPanicked`.
+//!
+//! Segments can also be sent to another thread through a bounded channel, see
+//! `Callbacks::segment_channel`, or written out as JSON Lines, see `Callbacks::json_lines`.
+
+use std::collections::VecDeque;
+use std::io::Write;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+
+use crate::batch::panic_message;
+use crate::error::Error;
+use crate::transcriber::TranscriberOutputSegment;
+
+type SegmentCallback<'a> = Box<dyn FnMut(&TranscriberOutputSegment) + 'a>;
+
+/// Closures called with the progress of a transcription.
+#[derive(Default)]
+pub struct Callbacks<'a> {
+    progress: Option<Box<dyn FnMut(i32) + 'a>>,
+    segment: Option<SegmentCallback<'a>>,
+    partial_text: Option<Arc<Mutex<String>>>,
+    channel: Option<SegmentSender>,
+    json_lines: Option<Box<dyn Write + 'a>>,
+}
+
+impl<'a> Callbacks<'a> {
+    pub fn new() -> Callbacks<'a> {
+        Callbacks::default()
+    }
+
+    /// Call `on_progress` with the percentage of the audio transcribed so far.
+    pub fn on_progress(mut self, on_progress: impl FnMut(i32) + 'a) -> Callbacks<'a> {
+        self.progress = Some(Box::new(on_progress));
+        self
+    }
+
+    /// Call `on_segment` with every segment as soon as whisper decodes it, before any post
+    /// processing.
+    pub fn on_segment(
+        mut self,
+        on_segment: impl FnMut(&TranscriberOutputSegment) + 'a,
+    ) -> Callbacks<'a> {
+        self.segment = Some(Box::new(on_segment));
+        self
+    }
+
+    /// Append the text of every segment to `text` as soon as whisper decodes it, trimmed
+    /// and separated by spaces like `TranscriberOutput::to_plain_text`, so a UI can show the
+    /// transcript as it grows from another thread.
+    ///
+    /// Like `on_segment` it gets the text before any post processing. The text is only ever
+    /// appended to, clear it before reusing it for another transcription.
+    pub fn partial_text(mut self, text: Arc<Mutex<String>>) -> Callbacks<'a> {
+        self.partial_text = Some(text);
+        self
+    }
+
+    /// Send every segment as soon as whisper decodes it to the returned receiver, buffering
+    /// at most `capacity` (at least 1) segments the receiver hasn't taken yet.
+    /// `backpressure` decides what happens to a segment arriving while the buffer is full.
+    ///
+    /// Like `on_segment` it gets the segments before any post processing. The receiver's
+    /// iterator ends once the transcription is over, and segments sent after it was dropped
+    /// are discarded. With `Backpressure::Block` the receiver must be read from another
+    /// thread than the one transcribing.
+    pub fn segment_channel(
+        mut self,
+        capacity: usize,
+        backpressure: Backpressure,
+    ) -> (Callbacks<'a>, SegmentReceiver) {
+        let channel = Arc::new(Channel {
+            state: Mutex::new(ChannelState {
+                segments: VecDeque::new(),
+                dropped: 0,
+                sending: true,
+                receiving: true,
+            }),
+            changed: Condvar::new(),
+            capacity: capacity.max(1),
+            backpressure,
+        });
+        self.channel = Some(SegmentSender {
+            channel: channel.clone(),
+        });
+        (self, SegmentReceiver { channel })
+    }
+
+    /// Write every segment to `writer` as soon as whisper decodes it, as a line of JSON
+    /// shaped like the segments of `TranscriberOutput::to_json`, and flush it, so another
+    /// process can work on the first segments while the rest are transcribed.
+    ///
+    /// Like `on_segment` it gets the segments before any post processing. A failed write
+    /// aborts the transcription with `Error::Io`.
+    pub fn json_lines(mut self, writer: impl Write + 'a) -> Callbacks<'a> {
+        self.json_lines = Some(Box::new(writer));
+        self
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.progress.is_none()
+            && self.segment.is_none()
+            && self.partial_text.is_none()
+            && self.channel.is_none()
+            && self.json_lines.is_none()
+    }
+
+    /// The segments `Backpressure::DropOldest` dropped from the segment channel.
+    pub(crate) fn dropped_segments(&self) -> usize {
+        self.channel
+            .as_ref()
+            .map_or(0, |sender| sender.channel.lock().dropped)
+    }
+}
+
+/// What a full segment channel does with a new segment, see `Callbacks::segment_channel`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Backpressure {
+    /// Wait until the receiver takes a segment. The wait happens inside whisper's segment
+    /// callback, which stalls inference: the transcription runs at the pace of the receiver,
+    /// keeping memory bounded.
+    Block,
+    /// Drop the oldest buffered segment to make room, counted in the transcript's
+    /// `Warning::SegmentsDropped`. The transcript itself still has every segment.
+    DropOldest,
+    /// Abort the transcription with `Error::SegmentChannelFull`.
+    Fail,
+}
+
+struct Channel {
+    state: Mutex<ChannelState>,
+    /// Notified when a segment is sent or taken, and when either end is dropped.
+    changed: Condvar,
+    capacity: usize,
+    backpressure: Backpressure,
+}
+
+struct ChannelState {
+    segments: VecDeque<TranscriberOutputSegment>,
+    dropped: usize,
+    /// Whether the sending `Callbacks` and the `SegmentReceiver` are still alive.
+    sending: bool,
+    receiving: bool,
+}
+
+impl Channel {
+    fn lock(&self) -> MutexGuard<'_, ChannelState> {
+        self.state.lock().unwrap_or_else(|err| err.into_inner())
+    }
+}
+
+/// The receiving end of `Callbacks::segment_channel`, iterating over the segments as
+/// whisper decodes them.
+pub struct SegmentReceiver {
+    channel: Arc<Channel>,
+}
+
+impl SegmentReceiver {
+    /// Wait for the next segment, `None` once the transcription is over and every segment
+    /// was received.
+    pub fn recv(&self) -> Option<TranscriberOutputSegment> {
+        let mut state = self.channel.lock();
+        loop {
+            if let Some(segment) = state.segments.pop_front() {
+                self.channel.changed.notify_all();
+                return Some(segment);
+            }
+            if !state.sending {
+                return None;
+            }
+            state = self
+                .channel
+                .changed
+                .wait(state)
+                .unwrap_or_else(|err| err.into_inner());
+        }
+    }
+
+    /// The next segment if one is buffered, without waiting.
+    pub fn try_recv(&self) -> Option<TranscriberOutputSegment> {
+        let segment = self.channel.lock().segments.pop_front();
+        if segment.is_some() {
+            self.channel.changed.notify_all();
+        }
+        segment
+    }
+
+    /// The number of segments `Backpressure::DropOldest` dropped so far.
+    pub fn dropped(&self) -> usize {
+        self.channel.lock().dropped
+    }
+}
+
+impl Iterator for SegmentReceiver {
+    type Item = TranscriberOutputSegment;
+
+    fn next(&mut self) -> Option<TranscriberOutputSegment> {
+        self.recv()
+    }
+}
+
+impl Drop for SegmentReceiver {
+    fn drop(&mut self) {
+        let mut state = self.channel.lock();
+        state.receiving = false;
+        state.segments.clear();
+        self.channel.changed.notify_all();
+    }
+}
+
+struct SegmentSender {
+    channel: Arc<Channel>,
+}
+
+impl SegmentSender {
+    /// Buffer `segment` for the receiver, applying the backpressure policy when full.
+    fn send(&self, segment: TranscriberOutputSegment) -> Result<(), Error> {
+        let channel = &self.channel;
+        let mut state = channel.lock();
+        while state.receiving && state.segments.len() >= channel.capacity {
+            match channel.backpressure {
+                Backpressure::Block => {
+                    state = channel
+                        .changed
+                        .wait(state)
+                        .unwrap_or_else(|err| err.into_inner());
+                }
+                Backpressure::DropOldest => {
+                    state.segments.pop_front();
+                    state.dropped += 1;
+                }
+                Backpressure::Fail => {
+                    return Err(Error::SegmentChannelFull {
+                        capacity: channel.capacity,
+                    })
+                }
+            }
+        }
+        if state.receiving {
+            state.segments.push_back(segment);
+            channel.changed.notify_all();
+        }
+        Ok(())
+    }
+}
+
+impl Drop for SegmentSender {
+    fn drop(&mut self) {
+        self.channel.lock().sending = false;
+        self.channel.changed.notify_all();
+    }
+}
+
+/// The callbacks of one inference run over a window of the audio, recording the first
+/// failure: a panic, or a full segment channel failing. Once a callback failed no callback
+/// is called again.
+pub(crate) struct Hooks<'c, 'a> {
+    callbacks: &'c mut Callbacks<'a>,
+    /// The window's start in centiseconds, added to segment timestamps.
+    offset: i64,
+    /// The overall progress at the start of the window, and the share of it the window is.
+    progress_start: f32,
+    progress_share: f32,
+    /// The id of the window's first segment, and of its next one.
+    first_id: u64,
+    next_id: u64,
+    failure: Option<Error>,
+}
+
+impl<'c, 'a> Hooks<'c, 'a> {
+    /// Hooks for a run over the whole audio.
+    pub(crate) fn new(callbacks: &'c mut Callbacks<'a>) -> Hooks<'c, 'a> {
+        Hooks {
+            callbacks,
+            offset: 0,
+            progress_start: 0.0,
+            progress_share: 1.0,
+            first_id: 0,
+            next_id: 0,
+            failure: None,
+        }
+    }
+
+    /// Number the window's segments from `first_id`, the number of segments before it.
+    pub(crate) fn numbered_from(self, first_id: usize) -> Hooks<'c, 'a> {
+        Hooks {
+            first_id: first_id as u64,
+            next_id: first_id as u64,
+            ..self
+        }
+    }
+
+    /// Start another attempt at the window's run, numbering its segments from the start.
+    pub(crate) fn start_run(&mut self) {
+        self.next_id = self.first_id;
+    }
+
+    /// Hooks for the `window`-th of `windows` runs, starting `offset` centiseconds into the
+    /// audio.
+    pub(crate) fn for_window(
+        callbacks: &'c mut Callbacks<'a>,
+        offset: i64,
+        window: usize,
+        windows: usize,
+    ) -> Hooks<'c, 'a> {
+        let windows = windows.max(1) as f32;
+        Hooks {
+            offset,
+            progress_start: window as f32 * 100.0 / windows,
+            progress_share: 1.0 / windows,
+            ..Hooks::new(callbacks)
+        }
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.callbacks.is_empty()
+    }
+
+    /// Whether a callback failed, which aborts the run.
+    pub(crate) fn failed(&self) -> bool {
+        self.failure.is_some()
+    }
+
+    /// The first failure, `Error::CallbackPanicked` for a panic.
+    pub(crate) fn take_failure(&mut self) -> Option<Error> {
+        self.failure.take()
+    }
+
+    /// Report that the run is `progress` percent done.
+    pub(crate) fn progress(&mut self, progress: i32) {
+        let overall = (self.progress_start + progress as f32 * self.progress_share).round();
+        if let Some(on_progress) = self.callbacks.progress.as_mut() {
+            if self.failure.is_none() {
+                let result = panic::catch_unwind(AssertUnwindSafe(|| on_progress(overall as i32)));
+                self.failure = result.err().map(panicked);
+            }
+        }
+    }
+
+    /// Report a newly decoded segment, timed in centiseconds from the start of the run.
+    pub(crate) fn segment(&mut self, t0: i64, t1: i64, text: &str) {
+        let id = self.next_id;
+        self.next_id += 1;
+        let new_segment = || TranscriberOutputSegment {
+            id,
+            ..TranscriberOutputSegment::new(t0 + self.offset, t1 + self.offset, text)
+        };
+        if let Some(partial_text) = self.callbacks.partial_text.as_ref() {
+            let text = text.trim();
+            if self.failure.is_none() && !text.is_empty() {
+                let mut partial_text = partial_text.lock().unwrap_or_else(|err| err.into_inner());
+                if !partial_text.is_empty() {
+                    partial_text.push(' ');
+                }
+                partial_text.push_str(text);
+            }
+        }
+        if let Some(on_segment) = self.callbacks.segment.as_mut() {
+            if self.failure.is_none() {
+                let segment = new_segment();
+                let result = panic::catch_unwind(AssertUnwindSafe(|| on_segment(&segment)));
+                self.failure = result.err().map(panicked);
+            }
+        }
+        if let Some(sender) = self.callbacks.channel.as_ref() {
+            if self.failure.is_none() {
+                self.failure = sender.send(new_segment()).err();
+            }
+        }
+        if let Some(writer) = self.callbacks.json_lines.as_mut() {
+            if self.failure.is_none() {
+                let segment = new_segment();
+                let result =
+                    panic::catch_unwind(AssertUnwindSafe(|| write_json_line(writer, &segment)));
+                self.failure = match result {
+                    Ok(result) => result.err().map(Error::Io),
+                    Err(payload) => Some(panicked(payload)),
+                };
+            }
+        }
+    }
+}
+
+/// Write `segment` as a line of JSON and flush it.
+fn write_json_line(
+    writer: &mut dyn Write,
+    segment: &TranscriberOutputSegment,
+) -> std::io::Result<()> {
+    serde_json::to_writer(&mut *writer, segment)?;
+    writer.write_all(b"\n")?;
+    writer.flush()
+}
+
+fn panicked(payload: Box<dyn std::any::Any + Send>) -> Error {
+    Error::CallbackPanicked(panic_message(payload.as_ref()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hooks_catch_panics_and_stop_calling() {
+        let mut calls = 0;
+        let mut callbacks = Callbacks::new().on_progress(|_| {
+            calls += 1;
+            panic!("progress failed");
+        });
+        let mut hooks = Hooks::new(&mut callbacks);
+
+        hooks.progress(10);
+        hooks.progress(20);
+
+        assert!(hooks.failed());
+        assert!(matches!(
+            hooks.take_failure(),
+            Some(Error::CallbackPanicked(message)) if message == "progress failed"
+        ));
+        drop(callbacks);
+        assert_eq!(calls, 1);
+    }
+
+    #[test]
+    fn test_window_hooks_offset_segments_and_progress() {
+        let mut progress = Vec::new();
+        let mut segments = Vec::new();
+        let mut callbacks = Callbacks::new()
+            .on_progress(|p| progress.push(p))
+            .on_segment(|s| segments.push(*s.get_start_timestamp()));
+        let mut hooks = Hooks::for_window(&mut callbacks, 1000, 1, 2);
+
+        hooks.progress(0);
+        hooks.progress(100);
+        hooks.segment(50, 100, " Hi.");
+
+        drop(callbacks);
+        assert_eq!(progress, vec![50, 100]);
+        assert_eq!(segments, vec![1050]);
+    }
+
+    #[test]
+    fn test_partial_text_grows_with_segments() {
+        let text = Arc::new(Mutex::new(String::new()));
+        let mut callbacks = Callbacks::new().partial_text(text.clone());
+        let mut hooks = Hooks::new(&mut callbacks);
+
+        hooks.segment(0, 100, " Hello there.");
+        assert_eq!(*text.lock().unwrap(), "Hello there.");
+        hooks.segment(100, 150, "  ");
+        hooks.segment(150, 300, " General Kenobi.");
+
+        assert_eq!(*text.lock().unwrap(), "Hello there. General Kenobi.");
+    }
+
+    /// A writer failing every write.
+    struct BrokenPipe;
+
+    impl Write for BrokenPipe {
+        fn write(&mut self, _: &[u8]) -> std::io::Result<usize> {
+            Err(std::io::ErrorKind::BrokenPipe.into())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_json_lines_writes_one_line_per_segment() {
+        let mut lines = Vec::new();
+        let mut callbacks = Callbacks::new().json_lines(&mut lines);
+        let mut hooks = Hooks::for_window(&mut callbacks, 1000, 1, 2).numbered_from(3);
+
+        hooks.segment(0, 150, " Say \"hi\"");
+        hooks.segment(150, 300, " Bye.");
+
+        assert!(!hooks.failed());
+        drop(callbacks);
+        assert_eq!(
+            String::from_utf8(lines).unwrap(),
+            concat!(
+                r#"{"id":3,"start_timestamp":1000,"end_timestamp":1150,"text":" Say \"hi\""}"#,
+                "\n",
+                r#"{"id":4,"start_timestamp":1150,"end_timestamp":1300,"text":" Bye."}"#,
+                "\n",
+            )
+        );
+    }
+
+    #[test]
+    fn test_json_lines_write_failure_fails_the_run() {
+        let mut callbacks = Callbacks::new().json_lines(BrokenPipe);
+        let mut hooks = Hooks::new(&mut callbacks);
+
+        hooks.segment(0, 150, " Hi.");
+
+        assert!(matches!(
+            hooks.take_failure(),
+            Some(Error::Io(err)) if err.kind() == std::io::ErrorKind::BrokenPipe
+        ));
+    }
+}
@@ -0,0 +1,167 @@
+//! The provenance of segments through post processing, so data keyed by segment ids (such as
+//! corrections stored in a database) can follow segments as they are merged, split and
+//! dropped. See `TranscriberOutput::change_log`.
+
+/// A post processing step that replaced or removed segments.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+pub enum Change {
+    /// Consecutive segments were merged into one new segment.
+    Merged { sources: Vec<u64>, into: u64 },
+    /// A segment was split into new segments, or cut down to one.
+    Split { parent: u64, into: Vec<u64> },
+    /// Segments were removed.
+    Dropped { ids: Vec<u64> },
+    /// New segments were added, not made from any segment before them.
+    Inserted { ids: Vec<u64> },
+}
+
+/// Every change made to the segments of a `TranscriberOutput` since it was transcribed, in
+/// order.
+///
+/// Segments untouched by a change keep their ids, while merges and splits give the segments
+/// they make new ids, higher than every id before them.
+#[derive(Debug, Clone, Default, PartialEq, Eq, serde::Serialize)]
+#[serde(transparent)]
+pub struct ChangeLog {
+    changes: Vec<Change>,
+}
+
+impl ChangeLog {
+    pub fn changes(&self) -> &[Change] {
+        &self.changes
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+
+    /// The ids of the segments that segment `id` was made from, `[id]` for a segment as it
+    /// was transcribed or inserted.
+    pub fn origins(&self, id: u64) -> Vec<u64> {
+        for change in self.changes.iter().rev() {
+            match change {
+                Change::Merged { sources, into } if *into == id => {
+                    return sources
+                        .iter()
+                        .flat_map(|&source| self.origins(source))
+                        .collect();
+                }
+                Change::Split { parent, into } if into.contains(&id) => {
+                    return self.origins(*parent);
+                }
+                _ => {}
+            }
+        }
+        vec![id]
+    }
+
+    /// The ids of the segments made from segment `id` that are left, `[id]` when it was never
+    /// changed and empty when it was dropped.
+    pub fn descendants(&self, id: u64) -> Vec<u64> {
+        for change in &self.changes {
+            match change {
+                Change::Merged { sources, into } if sources.contains(&id) => {
+                    return self.descendants(*into);
+                }
+                Change::Split { parent, into } if *parent == id => {
+                    return into
+                        .iter()
+                        .flat_map(|&piece| self.descendants(piece))
+                        .collect();
+                }
+                Change::Dropped { ids } if ids.contains(&id) => return Vec::new(),
+                _ => {}
+            }
+        }
+        vec![id]
+    }
+
+    /// Record `change`, unless it changes nothing.
+    pub(crate) fn record(&mut self, change: Change) {
+        let empty = match &change {
+            Change::Merged { sources, .. } => sources.is_empty(),
+            Change::Split { into, .. } => into.is_empty(),
+            Change::Dropped { ids } | Change::Inserted { ids } => ids.is_empty(),
+        };
+        if !empty {
+            self.changes.push(change);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transcriber::{TranscriberOutput, TranscriberOutputSegment};
+
+    fn ids(output: &TranscriberOutput) -> Vec<u64> {
+        output.get_segments().iter().map(|s| s.id()).collect()
+    }
+
+    #[test]
+    fn test_merge_and_filter_are_traced_back() {
+        let mut output = TranscriberOutput::new(vec![
+            TranscriberOutputSegment::new(0, 100, " One."),
+            TranscriberOutputSegment::new(110, 200, " Two."),
+            TranscriberOutputSegment::new(500, 600, " Uh."),
+            TranscriberOutputSegment::new(900, 1000, " Three."),
+            TranscriberOutputSegment::new(1010, 1100, " Four."),
+        ]);
+        assert_eq!(ids(&output), vec![0, 1, 2, 3, 4]);
+
+        output.merge_segments(std::time::Duration::from_millis(200), 80, 1);
+        output.retain_segments(|segment| segment.get_text() != " Uh.");
+
+        assert_eq!(ids(&output), vec![5, 6]);
+        let log = output.change_log();
+        assert_eq!(
+            log.changes(),
+            [
+                Change::Merged {
+                    sources: vec![0, 1],
+                    into: 5
+                },
+                Change::Merged {
+                    sources: vec![3, 4],
+                    into: 6
+                },
+                Change::Dropped { ids: vec![2] },
+            ]
+        );
+        assert_eq!(log.origins(5), vec![0, 1]);
+        assert_eq!(log.origins(6), vec![3, 4]);
+        let descendants: Vec<Vec<u64>> = (0..5).map(|id| log.descendants(id)).collect();
+        assert_eq!(
+            descendants,
+            vec![vec![5], vec![5], vec![], vec![6], vec![6]]
+        );
+    }
+
+    #[test]
+    fn test_split_and_splice_record_parents() {
+        let mut output = TranscriberOutput::new(vec![
+            TranscriberOutputSegment::new(0, 400, " abcd efgh ijkl mnop"),
+            TranscriberOutputSegment::new(400, 500, " Kept."),
+            TranscriberOutputSegment::new(500, 600, " Gone."),
+        ]);
+
+        output.split_segments(10, 1);
+        assert_eq!(ids(&output), vec![3, 4, 1, 2]);
+        output.splice(
+            std::time::Duration::from_millis(1000)..std::time::Duration::from_millis(4000),
+            TranscriberOutput::new(vec![TranscriberOutputSegment::new(0, 100, " New.")]),
+        );
+        output.retain_segments(|segment| segment.get_text() != " Gone.");
+
+        let texts: Vec<&str> = output.get_segments().iter().map(|s| s.get_text()).collect();
+        assert_eq!(texts, vec![" abcd", " New.", " Kept."]);
+        let log = output.change_log();
+        let [first, new, _] = [0, 1, 2].map(|i| output.get_segments()[i].id());
+        assert_eq!(log.origins(first), vec![0]);
+        assert_eq!(log.origins(new), vec![new]);
+        assert_eq!(log.descendants(0), vec![first]);
+        assert!(log.descendants(4).is_empty());
+        assert!(log.descendants(2).is_empty());
+        assert!(output.to_json().contains(r#""id":1,"#));
+    }
+}
@@ -0,0 +1,263 @@
+use std::fs::File;
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+
+use sha2::{Digest, Sha256};
+
+use crate::error::Error;
+use crate::transcriber::TranscriberOutputSegment;
+
+/// The progress of a transcription, so an interrupted one can resume where it stopped instead
+/// of decoding everything again.
+///
+/// Chunked transcriptions (see `TranscribeOptions::chunk_length`) report a checkpoint after
+/// every chunk to `Transcriber::transcribe_resumable`. A checkpoint can also be made by hand
+/// with `Checkpoint::at_sample` to skip the start of the audio.
+#[derive(Debug, Clone, Default, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct Checkpoint {
+    pub(crate) segments: Vec<TranscriberOutputSegment>,
+    /// The 16KHz sample the transcription continues from.
+    pub(crate) sample_offset: usize,
+    /// The length of the audio the checkpoint was made for, `None` when unknown.
+    #[serde(default)]
+    pub(crate) total_samples: Option<usize>,
+    /// The number of decode windows completed.
+    #[serde(default)]
+    pub(crate) windows: usize,
+    /// The text tokens prompting the next chunk.
+    #[serde(default)]
+    pub(crate) prompt: Vec<std::os::raw::c_int>,
+    /// The samples the chunks transcribed so far were cut at.
+    #[serde(default)]
+    pub(crate) boundaries: Vec<usize>,
+}
+
+impl Checkpoint {
+    /// A checkpoint resuming the transcription at the given 16KHz sample, with nothing
+    /// transcribed before it.
+    pub fn at_sample(sample_offset: usize) -> Checkpoint {
+        Checkpoint {
+            sample_offset,
+            ..Default::default()
+        }
+    }
+
+    /// Load a checkpoint saved by `Checkpoint::save`.
+    pub fn load(path: impl AsRef<Path>) -> Result<Checkpoint, Error> {
+        let json = std::fs::read_to_string(path)?;
+        serde_json::from_str(&json).map_err(|err| Error::InvalidCheckpoint(err.to_string()))
+    }
+
+    /// Save the checkpoint as JSON.
+    ///
+    /// The checkpoint is written next to `path` first and renamed over it, so an interruption
+    /// while saving never leaves a truncated checkpoint behind.
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), Error> {
+        let path = path.as_ref();
+        let json =
+            serde_json::to_string(self).map_err(|err| Error::InvalidCheckpoint(err.to_string()))?;
+        let mut part = path.as_os_str().to_owned();
+        part.push(".part");
+        std::fs::write(&part, json)?;
+        std::fs::rename(&part, path)?;
+        Ok(())
+    }
+
+    /// The segments transcribed before the checkpoint.
+    pub fn get_segments(&self) -> &Vec<TranscriberOutputSegment> {
+        &self.segments
+    }
+
+    /// The 16KHz sample the transcription continues from.
+    pub fn get_sample_offset(&self) -> usize {
+        self.sample_offset
+    }
+
+    /// Fail with `Error::InvalidCheckpoint` unless the checkpoint can resume audio of
+    /// `total_samples` samples.
+    pub(crate) fn check(&self, total_samples: usize) -> Result<(), Error> {
+        if let Some(expected) = self.total_samples {
+            if expected != total_samples {
+                return Err(Error::InvalidCheckpoint(format!(
+                    "made for {} samples of audio, got {}",
+                    expected, total_samples
+                )));
+            }
+        }
+        if self.sample_offset > total_samples {
+            return Err(Error::InvalidCheckpoint(format!(
+                "sample offset {} is past the end of the audio ({} samples)",
+                self.sample_offset, total_samples
+            )));
+        }
+        Ok(())
+    }
+}
+
+/// The first line of a checkpoint file, what the transcription it records was made of.
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub(crate) struct CheckpointHeader {
+    pub(crate) audio_path: PathBuf,
+    /// The hex encoded SHA-256 of the audio file.
+    pub(crate) audio_sha256: String,
+    /// The digest of the transcription's manifest, which covers the options and the model.
+    pub(crate) manifest_digest: String,
+}
+
+/// A line of a checkpoint file after the header, the progress made by a chunk.
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+struct ChunkRecord {
+    /// The segments the chunk added.
+    segments: Vec<TranscriberOutputSegment>,
+    sample_offset: usize,
+    total_samples: Option<usize>,
+    windows: usize,
+    prompt: Vec<std::os::raw::c_int>,
+}
+
+/// A checkpoint file of JSON lines, a header followed by a line per transcribed chunk, see
+/// `Transcriber::transcribe_with_checkpoint_file`.
+///
+/// Every chunk is appended and synced to disk as it completes, so a process dying at any
+/// point loses at most the chunk it was transcribing. A line cut short by the interruption
+/// is discarded when the file is resumed.
+pub(crate) struct CheckpointFile {
+    file: File,
+    /// The number of segments already written.
+    written: usize,
+}
+
+impl CheckpointFile {
+    /// Create the checkpoint file at `path`, replacing any file there, starting with `header`.
+    pub(crate) fn create(path: &Path, header: &CheckpointHeader) -> Result<CheckpointFile, Error> {
+        let mut file = File::create(path)?;
+        writeln!(file, "{}", to_json(header)?)?;
+        file.sync_data()?;
+        Ok(CheckpointFile { file, written: 0 })
+    }
+
+    /// Open the checkpoint file at `path` to continue it, returning its header and the
+    /// progress it records.
+    pub(crate) fn resume(
+        path: &Path,
+    ) -> Result<(CheckpointFile, CheckpointHeader, Checkpoint), Error> {
+        let mut reader = BufReader::new(File::open(path)?);
+        let mut line = String::new();
+        reader.read_line(&mut line)?;
+        let header: CheckpointHeader = from_json(&line)?;
+        let mut valid_len = line.len() as u64;
+
+        let mut progress = Checkpoint::default();
+        loop {
+            line.clear();
+            if reader.read_line(&mut line)? == 0 {
+                break;
+            }
+            if !line.ends_with('\n') {
+                // the write of the last chunk was interrupted
+                break;
+            }
+            let record: ChunkRecord = from_json(&line)?;
+            valid_len += line.len() as u64;
+            progress.segments.extend(record.segments);
+            progress.sample_offset = record.sample_offset;
+            progress.total_samples = record.total_samples;
+            progress.windows = record.windows;
+            progress.prompt = record.prompt;
+            if record.total_samples != Some(record.sample_offset) {
+                progress.boundaries.push(record.sample_offset);
+            }
+        }
+
+        let file = std::fs::OpenOptions::new().append(true).open(path)?;
+        file.set_len(valid_len)?;
+        let written = progress.segments.len();
+        Ok((CheckpointFile { file, written }, header, progress))
+    }
+
+    /// Append the chunk that brought the transcription to `progress`.
+    pub(crate) fn append(&mut self, progress: &Checkpoint) -> Result<(), Error> {
+        let record = ChunkRecord {
+            segments: progress.segments[self.written..].to_vec(),
+            sample_offset: progress.sample_offset,
+            total_samples: progress.total_samples,
+            windows: progress.windows,
+            prompt: progress.prompt.clone(),
+        };
+        writeln!(self.file, "{}", to_json(&record)?)?;
+        self.file.sync_data()?;
+        self.written = progress.segments.len();
+        Ok(())
+    }
+}
+
+/// The hex encoded SHA-256 of the file at `path`.
+pub(crate) fn file_sha256(path: &Path) -> Result<String, Error> {
+    let mut file = File::open(path)?;
+    let mut hasher = Sha256::new();
+    std::io::copy(&mut file, &mut hasher)?;
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+fn to_json(value: &impl serde::Serialize) -> Result<String, Error> {
+    serde_json::to_string(value).map_err(|err| Error::InvalidCheckpoint(err.to_string()))
+}
+
+fn from_json<T: serde::de::DeserializeOwned>(line: &str) -> Result<T, Error> {
+    serde_json::from_str(line).map_err(|err| Error::InvalidCheckpoint(err.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_checkpoint_save_and_load() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("progress.json");
+        let checkpoint = Checkpoint {
+            segments: vec![TranscriberOutputSegment::new(0, 150, " Hello")],
+            sample_offset: 32000,
+            total_samples: Some(64000),
+            windows: 1,
+            prompt: vec![1, 2, 3],
+            boundaries: vec![32000],
+        };
+
+        checkpoint.save(&path).unwrap();
+
+        assert_eq!(Checkpoint::load(&path).unwrap(), checkpoint);
+        assert!(!dir.path().join("progress.json.part").exists());
+    }
+
+    #[test]
+    fn test_checkpoint_load_invalid() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("progress.json");
+        std::fs::write(&path, "{\"segments\": 3}").unwrap();
+
+        assert!(matches!(
+            Checkpoint::load(&path),
+            Err(Error::InvalidCheckpoint(_))
+        ));
+    }
+
+    #[test]
+    fn test_checkpoint_check() {
+        let checkpoint = Checkpoint {
+            total_samples: Some(100),
+            ..Checkpoint::at_sample(50)
+        };
+
+        assert!(checkpoint.check(100).is_ok());
+        assert!(matches!(
+            checkpoint.check(200),
+            Err(Error::InvalidCheckpoint(_))
+        ));
+        assert!(Checkpoint::at_sample(100).check(100).is_ok());
+        assert!(matches!(
+            Checkpoint::at_sample(101).check(100),
+            Err(Error::InvalidCheckpoint(_))
+        ));
+    }
+}
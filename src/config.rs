@@ -0,0 +1,468 @@
+//! User editable configuration for apps built on the crate, read from TOML.
+//!
+//! A `Config` gathers the model to load, the transcribe options and the subtitle profile.
+//! Every field is optional and defaults to the value the code defaults to, and unknown
+//! fields are rejected with their name so typos don't go unnoticed:
+//!
+//! ```toml
+//! [model]
+//! name = "base"
+//! models_dir = "/var/lib/models"
+//!
+//! [transcribe]
+//! language = "de"
+//! chunk_length = "30s"
+//!
+//! [transcribe.denoise]
+//! aggressiveness = 0.7
+//!
+//! [subtitles]
+//! format = "Vtt"
+//! max_line_chars = 37
+//! ```
+//!
+//! Settings are layered: the file, then environment overrides (see `Config::load`), then
+//! whatever the program sets on the loaded `Config`. Durations are written as seconds (`1.5`)
+//! or with a unit (`"500ms"`, `"2s"`, `"1m"`), enum variants by name (`"Strict"`, or
+//! `{ LastNTokens = 8 }` for variants with data).
+//!
+//! There is no separate section for the text context carried between windows and chunks, it
+//! is set with the `context_tokens` and `context_policy` fields of `[transcribe]`.
+
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use serde::Deserialize;
+use toml::{Table, Value};
+
+use crate::error::Error;
+use crate::export::SubtitleProfile;
+use crate::model_handler::ModelHandlerBuilder;
+use crate::options::TranscribeOptions;
+
+/// The prefix of the environment variables overriding the configuration, see `Config::load`.
+pub const ENV_PREFIX: &str = "SIMPLE_TRANSCRIBE_";
+
+/// Everything a transcription app configures, see the module documentation.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// The model to load and where from.
+    pub model: ModelConfig,
+    /// The options to transcribe with.
+    pub transcribe: TranscribeOptions,
+    /// The subtitle profile of `Transcriber::transcribe_to_subtitles`. Its `transcribe`
+    /// options are its own and don't inherit from the `transcribe` section.
+    pub subtitles: SubtitleProfile,
+}
+
+/// The settings of `ModelHandlerBuilder` a configuration file can hold.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ModelConfig {
+    /// The model to load, such as "tiny" or "large-v3".
+    ///
+    /// Defaults to `None`, left to the app.
+    pub name: Option<String>,
+    /// The directory models are downloaded to and loaded from.
+    ///
+    /// Defaults to `None`, the builder's "models/".
+    pub models_dir: Option<PathBuf>,
+    /// Mirrors to download models from, in order, instead of Hugging Face.
+    ///
+    /// Defaults to none.
+    pub mirrors: Vec<String>,
+}
+
+impl ModelConfig {
+    /// A `ModelHandlerBuilder` with these settings, to load `name` with.
+    pub fn builder(&self) -> ModelHandlerBuilder {
+        let mut builder = ModelHandlerBuilder::new();
+        if let Some(models_dir) = &self.models_dir {
+            builder = builder.models_dir(models_dir);
+        }
+        for mirror in &self.mirrors {
+            builder = builder.mirror(mirror);
+        }
+        builder
+    }
+}
+
+impl Config {
+    /// Read a configuration from TOML.
+    pub fn from_toml_str(toml: &str) -> Result<Config, Error> {
+        Config::layered(toml, std::iter::empty())
+    }
+
+    /// Read a configuration from the TOML file at `path`.
+    pub fn from_path(path: impl AsRef<Path>) -> Result<Config, Error> {
+        Config::from_toml_str(&std::fs::read_to_string(path)?)
+    }
+
+    /// Read the configuration from the TOML file at `path`, when given, overridden by the
+    /// environment.
+    ///
+    /// An override is a variable named `SIMPLE_TRANSCRIBE_` followed by the path of the field,
+    /// with `__` between the parts: `SIMPLE_TRANSCRIBE_TRANSCRIBE__LANGUAGE=fr` or
+    /// `SIMPLE_TRANSCRIBE_MODEL__NAME=small`. Values are read as TOML values, such as `true`,
+    /// `4` or `["a", "b"]`, and as plain strings when they aren't one.
+    pub fn load(path: Option<&Path>) -> Result<Config, Error> {
+        let toml = match path {
+            Some(path) => std::fs::read_to_string(path)?,
+            None => String::new(),
+        };
+        Config::layered(&toml, std::env::vars())
+    }
+
+    /// Read a configuration from TOML, overridden by the `ENV_PREFIX` variables of `env`.
+    fn layered(
+        toml: &str,
+        env: impl IntoIterator<Item = (String, String)>,
+    ) -> Result<Config, Error> {
+        let mut document: Table =
+            toml::from_str(toml).map_err(|err| Error::InvalidConfig(err.to_string()))?;
+        for (name, value) in env {
+            let field = match name.strip_prefix(ENV_PREFIX) {
+                Some(field) => field,
+                None => continue,
+            };
+            let path: Vec<String> = field.split("__").map(str::to_lowercase).collect();
+            let value = parse_toml_value(&value).unwrap_or(Value::String(value));
+            override_value(&mut document, &path, value)
+                .map_err(|reason| Error::InvalidConfig(format!("{}: {}", name, reason)))?;
+        }
+        Value::Table(document)
+            .try_into()
+            .map_err(|err: toml::de::Error| Error::InvalidConfig(err.to_string()))
+    }
+}
+
+/// Parse a single TOML value, such as `true` or `"text"`.
+fn parse_toml_value(text: &str) -> Option<Value> {
+    let mut table: Table = toml::from_str(&format!("value = {}", text)).ok()?;
+    match (table.remove("value"), table.is_empty()) {
+        (Some(value), true) => Some(value),
+        _ => None,
+    }
+}
+
+/// Set the field at `path` of `document` to `value`, creating the tables on the way.
+fn override_value(document: &mut Table, path: &[String], value: Value) -> Result<(), String> {
+    let (key, tables) = path.split_last().ok_or("no field")?;
+    let mut table = document;
+    for name in tables {
+        let entry = table
+            .entry(name.clone())
+            .or_insert_with(|| Value::Table(Table::new()));
+        table = match entry {
+            Value::Table(table) => table,
+            _ => return Err(format!("'{}' is not a table", name)),
+        };
+    }
+    table.insert(key.clone(), value);
+    Ok(())
+}
+
+/// Deserialize a duration written as seconds, with a unit, or as serialized by serde.
+pub(crate) fn deserialize_duration<'de, D: serde::Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Duration, D::Error> {
+    DurationRepr::deserialize(deserializer)?
+        .to_duration()
+        .map_err(serde::de::Error::custom)
+}
+
+/// Deserialize an optional duration, see `deserialize_duration`.
+pub(crate) fn deserialize_optional_duration<'de, D: serde::Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<Duration>, D::Error> {
+    match Option::<DurationRepr>::deserialize(deserializer)? {
+        Some(duration) => duration
+            .to_duration()
+            .map(Some)
+            .map_err(serde::de::Error::custom),
+        None => Ok(None),
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum DurationRepr {
+    Seconds(f64),
+    Text(String),
+    Parts { secs: u64, nanos: u32 },
+}
+
+impl DurationRepr {
+    fn to_duration(&self) -> Result<Duration, String> {
+        match self {
+            DurationRepr::Seconds(seconds) => {
+                Duration::try_from_secs_f64(*seconds).map_err(|err| err.to_string())
+            }
+            DurationRepr::Text(text) => parse_duration(text),
+            DurationRepr::Parts { secs, nanos } => Ok(Duration::new(*secs, *nanos)),
+        }
+    }
+}
+
+/// A duration such as "500ms", "1.5s", "2m" or "1h".
+fn parse_duration(text: &str) -> Result<Duration, String> {
+    let text = text.trim();
+    let split = text
+        .find(|c: char| c.is_ascii_alphabetic())
+        .unwrap_or(text.len());
+    let (number, unit) = text.split_at(split);
+    let invalid = || format!("invalid duration '{}', expected such as \"500ms\"", text);
+    let number: f64 = number.trim().parse().map_err(|_| invalid())?;
+    let seconds = match unit {
+        "ms" => number / 1000.0,
+        "s" | "" => number,
+        "m" => number * 60.0,
+        "h" => number * 3600.0,
+        _ => return Err(invalid()),
+    };
+    Duration::try_from_secs_f64(seconds).map_err(|_| invalid())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::export::SubtitleFormat;
+    use crate::options::{ContextPolicy, EmptyAudioPolicy, NoiseGate, OverlapPolicy};
+
+    const SAMPLE: &str = r#"
+# every section of a configuration
+[model]
+name = "base"
+models_dir = "/var/lib/models"
+mirrors = [
+    "https://example.com/models", # the primary
+    'https://backup.example.com/models',
+]
+
+[transcribe]
+language = "de"
+word_timestamps = true
+min_duration = 1.5
+chunk_length = "45s"
+context_policy = { LastNTokens = 8 }
+empty_audio = "Error"
+retry.max_attempts = 3
+gain_db = -3.5
+
+[transcribe.denoise]
+aggressiveness = 0.75
+
+[subtitles]
+format = "Vtt"
+max_line_chars = 37
+merge_gap = "250ms"
+
+[subtitles.subtitles]
+overlap_policy = "SplitAtMidpoint"
+timestamp_rounding = { mode = "Nearest", precision = { Frames = { Whole = 25 } } }
+"#;
+
+    #[test]
+    fn test_sample_config_covers_every_section() {
+        let config = Config::from_toml_str(SAMPLE).unwrap();
+
+        assert_eq!(
+            config.model,
+            ModelConfig {
+                name: Some("base".to_string()),
+                models_dir: Some(PathBuf::from("/var/lib/models")),
+                mirrors: vec![
+                    "https://example.com/models".to_string(),
+                    "https://backup.example.com/models".to_string(),
+                ],
+            }
+        );
+
+        let transcribe = &config.transcribe;
+        assert_eq!(transcribe.language.as_deref(), Some("de"));
+        assert!(transcribe.word_timestamps);
+        assert_eq!(transcribe.min_duration, Duration::from_millis(1500));
+        assert_eq!(transcribe.chunk_length, Some(Duration::from_secs(45)));
+        assert_eq!(transcribe.context_policy, ContextPolicy::LastNTokens(8));
+        assert_eq!(transcribe.empty_audio, EmptyAudioPolicy::Error);
+        assert_eq!(transcribe.retry.max_attempts, 3);
+        // the rest of a table keeps its defaults
+        assert!(transcribe.retry.cpu_fallback);
+        assert_eq!(transcribe.gain_db, Some(-3.5));
+        assert_eq!(
+            transcribe.denoise,
+            Some(NoiseGate {
+                aggressiveness: 0.75
+            })
+        );
+        // and so does everything not in the file
+        assert!(transcribe.suppress_non_speech_tokens);
+        assert_eq!(
+            transcribe.context_tokens,
+            TranscribeOptions::default().context_tokens
+        );
+
+        let subtitles = &config.subtitles;
+        assert_eq!(subtitles.format, SubtitleFormat::Vtt);
+        assert_eq!(subtitles.max_line_chars, 37);
+        assert_eq!(subtitles.max_lines, 2);
+        assert_eq!(subtitles.merge_gap, Some(Duration::from_millis(250)));
+        assert!(subtitles.transcribe.word_timestamps);
+        assert_eq!(
+            subtitles.subtitles.overlap_policy,
+            Some(OverlapPolicy::SplitAtMidpoint)
+        );
+        assert_eq!(
+            subtitles.subtitles.timestamp_rounding,
+            Some(crate::export::TimestampRounding::new(
+                crate::export::RoundingMode::Nearest,
+                crate::export::RoundingPrecision::Frames(crate::export::FrameRate::Whole(25)),
+            ))
+        );
+    }
+
+    #[test]
+    fn test_empty_config_is_the_code_defaults() {
+        let config = Config::from_toml_str("# nothing\n").unwrap();
+
+        assert_eq!(config.model, ModelConfig::default());
+        assert_eq!(
+            serde_json::to_value(&config.transcribe).unwrap(),
+            serde_json::to_value(TranscribeOptions::default()).unwrap()
+        );
+        assert_eq!(
+            config.subtitles.max_chars_per_second,
+            SubtitleProfile::default().max_chars_per_second
+        );
+    }
+
+    #[test]
+    fn test_serialized_options_read_back() {
+        let options = TranscribeOptions {
+            chunk_length: Some(Duration::from_millis(2500)),
+            ..Default::default()
+        };
+        let json = serde_json::to_string(&options).unwrap();
+
+        let read: TranscribeOptions = serde_json::from_str(&json).unwrap();
+
+        assert_eq!(serde_json::to_string(&read).unwrap(), json);
+    }
+
+    #[test]
+    fn test_unknown_fields_are_named() {
+        let err = Config::from_toml_str("[transcribe]\nlanguag = \"de\"\n").unwrap_err();
+        assert!(
+            err.to_string().contains("unknown field `languag`"),
+            "{}",
+            err
+        );
+
+        let err = Config::from_toml_str("[transcribe.retry]\nmax_atempts = 2\n").unwrap_err();
+        assert!(err.to_string().contains("`max_atempts`"), "{}", err);
+
+        let err = Config::from_toml_str("[modle]\n").unwrap_err();
+        assert!(err.to_string().contains("`modle`"), "{}", err);
+    }
+
+    #[test]
+    fn test_environment_overrides_the_file() {
+        let env = [
+            ("SIMPLE_TRANSCRIBE_TRANSCRIBE__LANGUAGE", "fr"),
+            ("SIMPLE_TRANSCRIBE_TRANSCRIBE__WORD_TIMESTAMPS", "false"),
+            ("SIMPLE_TRANSCRIBE_MODEL__NAME", "small"),
+            (
+                "SIMPLE_TRANSCRIBE_TRANSCRIBE__DENOISE__AGGRESSIVENESS",
+                "0.25",
+            ),
+            ("HOME", "/root"),
+        ]
+        .map(|(name, value)| (name.to_string(), value.to_string()));
+
+        let mut config = Config::layered(SAMPLE, env).unwrap();
+        // and the program has the last word
+        config.transcribe.language = Some("es".to_string());
+
+        assert_eq!(config.model.name.as_deref(), Some("small"));
+        assert_eq!(config.model.mirrors.len(), 2);
+        assert!(!config.transcribe.word_timestamps);
+        assert_eq!(config.transcribe.denoise.unwrap().aggressiveness, 0.25);
+        assert_eq!(config.transcribe.language.as_deref(), Some("es"));
+
+        let err = Config::layered(
+            "",
+            [(
+                "SIMPLE_TRANSCRIBE_TRANSCRIBE__NO_SUCH".to_string(),
+                "1".to_string(),
+            )],
+        )
+        .unwrap_err();
+        assert!(err.to_string().contains("`no_such`"), "{}", err);
+    }
+
+    #[test]
+    fn test_model_config_builder() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("ggml-tiny.bin"), b"model").unwrap();
+        let config = ModelConfig {
+            name: Some("tiny".to_string()),
+            models_dir: Some(dir.path().to_path_buf()),
+            mirrors: vec!["https://example.com/models".to_string()],
+        };
+
+        let plan = config.builder().plan("tiny").unwrap();
+
+        assert!(plan.is_cached());
+        assert_eq!(
+            plan.source_urls(),
+            ["https://example.com/models/ggml-tiny.bin"]
+        );
+    }
+
+    #[test]
+    fn test_from_path() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("transcribe.toml");
+        std::fs::write(&path, "[transcribe]\ntranslate = true\n").unwrap();
+
+        assert!(Config::from_path(&path).unwrap().transcribe.translate);
+        assert!(matches!(
+            Config::from_path(dir.path().join("missing.toml")),
+            Err(Error::Io(_))
+        ));
+    }
+
+    #[test]
+    fn test_toml_errors_name_the_line() {
+        let err = Config::from_toml_str("[transcribe]\nlanguage = \"de\"\nlanguage = \"fr\"\n")
+            .unwrap_err();
+        assert!(err.to_string().contains("line 3"), "{}", err);
+
+        let err = Config::from_toml_str("[transcribe]\nword_timestamps = nope\n").unwrap_err();
+        assert!(err.to_string().contains("line 2"), "{}", err);
+    }
+
+    #[test]
+    fn test_parse_toml_value() {
+        assert_eq!(parse_toml_value("true"), Some(Value::Boolean(true)));
+        assert_eq!(
+            parse_toml_value("[\"a\", \"b\"]"),
+            Some(Value::Array(vec!["a".into(), "b".into()]))
+        );
+        // plain strings, and values smuggling in other keys, aren't TOML values
+        assert_eq!(parse_toml_value("fr"), None);
+        assert_eq!(parse_toml_value("1\nother = 2"), None);
+    }
+
+    #[test]
+    fn test_parse_duration() {
+        assert_eq!(parse_duration("500ms"), Ok(Duration::from_millis(500)));
+        assert_eq!(parse_duration("1.5s"), Ok(Duration::from_millis(1500)));
+        assert_eq!(parse_duration("2m"), Ok(Duration::from_secs(120)));
+        assert_eq!(parse_duration("1h"), Ok(Duration::from_secs(3600)));
+        assert_eq!(parse_duration("3"), Ok(Duration::from_secs(3)));
+        assert!(parse_duration("soon").is_err());
+        assert!(parse_duration("-1s").is_err());
+    }
+}
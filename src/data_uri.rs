@@ -0,0 +1,81 @@
+use base64::Engine;
+
+use crate::error::Error;
+
+/// A decoded `data:[<mime type>][;<parameter>...];base64,<payload>` URI.
+#[derive(Debug, PartialEq)]
+pub(crate) struct DataUri {
+    pub(crate) mime_type: Option<String>,
+    pub(crate) payload: Vec<u8>,
+}
+
+impl DataUri {
+    /// Parse a base64 data URI. URIs carrying percent-encoded (non base64) data are rejected,
+    /// since audio is always sent base64 encoded.
+    pub(crate) fn parse(uri: &str) -> Result<DataUri, Error> {
+        let rest = uri
+            .strip_prefix("data:")
+            .ok_or_else(|| Error::InvalidDataUri("missing 'data:' scheme".to_string()))?;
+        let (metadata, payload) = rest
+            .split_once(',')
+            .ok_or_else(|| Error::InvalidDataUri("missing ',' before the payload".to_string()))?;
+        let metadata = metadata
+            .strip_suffix(";base64")
+            .ok_or_else(|| Error::InvalidDataUri("payload is not base64 encoded".to_string()))?;
+
+        let mime_type = metadata.split(';').next().unwrap_or_default().trim();
+        let payload: String = payload.chars().filter(|c| !c.is_whitespace()).collect();
+        let payload = base64::engine::general_purpose::STANDARD
+            .decode(payload)
+            .map_err(|err| Error::InvalidDataUri(err.to_string()))?;
+
+        Ok(DataUri {
+            mime_type: (!mime_type.is_empty()).then(|| mime_type.to_string()),
+            payload,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_data_uri_happy_case() {
+        let data_uri = DataUri::parse("data:audio/wav;base64,UklGRg==").unwrap();
+
+        assert_eq!(
+            data_uri,
+            DataUri {
+                mime_type: Some("audio/wav".to_string()),
+                payload: b"RIFF".to_vec(),
+            }
+        );
+    }
+
+    #[test]
+    fn test_parse_data_uri_with_parameters_and_no_mime_type() {
+        let with_codecs = DataUri::parse("data:audio/webm;codecs=opus;base64,UklG\nRg==").unwrap();
+        let without_mime = DataUri::parse("data:;base64,UklGRg==").unwrap();
+
+        assert_eq!(with_codecs.mime_type.as_deref(), Some("audio/webm"));
+        assert_eq!(with_codecs.payload, b"RIFF");
+        assert_eq!(without_mime.mime_type, None);
+    }
+
+    #[test]
+    fn test_parse_data_uri_malformed() {
+        for uri in [
+            "audio/wav;base64,UklGRg==",
+            "data:audio/wav;base64",
+            "data:audio/wav,RIFF",
+            "data:audio/wav;base64,not base64!",
+        ] {
+            assert!(
+                matches!(DataUri::parse(uri), Err(Error::InvalidDataUri(_))),
+                "{}",
+                uri
+            );
+        }
+    }
+}
@@ -0,0 +1,155 @@
+//! A noise gate for noisy recordings, see `TranscribeOptions::denoise`.
+//!
+//! The audio is cut into short frames. The noise floor is estimated as the level of the
+//! quietest frames, and frames not clearly louder than it are attenuated. The gain moves
+//! linearly across every frame so gating doesn't click. Audio in which no frame stands out
+//! of the noise is left as is.
+//!
+//! Decoded audio is gated at its source rate, before it is resampled to 16KHz.
+
+use crate::options::NoiseGate;
+
+/// 20ms frames at any sample rate.
+const FRAMES_PER_SECOND: u32 = 50;
+/// The share of frames assumed to hold nothing but noise.
+pub(crate) const NOISE_PERCENTILE: f32 = 0.1;
+/// The attenuation of gated frames at full aggressiveness, in dB.
+const MAX_ATTENUATION_DB: f32 = 30.0;
+
+/// Attenuate the frames of `audio`, mono samples at `sample_rate`, close to its noise floor,
+/// in place.
+pub(crate) fn apply_noise_gate(audio: &mut [f32], gate: &NoiseGate, sample_rate: u32) {
+    let aggressiveness = gate.aggressiveness.clamp(0.0, 1.0);
+    if aggressiveness == 0.0 || audio.is_empty() {
+        return;
+    }
+    let levels = frame_levels(audio, sample_rate);
+    let noise_floor = percentile(&levels, NOISE_PERCENTILE);
+    if noise_floor == 0.0 {
+        // digital silence between the sounds, there is no noise to remove
+        return;
+    }
+    // 6dB above the floor at the lowest aggressiveness, up to 18dB at the highest
+    let threshold = noise_floor * (2.0 + 6.0 * aggressiveness);
+    if levels.iter().all(|&level| level < threshold) {
+        // nothing stands out of the noise, gating would only turn the whole audio down
+        return;
+    }
+    let gated_gain = 10f32.powf(-aggressiveness * MAX_ATTENUATION_DB / 20.0);
+
+    let mut gain = 1.0;
+    for (frame, level) in audio.chunks_mut(frame_samples(sample_rate)).zip(levels) {
+        let target = if level < threshold { gated_gain } else { 1.0 };
+        let step = (target - gain) / frame.len() as f32;
+        for sample in frame {
+            gain += step;
+            *sample *= gain;
+        }
+        gain = target;
+    }
+}
+
+/// The RMS level of every frame of `audio`, mono samples at `sample_rate`.
+pub(crate) fn frame_levels(audio: &[f32], sample_rate: u32) -> Vec<f32> {
+    audio.chunks(frame_samples(sample_rate)).map(rms).collect()
+}
+
+fn frame_samples(sample_rate: u32) -> usize {
+    (sample_rate / FRAMES_PER_SECOND).max(1) as usize
+}
+
+fn rms(frame: &[f32]) -> f32 {
+    (frame.iter().map(|sample| sample * sample).sum::<f32>() / frame.len() as f32).sqrt()
+}
+
+/// The level `share` of the frames are quieter than, `levels` must not be empty.
+pub(crate) fn percentile(levels: &[f32], share: f32) -> f32 {
+    let mut sorted = levels.to_vec();
+    sorted.sort_by(f32::total_cmp);
+    sorted[((sorted.len() - 1) as f32 * share) as usize]
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::test_util::noise;
+
+    /// One second of noise, one second of a loud tone over the noise, one second of noise.
+    fn noisy_recording() -> Vec<f32> {
+        let mut audio = noise(Duration::from_secs(3), 0.01);
+        for (i, sample) in audio[16000..32000].iter_mut().enumerate() {
+            *sample += 0.5 * (i as f32 * 0.1).sin();
+        }
+        audio
+    }
+
+    #[test]
+    fn test_noise_gate_attenuates_noise_and_keeps_signal() {
+        let original = noisy_recording();
+        let mut audio = original.clone();
+
+        apply_noise_gate(&mut audio, &NoiseGate::default(), 16000);
+
+        let noise_before = rms(&original[..15000]);
+        let noise_after = rms(&audio[..15000]);
+        assert!(
+            noise_after < noise_before / 4.0,
+            "{} {}",
+            noise_before,
+            noise_after
+        );
+        // away from the ramps at its edges the tone is untouched
+        assert_eq!(audio[17000..31000], original[17000..31000]);
+    }
+
+    #[test]
+    fn test_noise_gate_frames_last_20ms_at_any_rate() {
+        // the noisy recording at 48KHz, each sample repeated three times
+        let original: Vec<f32> = noisy_recording()
+            .iter()
+            .flat_map(|&sample| [sample; 3])
+            .collect();
+        let mut audio = original.clone();
+
+        apply_noise_gate(&mut audio, &NoiseGate::default(), 48000);
+
+        assert_eq!(frame_levels(&audio, 48000).len(), 150);
+        assert!(rms(&audio[..45000]) < rms(&original[..45000]) / 4.0);
+        // the tone is untouched, away from the ramps of the frames at its edges
+        assert_eq!(audio[49000..95000], original[49000..95000]);
+    }
+
+    #[test]
+    fn test_noise_gate_aggressiveness() {
+        let original = noisy_recording();
+        let gated = |aggressiveness: f32| {
+            let mut audio = original.clone();
+            apply_noise_gate(&mut audio, &NoiseGate { aggressiveness }, 16000);
+            rms(&audio[..15000])
+        };
+
+        assert_eq!(gated(0.0), rms(&original[..15000]));
+        assert!(gated(1.0) < gated(0.5));
+        assert_eq!(gated(2.0), gated(1.0));
+    }
+
+    #[test]
+    fn test_noise_gate_leaves_clean_audio_alone() {
+        let mut audio = vec![0.0; 16000];
+        audio.extend((0..16000).map(|i| 0.5 * (i as f32 * 0.1).sin()));
+        let original = audio.clone();
+        let mut tone: Vec<f32> = original[16000..].to_vec();
+        let mut only_noise = noise(Duration::from_secs(1), 0.01);
+
+        apply_noise_gate(&mut audio, &NoiseGate::default(), 16000);
+        apply_noise_gate(&mut tone, &NoiseGate::default(), 16000);
+        apply_noise_gate(&mut only_noise, &NoiseGate::default(), 16000);
+        apply_noise_gate(&mut [], &NoiseGate::default(), 16000);
+
+        assert_eq!(audio, original);
+        assert_eq!(tone, original[16000..]);
+        assert_eq!(only_noise, noise(Duration::from_secs(1), 0.01));
+    }
+}
@@ -0,0 +1,279 @@
+use std::fmt;
+
+use crate::warning::TimestampIssue;
+
+/// Errors returned by the transcriber and its exporters.
+#[derive(Debug)]
+pub enum Error {
+    /// Reading or writing a file failed.
+    Io(std::io::Error),
+    /// No exporter is registered for the given file extension.
+    UnsupportedExtension(String),
+    /// No model with the given name is known, see `model_handler::ModelHandlerBuilder::load`.
+    UnknownModel(String),
+    /// The model file is missing and can't be downloaded because the models directory is
+    /// read-only.
+    ReadOnlyModelsDir(String),
+    /// A downloaded model does not match the checksum reported by the server, or pinned for
+    /// its `model_handler::ModelVariant`.
+    ChecksumMismatch { expected: String, actual: String },
+    /// The transcribe options are incompatible with the loaded model.
+    IncompatibleOptions(Vec<OptionViolation>),
+    /// The audio holds no samples and `EmptyAudioPolicy::Error` is in use.
+    EmptyAudio,
+    /// A data URI is malformed or not base64 encoded.
+    InvalidDataUri(String),
+    /// A segment has invalid timestamps and `TimestampPolicy::Strict` is in use.
+    InvalidTimestamps {
+        segment: usize,
+        issue: TimestampIssue,
+    },
+    /// A checkpoint is malformed or does not match the audio it resumes.
+    InvalidCheckpoint(String),
+    /// The memory budget has no room for another inference state, see `memory::MemoryBudget`.
+    OverBudget { requested: u64, limit: u64 },
+    /// A callback passed to the transcriber panicked, see `callbacks::Callbacks`.
+    CallbackPanicked(String),
+    /// The segment channel was full with `callbacks::Backpressure::Fail` in use.
+    SegmentChannelFull { capacity: usize },
+    /// The model takes a number of mel bands whisper has no encoder for, see
+    /// `system::SUPPORTED_N_MELS`.
+    UnsupportedMelBands(i32),
+    /// The mel filterbank shipped in the model file has another number of bands than the
+    /// model takes, so whisper would feed it spectrograms it can't read. Usually a model
+    /// file converted with an outdated script.
+    MelBandsMismatch { n_mels: i32, filterbank_n_mels: i32 },
+    /// A transcription queue was full with `queue::FullQueuePolicy::Reject` in use.
+    QueueFull { capacity: usize },
+    /// A queued job was cancelled before it started, see `queue::JobHandle::cancel`.
+    JobCancelled,
+    /// A queued job failed with an error of another crate, or panicked.
+    JobFailed(String),
+    /// The audio is in a format no decoder is built in for, see
+    /// `audio_parser::SUPPORTED_FORMATS`. Holds the file, or "the audio" for audio in memory.
+    UnsupportedFormat(String),
+    /// The audio file holds no track of audio, such as a video without sound. Holds the file,
+    /// or "the audio" for audio in memory.
+    NoAudioTrack(String),
+    /// The audio has more channels than the mono or stereo that is mixed down, holding their
+    /// count.
+    UnsupportedChannels(usize),
+    /// Reading or demuxing the audio failed partway, after `decoded_secs` seconds of it were
+    /// decoded, or the stream ended before the length its header declares, or changed its
+    /// tracks midway. The audio isn't transcribed, as its transcript would end early.
+    AudioTruncated { decoded_secs: f32, reason: String },
+    /// The audio isn't sampled at the rate required, and resampling was opted out of with
+    /// `TranscribeOptions::strict_sample_rate`.
+    UnsupportedSampleRate { sample_rate: u32, required: u32 },
+    /// A configuration file is malformed or sets a field that doesn't exist, see
+    /// `config::Config`.
+    InvalidConfig(String),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Io(err) => write!(f, "io error: {}", err),
+            Error::UnsupportedExtension(extension) => {
+                write!(f, "no exporter registered for extension '{}'", extension)
+            }
+            Error::UnknownModel(name) => write!(f, "unknown model '{}'", name),
+            Error::ReadOnlyModelsDir(path) => write!(
+                f,
+                "model {} is missing and its directory is read-only, so it can't be downloaded",
+                path
+            ),
+            Error::ChecksumMismatch { expected, actual } => write!(
+                f,
+                "model checksum mismatch: expected sha256 {}, got {}",
+                expected, actual
+            ),
+            Error::IncompatibleOptions(violations) => {
+                write!(f, "options incompatible with the model: ")?;
+                for (i, violation) in violations.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, "; ")?;
+                    }
+                    write!(f, "{}", violation)?;
+                }
+                Ok(())
+            }
+            Error::EmptyAudio => write!(f, "the audio holds no samples"),
+            Error::InvalidDataUri(reason) => write!(f, "invalid data URI: {}", reason),
+            Error::InvalidTimestamps { segment, issue } => {
+                write!(f, "segment {} has invalid timestamps: {}", segment, issue)
+            }
+            Error::InvalidCheckpoint(reason) => write!(f, "invalid checkpoint: {}", reason),
+            Error::OverBudget { requested, limit } => write!(
+                f,
+                "memory budget exceeded: an inference state needs {} bytes of the {} byte budget",
+                requested, limit
+            ),
+            Error::CallbackPanicked(message) => write!(f, "a callback panicked: {}", message),
+            Error::SegmentChannelFull { capacity } => write!(
+                f,
+                "the segment channel is full, its receiver fell {} segments behind",
+                capacity
+            ),
+            Error::UnsupportedMelBands(n_mels) => write!(
+                f,
+                "the model takes {} mel bands, whisper supports 80 and 128",
+                n_mels
+            ),
+            Error::MelBandsMismatch {
+                n_mels,
+                filterbank_n_mels,
+            } => write!(
+                f,
+                "the model takes {} mel bands but its file ships a {} band filterbank, \
+                 reconvert the model",
+                n_mels, filterbank_n_mels
+            ),
+            Error::QueueFull { capacity } => {
+                write!(f, "the transcription queue is full ({} jobs)", capacity)
+            }
+            Error::JobCancelled => write!(f, "the job was cancelled"),
+            Error::JobFailed(message) => write!(f, "the job failed: {}", message),
+            Error::InvalidConfig(reason) => write!(f, "invalid config: {}", reason),
+            Error::UnsupportedSampleRate {
+                sample_rate,
+                required,
+            } => write!(
+                f,
+                "audio sample rate must be {}Hz (decoded {}Hz) when it isn't resampled, use {} to convert it",
+                required,
+                sample_rate,
+                crate::audio_parser::ffmpeg_command(*required)
+            ),
+            Error::NoAudioTrack(input) => write!(f, "{} has no audio track", input),
+            Error::AudioTruncated {
+                decoded_secs,
+                reason,
+            } => write!(
+                f,
+                "reading the audio failed after {:.2}s decoded, it would be truncated: {}",
+                decoded_secs, reason
+            ),
+            Error::UnsupportedChannels(channels) => write!(
+                f,
+                "{} channels not supported, use {} to convert to mono,{}Hz,f32 audio",
+                channels,
+                crate::audio_parser::ffmpeg_command(crate::audio_parser::WHISPER_SAMPLE_RATE),
+                crate::audio_parser::WHISPER_SAMPLE_RATE
+            ),
+            Error::UnsupportedFormat(input) => write!(
+                f,
+                "{} is in an unsupported audio format, supported: {}. Convert it with {}",
+                input,
+                crate::audio_parser::SUPPORTED_FORMATS.join(", "),
+                crate::audio_parser::ffmpeg_command(crate::audio_parser::WHISPER_SAMPLE_RATE)
+            ),
+        }
+    }
+}
+
+/// A way transcribe options are incompatible with the loaded model, see
+/// `TranscribeOptions::validate`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum OptionViolation {
+    /// Translation needs a multilingual model.
+    TranslateWithEnglishOnlyModel,
+    /// English-only models can't transcribe (or detect) other languages.
+    LanguageWithEnglishOnlyModel(String),
+    /// The language code is not one whisper knows.
+    UnknownLanguage(String),
+    /// The chunk prompt is longer than whisper accepts.
+    ContextTokensTooLarge { requested: usize, max: usize },
+    /// Chunked transcription needs a non-zero chunk length.
+    ZeroChunkLength,
+    /// A segment length cap of zero would mean no cap to whisper, use `None` for that.
+    ZeroMaxSegmentChars,
+    /// A token cap of zero would mean no cap to whisper, use `None` for that.
+    ZeroMaxTokens,
+    /// An audio context of zero would mean the full context to whisper, use `None` for that.
+    ZeroAudioCtx,
+    /// The audio context is larger than the model's.
+    AudioCtxTooLarge { requested: u32, max: u32 },
+    /// The gain is NaN or infinite.
+    NonFiniteGainDb,
+    /// The option makes whisper or the transcriber pick special tokens by id, which only
+    /// works with a stock vocabulary, see `ModelHyperparameters::has_stock_vocabulary`.
+    RequiresStockVocabulary {
+        option: &'static str,
+        vocab_size: i32,
+    },
+}
+
+impl fmt::Display for OptionViolation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            OptionViolation::TranslateWithEnglishOnlyModel => {
+                write!(f, "translate needs a multilingual model")
+            }
+            OptionViolation::LanguageWithEnglishOnlyModel(language) => {
+                match crate::language::language_name(language) {
+                    Some(name) => write!(
+                        f,
+                        "language '{}' ({}) needs a multilingual model",
+                        language, name
+                    ),
+                    None => write!(f, "language '{}' needs a multilingual model", language),
+                }
+            }
+            OptionViolation::UnknownLanguage(language) => write!(
+                f,
+                "unknown language '{}', see language::supported_languages",
+                language
+            ),
+            OptionViolation::ContextTokensTooLarge { requested, max } => write!(
+                f,
+                "the chunk prompt is {} tokens, whisper accepts at most {}",
+                requested, max
+            ),
+            OptionViolation::ZeroChunkLength => write!(f, "chunk_length must not be zero"),
+            OptionViolation::ZeroMaxSegmentChars => {
+                write!(
+                    f,
+                    "max_segment_chars must not be zero, use None for no limit"
+                )
+            }
+            OptionViolation::ZeroMaxTokens => {
+                write!(f, "max_tokens must not be zero, use None for no limit")
+            }
+            OptionViolation::ZeroAudioCtx => {
+                write!(
+                    f,
+                    "audio_ctx must not be zero, use None for the full context"
+                )
+            }
+            OptionViolation::AudioCtxTooLarge { requested, max } => write!(
+                f,
+                "audio_ctx is {} frames, whisper models have {}",
+                requested, max
+            ),
+            OptionViolation::NonFiniteGainDb => {
+                write!(f, "gain_db must be a finite number of dB")
+            }
+            OptionViolation::RequiresStockVocabulary { option, vocab_size } => write!(
+                f,
+                "{} needs a stock whisper vocabulary, the model's has {} tokens",
+                option, vocab_size
+            ),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for Error {
+    fn from(err: std::io::Error) -> Self {
+        Error::Io(err)
+    }
+}
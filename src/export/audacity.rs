@@ -0,0 +1,41 @@
+use std::fmt::Write;
+
+use crate::transcriber::TranscriberOutput;
+
+impl TranscriberOutput {
+    /// Convert the transcription to an Audacity label track.
+    ///
+    /// Each segment becomes a tab separated `start\tend\ttext` line with the times in
+    /// seconds, which Audacity imports through `File > Import > Labels`.
+    pub fn to_audacity_labels(&self) -> String {
+        let mut labels = String::new();
+        for segment in self.get_segments() {
+            let _ = writeln!(
+                labels,
+                "{:.6}\t{:.6}\t{}",
+                *segment.get_start_timestamp() as f64 / 100.0,
+                *segment.get_end_timestamp() as f64 / 100.0,
+                segment.get_text().trim().replace(['\t', '\n'], " ")
+            );
+        }
+        labels
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::transcriber::{TranscriberOutput, TranscriberOutputSegment};
+
+    #[test]
+    fn test_to_audacity_labels_happy_case() {
+        let output = TranscriberOutput::new(vec![
+            TranscriberOutputSegment::new(0, 150, " Hello."),
+            TranscriberOutputSegment::new(150, 6012, " Tab\there."),
+        ]);
+
+        assert_eq!(
+            output.to_audacity_labels(),
+            "0.000000\t1.500000\tHello.\n1.500000\t60.120000\tTab here.\n"
+        );
+    }
+}
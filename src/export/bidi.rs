@@ -0,0 +1,117 @@
+/// Left-to-right isolate.
+const LRI: char = '\u{2066}';
+/// Right-to-left isolate.
+pub(crate) const RLI: char = '\u{2067}';
+/// Pop directional isolate.
+pub(crate) const PDI: char = '\u{2069}';
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Direction {
+    Rtl,
+    Ltr,
+    Number,
+    Neutral,
+}
+
+impl Direction {
+    /// Numbers are kept in the left-to-right runs they appear in.
+    fn is_ltr_run(self) -> bool {
+        matches!(self, Direction::Ltr | Direction::Number)
+    }
+}
+
+fn direction(c: char) -> Direction {
+    let rtl_block = matches!(
+        c as u32,
+        0x0590..=0x08FF | 0xFB1D..=0xFDFF | 0xFE70..=0xFEFF
+    );
+    if c.is_alphabetic() {
+        if rtl_block {
+            Direction::Rtl
+        } else {
+            Direction::Ltr
+        }
+    } else if c.is_numeric() {
+        Direction::Number
+    } else {
+        Direction::Neutral
+    }
+}
+
+/// Wrap predominantly right-to-left text in directional isolates so players keep the
+/// punctuation and embedded left-to-right runs in order.
+///
+/// The line is wrapped in RLI ... PDI, and every embedded left-to-right run (Latin words,
+/// numbers, and the neutral characters between them) is wrapped in LRI ... PDI. Text with
+/// at least as many left-to-right as right-to-left letters is returned unchanged.
+pub(crate) fn isolate_bidi(text: &str) -> String {
+    if !is_predominantly_rtl(text) {
+        return text.to_string();
+    }
+    format!("{}{}{}", RLI, isolate_ltr_runs(text), PDI)
+}
+
+/// Whether the text has more right-to-left than left-to-right letters.
+pub(crate) fn is_predominantly_rtl(text: &str) -> bool {
+    let directions = text.chars().map(direction);
+    let (rtl_count, ltr_count) = directions.fold((0, 0), |(rtl, ltr), d| match d {
+        Direction::Rtl => (rtl + 1, ltr),
+        Direction::Ltr => (rtl, ltr + 1),
+        _ => (rtl, ltr),
+    });
+    rtl_count > ltr_count
+}
+
+/// Wrap the left-to-right runs of the text in LRI ... PDI.
+pub(crate) fn isolate_ltr_runs(text: &str) -> String {
+    let chars: Vec<char> = text.chars().collect();
+    let directions: Vec<Direction> = chars.iter().map(|c| direction(*c)).collect();
+
+    let mut isolated = String::with_capacity(text.len() + 8);
+    let mut i = 0;
+    while i < chars.len() {
+        if !directions[i].is_ltr_run() {
+            isolated.push(chars[i]);
+            i += 1;
+            continue;
+        }
+        // extend the run to the last left-to-right character before the next right-to-left one
+        let mut end = i;
+        let mut j = i;
+        while j < chars.len() && directions[j] != Direction::Rtl {
+            if directions[j].is_ltr_run() {
+                end = j;
+            }
+            j += 1;
+        }
+        isolated.push(LRI);
+        isolated.extend(&chars[i..=end]);
+        isolated.push(PDI);
+        i = end + 1;
+    }
+    isolated
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_isolate_bidi_mixed_arabic_english() {
+        assert_eq!(
+            isolate_bidi("قال John إنه سيصل في 2024."),
+            "\u{2067}قال \u{2066}John\u{2069} إنه سيصل في \u{2066}2024\u{2069}.\u{2069}"
+        );
+    }
+
+    #[test]
+    fn test_isolate_bidi_hebrew_only() {
+        assert_eq!(isolate_bidi("שלום, עולם."), "\u{2067}שלום, עולם.\u{2069}");
+    }
+
+    #[test]
+    fn test_isolate_bidi_leaves_ltr_text_untouched() {
+        assert_eq!(isolate_bidi("Hello مرحبا world"), "Hello مرحبا world");
+        assert_eq!(isolate_bidi("Hello, world!"), "Hello, world!");
+    }
+}
@@ -0,0 +1,202 @@
+use std::io::{BufWriter, Write};
+use std::time::Duration;
+
+use super::TimestampFormat;
+use crate::grouping::TimelineItem;
+use crate::transcriber::TranscriberOutput;
+
+/// Options for the CSV exporter.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CsvOptions {
+    /// How the `start` and `end` columns are written.
+    ///
+    /// Defaults to `HH:MM:SS.mmm`.
+    pub timestamps: TimestampFormat,
+    /// Add a row for every silence of at least this long between the segments, see
+    /// `TranscriberOutput::with_gaps`, and a `kind` column telling `speech` rows from
+    /// `silence` rows, whose text is empty.
+    ///
+    /// Defaults to `None`, only segments are written.
+    pub silences: Option<Duration>,
+}
+
+impl TranscriberOutput {
+    /// Convert the transcription to CSV with `start,end,text` columns, and a `source` column
+    /// with the input's path when segments came from several inputs.
+    pub fn to_csv(&self) -> String {
+        self.to_csv_with_options(&CsvOptions::default())
+    }
+
+    /// Convert the transcription to CSV using the given options, see `to_csv`.
+    pub fn to_csv_with_options(&self, options: &CsvOptions) -> String {
+        let mut buffer = Vec::new();
+        self.write_csv_with_options(&mut buffer, options)
+            .expect("writing to a Vec<u8> cannot fail");
+        String::from_utf8(buffer).expect("CSV output is valid UTF-8")
+    }
+
+    /// Stream the transcription as CSV with `start,end,text` columns, one row per segment,
+    /// and a `source` column when segments have a source.
+    ///
+    /// # Arguments
+    ///
+    /// * `w` - the writer (file, socket, ...) the rows are written to.
+    pub fn write_csv<W: Write>(&self, w: W) -> std::io::Result<()> {
+        self.write_csv_with_options(w, &CsvOptions::default())
+    }
+
+    /// Stream the transcription as CSV using the given options, see `write_csv`.
+    pub fn write_csv_with_options<W: Write>(
+        &self,
+        w: W,
+        options: &CsvOptions,
+    ) -> std::io::Result<()> {
+        let mut w = BufWriter::new(w);
+        let with_sources = self
+            .get_segments()
+            .iter()
+            .any(|segment| segment.get_source().is_some());
+        write!(w, "start,end,text")?;
+        if with_sources {
+            write!(w, ",source")?;
+        }
+        if options.silences.is_some() {
+            write!(w, ",kind")?;
+        }
+        writeln!(w)?;
+        let items = match options.silences {
+            Some(min_gap) => self.with_gaps(min_gap),
+            None => self
+                .get_segments()
+                .iter()
+                .map(TimelineItem::Speech)
+                .collect(),
+        };
+        for item in items {
+            let (start, end, text, source, kind) = match item {
+                TimelineItem::Speech(segment) => (
+                    *segment.get_start_timestamp(),
+                    *segment.get_end_timestamp(),
+                    segment.get_text().trim(),
+                    segment
+                        .get_source()
+                        .map(|source| source.path.to_string_lossy())
+                        .unwrap_or_default(),
+                    "speech",
+                ),
+                TimelineItem::Silence { start, end } => {
+                    (start, end, "", Default::default(), "silence")
+                }
+            };
+            write!(
+                w,
+                "{},{},{}",
+                timestamp(options.timestamps.format(start)),
+                timestamp(options.timestamps.format(end)),
+                quote(text)
+            )?;
+            if with_sources {
+                write!(w, ",{}", quote(&source))?;
+            }
+            if options.silences.is_some() {
+                write!(w, ",{}", kind)?;
+            }
+            writeln!(w)?;
+        }
+        w.flush()
+    }
+}
+
+/// Timestamps stay unquoted unless a comma decimal separator makes them need it.
+fn timestamp(field: String) -> String {
+    if field.contains(',') {
+        quote(&field)
+    } else {
+        field
+    }
+}
+
+fn quote(field: &str) -> String {
+    format!("\"{}\"", field.replace('"', "\"\""))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::batch::SourceRef;
+    use crate::export::FrameRate;
+    use crate::transcriber::{TranscriberOutput, TranscriberOutputSegment};
+
+    #[test]
+    fn test_to_csv_quotes_text() {
+        let output = TranscriberOutput::new(vec![TranscriberOutputSegment::new(
+            0,
+            150,
+            " Say \"hi\", then leave.",
+        )]);
+
+        assert_eq!(
+            output.to_csv(),
+            "start,end,text\n00:00:00.000,00:00:01.500,\"Say \"\"hi\"\", then leave.\"\n"
+        );
+    }
+
+    #[test]
+    fn test_to_csv_with_silences() {
+        let output = TranscriberOutput::new(vec![
+            TranscriberOutputSegment::new(100, 150, " Hi."),
+            TranscriberOutputSegment::new(150, 200, " Bye."),
+        ]);
+        let options = CsvOptions {
+            silences: Some(Duration::from_millis(500)),
+            ..Default::default()
+        };
+
+        assert_eq!(
+            output.to_csv_with_options(&options),
+            "start,end,text,kind\n\
+             00:00:00.000,00:00:01.000,\"\",silence\n\
+             00:00:01.000,00:00:01.500,\"Hi.\",speech\n\
+             00:00:01.500,00:00:02.000,\"Bye.\",speech\n"
+        );
+    }
+
+    #[test]
+    fn test_to_csv_adds_source_column() {
+        let mut segment = TranscriberOutputSegment::new(0, 150, " Hi.");
+        segment.source = Some(SourceRef::new(0, "talks/day \"one\".mp3".into()));
+        let output = TranscriberOutput::new(vec![
+            segment,
+            TranscriberOutputSegment::new(150, 200, " Bye."),
+        ]);
+
+        assert_eq!(
+            output.to_csv(),
+            "start,end,text,source\n\
+             00:00:00.000,00:00:01.500,\"Hi.\",\"talks/day \"\"one\"\".mp3\"\n\
+             00:00:01.500,00:00:02.000,\"Bye.\",\"\"\n"
+        );
+    }
+
+    #[test]
+    fn test_to_csv_with_timestamp_formats() {
+        let output = TranscriberOutput::new(vec![TranscriberOutputSegment::new(0, 6006, " Hi.")]);
+        let options = |timestamps| CsvOptions {
+            timestamps,
+            ..Default::default()
+        };
+
+        assert_eq!(
+            output.to_csv_with_options(&options(TimestampFormat::Clock {
+                decimal_separator: ','
+            })),
+            "start,end,text\n\"00:00:00,000\",\"00:01:00,060\",\"Hi.\"\n"
+        );
+        assert_eq!(
+            output.to_csv_with_options(&options(TimestampFormat::Timecode(FrameRate::Ntsc2997 {
+                drop_frame: true
+            }))),
+            "start,end,text\n00:00:00;00,00:01:00;02,\"Hi.\"\n"
+        );
+    }
+}
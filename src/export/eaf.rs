@@ -0,0 +1,287 @@
+use std::fmt::Write;
+
+use super::{centiseconds_to_ms, escape_xml};
+use crate::transcriber::TranscriberOutput;
+
+/// Options for ELAN annotation (EAF) export.
+#[derive(Debug, Clone)]
+pub struct EafOptions {
+    /// Identifier of the tier holding the segment annotations.
+    pub tier_id: String,
+    /// Participant recorded on the tier, left out of the document when empty.
+    pub participant: String,
+    /// The speakers to put on tiers of their own, in speaking order, each tier named after
+    /// its speaker, who is recorded as its participant. With two or more, the segments move
+    /// to the next speaker's tier at every speaker turn
+    /// (`TranscriberOutputSegment::is_speaker_turn`), back to the first after the last.
+    ///
+    /// Speaker turns are guessed from pauses, the transcriber doesn't tell speakers apart,
+    /// so this only fits conversations taking turns in order, like a two person interview.
+    /// With fewer than two, every segment goes on the `tier_id` tier.
+    pub speaker_tiers: Vec<String>,
+}
+
+impl Default for EafOptions {
+    fn default() -> Self {
+        EafOptions {
+            tier_id: "transcription".to_string(),
+            participant: String::new(),
+            speaker_tiers: Vec::new(),
+        }
+    }
+}
+
+impl TranscriberOutput {
+    /// Convert the transcription to a minimal ELAN annotation document (EAF 3.0).
+    ///
+    /// Every segment becomes an alignable annotation on a single tier, see
+    /// `to_eaf_with_options` to configure the tier or put speakers on tiers of their own.
+    pub fn to_eaf(&self) -> String {
+        self.to_eaf_with_options(&EafOptions::default())
+    }
+
+    /// Convert the transcription to a minimal ELAN annotation document (EAF 3.0).
+    ///
+    /// # Arguments
+    ///
+    /// * `options` - the tier id and participant to use, or the speakers' tiers.
+    pub fn to_eaf_with_options(&self, options: &EafOptions) -> String {
+        // the tier ids with their participants
+        let tiers: Vec<(&str, &str)> = if options.speaker_tiers.len() >= 2 {
+            options
+                .speaker_tiers
+                .iter()
+                .map(|speaker| (speaker.as_str(), speaker.as_str()))
+                .collect()
+        } else {
+            vec![(options.tier_id.as_str(), options.participant.as_str())]
+        };
+        let mut time_slots = String::new();
+        let mut annotations = vec![String::new(); tiers.len()];
+        let mut tier = 0;
+        for (i, segment) in self.get_segments().iter().enumerate() {
+            if i > 0 && segment.is_speaker_turn() {
+                tier = (tier + 1) % tiers.len();
+            }
+            let start_slot = 2 * i + 1;
+            let end_slot = 2 * i + 2;
+            for (slot, timestamp) in [
+                (start_slot, segment.get_start_timestamp()),
+                (end_slot, segment.get_end_timestamp()),
+            ] {
+                let _ = writeln!(
+                    time_slots,
+                    "        <TIME_SLOT TIME_SLOT_ID=\"ts{}\" TIME_VALUE=\"{}\"/>",
+                    slot,
+                    centiseconds_to_ms(*timestamp)
+                );
+            }
+            let _ = write!(
+                annotations[tier],
+                concat!(
+                    "        <ANNOTATION>\n",
+                    "            <ALIGNABLE_ANNOTATION ANNOTATION_ID=\"a{}\" TIME_SLOT_REF1=\"ts{}\" TIME_SLOT_REF2=\"ts{}\">\n",
+                    "                <ANNOTATION_VALUE>{}</ANNOTATION_VALUE>\n",
+                    "            </ALIGNABLE_ANNOTATION>\n",
+                    "        </ANNOTATION>\n"
+                ),
+                i + 1,
+                start_slot,
+                end_slot,
+                escape_xml(segment.get_text().trim())
+            );
+        }
+
+        let mut eaf = String::new();
+        eaf.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+        let _ = writeln!(
+            eaf,
+            concat!(
+                "<ANNOTATION_DOCUMENT AUTHOR=\"\" DATE=\"{}\" FORMAT=\"3.0\" VERSION=\"3.0\" ",
+                "xmlns:xsi=\"http://www.w3.org/2001/XMLSchema-instance\" ",
+                "xsi:noNamespaceSchemaLocation=\"http://www.mpi.nl/tools/elan/EAFv3.0.xsd\">"
+            ),
+            current_date_time()
+        );
+        eaf.push_str("    <HEADER MEDIA_FILE=\"\" TIME_UNITS=\"milliseconds\"/>\n");
+        eaf.push_str("    <TIME_ORDER>\n");
+        eaf.push_str(&time_slots);
+        eaf.push_str("    </TIME_ORDER>\n");
+        for ((tier_id, participant), annotations) in tiers.iter().zip(&annotations) {
+            let participant = if participant.is_empty() {
+                String::new()
+            } else {
+                format!(" PARTICIPANT=\"{}\"", escape_xml(participant))
+            };
+            let _ = writeln!(
+                eaf,
+                "    <TIER LINGUISTIC_TYPE_REF=\"default-lt\" TIER_ID=\"{}\"{}>",
+                escape_xml(tier_id),
+                participant
+            );
+            eaf.push_str(annotations);
+            eaf.push_str("    </TIER>\n");
+        }
+        eaf.push_str(
+            "    <LINGUISTIC_TYPE GRAPHIC_REFERENCES=\"false\" LINGUISTIC_TYPE_ID=\"default-lt\" TIME_ALIGNABLE=\"true\"/>\n",
+        );
+        eaf.push_str("</ANNOTATION_DOCUMENT>\n");
+        eaf
+    }
+}
+
+/// The current UTC time formatted as an xsd:dateTime, as required by the EAF `DATE` attribute.
+fn current_date_time() -> String {
+    let secs = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or(0);
+    let (days, secs_of_day) = (secs.div_euclid(86400), secs.rem_euclid(86400));
+
+    // civil-from-days, see http://howardhinnant.github.io/date_algorithms.html
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}+00:00",
+        year,
+        month,
+        day,
+        secs_of_day / 3600,
+        secs_of_day % 3600 / 60,
+        secs_of_day % 60
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transcriber::TranscriberOutputSegment;
+
+    fn test_output() -> TranscriberOutput {
+        TranscriberOutput::new(vec![
+            TranscriberOutputSegment::new(0, 150, " Hello & welcome."),
+            TranscriberOutputSegment::new(150, 320, " <laughs> \"quoted\""),
+        ])
+    }
+
+    #[test]
+    fn test_to_eaf_is_well_formed() {
+        let eaf = test_output().to_eaf();
+        let doc = roxmltree::Document::parse(&eaf).unwrap();
+        assert_eq!(doc.root_element().tag_name().name(), "ANNOTATION_DOCUMENT");
+
+        let texts: Vec<&str> = doc
+            .descendants()
+            .filter(|n| n.has_tag_name("ANNOTATION_VALUE"))
+            .map(|n| n.text().unwrap_or(""))
+            .collect();
+        assert_eq!(texts, vec!["Hello & welcome.", "<laughs> \"quoted\""]);
+    }
+
+    #[test]
+    fn test_to_eaf_time_slot_references() {
+        let eaf = test_output().to_eaf();
+        let doc = roxmltree::Document::parse(&eaf).unwrap();
+
+        let slots: std::collections::HashMap<&str, &str> = doc
+            .descendants()
+            .filter(|n| n.has_tag_name("TIME_SLOT"))
+            .map(|n| {
+                (
+                    n.attribute("TIME_SLOT_ID").unwrap(),
+                    n.attribute("TIME_VALUE").unwrap(),
+                )
+            })
+            .collect();
+        let annotations: Vec<_> = doc
+            .descendants()
+            .filter(|n| n.has_tag_name("ALIGNABLE_ANNOTATION"))
+            .collect();
+
+        assert_eq!(annotations.len(), 2);
+        assert_eq!(slots.len(), 4);
+        let second = annotations[1];
+        assert_eq!(slots[second.attribute("TIME_SLOT_REF1").unwrap()], "1500");
+        assert_eq!(slots[second.attribute("TIME_SLOT_REF2").unwrap()], "3200");
+    }
+
+    #[test]
+    fn test_to_eaf_with_options_tier() {
+        let options = EafOptions {
+            tier_id: "speaker A".to_string(),
+            participant: "Ann".to_string(),
+            ..Default::default()
+        };
+        let eaf = test_output().to_eaf_with_options(&options);
+        let doc = roxmltree::Document::parse(&eaf).unwrap();
+        let tiers: Vec<_> = doc
+            .descendants()
+            .filter(|n| n.has_tag_name("TIER"))
+            .collect();
+
+        assert_eq!(tiers.len(), 1);
+        assert_eq!(tiers[0].attribute("TIER_ID"), Some("speaker A"));
+        assert_eq!(tiers[0].attribute("PARTICIPANT"), Some("Ann"));
+    }
+
+    #[test]
+    fn test_to_eaf_speaker_tiers() {
+        let mut segments = vec![
+            TranscriberOutputSegment::new(0, 100, " Where were you?"),
+            TranscriberOutputSegment::new(100, 200, " That night?"),
+            TranscriberOutputSegment::new(400, 500, " At home."),
+            TranscriberOutputSegment::new(800, 900, " Alone?"),
+        ];
+        segments[2].speaker_turn = true;
+        segments[3].speaker_turn = true;
+        let options = EafOptions {
+            speaker_tiers: vec!["interviewer".to_string(), "witness".to_string()],
+            ..Default::default()
+        };
+
+        let eaf = TranscriberOutput::new(segments).to_eaf_with_options(&options);
+
+        let doc = roxmltree::Document::parse(&eaf).unwrap();
+        let tiers: Vec<(&str, Option<&str>, Vec<&str>)> = doc
+            .descendants()
+            .filter(|n| n.has_tag_name("TIER"))
+            .map(|tier| {
+                let texts = tier
+                    .descendants()
+                    .filter(|n| n.has_tag_name("ANNOTATION_VALUE"))
+                    .map(|n| n.text().unwrap_or(""))
+                    .collect();
+                (
+                    tier.attribute("TIER_ID").unwrap(),
+                    tier.attribute("PARTICIPANT"),
+                    texts,
+                )
+            })
+            .collect();
+        assert_eq!(
+            tiers,
+            vec![
+                (
+                    "interviewer",
+                    Some("interviewer"),
+                    vec!["Where were you?", "That night?", "Alone?"]
+                ),
+                ("witness", Some("witness"), vec!["At home."]),
+            ]
+        );
+        let ids: std::collections::HashSet<&str> = doc
+            .descendants()
+            .filter(|n| n.has_tag_name("ALIGNABLE_ANNOTATION"))
+            .map(|n| n.attribute("ANNOTATION_ID").unwrap())
+            .collect();
+        assert_eq!(ids.len(), 4);
+    }
+}
@@ -0,0 +1,238 @@
+use std::io::Write;
+use std::path::Path;
+
+use crate::error::Error;
+use crate::export::TextSeparator;
+use crate::transcriber::TranscriberOutput;
+
+/// An output format a `TranscriberOutput` can be written in.
+///
+/// Implement this to plug a custom format into `TranscriberOutput::save_as` or an
+/// `ExporterRegistry`. Exporters are `Send + Sync` so a registry can be shared between
+/// threads.
+pub trait TranscriptExporter: Send + Sync {
+    /// Write the transcription to `w`.
+    fn export(&self, output: &TranscriberOutput, w: &mut dyn Write) -> Result<(), Error>;
+
+    /// The file extension (without the leading dot) files of this format use.
+    fn extension(&self) -> &'static str;
+}
+
+/// The formats built into the crate.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Srt,
+    Vtt,
+    Json,
+    Csv,
+    Eaf,
+    TextGrid,
+    /// Plain text, a line per segment, see `TranscriberOutput::to_plain_text`.
+    Txt,
+}
+
+impl Format {
+    /// Every built-in format.
+    pub const ALL: [Format; 7] = [
+        Format::Srt,
+        Format::Vtt,
+        Format::Json,
+        Format::Csv,
+        Format::Eaf,
+        Format::TextGrid,
+        Format::Txt,
+    ];
+}
+
+impl TranscriptExporter for Format {
+    fn export(&self, output: &TranscriberOutput, w: &mut dyn Write) -> Result<(), Error> {
+        match self {
+            Format::Srt => output.write_srt(w)?,
+            Format::Vtt => output.write_vtt(w)?,
+            Format::Json => output.write_json(w)?,
+            Format::Csv => output.write_csv(w)?,
+            Format::Eaf => w.write_all(output.to_eaf().as_bytes())?,
+            Format::TextGrid => w.write_all(output.to_textgrid().as_bytes())?,
+            Format::Txt => writeln!(w, "{}", output.to_plain_text(TextSeparator::Newline))?,
+        }
+        Ok(())
+    }
+
+    fn extension(&self) -> &'static str {
+        match self {
+            Format::Srt => "srt",
+            Format::Vtt => "vtt",
+            Format::Json => "json",
+            Format::Csv => "csv",
+            Format::Eaf => "eaf",
+            Format::TextGrid => "TextGrid",
+            Format::Txt => "txt",
+        }
+    }
+}
+
+/// Maps file extensions to exporters, used to pick the format when saving by file name.
+///
+/// The default registry holds the built-in formats. Exporters registered later take
+/// precedence over earlier ones with the same extension.
+pub struct ExporterRegistry {
+    exporters: Vec<Box<dyn TranscriptExporter>>,
+}
+
+impl Default for ExporterRegistry {
+    fn default() -> Self {
+        let mut registry = ExporterRegistry {
+            exporters: Vec::new(),
+        };
+        for format in Format::ALL {
+            registry.register(Box::new(format));
+        }
+        registry
+    }
+}
+
+impl ExporterRegistry {
+    /// Register an exporter for its extension.
+    pub fn register(&mut self, exporter: Box<dyn TranscriptExporter>) {
+        self.exporters.push(exporter);
+    }
+
+    /// Look up the exporter for an extension, ignoring case.
+    pub fn get(&self, extension: &str) -> Option<&dyn TranscriptExporter> {
+        self.exporters
+            .iter()
+            .rev()
+            .find(|exporter| exporter.extension().eq_ignore_ascii_case(extension))
+            .map(|exporter| exporter.as_ref())
+    }
+
+    /// Save the transcription to `path`, picking the exporter from the path's extension.
+    pub fn save(&self, output: &TranscriberOutput, path: impl AsRef<Path>) -> Result<(), Error> {
+        let path = path.as_ref();
+        let extension = path
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .unwrap_or_default();
+        let exporter = self
+            .get(extension)
+            .ok_or_else(|| Error::UnsupportedExtension(extension.to_string()))?;
+        output.save_as(path, exporter)
+    }
+}
+
+impl TranscriberOutput {
+    /// Save the transcription to `path`, picking a built-in format from the path's extension.
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), Error> {
+        ExporterRegistry::default().save(self, path)
+    }
+
+    /// Save the transcription to `path` using the given exporter.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - the file to create or overwrite.
+    /// * `exporter` - a built-in `Format` or a custom `TranscriptExporter`.
+    pub fn save_as(
+        &self,
+        path: impl AsRef<Path>,
+        exporter: &dyn TranscriptExporter,
+    ) -> Result<(), Error> {
+        let mut file = std::io::BufWriter::new(std::fs::File::create(path)?);
+        exporter.export(self, &mut file)?;
+        file.flush()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transcriber::TranscriberOutputSegment;
+
+    struct UppercaseExporter;
+
+    impl TranscriptExporter for UppercaseExporter {
+        fn export(&self, output: &TranscriberOutput, w: &mut dyn Write) -> Result<(), Error> {
+            for segment in output.get_segments() {
+                writeln!(w, "{}", segment.get_text().trim().to_uppercase())?;
+            }
+            Ok(())
+        }
+
+        fn extension(&self) -> &'static str {
+            "upper"
+        }
+    }
+
+    fn test_output() -> TranscriberOutput {
+        TranscriberOutput::new(vec![
+            TranscriberOutputSegment::new(0, 100, " Hello"),
+            TranscriberOutputSegment::new(100, 200, " world"),
+        ])
+    }
+
+    #[test]
+    fn test_save_as_custom_exporter() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("out.txt");
+        let path = path.to_str().unwrap();
+
+        test_output().save_as(path, &UppercaseExporter).unwrap();
+
+        assert_eq!(std::fs::read_to_string(path).unwrap(), "HELLO\nWORLD\n");
+    }
+
+    #[test]
+    fn test_save_as_built_in_format() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("out.txt");
+        let path = path.to_str().unwrap();
+        let output = test_output();
+
+        output.save_as(path, &Format::Vtt).unwrap();
+
+        assert_eq!(std::fs::read_to_string(path).unwrap(), output.to_vtt());
+    }
+
+    #[test]
+    fn test_save_picks_format_from_extension() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("out.SRT");
+        let path = path.to_str().unwrap();
+        let output = test_output();
+
+        output.save(path).unwrap();
+
+        assert_eq!(std::fs::read_to_string(path).unwrap(), output.to_srt());
+    }
+
+    #[test]
+    fn test_save_txt_writes_plain_text() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("out.txt");
+
+        test_output().save(&path).unwrap();
+
+        assert_eq!(std::fs::read_to_string(path).unwrap(), "Hello\nworld\n");
+    }
+
+    #[test]
+    fn test_registry_picks_up_registered_exporter() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("out.upper");
+        let path = path.to_str().unwrap();
+        let mut registry = ExporterRegistry::default();
+        registry.register(Box::new(UppercaseExporter));
+
+        registry.save(&test_output(), path).unwrap();
+
+        assert_eq!(std::fs::read_to_string(path).unwrap(), "HELLO\nWORLD\n");
+    }
+
+    #[test]
+    fn test_save_unknown_extension() {
+        let result = test_output().save("out.unknown");
+
+        assert!(matches!(result, Err(Error::UnsupportedExtension(ext)) if ext == "unknown"));
+    }
+}
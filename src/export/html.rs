@@ -0,0 +1,86 @@
+use std::fmt::Write;
+
+use super::escape_xml;
+use crate::transcriber::TranscriberOutput;
+
+impl TranscriberOutput {
+    /// Convert the transcription to an HTML snippet to embed in a web page.
+    ///
+    /// The snippet is a `<div class="transcript">` with a paragraph per segment, led by a
+    /// link to its start time. The times are in seconds, in the paragraph's `data-start` and
+    /// `data-end` attributes and in the link's `data-time`, whose `href` is a `#t=` media
+    /// fragment. A few lines of script make it an interactive transcript:
+    ///
+    /// ```js
+    /// document.querySelectorAll(".transcript a[data-time]").forEach((link) =>
+    ///   link.addEventListener("click", (event) => {
+    ///     event.preventDefault();
+    ///     player.currentTime = Number(link.dataset.time);
+    ///     player.play();
+    ///   })
+    /// );
+    /// ```
+    pub fn to_html(&self) -> String {
+        let mut html = String::from("<div class=\"transcript\">\n");
+        for segment in self.get_segments() {
+            let start = seconds(*segment.get_start_timestamp());
+            let _ = writeln!(
+                html,
+                "<p data-start=\"{}\" data-end=\"{}\"><a class=\"timestamp\" href=\"#t={}\" \
+                 data-time=\"{}\">{}</a> {}</p>",
+                start,
+                seconds(*segment.get_end_timestamp()),
+                start,
+                start,
+                clock(*segment.get_start_timestamp()),
+                escape_xml(segment.get_text().trim())
+            );
+        }
+        html.push_str("</div>\n");
+        html
+    }
+}
+
+/// A timestamp in centiseconds as seconds, such as `1.5`.
+fn seconds(timestamp: i64) -> String {
+    let seconds = format!("{:.2}", timestamp.max(0) as f64 / 100.0);
+    seconds
+        .trim_end_matches('0')
+        .trim_end_matches('.')
+        .to_string()
+}
+
+/// A timestamp in centiseconds as a clock reading, `m:ss` or `h:mm:ss`.
+fn clock(timestamp: i64) -> String {
+    let seconds = timestamp.max(0) / 100;
+    let (hours, minutes, seconds) = (seconds / 3600, seconds / 60 % 60, seconds % 60);
+    if hours > 0 {
+        format!("{}:{:02}:{:02}", hours, minutes, seconds)
+    } else {
+        format!("{}:{:02}", minutes, seconds)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::transcriber::{TranscriberOutput, TranscriberOutputSegment};
+
+    #[test]
+    fn test_to_html_happy_case() {
+        let output = TranscriberOutput::new(vec![
+            TranscriberOutputSegment::new(0, 150, " Hello."),
+            TranscriberOutputSegment::new(150, 372012, " Tom & <Jerry>."),
+        ]);
+
+        assert_eq!(
+            output.to_html(),
+            "<div class=\"transcript\">\n\
+             <p data-start=\"0\" data-end=\"1.5\"><a class=\"timestamp\" href=\"#t=0\" \
+             data-time=\"0\">0:00</a> Hello.</p>\n\
+             <p data-start=\"1.5\" data-end=\"3720.12\"><a class=\"timestamp\" href=\"#t=1.5\" \
+             data-time=\"1.5\">0:01</a> Tom &amp; &lt;Jerry&gt;.</p>\n\
+             </div>\n"
+        );
+        assert_eq!(super::clock(372012), "1:02:00");
+    }
+}
@@ -0,0 +1,129 @@
+use std::io::{BufWriter, Write};
+use std::time::Duration;
+
+use crate::transcriber::TranscriberOutput;
+
+/// Options for the JSON exporter.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct JsonOptions {
+    /// Add a `silences` array with the `start` and `end` of every silence of at least this
+    /// long between the segments, see `TranscriberOutput::with_gaps`.
+    ///
+    /// Defaults to `None`.
+    pub silences: Option<Duration>,
+}
+
+impl TranscriberOutput {
+    /// Convert the transcription to JSON.
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).expect("transcriber output is always serializable")
+    }
+
+    /// Convert the transcription to JSON using the given options, see `to_json`.
+    pub fn to_json_with_options(&self, options: &JsonOptions) -> String {
+        serde_json::to_string(&self.optioned_json(options))
+            .expect("transcriber output is always serializable")
+    }
+
+    /// Stream the transcription as JSON without building the whole document in memory.
+    ///
+    /// # Arguments
+    ///
+    /// * `w` - the writer (file, socket, ...) the JSON is written to.
+    pub fn write_json<W: Write>(&self, w: W) -> std::io::Result<()> {
+        let mut w = BufWriter::new(w);
+        serde_json::to_writer(&mut w, self)?;
+        w.flush()
+    }
+
+    /// Stream the transcription as JSON using the given options, see `write_json`.
+    pub fn write_json_with_options<W: Write>(
+        &self,
+        w: W,
+        options: &JsonOptions,
+    ) -> std::io::Result<()> {
+        let mut w = BufWriter::new(w);
+        serde_json::to_writer(&mut w, &self.optioned_json(options))?;
+        w.flush()
+    }
+
+    fn optioned_json(&self, options: &JsonOptions) -> OptionedJson<'_> {
+        OptionedJson {
+            output: self,
+            silences: options.silences.map(|min_gap| {
+                self.silences(min_gap)
+                    .into_iter()
+                    .map(|(start, end)| Silence { start, end })
+                    .collect()
+            }),
+        }
+    }
+}
+
+/// The JSON of a transcription with the additions asked for by `JsonOptions`.
+#[derive(serde::Serialize)]
+struct OptionedJson<'a> {
+    #[serde(flatten)]
+    output: &'a TranscriberOutput,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    silences: Option<Vec<Silence>>,
+}
+
+#[derive(serde::Serialize)]
+struct Silence {
+    start: i64,
+    end: i64,
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::transcriber::{TranscriberOutput, TranscriberOutputSegment};
+
+    #[test]
+    fn test_to_json_happy_case() {
+        let output =
+            TranscriberOutput::new(vec![TranscriberOutputSegment::new(0, 150, " Say \"hi\"")]);
+
+        assert_eq!(
+            output.to_json(),
+            r#"{"segments":[{"id":0,"start_timestamp":0,"end_timestamp":150,"text":" Say \"hi\""}]}"#
+        );
+    }
+
+    #[test]
+    fn test_to_json_with_silences() {
+        let output = TranscriberOutput::new(vec![TranscriberOutputSegment::new(100, 150, " Hi")]);
+        let options = super::JsonOptions {
+            silences: Some(std::time::Duration::from_millis(500)),
+        };
+
+        let json: serde_json::Value =
+            serde_json::from_str(&output.to_json_with_options(&options)).unwrap();
+        assert_eq!(
+            json["silences"],
+            serde_json::json!([{"start": 0, "end": 100}])
+        );
+        assert_eq!(json["segments"][0]["text"], " Hi");
+        assert_eq!(
+            output.to_json_with_options(&Default::default()),
+            output.to_json()
+        );
+        let mut buffer = Vec::new();
+        output
+            .write_json_with_options(&mut buffer, &options)
+            .unwrap();
+        assert_eq!(
+            String::from_utf8(buffer).unwrap(),
+            output.to_json_with_options(&options)
+        );
+    }
+
+    #[test]
+    fn test_write_json_matches_to_json() {
+        let output = TranscriberOutput::new(vec![TranscriberOutputSegment::new(10, 20, " Hi")]);
+        let mut buffer = Vec::new();
+        output.write_json(&mut buffer).unwrap();
+
+        assert_eq!(String::from_utf8(buffer).unwrap(), output.to_json());
+    }
+}
@@ -0,0 +1,235 @@
+//! Conversions from a `TranscriberOutput` into common transcript and annotation formats.
+
+mod audacity;
+mod bidi;
+mod csv;
+mod eaf;
+mod exporter;
+mod html;
+mod json;
+mod openai;
+mod profile;
+mod srt;
+mod text;
+mod textgrid;
+mod timecode;
+mod vtt;
+
+pub use csv::CsvOptions;
+pub use eaf::EafOptions;
+pub use exporter::{ExporterRegistry, Format, TranscriptExporter};
+pub use json::JsonOptions;
+pub use profile::{SubtitleFormat, SubtitleProfile};
+pub use text::{ParagraphOptions, TextSeparator};
+pub use timecode::{
+    format_timecode, FrameRate, RoundingMode, RoundingPrecision, TimestampFormat, TimestampRounding,
+};
+
+use std::borrow::Cow;
+
+use crate::options::OverlapPolicy;
+use crate::transcriber::{TranscriberOutput, TranscriberOutputSegment};
+
+/// Options for the SRT and VTT subtitle exporters.
+#[derive(Debug, Clone, serde::Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SubtitleOptions {
+    /// Wrap predominantly right-to-left cues (Arabic, Hebrew, ...) and the left-to-right runs
+    /// embedded in them with Unicode directional isolates, so players don't reorder their
+    /// punctuation. Left-to-right cues are never changed.
+    ///
+    /// Defaults to `false`.
+    pub bidi_isolation: bool,
+    /// How overlapping segments are resolved before being written as cues, since subtitle
+    /// players expect cues not to overlap. `None` writes the segments as they are.
+    ///
+    /// Defaults to `Some(OverlapPolicy::TrimEarlier)`.
+    pub overlap_policy: Option<OverlapPolicy>,
+    /// WebVTT only: wrap words whose probability is below this threshold in `<c.low>` class
+    /// spans, so a styled player can flag uncertain words. Needs word timestamps, cues
+    /// without words are written unstyled.
+    ///
+    /// Defaults to `None`.
+    pub low_confidence_threshold: Option<f32>,
+    /// Break cue text into lines of at most this many characters, between words. A single
+    /// word longer than a line gets a line of its own.
+    ///
+    /// Defaults to `None`, one line per cue.
+    pub max_line_chars: Option<usize>,
+    /// Round cue timestamps, such as to the frames of the video the subtitles go with. whisper
+    /// times segments to the centisecond, written as they are by default.
+    ///
+    /// Defaults to `None`.
+    pub timestamp_rounding: Option<TimestampRounding>,
+}
+
+impl Default for SubtitleOptions {
+    fn default() -> Self {
+        SubtitleOptions {
+            bidi_isolation: false,
+            overlap_policy: Some(OverlapPolicy::TrimEarlier),
+            low_confidence_threshold: None,
+            max_line_chars: None,
+            timestamp_rounding: None,
+        }
+    }
+}
+
+impl SubtitleOptions {
+    /// The segments to write as cues, with the overlap policy applied. Resolving overlaps
+    /// sorts the segments by start time first, as players expect cues in order.
+    pub(crate) fn cue_segments<'a>(
+        &self,
+        output: &'a TranscriberOutput,
+    ) -> Cow<'a, [TranscriberOutputSegment]> {
+        match self.overlap_policy {
+            Some(policy) => {
+                let mut segments = output.get_segments().clone();
+                crate::postprocess::sort_segments(&mut segments);
+                crate::postprocess::resolve_overlaps(&mut segments, policy);
+                Cow::Owned(segments)
+            }
+            None => Cow::Borrowed(output.get_segments()),
+        }
+    }
+
+    /// A cue timestamp, rounded as the options say.
+    pub(crate) fn cue_timestamp(&self, timestamp: i64, separator: char) -> String {
+        match self.timestamp_rounding {
+            Some(rounding) => rounding.format(timestamp, separator),
+            None => format_timestamp(timestamp, separator),
+        }
+    }
+
+    /// The text of a cue, with the options applied.
+    pub(crate) fn cue_text(&self, text: &str) -> String {
+        let lines = match self.max_line_chars {
+            Some(max_line_chars) => wrap_lines(text, max_line_chars),
+            None => vec![text.trim().to_string()],
+        };
+        let lines: Vec<String> = if self.bidi_isolation {
+            lines.iter().map(|line| bidi::isolate_bidi(line)).collect()
+        } else {
+            lines
+        };
+        lines.join("\n")
+    }
+}
+
+/// Greedily break text into lines of at most `max_line_chars` characters between words,
+/// collapsing the whitespace between words to single spaces.
+pub(crate) fn wrap_lines(text: &str, max_line_chars: usize) -> Vec<String> {
+    let mut lines: Vec<String> = Vec::new();
+    let mut line = String::new();
+    for word in text.split_whitespace() {
+        if !line.is_empty() && line.chars().count() + 1 + word.chars().count() > max_line_chars {
+            lines.push(std::mem::take(&mut line));
+        }
+        if !line.is_empty() {
+            line.push(' ');
+        }
+        line.push_str(word);
+    }
+    if !line.is_empty() || lines.is_empty() {
+        lines.push(line);
+    }
+    lines
+}
+
+/// Whether text wraps (see `wrap_lines`) into at most `max_lines` lines.
+pub(crate) fn fits_lines(text: &str, max_line_chars: usize, max_lines: usize) -> bool {
+    wrap_lines(text, max_line_chars).len() <= max_lines
+}
+
+/// Escape the characters that are not allowed verbatim in XML text and attribute values.
+pub(crate) fn escape_xml(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Convert a whisper timestamp (in centiseconds) to milliseconds.
+pub(crate) fn centiseconds_to_ms(timestamp: i64) -> i64 {
+    timestamp * 10
+}
+
+/// Format a whisper timestamp (in centiseconds) as `HH:MM:SS<separator>mmm`, such as
+/// `00:01:02.500` or `00:01:02,500`. Negative timestamps are taken as zero.
+pub fn format_timestamp(timestamp: i64, separator: char) -> String {
+    format_ms(centiseconds_to_ms(timestamp.max(0)), separator)
+}
+
+/// Format a non-negative number of milliseconds as `HH:MM:SS<separator>mmm`.
+pub(crate) fn format_ms(ms: i64, separator: char) -> String {
+    format!(
+        "{:02}:{:02}:{:02}{}{:03}",
+        ms / 3_600_000,
+        ms % 3_600_000 / 60_000,
+        ms % 60_000 / 1000,
+        separator,
+        ms % 1000
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_timestamp_happy_case() {
+        assert_eq!(format_timestamp(0, ','), "00:00:00,000");
+        assert_eq!(format_timestamp(366012, '.'), "01:01:00.120");
+    }
+
+    #[test]
+    fn test_escape_xml_special_characters() {
+        assert_eq!(
+            escape_xml(r#"<a href="x">Tom & Jerry's</a>"#),
+            "&lt;a href=&quot;x&quot;&gt;Tom &amp; Jerry&apos;s&lt;/a&gt;"
+        );
+    }
+
+    #[test]
+    fn test_wrap_lines() {
+        assert_eq!(
+            wrap_lines(" The quick brown fox jumps over the lazy dog.", 16),
+            vec!["The quick brown", "fox jumps over", "the lazy dog."]
+        );
+        assert_eq!(
+            wrap_lines("a verylongword b", 4),
+            vec!["a", "verylongword", "b"]
+        );
+        assert_eq!(wrap_lines("  ", 10), vec![""]);
+    }
+
+    #[test]
+    fn test_cue_text_wraps_lines() {
+        let options = SubtitleOptions {
+            max_line_chars: Some(10),
+            ..Default::default()
+        };
+
+        assert_eq!(
+            options.cue_text(" Hello there, world."),
+            "Hello\nthere,\nworld."
+        );
+        assert_eq!(
+            SubtitleOptions::default().cue_text(" Hello there, world."),
+            "Hello there, world."
+        );
+    }
+
+    #[test]
+    fn test_escape_xml_plain_text_unchanged() {
+        assert_eq!(escape_xml("hello world"), "hello world");
+    }
+}
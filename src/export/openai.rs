@@ -0,0 +1,254 @@
+use serde::Serialize;
+
+use crate::transcriber::TranscriberOutput;
+
+/// The names OpenAI's API reports languages by, keyed by whisper language code.
+const LANGUAGE_NAMES: phf::Map<&'static str, &'static str> = phf::phf_map! {
+    "en" => "english", "zh" => "chinese", "de" => "german", "es" => "spanish",
+    "ru" => "russian", "ko" => "korean", "fr" => "french", "ja" => "japanese",
+    "pt" => "portuguese", "tr" => "turkish", "pl" => "polish", "ca" => "catalan",
+    "nl" => "dutch", "ar" => "arabic", "sv" => "swedish", "it" => "italian",
+    "id" => "indonesian", "hi" => "hindi", "fi" => "finnish", "vi" => "vietnamese",
+    "he" => "hebrew", "uk" => "ukrainian", "el" => "greek", "ms" => "malay",
+    "cs" => "czech", "ro" => "romanian", "da" => "danish", "hu" => "hungarian",
+    "ta" => "tamil", "no" => "norwegian", "th" => "thai", "ur" => "urdu",
+    "hr" => "croatian", "bg" => "bulgarian", "lt" => "lithuanian", "la" => "latin",
+    "mi" => "maori", "ml" => "malayalam", "cy" => "welsh", "sk" => "slovak",
+    "te" => "telugu", "fa" => "persian", "lv" => "latvian", "bn" => "bengali",
+    "sr" => "serbian", "az" => "azerbaijani", "sl" => "slovenian", "kn" => "kannada",
+    "et" => "estonian", "mk" => "macedonian", "br" => "breton", "eu" => "basque",
+    "is" => "icelandic", "hy" => "armenian", "ne" => "nepali", "mn" => "mongolian",
+    "bs" => "bosnian", "kk" => "kazakh", "sq" => "albanian", "sw" => "swahili",
+    "gl" => "galician", "mr" => "marathi", "pa" => "punjabi", "si" => "sinhala",
+    "km" => "khmer", "sn" => "shona", "yo" => "yoruba", "so" => "somali",
+    "af" => "afrikaans", "oc" => "occitan", "ka" => "georgian", "be" => "belarusian",
+    "tg" => "tajik", "sd" => "sindhi", "gu" => "gujarati", "am" => "amharic",
+    "yi" => "yiddish", "lo" => "lao", "uz" => "uzbek", "fo" => "faroese",
+    "ht" => "haitian creole", "ps" => "pashto", "tk" => "turkmen", "nn" => "nynorsk",
+    "mt" => "maltese", "sa" => "sanskrit", "lb" => "luxembourgish", "my" => "myanmar",
+    "bo" => "tibetan", "tl" => "tagalog", "mg" => "malagasy", "as" => "assamese",
+    "tt" => "tatar", "haw" => "hawaiian", "ln" => "lingala", "ha" => "hausa",
+    "ba" => "bashkir", "jw" => "javanese", "su" => "sundanese", "yue" => "cantonese",
+};
+
+#[derive(Serialize)]
+struct VerboseJson<'a> {
+    task: &'static str,
+    language: &'a str,
+    duration: f64,
+    text: String,
+    segments: Vec<VerboseSegment<'a>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    words: Option<Vec<VerboseWord<'a>>>,
+}
+
+#[derive(Serialize)]
+struct VerboseSegment<'a> {
+    id: usize,
+    seek: i64,
+    start: f64,
+    end: f64,
+    text: &'a str,
+    tokens: &'a [i32],
+    temperature: f64,
+    avg_logprob: f64,
+    compression_ratio: f64,
+    no_speech_prob: f64,
+}
+
+#[derive(Serialize)]
+struct VerboseWord<'a> {
+    word: &'a str,
+    start: f64,
+    end: f64,
+}
+
+fn seconds(centiseconds: i64) -> f64 {
+    centiseconds as f64 / 100.0
+}
+
+impl TranscriberOutput {
+    /// Convert the transcription to the `verbose_json` response format of OpenAI's
+    /// transcription API, so tools built for it can read it.
+    ///
+    /// `seek` is the segment's `get_seek`, or `0` when it has none. `tokens` are the
+    /// segment's `get_token_ids` and `avg_logprob` the mean log probability of its tokens,
+    /// both collected with `TranscribeOptions::token_probabilities`. Without them `tokens` is
+    /// empty and `avg_logprob` the mean log probability of the segment's words, or `0.0`
+    /// without word timestamps either. Fields whisper-rs doesn't report are filled with
+    /// defaults: `temperature` is `0.0`, `compression_ratio` is `1.0` and `no_speech_prob` is
+    /// `0.0`. `language` is `"unknown"` when it is not known, and `words` is only present
+    /// when some segment has word timestamps.
+    pub fn to_openai_verbose_json(&self) -> String {
+        let segments = self
+            .segments
+            .iter()
+            .enumerate()
+            .map(|(id, segment)| {
+                let probabilities: Vec<f32> = if segment.token_probabilities.is_empty() {
+                    segment
+                        .words
+                        .iter()
+                        .filter_map(|word| word.probability)
+                        .collect()
+                } else {
+                    segment.token_probabilities.clone()
+                };
+                let probabilities: Vec<f64> = probabilities
+                    .iter()
+                    .map(|p| f64::from(p.max(f32::MIN_POSITIVE)).ln())
+                    .collect();
+                let avg_logprob = if probabilities.is_empty() {
+                    0.0
+                } else {
+                    probabilities.iter().sum::<f64>() / probabilities.len() as f64
+                };
+                VerboseSegment {
+                    id,
+                    seek: segment.seek.unwrap_or(0),
+                    start: seconds(segment.start_timestamp),
+                    end: seconds(segment.end_timestamp),
+                    text: &segment.text,
+                    tokens: &segment.token_ids,
+                    temperature: 0.0,
+                    avg_logprob,
+                    compression_ratio: 1.0,
+                    no_speech_prob: 0.0,
+                }
+            })
+            .collect();
+        let words: Vec<VerboseWord> = self
+            .segments
+            .iter()
+            .flat_map(|segment| &segment.words)
+            .map(|word| VerboseWord {
+                word: word.text.trim(),
+                start: seconds(word.start_timestamp),
+                end: seconds(word.end_timestamp),
+            })
+            .collect();
+        let duration = self.audio_duration.unwrap_or_else(|| {
+            self.segments
+                .iter()
+                .map(|segment| segment.end_timestamp)
+                .max()
+                .unwrap_or(0)
+        });
+
+        let json = VerboseJson {
+            task: if self.translated {
+                "translate"
+            } else {
+                "transcribe"
+            },
+            language: self
+                .language
+                .as_deref()
+                .and_then(|code| LANGUAGE_NAMES.get(code).copied())
+                .unwrap_or("unknown"),
+            duration: seconds(duration),
+            text: self
+                .segments
+                .iter()
+                .map(|segment| segment.text.as_str())
+                .collect::<String>()
+                .trim()
+                .to_string(),
+            segments,
+            words: (!words.is_empty()).then_some(words),
+        };
+        serde_json::to_string(&json).expect("verbose json is always serializable")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::transcriber::{TranscriberOutput, TranscriberOutputSegment, TranscriberOutputWord};
+
+    #[test]
+    fn test_to_openai_verbose_json() {
+        let mut output = TranscriberOutput::new(vec![
+            TranscriberOutputSegment::new(0, 150, " Hello world.").with_words(vec![
+                TranscriberOutputWord::new(0, 70, " Hello").with_probability(1.0),
+                TranscriberOutputWord::new(70, 150, " world.").with_probability(1.0),
+            ]),
+            TranscriberOutputSegment::new(150, 250, " Bye."),
+        ])
+        .with_audio_duration(300);
+        output.language = Some("en".to_string());
+        output.segments[1].seek = Some(150);
+
+        let json: serde_json::Value =
+            serde_json::from_str(&output.to_openai_verbose_json()).unwrap();
+
+        assert_eq!(json["task"], "transcribe");
+        assert_eq!(json["language"], "english");
+        assert_eq!(json["duration"], 3.0);
+        assert_eq!(json["text"], "Hello world. Bye.");
+        let segments = json["segments"].as_array().unwrap();
+        assert_eq!(segments.len(), 2);
+        assert_eq!(segments[1]["id"], 1);
+        assert_eq!(segments[1]["start"], 1.5);
+        assert_eq!(segments[1]["end"], 2.5);
+        assert_eq!(segments[1]["text"], " Bye.");
+        assert_eq!(segments[0]["seek"], 0);
+        assert_eq!(segments[1]["seek"], 150);
+        assert_eq!(segments[0]["avg_logprob"], 0.0);
+        assert_eq!(segments[0]["tokens"], serde_json::json!([]));
+        for key in [
+            "seek",
+            "tokens",
+            "temperature",
+            "compression_ratio",
+            "no_speech_prob",
+        ] {
+            assert!(segments[0].get(key).is_some(), "{}", key);
+        }
+        assert_eq!(
+            json["words"],
+            serde_json::json!([
+                {"word": "Hello", "start": 0.0, "end": 0.7},
+                {"word": "world.", "start": 0.7, "end": 1.5},
+            ])
+        );
+    }
+
+    #[test]
+    fn test_to_openai_verbose_json_tokens() {
+        let mut segment = TranscriberOutputSegment::new(0, 150, " Hello world.").with_words(vec![
+            TranscriberOutputWord::new(0, 70, " Hello").with_probability(0.5),
+            TranscriberOutputWord::new(70, 150, " world.").with_probability(0.5),
+        ]);
+        segment.token_ids = vec![2425, 1002, 13];
+        segment.token_probabilities = vec![1.0, 1.0, 0.25];
+        let output = TranscriberOutput::new(vec![segment]);
+
+        let json: serde_json::Value =
+            serde_json::from_str(&output.to_openai_verbose_json()).unwrap();
+
+        let segment = &json["segments"][0];
+        assert_eq!(segment["tokens"], serde_json::json!([2425, 1002, 13]));
+        // the tokens' probabilities are preferred over the words'
+        let avg_logprob = segment["avg_logprob"].as_f64().unwrap();
+        assert!(
+            (avg_logprob - 0.25f64.ln() / 3.0).abs() < 1e-6,
+            "{}",
+            avg_logprob
+        );
+    }
+
+    #[test]
+    fn test_to_openai_verbose_json_defaults() {
+        let mut output =
+            TranscriberOutput::new(vec![TranscriberOutputSegment::new(0, 120, " Hola.")]);
+        output.translated = true;
+
+        let json: serde_json::Value =
+            serde_json::from_str(&output.to_openai_verbose_json()).unwrap();
+
+        assert_eq!(json["task"], "translate");
+        assert_eq!(json["language"], "unknown");
+        assert_eq!(json["duration"], 1.2);
+        assert!(json.get("words").is_none());
+    }
+}
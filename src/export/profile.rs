@@ -0,0 +1,234 @@
+use std::time::Duration;
+
+use super::SubtitleOptions;
+use crate::options::TranscribeOptions;
+use crate::transcriber::{Transcriber, TranscriberOutput};
+
+/// The subtitle format `Transcriber::transcribe_to_subtitles` produces.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Deserialize)]
+pub enum SubtitleFormat {
+    Srt,
+    Vtt,
+}
+
+/// Everything needed to turn audio into ready to use subtitles, see
+/// `Transcriber::transcribe_to_subtitles`.
+///
+/// Every step is also available on its own: `transcribe` and `subtitles` are the options the
+/// transcriber and exporter take, and the cue layout is applied with
+/// `TranscriberOutput::merge_segments`, `TranscriberOutput::split_segments` and
+/// `TranscriberOutput::extend_for_reading_speed`.
+#[derive(Debug, Clone, serde::Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SubtitleProfile {
+    pub format: SubtitleFormat,
+    /// The longest line of a cue, in characters.
+    ///
+    /// Defaults to `42`.
+    pub max_line_chars: usize,
+    /// The most lines in a cue.
+    ///
+    /// Defaults to `2`.
+    pub max_lines: usize,
+    /// The fastest a cue may have to be read, in characters per second, see
+    /// `TranscriberOutput::extend_for_reading_speed`. `None` keeps whisper's timing.
+    ///
+    /// Defaults to `Some(20.0)`.
+    pub max_chars_per_second: Option<f32>,
+    /// Consecutive segments at most this far apart are merged into one cue when they fit.
+    /// `None` keeps whisper's segments.
+    ///
+    /// Defaults to half a second.
+    #[serde(deserialize_with = "crate::config::deserialize_optional_duration")]
+    pub merge_gap: Option<Duration>,
+    /// The options to transcribe with.
+    ///
+    /// Defaults to `TranscribeOptions::default()` with word timestamps, so cues split at the
+    /// right time, and segments of at most `max_lines` full lines split between words.
+    pub transcribe: TranscribeOptions,
+    /// The options to export with. `max_line_chars` is always replaced by the profile's.
+    ///
+    /// Defaults to `SubtitleOptions::default()`.
+    pub subtitles: SubtitleOptions,
+}
+
+impl Default for SubtitleProfile {
+    fn default() -> Self {
+        SubtitleProfile::with_layout(42, 2, 20.0)
+    }
+}
+
+impl SubtitleProfile {
+    /// Streaming service style subtitles: 2 lines of at most 42 characters, read at most at
+    /// 17 characters per second, as written in SRT.
+    pub fn netflix() -> SubtitleProfile {
+        SubtitleProfile::with_layout(42, 2, 17.0)
+    }
+
+    fn with_layout(
+        max_line_chars: usize,
+        max_lines: usize,
+        max_chars_per_second: f32,
+    ) -> SubtitleProfile {
+        SubtitleProfile {
+            format: SubtitleFormat::Srt,
+            max_line_chars,
+            max_lines,
+            max_chars_per_second: Some(max_chars_per_second),
+            merge_gap: Some(Duration::from_millis(500)),
+            transcribe: TranscribeOptions {
+                word_timestamps: true,
+                max_segment_chars: Some((max_line_chars * max_lines) as u32),
+                split_on_word: true,
+                ..Default::default()
+            },
+            subtitles: SubtitleOptions::default(),
+        }
+    }
+
+    /// Lay out a transcription's segments as cues and write them in the profile's format.
+    pub fn apply(&self, output: &mut TranscriberOutput) -> String {
+        if let Some(merge_gap) = self.merge_gap {
+            output.merge_segments(merge_gap, self.max_line_chars, self.max_lines);
+        }
+        output.split_segments(self.max_line_chars, self.max_lines);
+        if let Some(policy) = self.subtitles.overlap_policy {
+            output.resolve_overlaps(policy);
+        }
+        if let Some(max_chars_per_second) = self.max_chars_per_second {
+            output.extend_for_reading_speed(max_chars_per_second);
+        }
+
+        let options = SubtitleOptions {
+            max_line_chars: Some(self.max_line_chars),
+            ..self.subtitles.clone()
+        };
+        match self.format {
+            SubtitleFormat::Srt => output.to_srt_with_options(&options),
+            SubtitleFormat::Vtt => output.to_vtt_with_options(&options),
+        }
+    }
+}
+
+impl Transcriber {
+    /// Transcribe an audio file straight to subtitles laid out by the given profile.
+    ///
+    /// # Returns
+    ///
+    /// * `String` - the subtitles, in the profile's format.
+    pub fn transcribe_to_subtitles(
+        &self,
+        audio_path: impl AsRef<std::path::Path>,
+        profile: &SubtitleProfile,
+    ) -> Result<String, Box<dyn std::error::Error + Send + Sync>> {
+        let mut output = self.transcribe_with_options(audio_path, &profile.transcribe)?;
+        Ok(profile.apply(&mut output))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::audio_parser::tests::write_test_wav;
+    use crate::model_handler;
+    use crate::test_util::{MockBackend, MockSegment};
+
+    /// The cues of SRT subtitles, as (start, end, lines) with timestamps in milliseconds.
+    fn parse_srt(srt: &str) -> Vec<(i64, i64, Vec<String>)> {
+        let ms = |timestamp: &str| -> i64 {
+            let (hms, millis) = timestamp.split_once(',').unwrap();
+            let parts: Vec<i64> = hms.split(':').map(|p| p.parse().unwrap()).collect();
+            ((parts[0] * 60 + parts[1]) * 60 + parts[2]) * 1000 + millis.parse::<i64>().unwrap()
+        };
+        srt.split("\n\n")
+            .filter(|cue| !cue.trim().is_empty())
+            .map(|cue| {
+                let mut lines = cue.lines().skip(1);
+                let (start, end) = lines.next().unwrap().split_once(" --> ").unwrap();
+                (ms(start), ms(end), lines.map(str::to_string).collect())
+            })
+            .collect()
+    }
+
+    /// Assert SRT subtitles satisfy a profile's layout and reading speed, returning the cue count.
+    fn assert_satisfies(srt: &str, profile: &SubtitleProfile) -> usize {
+        let cues = parse_srt(srt);
+        for (start, end, lines) in &cues {
+            assert!(start <= end, "{:?}", lines);
+            assert!(lines.len() <= profile.max_lines, "{:?}", lines);
+            assert!(
+                lines
+                    .iter()
+                    .all(|line| line.chars().count() <= profile.max_line_chars),
+                "{:?}",
+                lines
+            );
+            let chars = lines.join(" ").chars().count() as f32;
+            let seconds = (end - start) as f32 / 1000.0;
+            assert!(
+                chars / seconds <= profile.max_chars_per_second.unwrap() + 0.1,
+                "{:?}",
+                lines
+            );
+        }
+        for pair in cues.windows(2) {
+            assert!(pair[0].1 <= pair[1].0);
+        }
+        cues.len()
+    }
+
+    #[test]
+    fn test_transcribe_to_subtitles_with_mock_backend() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("speech.wav");
+        write_test_wav(&path, 16000, 1, &vec![0; 16000 * 12]);
+        let transcriber = Transcriber::with_mock_backend(MockBackend::new(vec![
+            MockSegment::new(0, 100, " Hi."),
+            MockSegment::new(120, 200, " How are you?"),
+            MockSegment::new(
+                250,
+                900,
+                " I have been meaning to tell you about the trip we took to the mountains \
+                 last summer, it was",
+            ),
+            MockSegment::new(900, 1150, " wonderful."),
+        ]));
+        let profile = SubtitleProfile::netflix();
+
+        let srt = transcriber
+            .transcribe_to_subtitles(path.to_str().unwrap(), &profile)
+            .unwrap();
+
+        assert_eq!(assert_satisfies(&srt, &profile), 4);
+        assert!(srt.starts_with("1\n00:00:00,000 --> 00:00:02,000\nHi. How are you?\n"));
+    }
+
+    #[test]
+    fn test_subtitle_profile_vtt_format() {
+        let mut output =
+            TranscriberOutput::new(vec![crate::transcriber::TranscriberOutputSegment::new(
+                0, 150, " Hello.",
+            )]);
+        let profile = SubtitleProfile {
+            format: SubtitleFormat::Vtt,
+            ..Default::default()
+        };
+
+        assert!(profile.apply(&mut output).starts_with("WEBVTT\n"));
+    }
+
+    #[tokio::test]
+    async fn component_test_transcribe_to_subtitles() {
+        let tiny_model_handler = model_handler::ModelHandler::new("Tiny", "models").await;
+        let whisper_wrp = Transcriber::new(tiny_model_handler);
+        let profile = SubtitleProfile::netflix();
+
+        let srt = whisper_wrp
+            .transcribe_to_subtitles("src/test_data/test.mp3", &profile)
+            .unwrap();
+
+        assert!(assert_satisfies(&srt, &profile) > 0);
+
+        let _ = std::fs::remove_dir_all("models/");
+    }
+}
@@ -0,0 +1,146 @@
+use std::io::{BufWriter, Write};
+
+use super::SubtitleOptions;
+use crate::transcriber::TranscriberOutput;
+
+impl TranscriberOutput {
+    /// Convert the transcription to SubRip (SRT) subtitles.
+    pub fn to_srt(&self) -> String {
+        self.to_srt_with_options(&SubtitleOptions::default())
+    }
+
+    /// Convert the transcription to SubRip (SRT) subtitles using the given options.
+    pub fn to_srt_with_options(&self, options: &SubtitleOptions) -> String {
+        let mut buffer = Vec::new();
+        self.write_srt_with_options(&mut buffer, options)
+            .expect("writing to a Vec<u8> cannot fail");
+        String::from_utf8(buffer).expect("SRT output is valid UTF-8")
+    }
+
+    /// Stream the transcription as SubRip (SRT) subtitles, one cue at a time.
+    ///
+    /// # Arguments
+    ///
+    /// * `w` - the writer (file, socket, ...) the subtitles are written to.
+    pub fn write_srt<W: Write>(&self, w: W) -> std::io::Result<()> {
+        self.write_srt_with_options(w, &SubtitleOptions::default())
+    }
+
+    /// Stream the transcription as SubRip (SRT) subtitles using the given options.
+    pub fn write_srt_with_options<W: Write>(
+        &self,
+        w: W,
+        options: &SubtitleOptions,
+    ) -> std::io::Result<()> {
+        let mut w = BufWriter::new(w);
+        for (i, segment) in options.cue_segments(self).iter().enumerate() {
+            writeln!(w, "{}", i + 1)?;
+            writeln!(
+                w,
+                "{} --> {}",
+                options.cue_timestamp(*segment.get_start_timestamp(), ','),
+                options.cue_timestamp(*segment.get_end_timestamp(), ',')
+            )?;
+            writeln!(w, "{}", options.cue_text(segment.get_text()))?;
+            writeln!(w)?;
+        }
+        w.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::export::{
+        FrameRate, RoundingMode, RoundingPrecision, SubtitleOptions, TimestampRounding,
+    };
+    use crate::transcriber::{TranscriberOutput, TranscriberOutputSegment};
+
+    #[test]
+    fn test_to_srt_happy_case() {
+        let output = TranscriberOutput::new(vec![
+            TranscriberOutputSegment::new(0, 150, " Hello."),
+            TranscriberOutputSegment::new(150, 366012, " Goodbye."),
+        ]);
+
+        assert_eq!(
+            output.to_srt(),
+            "1\n00:00:00,000 --> 00:00:01,500\nHello.\n\n2\n00:00:01,500 --> 01:01:00,120\nGoodbye.\n\n"
+        );
+    }
+
+    #[test]
+    fn test_write_srt_matches_to_srt() {
+        let output = TranscriberOutput::new(vec![TranscriberOutputSegment::new(10, 20, " Hi")]);
+        let mut buffer = Vec::new();
+        output.write_srt(&mut buffer).unwrap();
+
+        assert_eq!(String::from_utf8(buffer).unwrap(), output.to_srt());
+    }
+
+    #[test]
+    fn test_to_srt_bidi_isolation() {
+        let output = TranscriberOutput::new(vec![TranscriberOutputSegment::new(
+            0,
+            150,
+            " قال Bob: 3 كتب.",
+        )]);
+        let options = SubtitleOptions {
+            bidi_isolation: true,
+            ..Default::default()
+        };
+
+        assert_eq!(
+            output.to_srt_with_options(&options),
+            "1\n00:00:00,000 --> 00:00:01,500\n\u{2067}قال \u{2066}Bob: 3\u{2069} كتب.\u{2069}\n\n"
+        );
+    }
+
+    #[test]
+    fn test_to_srt_resolves_overlaps() {
+        let output = TranscriberOutput::new(vec![
+            TranscriberOutputSegment::new(0, 150, " Hello."),
+            TranscriberOutputSegment::new(100, 200, " Goodbye."),
+        ]);
+        let keep_overlaps = SubtitleOptions {
+            overlap_policy: None,
+            ..Default::default()
+        };
+
+        assert!(output.to_srt().contains("00:00:00,000 --> 00:00:01,000\n"));
+        assert!(output
+            .to_srt_with_options(&keep_overlaps)
+            .contains("00:00:00,000 --> 00:00:01,500\n"));
+    }
+
+    #[test]
+    fn test_to_srt_rounds_timestamps_to_frames() {
+        let output =
+            TranscriberOutput::new(vec![TranscriberOutputSegment::new(101, 349, " Hello.")]);
+        let options = SubtitleOptions {
+            timestamp_rounding: Some(TimestampRounding::new(
+                RoundingMode::Nearest,
+                RoundingPrecision::Frames(FrameRate::Whole(25)),
+            )),
+            ..Default::default()
+        };
+
+        assert_eq!(
+            output.to_srt_with_options(&options),
+            "1\n00:00:01,000 --> 00:00:03,480\nHello.\n\n"
+        );
+    }
+
+    #[test]
+    fn test_to_srt_sorts_cues() {
+        let output = TranscriberOutput::new(vec![
+            TranscriberOutputSegment::new(200, 300, " Second."),
+            TranscriberOutputSegment::new(0, 250, " First."),
+        ]);
+
+        assert_eq!(
+            output.to_srt(),
+            "1\n00:00:00,000 --> 00:00:02,000\nFirst.\n\n\
+             2\n00:00:02,000 --> 00:00:03,000\nSecond.\n\n"
+        );
+    }
+}
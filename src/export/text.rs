@@ -0,0 +1,173 @@
+use std::time::Duration;
+
+use crate::postprocess::ends_sentence;
+use crate::transcriber::TranscriberOutput;
+
+/// How `TranscriberOutput::to_plain_text` separates the text of consecutive segments.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TextSeparator {
+    /// Run the segments together into a single paragraph.
+    #[default]
+    Space,
+    /// Put every segment on a line of its own.
+    Newline,
+}
+
+/// Where `TranscriberOutput::to_paragraphs` breaks the text into paragraphs.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParagraphOptions {
+    /// Start a paragraph after a pause at least this long that follows the end of a
+    /// sentence.
+    ///
+    /// Defaults to 1.5 seconds.
+    pub min_pause: Duration,
+    /// Start a paragraph before a segment that would make the current one longer than this
+    /// many characters, pause or not. `None` lets paragraphs grow without limit.
+    ///
+    /// Defaults to `Some(600)`.
+    pub max_chars: Option<usize>,
+}
+
+impl Default for ParagraphOptions {
+    fn default() -> Self {
+        ParagraphOptions {
+            min_pause: Duration::from_millis(1500),
+            max_chars: Some(600),
+        }
+    }
+}
+
+impl TranscriberOutput {
+    /// Convert the transcription to plain text, without timestamps or numbering.
+    ///
+    /// Every segment's text is trimmed and segments without text are left out.
+    pub fn to_plain_text(&self, separator: TextSeparator) -> String {
+        let separator = match separator {
+            TextSeparator::Space => " ",
+            TextSeparator::Newline => "\n",
+        };
+        self.get_segments()
+            .iter()
+            .map(|segment| segment.get_text().trim())
+            .filter(|text| !text.is_empty())
+            .collect::<Vec<&str>>()
+            .join(separator)
+    }
+
+    /// Convert the transcription to plain text in paragraphs separated by blank lines.
+    ///
+    /// Segments are joined by spaces like `to_plain_text` does, breaking the paragraph at a
+    /// long pause after a sentence, before a segment that would make it too long (see
+    /// `ParagraphOptions`) and always at a speaker turn (see
+    /// `TranscriberOutputSegment::is_speaker_turn`).
+    pub fn to_paragraphs(&self, options: &ParagraphOptions) -> String {
+        let min_pause = (options.min_pause.as_millis() / 10) as i64;
+        let mut paragraphs: Vec<String> = Vec::new();
+        let mut paragraph = String::new();
+        let mut previous_end = None;
+        let mut speaker_turn = false;
+        for segment in self.get_segments() {
+            // a turn marked on a segment without text starts the next one's paragraph
+            speaker_turn |= segment.is_speaker_turn();
+            let text = segment.get_text().trim();
+            if text.is_empty() {
+                continue;
+            }
+            let paused =
+                previous_end.is_some_and(|end| *segment.get_start_timestamp() - end >= min_pause);
+            let too_long = options.max_chars.is_some_and(|max_chars| {
+                paragraph.chars().count() + 1 + text.chars().count() > max_chars
+            });
+            if !paragraph.is_empty()
+                && (speaker_turn || too_long || (paused && ends_sentence(&paragraph)))
+            {
+                paragraphs.push(std::mem::take(&mut paragraph));
+            }
+            if !paragraph.is_empty() {
+                paragraph.push(' ');
+            }
+            paragraph.push_str(text);
+            previous_end = Some(*segment.get_end_timestamp());
+            speaker_turn = false;
+        }
+        if !paragraph.is_empty() {
+            paragraphs.push(paragraph);
+        }
+        paragraphs.join("\n\n")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transcriber::TranscriberOutputSegment;
+
+    #[test]
+    fn test_to_plain_text_separators() {
+        let output = TranscriberOutput::new(vec![
+            TranscriberOutputSegment::new(0, 150, " Hello there. "),
+            TranscriberOutputSegment::new(150, 200, "  "),
+            TranscriberOutputSegment::new(200, 300, " General Kenobi."),
+        ]);
+
+        assert_eq!(
+            output.to_plain_text(TextSeparator::Space),
+            "Hello there. General Kenobi."
+        );
+        assert_eq!(
+            output.to_plain_text(TextSeparator::Newline),
+            "Hello there.\nGeneral Kenobi."
+        );
+        assert_eq!(
+            TranscriberOutput::new(Vec::new()).to_plain_text(TextSeparator::Space),
+            ""
+        );
+    }
+
+    fn paragraph_output() -> TranscriberOutput {
+        let mut turn = TranscriberOutputSegment::new(1000, 1100, " Over to you.");
+        turn.speaker_turn = true;
+        TranscriberOutput::new(vec![
+            TranscriberOutputSegment::new(0, 100, " Welcome back."),
+            // a long pause mid-sentence doesn't break
+            TranscriberOutputSegment::new(200, 400, " Today we talk"),
+            TranscriberOutputSegment::new(600, 700, " about rivers."),
+            // exactly the minimum pause after a sentence breaks
+            TranscriberOutputSegment::new(850, 900, " First, the Nile."),
+            // a speaker turn breaks even without a pause or a sentence end
+            turn,
+            TranscriberOutputSegment::new(1100, 1200, " Thanks."),
+        ])
+    }
+
+    #[test]
+    fn test_to_paragraphs_breaks_at_pauses_and_turns() {
+        assert_eq!(
+            paragraph_output().to_paragraphs(&ParagraphOptions::default()),
+            "Welcome back. Today we talk about rivers.\n\n\
+             First, the Nile.\n\n\
+             Over to you. Thanks."
+        );
+    }
+
+    #[test]
+    fn test_to_paragraphs_caps_paragraph_length() {
+        let options = ParagraphOptions {
+            min_pause: Duration::from_secs(60),
+            max_chars: Some(27),
+        };
+
+        // "Welcome back. Today we talk" is exactly 27 characters
+        assert_eq!(
+            paragraph_output().to_paragraphs(&options),
+            "Welcome back. Today we talk\n\n\
+             about rivers.\n\n\
+             First, the Nile.\n\n\
+             Over to you. Thanks."
+        );
+        assert_eq!(
+            TranscriberOutput::new(Vec::new()).to_paragraphs(&options),
+            ""
+        );
+    }
+}
@@ -0,0 +1,220 @@
+use std::fmt::Write;
+
+use crate::transcriber::TranscriberOutput;
+
+/// A single interval of a TextGrid interval tier, in centiseconds.
+#[derive(Debug, PartialEq)]
+struct Interval {
+    start: i64,
+    end: i64,
+    text: String,
+}
+
+impl TranscriberOutput {
+    /// Convert the transcription to a Praat TextGrid in the long text format.
+    ///
+    /// The grid has a `segments` interval tier, plus a `words` tier when word timestamps
+    /// were collected. The grid spans from zero to the audio duration (or the last segment
+    /// end when the duration is unknown), and gaps between segments are filled with empty
+    /// intervals.
+    pub fn to_textgrid(&self) -> String {
+        let segments: Vec<(i64, i64, &str)> = self
+            .get_segments()
+            .iter()
+            .map(|s| {
+                (
+                    *s.get_start_timestamp(),
+                    *s.get_end_timestamp(),
+                    s.get_text(),
+                )
+            })
+            .collect();
+        let words: Vec<(i64, i64, &str)> = self
+            .get_segments()
+            .iter()
+            .flat_map(|s| s.get_words())
+            .map(|w| {
+                (
+                    *w.get_start_timestamp(),
+                    *w.get_end_timestamp(),
+                    w.get_text(),
+                )
+            })
+            .collect();
+
+        let last_end = segments
+            .iter()
+            .chain(words.iter())
+            .map(|(_, end, _)| *end)
+            .max()
+            .unwrap_or(0);
+        let xmax = self.get_audio_duration().unwrap_or(0).max(last_end);
+
+        let mut tiers = vec![("segments", build_intervals(&segments, xmax))];
+        if !words.is_empty() {
+            tiers.push(("words", build_intervals(&words, xmax)));
+        }
+
+        let mut grid = String::new();
+        grid.push_str("File type = \"ooTextFile\"\nObject class = \"TextGrid\"\n\n");
+        let _ = writeln!(grid, "xmin = 0\nxmax = {}", seconds(xmax));
+        let _ = writeln!(grid, "tiers? <exists>\nsize = {}\nitem []:", tiers.len());
+        for (i, (name, intervals)) in tiers.iter().enumerate() {
+            let _ = writeln!(grid, "    item [{}]:", i + 1);
+            let _ = writeln!(grid, "        class = \"IntervalTier\"");
+            let _ = writeln!(grid, "        name = \"{}\"", name);
+            let _ = writeln!(grid, "        xmin = 0\n        xmax = {}", seconds(xmax));
+            let _ = writeln!(grid, "        intervals: size = {}", intervals.len());
+            for (j, interval) in intervals.iter().enumerate() {
+                let _ = writeln!(grid, "        intervals [{}]:", j + 1);
+                let _ = writeln!(grid, "            xmin = {}", seconds(interval.start));
+                let _ = writeln!(grid, "            xmax = {}", seconds(interval.end));
+                let _ = writeln!(
+                    grid,
+                    "            text = \"{}\"",
+                    escape_text(&interval.text)
+                );
+            }
+        }
+        grid
+    }
+}
+
+/// Build contiguous intervals covering `0..xmax` from possibly overlapping timed texts.
+///
+/// Overlaps are resolved in favour of the earlier item: a later item starts where the
+/// previous one ended, and is dropped if nothing of it remains. Gaps become empty intervals.
+fn build_intervals(items: &[(i64, i64, &str)], xmax: i64) -> Vec<Interval> {
+    let mut sorted: Vec<&(i64, i64, &str)> = items.iter().collect();
+    sorted.sort_by_key(|(start, _, _)| *start);
+
+    let mut intervals = Vec::new();
+    let mut cursor = 0;
+    for (start, end, text) in sorted {
+        let start = (*start).max(cursor);
+        let end = (*end).min(xmax);
+        if end <= start {
+            continue;
+        }
+        if start > cursor {
+            intervals.push(Interval {
+                start: cursor,
+                end: start,
+                text: String::new(),
+            });
+        }
+        intervals.push(Interval {
+            start,
+            end,
+            text: text.trim().to_string(),
+        });
+        cursor = end;
+    }
+    if cursor < xmax || intervals.is_empty() {
+        intervals.push(Interval {
+            start: cursor,
+            end: xmax,
+            text: String::new(),
+        });
+    }
+    intervals
+}
+
+/// Format a centisecond timestamp as seconds.
+fn seconds(timestamp: i64) -> String {
+    format!("{}", timestamp as f64 / 100.0)
+}
+
+/// TextGrid strings escape a double quote by doubling it.
+fn escape_text(text: &str) -> String {
+    text.replace('"', "\"\"")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transcriber::{TranscriberOutputSegment, TranscriberOutputWord};
+
+    #[test]
+    fn test_build_intervals_fills_gaps() {
+        let intervals = build_intervals(&[(50, 100, " a"), (150, 200, " b")], 300);
+
+        assert_eq!(
+            intervals,
+            vec![
+                Interval {
+                    start: 0,
+                    end: 50,
+                    text: "".to_string()
+                },
+                Interval {
+                    start: 50,
+                    end: 100,
+                    text: "a".to_string()
+                },
+                Interval {
+                    start: 100,
+                    end: 150,
+                    text: "".to_string()
+                },
+                Interval {
+                    start: 150,
+                    end: 200,
+                    text: "b".to_string()
+                },
+                Interval {
+                    start: 200,
+                    end: 300,
+                    text: "".to_string()
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_build_intervals_resolves_overlaps_monotonically() {
+        let intervals = build_intervals(
+            &[
+                (0, 120, "a"),
+                (100, 200, "b"),
+                (110, 190, "swallowed"),
+                (190, 250, "c"),
+            ],
+            250,
+        );
+
+        for pair in intervals.windows(2) {
+            assert_eq!(pair[0].end, pair[1].start);
+        }
+        for interval in &intervals {
+            assert!(interval.start < interval.end);
+        }
+        let texts: Vec<&str> = intervals.iter().map(|i| i.text.as_str()).collect();
+        assert_eq!(texts, vec!["a", "b", "c"]);
+        assert_eq!((intervals[1].start, intervals[1].end), (120, 200));
+        assert_eq!((intervals[2].start, intervals[2].end), (200, 250));
+    }
+
+    #[test]
+    fn test_to_textgrid_escapes_quotes_and_adds_word_tier() {
+        let output = TranscriberOutput::new(vec![TranscriberOutputSegment::new(
+            0,
+            150,
+            " He said \"hi\"",
+        )
+        .with_words(vec![
+            TranscriberOutputWord::new(0, 50, " He"),
+            TranscriberOutputWord::new(50, 100, " said"),
+            TranscriberOutputWord::new(100, 150, " \"hi\""),
+        ])])
+        .with_audio_duration(200);
+
+        let grid = output.to_textgrid();
+
+        assert!(grid.contains("xmax = 2\n"));
+        assert!(grid.contains("size = 2\n"));
+        assert!(grid.contains("name = \"words\""));
+        assert!(grid.contains("text = \"He said \"\"hi\"\"\""));
+        assert!(grid.contains("text = \"\"\"hi\"\"\""));
+    }
+}
@@ -0,0 +1,293 @@
+use super::{format_ms, format_timestamp};
+
+/// The frame rate of SMPTE timecodes, see `format_timecode`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Deserialize)]
+pub enum FrameRate {
+    /// A whole number of frames per second, such as 24, 25 or 30.
+    Whole(u32),
+    /// NTSC's 30000/1001 (29.97) frames per second. Drop-frame timecodes skip frame numbers
+    /// 0 and 1 at the start of every minute but every tenth, so they keep up with the
+    /// clock, and are written with a `;` before the frames.
+    Ntsc2997 { drop_frame: bool },
+    /// 60000/1001 (59.94) frames per second, whose drop-frame timecodes skip frame numbers
+    /// 0 to 3.
+    Ntsc5994 { drop_frame: bool },
+}
+
+impl FrameRate {
+    /// The frames numbered in a timecode second, and the frames per second as a fraction.
+    fn rates(self) -> (u64, u64, u64) {
+        match self {
+            FrameRate::Whole(fps) => (u64::from(fps.max(1)), u64::from(fps.max(1)), 1),
+            FrameRate::Ntsc2997 { .. } => (30, 30000, 1001),
+            FrameRate::Ntsc5994 { .. } => (60, 60000, 1001),
+        }
+    }
+
+    /// The frame numbers skipped every minute but every tenth, 0 without drop frames.
+    fn dropped_frames(self) -> u64 {
+        match self {
+            FrameRate::Ntsc2997 { drop_frame: true } => 2,
+            FrameRate::Ntsc5994 { drop_frame: true } => 4,
+            _ => 0,
+        }
+    }
+}
+
+/// How exporters that let you choose write timestamps.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TimestampFormat {
+    /// `HH:MM:SS.mmm`, with the given separator before the milliseconds (`,` in many
+    /// locales).
+    Clock { decimal_separator: char },
+    /// SMPTE `HH:MM:SS:FF` timecodes, see `format_timecode`.
+    Timecode(FrameRate),
+}
+
+impl Default for TimestampFormat {
+    fn default() -> Self {
+        TimestampFormat::Clock {
+            decimal_separator: '.',
+        }
+    }
+}
+
+impl TimestampFormat {
+    /// Format a whisper timestamp (in centiseconds).
+    pub fn format(&self, timestamp: i64) -> String {
+        match *self {
+            TimestampFormat::Clock { decimal_separator } => {
+                format_timestamp(timestamp, decimal_separator)
+            }
+            TimestampFormat::Timecode(fps) => format_timecode(timestamp, fps),
+        }
+    }
+}
+
+/// Which way `TimestampRounding` moves timestamps that fall between two steps.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Deserialize)]
+pub enum RoundingMode {
+    /// To the closest step, halfway timestamps up.
+    Nearest,
+    /// To the step before.
+    Floor,
+    /// To the step after.
+    Ceil,
+}
+
+/// The steps `TimestampRounding` snaps timestamps to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Deserialize)]
+pub enum RoundingPrecision {
+    /// Multiples of this many milliseconds, such as 100 for tenths of a second.
+    Milliseconds(u32),
+    /// The starts of video frames at this rate. Whether its timecodes drop frames doesn't
+    /// matter, the frames themselves are evenly spaced.
+    Frames(FrameRate),
+}
+
+/// How the subtitle exporters round cue timestamps, see `SubtitleOptions::timestamp_rounding`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TimestampRounding {
+    pub mode: RoundingMode,
+    pub precision: RoundingPrecision,
+}
+
+impl TimestampRounding {
+    pub fn new(mode: RoundingMode, precision: RoundingPrecision) -> TimestampRounding {
+        TimestampRounding { mode, precision }
+    }
+
+    /// Round a whisper timestamp (in centiseconds) to milliseconds on a step. Negative
+    /// timestamps are taken as zero.
+    ///
+    /// Frame starts rarely fall on a whole millisecond, they are rounded up to the first
+    /// millisecond inside the frame.
+    pub fn round_ms(&self, timestamp: i64) -> i64 {
+        let ms = timestamp.max(0) as u64 * 10;
+        // the step as a fraction of milliseconds
+        let (numerator, denominator) = match self.precision {
+            RoundingPrecision::Milliseconds(step) => (u64::from(step.max(1)), 1),
+            RoundingPrecision::Frames(fps) => {
+                let (_, frames, seconds) = fps.rates();
+                (1000 * seconds, frames)
+            }
+        };
+        let scaled = ms * denominator;
+        let steps = match self.mode {
+            RoundingMode::Nearest => (2 * scaled + numerator) / (2 * numerator),
+            RoundingMode::Floor => scaled / numerator,
+            RoundingMode::Ceil => scaled.div_ceil(numerator),
+        };
+        (steps * numerator).div_ceil(denominator) as i64
+    }
+
+    /// Format a whisper timestamp (in centiseconds) rounded, as `format_timestamp` does.
+    pub fn format(&self, timestamp: i64, separator: char) -> String {
+        format_ms(self.round_ms(timestamp), separator)
+    }
+}
+
+/// Format a whisper timestamp (in centiseconds) as an SMPTE `HH:MM:SS:FF` timecode of the
+/// frame it falls in, `HH:MM:SS;FF` for drop-frame rates. Negative timestamps are taken as
+/// zero.
+pub fn format_timecode(timestamp: i64, fps: FrameRate) -> String {
+    let (nominal, numerator, denominator) = fps.rates();
+    let mut frame = timestamp.max(0) as u64 * numerator / (100 * denominator);
+
+    let dropped = fps.dropped_frames();
+    if dropped > 0 {
+        // renumber the frame as if the dropped numbers had been used
+        let per_minute = 60 * nominal - dropped;
+        let per_ten_minutes = 10 * per_minute + dropped;
+        let tens = frame / per_ten_minutes;
+        let rest = frame % per_ten_minutes;
+        frame += 9 * dropped * tens;
+        if rest > dropped {
+            frame += dropped * ((rest - dropped) / per_minute);
+        }
+    }
+
+    let frames_separator = if dropped > 0 { ';' } else { ':' };
+    let seconds = frame / nominal;
+    format!(
+        "{:02}:{:02}:{:02}{}{:02}",
+        seconds / 3600,
+        seconds % 3600 / 60,
+        seconds % 60,
+        frames_separator,
+        frame % nominal
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DROP_FRAME: FrameRate = FrameRate::Ntsc2997 { drop_frame: true };
+
+    /// The timestamp, in centiseconds, at which frame `frame` starts at 29.97 fps.
+    fn ntsc_frame(frame: i64) -> i64 {
+        // rounded up, the frame's start is rarely a whole centisecond
+        (frame * 100_100 + 29_999) / 30_000
+    }
+
+    #[test]
+    fn test_format_timecode_whole_rates() {
+        assert_eq!(format_timecode(0, FrameRate::Whole(25)), "00:00:00:00");
+        assert_eq!(format_timecode(-50, FrameRate::Whole(25)), "00:00:00:00");
+        assert_eq!(
+            format_timecode(366_012, FrameRate::Whole(25)),
+            "01:01:00:03"
+        );
+        assert_eq!(format_timecode(99, FrameRate::Whole(24)), "00:00:00:23");
+    }
+
+    #[test]
+    fn test_format_timecode_drop_frame_reference_values() {
+        let cases = [
+            (0, "00:00:00;00"),
+            (29, "00:00:00;29"),
+            (30, "00:00:01;00"),
+            // later minutes start at ;02
+            (1799, "00:00:59;29"),
+            (1800, "00:01:00;02"),
+            (3597, "00:01:59;29"),
+            (3598, "00:02:00;02"),
+            // every tenth minute doesn't
+            (17981, "00:09:59;29"),
+            (17982, "00:10:00;00"),
+            (17983, "00:10:00;01"),
+            (19781, "00:10:59;29"),
+            (19782, "00:11:00;02"),
+            // the clock and the timecode agree on the hour
+            (107_892, "01:00:00;00"),
+        ];
+        for (frame, timecode) in cases {
+            assert_eq!(
+                format_timecode(ntsc_frame(frame), DROP_FRAME),
+                timecode,
+                "frame {}",
+                frame
+            );
+        }
+    }
+
+    #[test]
+    fn test_format_timecode_ntsc_without_drop_frames_drifts() {
+        let non_drop = FrameRate::Ntsc2997 { drop_frame: false };
+
+        assert_eq!(format_timecode(ntsc_frame(1800), non_drop), "00:01:00:00");
+        // an hour of wall clock is 108 frames short of an hour of timecode
+        assert_eq!(format_timecode(360_000, non_drop), "00:59:56:12");
+        assert_eq!(format_timecode(360_000, DROP_FRAME), "01:00:00;00");
+    }
+
+    #[test]
+    fn test_format_timecode_59_94_drop_frame() {
+        let fps = FrameRate::Ntsc5994 { drop_frame: true };
+        let frame = |frame: i64| (frame * 100_100 + 59_999) / 60_000;
+
+        // the first minute holds all 3600 frame numbers
+        assert_eq!(format_timecode(frame(3599), fps), "00:00:59;59");
+        assert_eq!(format_timecode(frame(3600), fps), "00:01:00;04");
+        assert_eq!(format_timecode(frame(7195), fps), "00:01:59;59");
+        assert_eq!(format_timecode(frame(7196), fps), "00:02:00;04");
+        assert_eq!(format_timecode(frame(35964), fps), "00:10:00;00");
+    }
+
+    #[test]
+    fn test_timestamp_rounding_to_milliseconds() {
+        let tenths = |mode| TimestampRounding::new(mode, RoundingPrecision::Milliseconds(100));
+
+        assert_eq!(tenths(RoundingMode::Nearest).round_ms(149), 1500);
+        assert_eq!(tenths(RoundingMode::Nearest).round_ms(144), 1400);
+        assert_eq!(tenths(RoundingMode::Nearest).round_ms(145), 1500);
+        assert_eq!(tenths(RoundingMode::Floor).round_ms(149), 1400);
+        assert_eq!(tenths(RoundingMode::Ceil).round_ms(141), 1500);
+        assert_eq!(tenths(RoundingMode::Ceil).round_ms(140), 1400);
+        assert_eq!(tenths(RoundingMode::Floor).round_ms(-20), 0);
+        assert_eq!(
+            tenths(RoundingMode::Nearest).format(149, ','),
+            "00:00:01,500"
+        );
+    }
+
+    #[test]
+    fn test_timestamp_rounding_to_frames() {
+        let frames = |mode, fps| TimestampRounding::new(mode, RoundingPrecision::Frames(fps));
+        let fps25 = FrameRate::Whole(25);
+
+        assert_eq!(frames(RoundingMode::Nearest, fps25).round_ms(101), 1000);
+        assert_eq!(frames(RoundingMode::Nearest, fps25).round_ms(102), 1040);
+        assert_eq!(frames(RoundingMode::Floor, fps25).round_ms(103), 1000);
+        assert_eq!(frames(RoundingMode::Ceil, fps25).round_ms(101), 1040);
+
+        // frame 30 at 29.97 fps starts at 1001ms, frame 29 at 967.63ms
+        let ntsc = FrameRate::Ntsc2997 { drop_frame: true };
+        assert_eq!(frames(RoundingMode::Floor, ntsc).round_ms(100), 968);
+        assert_eq!(frames(RoundingMode::Ceil, ntsc).round_ms(100), 1001);
+        assert_eq!(frames(RoundingMode::Nearest, ntsc).round_ms(100), 1001);
+        // an exact frame start stays put
+        assert_eq!(
+            frames(RoundingMode::Ceil, FrameRate::Whole(50)).round_ms(120),
+            1200
+        );
+    }
+
+    #[test]
+    fn test_timestamp_format_separators() {
+        assert_eq!(TimestampFormat::default().format(150), "00:00:01.500");
+        assert_eq!(
+            TimestampFormat::Clock {
+                decimal_separator: ','
+            }
+            .format(150),
+            "00:00:01,500"
+        );
+        assert_eq!(
+            TimestampFormat::Timecode(FrameRate::Whole(30)).format(150),
+            "00:00:01:15"
+        );
+    }
+}
@@ -0,0 +1,211 @@
+use std::io::{BufWriter, Write};
+
+use super::{bidi, SubtitleOptions};
+use crate::transcriber::{TranscriberOutput, TranscriberOutputSegment};
+
+impl TranscriberOutput {
+    /// Convert the transcription to WebVTT subtitles.
+    pub fn to_vtt(&self) -> String {
+        self.to_vtt_with_options(&SubtitleOptions::default())
+    }
+
+    /// Convert the transcription to WebVTT subtitles using the given options.
+    pub fn to_vtt_with_options(&self, options: &SubtitleOptions) -> String {
+        let mut buffer = Vec::new();
+        self.write_vtt_with_options(&mut buffer, options)
+            .expect("writing to a Vec<u8> cannot fail");
+        String::from_utf8(buffer).expect("VTT output is valid UTF-8")
+    }
+
+    /// Stream the transcription as WebVTT subtitles, one cue at a time.
+    ///
+    /// # Arguments
+    ///
+    /// * `w` - the writer (file, socket, ...) the subtitles are written to.
+    pub fn write_vtt<W: Write>(&self, w: W) -> std::io::Result<()> {
+        self.write_vtt_with_options(w, &SubtitleOptions::default())
+    }
+
+    /// Stream the transcription as WebVTT subtitles using the given options.
+    pub fn write_vtt_with_options<W: Write>(
+        &self,
+        w: W,
+        options: &SubtitleOptions,
+    ) -> std::io::Result<()> {
+        let mut w = BufWriter::new(w);
+        writeln!(w, "WEBVTT")?;
+        for segment in options.cue_segments(self).iter() {
+            writeln!(w)?;
+            writeln!(
+                w,
+                "{} --> {}",
+                options.cue_timestamp(*segment.get_start_timestamp(), '.'),
+                options.cue_timestamp(*segment.get_end_timestamp(), '.')
+            )?;
+            let text = match options.low_confidence_threshold {
+                Some(threshold) if !segment.get_words().is_empty() => {
+                    styled_cue_text(segment, options, threshold)
+                }
+                _ => escape_cue_text(&options.cue_text(segment.get_text())),
+            };
+            writeln!(w, "{}", text)?;
+        }
+        w.flush()
+    }
+}
+
+/// Build a cue from the segment's words, wrapping those below `threshold` in `<c.low>`.
+fn styled_cue_text(
+    segment: &TranscriberOutputSegment,
+    options: &SubtitleOptions,
+    threshold: f32,
+) -> String {
+    let rtl = options.bidi_isolation && bidi::is_predominantly_rtl(segment.get_text());
+    let mut cue = String::new();
+    let mut line_chars = 0;
+    for word in segment.get_words() {
+        let text = word.get_text();
+        let trimmed = text.trim_start();
+        let whitespace = &text[..text.len() - trimmed.len()];
+        // break lines where `SubtitleOptions::cue_text` would for the plain text
+        let word_chars = trimmed.chars().count();
+        match options.max_line_chars {
+            Some(max) if line_chars > 0 && line_chars + 1 + word_chars > max => {
+                cue.push('\n');
+                line_chars = word_chars;
+            }
+            _ => {
+                cue.push_str(whitespace);
+                if line_chars > 0 && !whitespace.is_empty() {
+                    line_chars += 1;
+                }
+                line_chars += word_chars;
+            }
+        }
+        let trimmed = if rtl {
+            bidi::isolate_ltr_runs(trimmed)
+        } else {
+            trimmed.to_string()
+        };
+        let trimmed = escape_cue_text(&trimmed);
+        if word.get_probability().is_some_and(|p| p < threshold) {
+            cue.push_str(&format!("<c.low>{}</c>", trimmed));
+        } else {
+            cue.push_str(&trimmed);
+        }
+    }
+    let cue = cue.trim();
+    if rtl {
+        format!("{}{}{}", bidi::RLI, cue, bidi::PDI)
+    } else {
+        cue.to_string()
+    }
+}
+
+/// Escape the characters that would start markup in WebVTT cue text.
+fn escape_cue_text(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::export::SubtitleOptions;
+    use crate::transcriber::{TranscriberOutput, TranscriberOutputSegment, TranscriberOutputWord};
+
+    #[test]
+    fn test_to_vtt_happy_case() {
+        let output = TranscriberOutput::new(vec![
+            TranscriberOutputSegment::new(0, 150, " Hello."),
+            TranscriberOutputSegment::new(150, 300, " Goodbye."),
+        ]);
+
+        assert_eq!(
+            output.to_vtt(),
+            "WEBVTT\n\n00:00:00.000 --> 00:00:01.500\nHello.\n\n00:00:01.500 --> 00:00:03.000\nGoodbye.\n"
+        );
+    }
+
+    #[test]
+    fn test_to_vtt_escapes_markup() {
+        let output = TranscriberOutput::new(vec![TranscriberOutputSegment::new(
+            0,
+            150,
+            " <laughs> Tom & Jerry --> next",
+        )]);
+
+        assert_eq!(
+            output.to_vtt(),
+            "WEBVTT\n\n00:00:00.000 --> 00:00:01.500\n&lt;laughs&gt; Tom &amp; Jerry --&gt; next\n"
+        );
+    }
+
+    #[test]
+    fn test_to_vtt_bidi_isolation() {
+        let output = TranscriberOutput::new(vec![
+            TranscriberOutputSegment::new(0, 150, " قال John إنه سيصل في 2024."),
+            TranscriberOutputSegment::new(150, 300, " Hello."),
+        ]);
+        let options = SubtitleOptions {
+            bidi_isolation: true,
+            ..Default::default()
+        };
+
+        assert_eq!(
+            output.to_vtt_with_options(&options),
+            "WEBVTT\n\n00:00:00.000 --> 00:00:01.500\n\u{2067}قال \u{2066}John\u{2069} إنه سيصل في \u{2066}2024\u{2069}.\u{2069}\n\n00:00:01.500 --> 00:00:03.000\nHello.\n"
+        );
+        assert!(!output.to_vtt().contains('\u{2067}'));
+    }
+
+    #[test]
+    fn test_to_vtt_low_confidence_styling() {
+        let output = TranscriberOutput::new(vec![TranscriberOutputSegment::new(
+            0,
+            150,
+            " I saw <Nguyen> today",
+        )
+        .with_words(vec![
+            TranscriberOutputWord::new(0, 30, " I").with_probability(0.9),
+            TranscriberOutputWord::new(30, 60, " saw").with_probability(0.8),
+            TranscriberOutputWord::new(60, 120, " <Nguyen>").with_probability(0.3),
+            TranscriberOutputWord::new(120, 150, " today"),
+        ])]);
+        let options = SubtitleOptions {
+            low_confidence_threshold: Some(0.5),
+            ..Default::default()
+        };
+
+        assert_eq!(
+            output.to_vtt_with_options(&options),
+            "WEBVTT\n\n00:00:00.000 --> 00:00:01.500\nI saw <c.low>&lt;Nguyen&gt;</c> today\n"
+        );
+        assert!(!output.to_vtt().contains("<c.low>"));
+    }
+
+    #[test]
+    fn test_to_vtt_low_confidence_styling_wraps_lines() {
+        let output = TranscriberOutput::new(vec![TranscriberOutputSegment::new(
+            0,
+            150,
+            " I saw Nguyen today",
+        )
+        .with_words(vec![
+            TranscriberOutputWord::new(0, 30, " I").with_probability(0.9),
+            TranscriberOutputWord::new(30, 60, " saw").with_probability(0.8),
+            TranscriberOutputWord::new(60, 120, " Nguyen").with_probability(0.3),
+            TranscriberOutputWord::new(120, 150, " today"),
+        ])]);
+        let options = SubtitleOptions {
+            low_confidence_threshold: Some(0.5),
+            max_line_chars: Some(12),
+            ..Default::default()
+        };
+
+        assert_eq!(
+            output.to_vtt_with_options(&options),
+            "WEBVTT\n\n00:00:00.000 --> 00:00:01.500\nI saw <c.low>Nguyen</c>\ntoday\n"
+        );
+    }
+}
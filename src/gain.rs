@@ -0,0 +1,103 @@
+//! Gain for quiet recordings, see `TranscribeOptions::gain_db`.
+//!
+//! Amplified samples below `LIMITER_THRESHOLD` pass through unchanged. Louder ones are bent
+//! by a tanh knee towards full scale, which the output never exceeds. Unlike hard clipping
+//! the curve has no corner, so loud moments are compressed instead of squared off.
+//! Attenuating can't push samples past full scale, so it bypasses the limiter.
+
+/// The level above which the limiter engages, the knee takes the rest of the range.
+const LIMITER_THRESHOLD: f32 = 0.9;
+
+/// Amplify `audio` by `gain_db`, in place, keeping it within [-1, 1]. Returns the number of
+/// samples the limiter bent, none when `gain_db` isn't positive.
+pub(crate) fn apply_gain(audio: &mut [f32], gain_db: f32) -> usize {
+    let gain = 10f32.powf(gain_db / 20.0);
+    if gain_db <= 0.0 {
+        audio.iter_mut().for_each(|sample| *sample *= gain);
+        return 0;
+    }
+    let mut limited = 0;
+    for sample in audio {
+        let amplified = *sample * gain;
+        if amplified.abs() > LIMITER_THRESHOLD {
+            limited += 1;
+            *sample = amplified.signum() * limit(amplified.abs());
+        } else {
+            *sample = amplified;
+        }
+    }
+    limited
+}
+
+/// The limited level of `level`, above `LIMITER_THRESHOLD`. The knee starts with the slope
+/// of the linear range and approaches 1.
+fn limit(level: f32) -> f32 {
+    let headroom = 1.0 - LIMITER_THRESHOLD;
+    (LIMITER_THRESHOLD + headroom * ((level - LIMITER_THRESHOLD) / headroom).tanh()).min(1.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A quiet tone, then a loud one.
+    fn quiet_then_loud() -> Vec<f32> {
+        (0..32000)
+            .map(|i| {
+                let amplitude = if i < 16000 { 0.02 } else { 0.5 };
+                amplitude * (i as f32 * 0.05).sin()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_gain_amplifies_quiet_audio() {
+        let original = quiet_then_loud();
+        let mut audio = original.clone();
+
+        let limited = apply_gain(&mut audio, 12.0);
+
+        let gain = 10f32.powf(12.0 / 20.0);
+        for (sample, original) in audio[..16000].iter().zip(&original[..16000]) {
+            assert!((sample - original * gain).abs() < 1e-6);
+        }
+        assert!(limited > 0);
+        assert!(limited < 16000);
+    }
+
+    #[test]
+    fn test_gain_stays_within_full_scale() {
+        let mut audio = quiet_then_loud();
+        audio.extend([1.0, -1.0, 3.0, -7.5]);
+
+        apply_gain(&mut audio, 30.0);
+
+        assert!(audio.iter().all(|sample| sample.abs() <= 1.0));
+        // the limiter compresses instead of clipping, louder input stays louder
+        assert!(limit(1.2) < limit(1.5));
+        assert!((limit(LIMITER_THRESHOLD) - LIMITER_THRESHOLD).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_zero_gain_leaves_quiet_audio_alone() {
+        let original = quiet_then_loud();
+        let mut audio = original.clone();
+
+        assert_eq!(apply_gain(&mut audio, 0.0), 0);
+        assert_eq!(audio, original);
+    }
+
+    #[test]
+    fn test_attenuation_bypasses_the_limiter() {
+        // above the limiter's threshold even after
+        let original = vec![0.98, -0.99, 0.5];
+        let mut audio = original.clone();
+
+        assert_eq!(apply_gain(&mut audio, -0.5), 0);
+
+        let gain = 10f32.powf(-0.5 / 20.0);
+        for (sample, original) in audio.iter().zip(&original) {
+            assert!((sample - original * gain).abs() < 1e-6);
+        }
+    }
+}
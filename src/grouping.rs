@@ -0,0 +1,284 @@
+//! Grouping the segments of a transcript into larger units, such as the utterances between
+//! pauses or fixed windows of time, and laying them out on a timeline with the silences
+//! between them.
+//!
+//! A segment always lands whole in a single group, groups never split it.
+
+use std::time::Duration;
+
+use crate::transcriber::{TranscriberOutput, TranscriberOutputSegment};
+
+/// Consecutive segments of a transcript, borrowed from its `TranscriberOutput`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SegmentGroup<'a> {
+    segments: &'a [TranscriberOutputSegment],
+}
+
+impl<'a> SegmentGroup<'a> {
+    /// The segments of the group, never empty.
+    pub fn get_segments(&self) -> &'a [TranscriberOutputSegment] {
+        self.segments
+    }
+
+    /// The start of the group's first segment, in centiseconds.
+    pub fn get_start_timestamp(&self) -> i64 {
+        *self.segments[0].get_start_timestamp()
+    }
+
+    /// The latest end of the group's segments, in centiseconds.
+    pub fn get_end_timestamp(&self) -> i64 {
+        self.segments
+            .iter()
+            .map(|segment| *segment.get_end_timestamp())
+            .max()
+            .unwrap_or_default()
+    }
+
+    /// The text of the segments, trimmed and joined by spaces like
+    /// `TranscriberOutput::to_plain_text` does.
+    pub fn get_text(&self) -> String {
+        self.segments
+            .iter()
+            .map(|segment| segment.get_text().trim())
+            .filter(|text| !text.is_empty())
+            .collect::<Vec<&str>>()
+            .join(" ")
+    }
+}
+
+/// A stretch of a transcript's timeline, see `TranscriberOutput::with_gaps`.
+#[derive(Debug, Clone, Copy, PartialEq, serde::Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum TimelineItem<'a> {
+    Speech(&'a TranscriberOutputSegment),
+    /// A pause between segments, in centiseconds.
+    Silence {
+        start: i64,
+        end: i64,
+    },
+}
+
+impl TranscriberOutput {
+    /// Lay the segments out on a timeline, with the silences of at least `min_gap` between
+    /// them, such as for dubbing scripts. The timeline starts at 0 and ends with the audio
+    /// when its duration is known (see `get_audio_duration`), otherwise with the last segment.
+    ///
+    /// Pauses are measured like `group_by_pause` does, from the latest end so far, so
+    /// overlapping segments leave no silence and a pause exactly `min_gap` long is one.
+    pub fn with_gaps(&self, min_gap: Duration) -> Vec<TimelineItem<'_>> {
+        let min_gap = (min_gap.as_millis() / 10) as i64;
+        let mut timeline = Vec::with_capacity(self.get_segments().len());
+        let mut end = 0;
+        let silence = |timeline: &mut Vec<TimelineItem>, start: i64, end: i64| {
+            if end > start && end - start >= min_gap {
+                timeline.push(TimelineItem::Silence { start, end });
+            }
+        };
+        for segment in self.get_segments() {
+            silence(&mut timeline, end, *segment.get_start_timestamp());
+            timeline.push(TimelineItem::Speech(segment));
+            end = end.max(*segment.get_end_timestamp());
+        }
+        if let Some(duration) = self.get_audio_duration() {
+            silence(&mut timeline, end, duration);
+        }
+        timeline
+    }
+
+    /// The silences of `with_gaps(min_gap)`, as `(start, end)` pairs in centiseconds.
+    pub(crate) fn silences(&self, min_gap: Duration) -> Vec<(i64, i64)> {
+        self.with_gaps(min_gap)
+            .into_iter()
+            .filter_map(|item| match item {
+                TimelineItem::Silence { start, end } => Some((start, end)),
+                TimelineItem::Speech(_) => None,
+            })
+            .collect()
+    }
+
+    /// Group the segments into runs separated by pauses of at least `min_gap`.
+    ///
+    /// The pause before a segment is the time from the latest end of the group so far to
+    /// the segment's start, so a pause exactly `min_gap` long starts a new group.
+    pub fn group_by_pause(&self, min_gap: Duration) -> Vec<SegmentGroup<'_>> {
+        let min_gap = (min_gap.as_millis() / 10) as i64;
+        let mut end: Option<i64> = None;
+        self.group_by(|segment| {
+            let starts_group =
+                end.is_none_or(|end| *segment.get_start_timestamp() - end >= min_gap);
+            end = end.max(Some(*segment.get_end_timestamp()));
+            starts_group
+        })
+    }
+
+    /// Group the segments into consecutive windows of `window` from the start of the audio,
+    /// each segment going to the window it starts in. Windows without a segment starting
+    /// in them are left out, and a segment reaching past its window still isn't split.
+    ///
+    /// A zero `window` is taken to be one centisecond.
+    pub fn window_by_duration(&self, window: Duration) -> Vec<SegmentGroup<'_>> {
+        let window = ((window.as_millis() / 10) as i64).max(1);
+        let mut current = None;
+        self.group_by(|segment| {
+            let index = segment.get_start_timestamp().div_euclid(window);
+            current.replace(index) != Some(index)
+        })
+    }
+
+    /// Split the segments into groups, starting a new one before every segment for which
+    /// `starts_group` returns true. It is called on every segment in order.
+    fn group_by(
+        &self,
+        mut starts_group: impl FnMut(&TranscriberOutputSegment) -> bool,
+    ) -> Vec<SegmentGroup<'_>> {
+        let segments = self.get_segments();
+        let mut groups = Vec::new();
+        let mut start = 0;
+        for (i, segment) in segments.iter().enumerate() {
+            if starts_group(segment) && i > start {
+                groups.push(SegmentGroup {
+                    segments: &segments[start..i],
+                });
+                start = i;
+            }
+        }
+        if start < segments.len() {
+            groups.push(SegmentGroup {
+                segments: &segments[start..],
+            });
+        }
+        groups
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn output(times: &[(i64, i64)]) -> TranscriberOutput {
+        TranscriberOutput::new(
+            times
+                .iter()
+                .enumerate()
+                .map(|(i, &(start, end))| {
+                    TranscriberOutputSegment::new(start, end, format!(" s{}", i))
+                })
+                .collect(),
+        )
+    }
+
+    fn ranges(groups: &[SegmentGroup]) -> Vec<(i64, i64, String)> {
+        groups
+            .iter()
+            .map(|g| (g.get_start_timestamp(), g.get_end_timestamp(), g.get_text()))
+            .collect()
+    }
+
+    fn timeline(items: &[TimelineItem]) -> Vec<String> {
+        items
+            .iter()
+            .map(|item| match item {
+                TimelineItem::Speech(segment) => segment.get_text().trim().to_string(),
+                TimelineItem::Silence { start, end } => format!("{}-{}", start, end),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_with_gaps_leading_and_inner_silence() {
+        // a leading silence of 100, then pauses of 50 (exactly the threshold) and 49, and no
+        // silence under the overlapping segment
+        let output = output(&[(100, 200), (250, 400), (300, 350), (449, 500)]);
+
+        let items = output.with_gaps(Duration::from_millis(500));
+
+        assert_eq!(
+            timeline(&items),
+            vec!["0-100", "s0", "200-250", "s1", "s2", "s3"]
+        );
+        assert_eq!(output.with_gaps(Duration::from_millis(510)).len(), 5);
+        // without a minimum every pause counts, but there is none before a segment at 0
+        assert_eq!(
+            timeline(
+                &TranscriberOutput::new(vec![TranscriberOutputSegment::new(0, 10, " a")])
+                    .with_gaps(Duration::ZERO)
+            ),
+            vec!["a"]
+        );
+    }
+
+    #[test]
+    fn test_with_gaps_trailing_silence_needs_audio_duration() {
+        let output = output(&[(0, 100)]);
+        assert_eq!(
+            timeline(&output.with_gaps(Duration::from_secs(1))),
+            vec!["s0"]
+        );
+
+        let output = output.with_audio_duration(300);
+        assert_eq!(
+            timeline(&output.with_gaps(Duration::from_secs(1))),
+            vec!["s0", "100-300"]
+        );
+        assert_eq!(output.with_gaps(Duration::from_secs(3)).len(), 1);
+        assert_eq!(
+            serde_json::to_string(&output.with_gaps(Duration::from_secs(1))[1]).unwrap(),
+            r#"{"type":"silence","start":100,"end":300}"#
+        );
+    }
+
+    #[test]
+    fn test_group_by_pause_boundaries() {
+        // pauses of 50 (exactly the threshold), 49 and 100
+        let output = output(&[(0, 100), (150, 200), (249, 300), (400, 500)]);
+
+        let groups = output.group_by_pause(Duration::from_millis(500));
+
+        assert_eq!(
+            ranges(&groups),
+            vec![
+                (0, 100, "s0".to_string()),
+                (150, 300, "s1 s2".to_string()),
+                (400, 500, "s3".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_group_by_pause_measures_from_latest_end() {
+        // the second segment ends before the first, the pause is measured from 300
+        let output = output(&[(0, 300), (100, 200), (320, 400)]);
+
+        let groups = output.group_by_pause(Duration::from_millis(500));
+
+        assert_eq!(ranges(&groups), vec![(0, 400, "s0 s1 s2".to_string())]);
+        // overlapping segments stay together even without a minimum pause
+        assert_eq!(output.group_by_pause(Duration::ZERO).len(), 2);
+        assert!(TranscriberOutput::new(Vec::new())
+            .group_by_pause(Duration::from_secs(1))
+            .is_empty());
+    }
+
+    #[test]
+    fn test_window_by_duration_never_splits_segments() {
+        let output = output(&[
+            (0, 250),
+            (600, 1100),
+            (999, 1200),
+            (1000, 1100),
+            (3500, 3600),
+        ]);
+
+        let groups = output.window_by_duration(Duration::from_secs(10));
+
+        assert_eq!(
+            ranges(&groups),
+            vec![
+                (0, 1200, "s0 s1 s2".to_string()),
+                (1000, 1100, "s3".to_string()),
+                (3500, 3600, "s4".to_string()),
+            ]
+        );
+        assert_eq!(output.window_by_duration(Duration::ZERO).len(), 5);
+    }
+}
@@ -0,0 +1,236 @@
+//! whisper's languages, and mapping the language tags of audio files onto their codes, see
+//! `TranscribeOptions::use_metadata_language`.
+
+/// whisper's language codes and their names, in the order of whisper's language ids.
+const LANGUAGES: &[(&str, &str)] = &[
+    ("en", "English"),
+    ("zh", "Chinese"),
+    ("de", "German"),
+    ("es", "Spanish"),
+    ("ru", "Russian"),
+    ("ko", "Korean"),
+    ("fr", "French"),
+    ("ja", "Japanese"),
+    ("pt", "Portuguese"),
+    ("tr", "Turkish"),
+    ("pl", "Polish"),
+    ("ca", "Catalan"),
+    ("nl", "Dutch"),
+    ("ar", "Arabic"),
+    ("sv", "Swedish"),
+    ("it", "Italian"),
+    ("id", "Indonesian"),
+    ("hi", "Hindi"),
+    ("fi", "Finnish"),
+    ("vi", "Vietnamese"),
+    ("he", "Hebrew"),
+    ("uk", "Ukrainian"),
+    ("el", "Greek"),
+    ("ms", "Malay"),
+    ("cs", "Czech"),
+    ("ro", "Romanian"),
+    ("da", "Danish"),
+    ("hu", "Hungarian"),
+    ("ta", "Tamil"),
+    ("no", "Norwegian"),
+    ("th", "Thai"),
+    ("ur", "Urdu"),
+    ("hr", "Croatian"),
+    ("bg", "Bulgarian"),
+    ("lt", "Lithuanian"),
+    ("la", "Latin"),
+    ("mi", "Maori"),
+    ("ml", "Malayalam"),
+    ("cy", "Welsh"),
+    ("sk", "Slovak"),
+    ("te", "Telugu"),
+    ("fa", "Persian"),
+    ("lv", "Latvian"),
+    ("bn", "Bengali"),
+    ("sr", "Serbian"),
+    ("az", "Azerbaijani"),
+    ("sl", "Slovenian"),
+    ("kn", "Kannada"),
+    ("et", "Estonian"),
+    ("mk", "Macedonian"),
+    ("br", "Breton"),
+    ("eu", "Basque"),
+    ("is", "Icelandic"),
+    ("hy", "Armenian"),
+    ("ne", "Nepali"),
+    ("mn", "Mongolian"),
+    ("bs", "Bosnian"),
+    ("kk", "Kazakh"),
+    ("sq", "Albanian"),
+    ("sw", "Swahili"),
+    ("gl", "Galician"),
+    ("mr", "Marathi"),
+    ("pa", "Punjabi"),
+    ("si", "Sinhala"),
+    ("km", "Khmer"),
+    ("sn", "Shona"),
+    ("yo", "Yoruba"),
+    ("so", "Somali"),
+    ("af", "Afrikaans"),
+    ("oc", "Occitan"),
+    ("ka", "Georgian"),
+    ("be", "Belarusian"),
+    ("tg", "Tajik"),
+    ("sd", "Sindhi"),
+    ("gu", "Gujarati"),
+    ("am", "Amharic"),
+    ("yi", "Yiddish"),
+    ("lo", "Lao"),
+    ("uz", "Uzbek"),
+    ("fo", "Faroese"),
+    ("ht", "Haitian Creole"),
+    ("ps", "Pashto"),
+    ("tk", "Turkmen"),
+    ("nn", "Nynorsk"),
+    ("mt", "Maltese"),
+    ("sa", "Sanskrit"),
+    ("lb", "Luxembourgish"),
+    ("my", "Myanmar"),
+    ("bo", "Tibetan"),
+    ("tl", "Tagalog"),
+    ("mg", "Malagasy"),
+    ("as", "Assamese"),
+    ("tt", "Tatar"),
+    ("haw", "Hawaiian"),
+    ("ln", "Lingala"),
+    ("ha", "Hausa"),
+    ("ba", "Bashkir"),
+    ("jw", "Javanese"),
+    ("su", "Sundanese"),
+    ("yue", "Cantonese"),
+];
+
+/// The languages whisper transcribes, as `(code, name)` pairs such as `("de", "German")`,
+/// in the order of whisper's language ids. The codes are the ones `TranscribeOptions::language`
+/// takes. English-only models only transcribe `"en"`, see `Transcriber::is_multilingual`.
+pub fn supported_languages() -> &'static [(&'static str, &'static str)] {
+    LANGUAGES
+}
+
+/// The name of the language with the whisper code `code`, such as "German" for `"de"`.
+pub fn language_name(code: &str) -> Option<&'static str> {
+    LANGUAGES
+        .iter()
+        .find(|(language, _)| *language == code)
+        .map(|(_, name)| *name)
+}
+
+/// whisper's codes for the ISO 639-2 codes of its languages, both the bibliographic and the
+/// terminology variants. ID3 `TLAN` frames and Matroska tracks use them.
+const ISO_639_2: phf::Map<&'static str, &'static str> = phf::phf_map! {
+    "eng" => "en", "chi" => "zh", "zho" => "zh", "ger" => "de", "deu" => "de",
+    "spa" => "es", "rus" => "ru", "kor" => "ko", "fre" => "fr", "fra" => "fr",
+    "jpn" => "ja", "por" => "pt", "tur" => "tr", "pol" => "pl", "cat" => "ca",
+    "dut" => "nl", "nld" => "nl", "ara" => "ar", "swe" => "sv", "ita" => "it",
+    "ind" => "id", "hin" => "hi", "fin" => "fi", "vie" => "vi", "heb" => "he",
+    "ukr" => "uk", "gre" => "el", "ell" => "el", "may" => "ms", "msa" => "ms",
+    "cze" => "cs", "ces" => "cs", "rum" => "ro", "ron" => "ro", "dan" => "da",
+    "hun" => "hu", "tam" => "ta", "nor" => "no", "nob" => "no", "tha" => "th",
+    "urd" => "ur", "hrv" => "hr", "bul" => "bg", "lit" => "lt", "lat" => "la",
+    "mao" => "mi", "mri" => "mi", "mal" => "ml", "wel" => "cy", "cym" => "cy",
+    "slo" => "sk", "slk" => "sk", "tel" => "te", "per" => "fa", "fas" => "fa",
+    "lav" => "lv", "ben" => "bn", "srp" => "sr", "aze" => "az", "slv" => "sl",
+    "kan" => "kn", "est" => "et", "mac" => "mk", "mkd" => "mk", "bre" => "br",
+    "baq" => "eu", "eus" => "eu", "ice" => "is", "isl" => "is", "arm" => "hy",
+    "hye" => "hy", "nep" => "ne", "mon" => "mn", "bos" => "bs", "kaz" => "kk",
+    "alb" => "sq", "sqi" => "sq", "swa" => "sw", "glg" => "gl", "mar" => "mr",
+    "pan" => "pa", "sin" => "si", "khm" => "km", "sna" => "sn", "yor" => "yo",
+    "som" => "so", "afr" => "af", "oci" => "oc", "geo" => "ka", "kat" => "ka",
+    "bel" => "be", "tgk" => "tg", "snd" => "sd", "guj" => "gu", "amh" => "am",
+    "yid" => "yi", "lao" => "lo", "uzb" => "uz", "fao" => "fo", "hat" => "ht",
+    "pus" => "ps", "tuk" => "tk", "nno" => "nn", "mlt" => "mt", "san" => "sa",
+    "ltz" => "lb", "bur" => "my", "mya" => "my", "tib" => "bo", "bod" => "bo",
+    "tgl" => "tl", "mlg" => "mg", "asm" => "as", "tat" => "tt", "haw" => "haw",
+    "lin" => "ln", "hau" => "ha", "bak" => "ba", "jav" => "jw", "sun" => "su",
+    "yue" => "yue",
+};
+
+/// The whisper language code of a language tag such as "de", "en-US" or "ger", `None` when
+/// it names no language whisper knows. Case is ignored, and so are region and script
+/// subtags.
+pub fn whisper_code(tag: &str) -> Option<&'static str> {
+    let language = tag
+        .trim()
+        .trim_end_matches('\0')
+        .split(['-', '_'])
+        .next()?
+        .to_ascii_lowercase();
+    let language = match language.as_str() {
+        // the ISO 639-1 codes whisper spells differently
+        "nb" => "no",
+        "jv" => "jw",
+        language => language,
+    };
+    if !language.bytes().all(|byte| byte.is_ascii_lowercase()) {
+        return None;
+    }
+    match language.len() {
+        2 => LANGUAGES
+            .iter()
+            .find(|(code, _)| *code == language)
+            .map(|(code, _)| *code),
+        3 => ISO_639_2.get(language).copied(),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_whisper_code_of_tags() {
+        assert_eq!(whisper_code("de"), Some("de"));
+        assert_eq!(whisper_code("en-US"), Some("en"));
+        assert_eq!(whisper_code("pt_BR"), Some("pt"));
+        assert_eq!(whisper_code("GER"), Some("de"));
+        assert_eq!(whisper_code("fra\0"), Some("fr"));
+        assert_eq!(whisper_code("nb"), Some("no"));
+        assert_eq!(whisper_code("haw"), Some("haw"));
+    }
+
+    #[test]
+    fn test_whisper_code_rejects_unknown_tags() {
+        assert_eq!(whisper_code(""), None);
+        assert_eq!(whisper_code("und"), None);
+        assert_eq!(whisper_code("xx"), None);
+        assert_eq!(whisper_code("%%garbage%%"), None);
+        assert_eq!(whisper_code("d\0"), None);
+    }
+
+    #[test]
+    fn test_every_code_is_a_whisper_language() {
+        for code in ISO_639_2.values() {
+            assert!(whisper_rs::get_lang_id(code).is_some(), "{}", code);
+        }
+    }
+
+    #[test]
+    fn test_supported_languages_mirror_whisper() {
+        let languages = supported_languages();
+        assert_eq!(languages.len() as i32, whisper_rs::get_lang_max_id() + 1);
+        for (id, (code, _)) in languages.iter().enumerate() {
+            assert_eq!(whisper_rs::get_lang_id(code), Some(id as i32), "{}", code);
+        }
+        for (code, name) in [
+            ("en", "English"),
+            ("zh", "Chinese"),
+            ("es", "Spanish"),
+            ("hi", "Hindi"),
+            ("ar", "Arabic"),
+            ("fr", "French"),
+            ("de", "German"),
+            ("ja", "Japanese"),
+            ("pt", "Portuguese"),
+            ("ru", "Russian"),
+        ] {
+            assert_eq!(language_name(code), Some(name));
+        }
+        assert_eq!(language_name("xx"), None);
+    }
+}
@@ -1,3 +1,65 @@
-mod audio_parser;
+pub mod audio_parser;
+mod backend;
+pub mod batch;
+pub mod callbacks;
+pub mod change_log;
+pub mod checkpoint;
+pub mod config;
+mod data_uri;
+mod denoise;
+pub mod error;
+pub mod export;
+mod gain;
+pub mod grouping;
+pub mod language;
+pub mod manifest;
+#[cfg(feature = "mel-spectrogram")]
+pub mod mel;
+pub mod memory;
 pub mod model_handler;
+pub mod options;
+mod postprocess;
+pub mod preflight;
+pub mod quality;
+pub mod queue;
+mod remote;
+pub mod report;
+mod resample;
+mod retry;
+pub mod system;
+#[cfg(any(test, feature = "test-util"))]
+pub mod test_util;
 pub mod transcriber;
+pub mod warning;
+
+pub use language::supported_languages;
+pub use preflight::preflight;
+pub use system::system_info;
+
+// The types meant to cross threads, checked at compile time so a field that isn't thread
+// safe fails the build instead of the code of users putting them in shared state, see
+// `Transcriber` for the threading model. `Callbacks` borrow the caller's closures for a
+// single transcription on its thread and aren't among them.
+const _: () = {
+    const fn send_sync<T: Send + Sync>() {}
+    const fn send<T: Send>() {}
+    send_sync::<transcriber::Transcriber>();
+    send_sync::<model_handler::ModelHandler>();
+    send_sync::<transcriber::TranscriberOutput>();
+    send_sync::<transcriber::TranscriberOutputSegment>();
+    send_sync::<transcriber::TranscriberOutputWord>();
+    send_sync::<error::Error>();
+    send_sync::<export::ExporterRegistry>();
+    send_sync::<queue::TranscriptionQueue>();
+    send_sync::<queue::JobHandle>();
+    send_sync::<memory::MemoryBudget>();
+    send::<options::TranscribeOptions>();
+    send::<export::SubtitleOptions>();
+    send::<export::CsvOptions>();
+    send::<export::JsonOptions>();
+    send::<checkpoint::Checkpoint>();
+    send::<queue::Job>();
+    // the errors of transcriptions, returned from async handlers and across threads
+    send_sync::<Box<dyn std::error::Error + Send + Sync>>();
+    send_sync::<Result<transcriber::TranscriberOutput, Box<dyn std::error::Error + Send + Sync>>>();
+};
@@ -0,0 +1,235 @@
+//! A record of how a transcript was produced, attached to every `TranscriberOutput`, so it
+//! can be audited or reproduced long after the fact.
+
+use std::sync::OnceLock;
+
+use sha2::{Digest, Sha256};
+
+use crate::options::TranscribeOptions;
+
+/// The crate's Cargo manifest, which the whisper-rs version is read from.
+const CARGO_TOML: &str = include_str!("../Cargo.toml");
+/// The whisper.cpp release each whisper-rs-sys release bundles, which neither reports.
+const WHISPER_CPP_RELEASES: &[(&str, &str)] = &[("0.8.0", "1.5.0")];
+
+/// Everything that determines a transcript besides the audio itself.
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+pub struct Manifest {
+    /// The version of this crate.
+    pub crate_version: String,
+    /// The whisper-rs version the crate's Cargo manifest requires.
+    pub whisper_rs_version: String,
+    /// The whisper.cpp release bundled by the whisper-rs-sys version the crate requires, or
+    /// "unknown".
+    pub whisper_cpp_version: String,
+    pub model: ModelManifest,
+    /// The options the transcription ran with, every field included, with object keys
+    /// sorted.
+    pub options: serde_json::Value,
+    /// Whether raw `whisper_rs::FullParams` were passed to `Transcriber::transcribe`, which
+    /// `options` doesn't capture.
+    pub custom_whisper_params: bool,
+    /// The hex encoded SHA-256 of the audio file, only computed by
+    /// `Transcriber::transcribe_with_report`.
+    pub audio_sha256: Option<String>,
+}
+
+/// The model a transcription ran on.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+pub struct ModelManifest {
+    /// The model type, such as "tiny".
+    pub model_type: String,
+    /// The file name of the model, `None` when it wasn't loaded from a file.
+    pub file_name: Option<String>,
+    /// The hex encoded SHA-256 of the model file, recorded when it was downloaded, see
+    /// `model_handler::ModelHandler::sha256`.
+    pub sha256: Option<String>,
+}
+
+impl Manifest {
+    pub(crate) fn new(
+        model: ModelManifest,
+        options: &TranscribeOptions,
+        custom_whisper_params: bool,
+    ) -> Manifest {
+        Manifest {
+            crate_version: env!("CARGO_PKG_VERSION").to_string(),
+            whisper_rs_version: whisper_versions().0.clone(),
+            whisper_cpp_version: whisper_versions().1.clone(),
+            model,
+            options: serde_json::to_value(options).expect("options are always serializable"),
+            custom_whisper_params,
+            audio_sha256: None,
+        }
+    }
+
+    /// A hex encoded SHA-256 of the whole manifest, equal for equal manifests, to use as a
+    /// cache key for transcripts.
+    pub fn digest(&self) -> String {
+        let json = serde_json::to_string(self).expect("a manifest always serializes");
+        format!("{:x}", Sha256::digest(json))
+    }
+}
+
+/// The whisper-rs and whisper.cpp versions of the manifest, read once.
+fn whisper_versions() -> &'static (String, String) {
+    static VERSIONS: OnceLock<(String, String)> = OnceLock::new();
+    VERSIONS.get_or_init(|| {
+        let whisper_cpp = dependency_version("whisper-rs-sys")
+            .as_deref()
+            .and_then(whisper_cpp_version)
+            .unwrap_or("unknown");
+        (
+            dependency_version("whisper-rs").unwrap_or_default(),
+            whisper_cpp.to_string(),
+        )
+    })
+}
+
+/// The version of the dependency `name` this crate's manifest requires.
+fn dependency_version(name: &str) -> Option<String> {
+    let manifest: toml::Table = toml::from_str(CARGO_TOML).ok()?;
+    match manifest.get("dependencies")?.get(name)? {
+        toml::Value::String(version) => Some(version.clone()),
+        dependency => dependency.get("version")?.as_str().map(str::to_string),
+    }
+}
+
+/// The whisper.cpp release bundled by the whisper-rs-sys release `sys_version`.
+fn whisper_cpp_version(sys_version: &str) -> Option<&'static str> {
+    WHISPER_CPP_RELEASES
+        .iter()
+        .find(|(sys, _)| *sys == sys_version)
+        .map(|(_, cpp)| *cpp)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::options::{
+        ContextPolicy, EmptyAudioPolicy, RetryPolicy, TimestampPolicy, TranscribeOptions,
+    };
+    use crate::test_util::{MockBackend, MockSegment};
+    use crate::transcriber::Transcriber;
+
+    fn model() -> ModelManifest {
+        ModelManifest {
+            model_type: "tiny".to_string(),
+            file_name: Some("ggml-tiny.bin".to_string()),
+            sha256: None,
+        }
+    }
+
+    fn digest(options: &TranscribeOptions) -> String {
+        Manifest::new(model(), options, false).digest()
+    }
+
+    #[test]
+    fn test_every_option_changes_the_digest() {
+        let changes: Vec<fn(&mut TranscribeOptions)> = vec![
+            |o| o.suppress_non_speech_tokens = false,
+            |o| o.language = Some("de".to_string()),
+            |o| o.use_metadata_language = true,
+            |o| o.translate = true,
+            |o| o.word_timestamps = true,
+            |o| o.token_probabilities = true,
+            |o| o.n_best = 3,
+            |o| o.single_segment = true,
+            |o| o.strip_first_leading_space = true,
+            |o| o.drop_empty_segments = false,
+            |o| o.collapse_repetitions = Some(3),
+            |o| o.normalize_unicode = false,
+            #[cfg(feature = "chinese-conversion")]
+            |o| o.chinese_variant = Some(crate::options::ChineseVariant::Simplified),
+            |o| o.timestamp_policy = TimestampPolicy::Strict,
+            |o| o.normalize_segments = true,
+            |o| o.expected_speakers = Some(4),
+            |o| o.denoise = Some(crate::options::NoiseGate::default()),
+            |o| o.gain_db = Some(12.0),
+            |o| o.min_snr_db = Some(10.0),
+            |o| o.empty_audio = EmptyAudioPolicy::Error,
+            |o| o.min_duration = std::time::Duration::from_millis(1500),
+            |o| o.concat_gap = std::time::Duration::from_millis(500),
+            |o| o.strict_sample_rate = true,
+            |o| o.max_segment_chars = Some(42),
+            |o| o.split_on_word = true,
+            |o| o.max_tokens = Some(32),
+            |o| o.audio_ctx = Some(768),
+            |o| o.temperature_fallback = false,
+            |o| o.chunk_length = Some(std::time::Duration::from_secs(60)),
+            |o| o.chunk_boundary_search = std::time::Duration::ZERO,
+            |o| o.context_tokens = 32,
+            |o| o.context_policy = ContextPolicy::LastNTokens(8),
+            |o| o.n_max_text_ctx = Some(64),
+            |o| {
+                o.retry = RetryPolicy {
+                    max_attempts: 3,
+                    ..RetryPolicy::default()
+                }
+            },
+            |o| o.segment_timing = true,
+            |o| o.include_diagnostics = true,
+            |o| o.debug_print = true,
+            |o| o.skip_validation = true,
+        ];
+        let defaults = serde_json::to_value(TranscribeOptions::default()).unwrap();
+        // a new option needs a change above
+        assert_eq!(changes.len(), defaults.as_object().unwrap().len());
+
+        let mut digests = vec![digest(&TranscribeOptions::default())];
+        for change in changes {
+            let mut options = TranscribeOptions::default();
+            change(&mut options);
+            let changed = digest(&options);
+            assert!(!digests.contains(&changed), "{:?}", options);
+            digests.push(changed);
+        }
+    }
+
+    #[test]
+    fn test_digest_covers_model_and_params() {
+        let options = TranscribeOptions::default();
+        let manifest = Manifest::new(model(), &options, false);
+
+        let mut other_model = manifest.clone();
+        other_model.model.sha256 = Some("00".to_string());
+        assert_ne!(manifest.digest(), other_model.digest());
+        assert_ne!(
+            manifest.digest(),
+            Manifest::new(model(), &options, true).digest()
+        );
+        assert_eq!(manifest.digest(), manifest.clone().digest());
+    }
+
+    #[test]
+    fn test_identical_runs_produce_identical_manifests() {
+        let transcriber = Transcriber::with_mock_backend(MockBackend::new(vec![MockSegment::new(
+            0, 150, " Hi.",
+        )]));
+        let options = TranscribeOptions::default();
+
+        let first = transcriber.run(vec![0.0; 32000], None, &options).unwrap();
+        let second = transcriber.run(vec![0.0; 32000], None, &options).unwrap();
+
+        let manifest = first.manifest().unwrap();
+        assert_eq!(Some(manifest), second.manifest());
+        assert_eq!(manifest.model.model_type, "mock");
+        assert_eq!(manifest.options["language"], "en");
+        assert!(first.to_json().contains(r#""manifest":{"crate_version""#));
+    }
+
+    #[test]
+    fn test_whisper_versions_come_from_the_cargo_manifest() {
+        let manifest = Manifest::new(model(), &TranscribeOptions::default(), false);
+
+        assert_eq!(manifest.whisper_rs_version, "0.10.0");
+        // a whisper-rs-sys update needs its whisper.cpp release added
+        assert_eq!(manifest.whisper_cpp_version, "1.5.0");
+        assert_eq!(
+            dependency_version("serde"),
+            Some("1.0.195".to_string()),
+            "tables with a version"
+        );
+        assert_eq!(dependency_version("no-such-crate"), None);
+    }
+}
@@ -0,0 +1,226 @@
+//! The log-mel spectrogram whisper feeds its encoder, for visualization and debugging.
+//!
+//! whisper keeps its spectrogram internal, so it is recomputed here with the same parameters:
+//! a 400 sample Hann window every 160 samples (10ms at 16KHz), a Slaney mel filterbank and
+//! whisper's log scaling, which clamps everything to 8 (log10) below the loudest value.
+
+use std::f32::consts::PI;
+
+const N_FFT: usize = 400;
+const HOP_LENGTH: usize = 160;
+const SAMPLE_RATE: f32 = 16000.0;
+
+/// Compute whisper's log-mel spectrogram of 16KHz mono samples.
+///
+/// # Returns
+///
+/// * `Vec<Vec<f32>>` - one row per mel band, each holding one value per 10ms frame, the same
+///   layout whisper uses.
+pub fn log_mel_spectrogram(samples: &[f32], n_mels: usize) -> Vec<Vec<f32>> {
+    let n_frames = samples.len() / HOP_LENGTH;
+    let filters = mel_filters(n_mels);
+    let window: Vec<f32> = (0..N_FFT)
+        .map(|i| 0.5 * (1.0 - (2.0 * PI * i as f32 / N_FFT as f32).cos()))
+        .collect();
+
+    let mut mel = vec![vec![0.0; n_frames]; n_mels];
+    let mut frame = vec![0.0; N_FFT];
+    for t in 0..n_frames {
+        // frames are centered on their hop, the audio is reflected at its edges
+        for (i, value) in frame.iter_mut().enumerate() {
+            let position = (t * HOP_LENGTH + i) as isize - (N_FFT / 2) as isize;
+            *value = reflected_sample(samples, position) * window[i];
+        }
+        let spectrum = fft(&frame);
+        let power: Vec<f32> = spectrum[..N_FFT / 2 + 1]
+            .iter()
+            .map(|(re, im)| re * re + im * im)
+            .collect();
+        for (row, weights) in mel.iter_mut().zip(&filters) {
+            let energy: f32 = weights.iter().zip(&power).map(|(w, p)| w * p).sum();
+            row[t] = energy.max(1e-10).log10();
+        }
+    }
+
+    let max = mel
+        .iter()
+        .flatten()
+        .copied()
+        .fold(f32::NEG_INFINITY, f32::max);
+    for value in mel.iter_mut().flatten() {
+        *value = (value.max(max - 8.0) + 4.0) / 4.0;
+    }
+    mel
+}
+
+fn reflected_sample(samples: &[f32], position: isize) -> f32 {
+    let len = samples.len() as isize;
+    if len == 1 {
+        return samples[0];
+    }
+    let mut position = position;
+    while position < 0 || position >= len {
+        position = if position < 0 {
+            -position
+        } else {
+            2 * (len - 1) - position
+        };
+    }
+    samples[position as usize]
+}
+
+/// FFT of a real signal, splitting even lengths recursively and using a plain DFT for odd ones.
+fn fft(input: &[f32]) -> Vec<(f32, f32)> {
+    let n = input.len();
+    if n % 2 == 1 {
+        return dft(input);
+    }
+    let even: Vec<f32> = input.iter().step_by(2).copied().collect();
+    let odd: Vec<f32> = input.iter().skip(1).step_by(2).copied().collect();
+    let (even, odd) = (fft(&even), fft(&odd));
+
+    let mut output = vec![(0.0, 0.0); n];
+    for k in 0..n / 2 {
+        let theta = -2.0 * PI * k as f32 / n as f32;
+        let (cos, sin) = (theta.cos(), theta.sin());
+        let (odd_re, odd_im) = odd[k];
+        let twiddled = (odd_re * cos - odd_im * sin, odd_re * sin + odd_im * cos);
+        output[k] = (even[k].0 + twiddled.0, even[k].1 + twiddled.1);
+        output[k + n / 2] = (even[k].0 - twiddled.0, even[k].1 - twiddled.1);
+    }
+    output
+}
+
+fn dft(input: &[f32]) -> Vec<(f32, f32)> {
+    let n = input.len();
+    (0..n)
+        .map(|k| {
+            input
+                .iter()
+                .enumerate()
+                .fold((0.0, 0.0), |(re, im), (i, value)| {
+                    let theta = -2.0 * PI * (k * i) as f32 / n as f32;
+                    (re + value * theta.cos(), im + value * theta.sin())
+                })
+        })
+        .collect()
+}
+
+/// A Slaney style mel filterbank with Slaney normalization, as whisper's model files ship.
+fn mel_filters(n_mels: usize) -> Vec<Vec<f32>> {
+    let n_bins = N_FFT / 2 + 1;
+    let fft_frequencies: Vec<f32> = (0..n_bins)
+        .map(|i| i as f32 * SAMPLE_RATE / N_FFT as f32)
+        .collect();
+    let max_mel = hz_to_mel(SAMPLE_RATE / 2.0);
+    let mel_frequencies: Vec<f32> = (0..n_mels + 2)
+        .map(|i| mel_to_hz(max_mel * i as f32 / (n_mels + 1) as f32))
+        .collect();
+
+    (0..n_mels)
+        .map(|band| {
+            let (lower, center, upper) = (
+                mel_frequencies[band],
+                mel_frequencies[band + 1],
+                mel_frequencies[band + 2],
+            );
+            let norm = 2.0 / (upper - lower);
+            fft_frequencies
+                .iter()
+                .map(|f| {
+                    let rising = (f - lower) / (center - lower);
+                    let falling = (upper - f) / (upper - center);
+                    rising.min(falling).max(0.0) * norm
+                })
+                .collect()
+        })
+        .collect()
+}
+
+const MIN_LOG_HZ: f32 = 1000.0;
+const MIN_LOG_MEL: f32 = MIN_LOG_HZ / (200.0 / 3.0);
+
+fn log_step() -> f32 {
+    6.4f32.ln() / 27.0
+}
+
+/// The Slaney mel scale: linear below 1KHz, logarithmic above.
+fn hz_to_mel(hz: f32) -> f32 {
+    if hz < MIN_LOG_HZ {
+        hz / (200.0 / 3.0)
+    } else {
+        MIN_LOG_MEL + (hz / MIN_LOG_HZ).ln() / log_step()
+    }
+}
+
+fn mel_to_hz(mel: f32) -> f32 {
+    if mel < MIN_LOG_MEL {
+        mel * (200.0 / 3.0)
+    } else {
+        MIN_LOG_HZ * (log_step() * (mel - MIN_LOG_MEL)).exp()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fft_matches_dft() {
+        let input: Vec<f32> = (0..N_FFT).map(|i| (i as f32 * 0.37).sin()).collect();
+
+        for ((re, im), (dft_re, dft_im)) in fft(&input).iter().zip(dft(&input)) {
+            assert!((re - dft_re).abs() < 1e-2 && (im - dft_im).abs() < 1e-2);
+        }
+    }
+
+    #[test]
+    fn test_mel_scale_round_trips() {
+        for hz in [0.0, 440.0, 1000.0, 4000.0, 8000.0] {
+            assert!((mel_to_hz(hz_to_mel(hz)) - hz).abs() < 0.1);
+        }
+    }
+
+    #[test]
+    fn test_log_mel_spectrogram_shape_and_range() {
+        let samples: Vec<f32> = (0..16000)
+            .map(|i| (2.0 * PI * 1000.0 * i as f32 / SAMPLE_RATE).sin())
+            .collect();
+
+        let mel = log_mel_spectrogram(&samples, 80);
+
+        assert_eq!(mel.len(), 80);
+        assert!(mel.iter().all(|band| band.len() == 100));
+        let max = mel.iter().flatten().copied().fold(f32::MIN, f32::max);
+        let min = mel.iter().flatten().copied().fold(f32::MAX, f32::min);
+        assert!(max - min <= 2.0 + 1e-6);
+    }
+
+    #[test]
+    fn test_log_mel_spectrogram_128_bins() {
+        let samples = vec![0.0; 16000];
+
+        let mel = log_mel_spectrogram(&samples, 128);
+
+        assert_eq!(mel.len(), 128);
+        assert!(mel_filters(128)
+            .iter()
+            .all(|filter| filter.iter().any(|&w| w > 0.0)));
+    }
+
+    #[test]
+    fn test_log_mel_spectrogram_peaks_at_tone_frequency() {
+        let samples: Vec<f32> = (0..16000)
+            .map(|i| (2.0 * PI * 1000.0 * i as f32 / SAMPLE_RATE).sin())
+            .collect();
+        let filters = mel_filters(80);
+
+        let mel = log_mel_spectrogram(&samples, 80);
+
+        let loudest_band = (0..80)
+            .max_by(|a, b| mel[*a][50].total_cmp(&mel[*b][50]))
+            .unwrap();
+        let bin_1khz = (1000.0 * N_FFT as f32 / SAMPLE_RATE) as usize;
+        assert!(filters[loudest_band][bin_1khz] > 0.0);
+    }
+}
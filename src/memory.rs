@@ -0,0 +1,189 @@
+//! Limits on the memory concurrent transcriptions may use.
+//!
+//! Every inference state takes a few hundred megabytes on top of the model, so running many
+//! transcriptions at once can exhaust the machine's memory. A `MemoryBudget` makes a
+//! transcription wait, or fail with `Error::OverBudget`, until the states already running
+//! leave room for its own.
+
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::error::Error;
+
+/// The budget transcribers without their own (see `Transcriber::with_memory_budget`) use.
+static GLOBAL_BUDGET: Mutex<Option<MemoryBudget>> = Mutex::new(None);
+
+/// A budget of bytes shared by the transcriptions running in it, clones share the budget.
+#[derive(Debug, Clone)]
+pub struct MemoryBudget {
+    inner: Arc<Inner>,
+}
+
+#[derive(Debug)]
+struct Inner {
+    limit: u64,
+    timeout: Duration,
+    used: Mutex<u64>,
+    released: Condvar,
+}
+
+impl MemoryBudget {
+    /// A budget of `limit` bytes. A transcription not fitting in what is left waits up to
+    /// `timeout` for running ones to finish, `Duration::ZERO` fails it right away.
+    pub fn new(limit: u64, timeout: Duration) -> MemoryBudget {
+        MemoryBudget {
+            inner: Arc::new(Inner {
+                limit,
+                timeout,
+                used: Mutex::new(0),
+                released: Condvar::new(),
+            }),
+        }
+    }
+
+    pub fn limit(&self) -> u64 {
+        self.inner.limit
+    }
+
+    /// The bytes reserved by running transcriptions.
+    pub fn in_use(&self) -> u64 {
+        *self
+            .inner
+            .used
+            .lock()
+            .unwrap_or_else(|err| err.into_inner())
+    }
+
+    /// Reserve `bytes`, waiting for room until the budget's timeout. The reservation is
+    /// released when the returned guard is dropped, also while unwinding from a panic.
+    pub(crate) fn reserve(&self, bytes: u64) -> Result<MemoryReservation, Error> {
+        let over_budget = || Error::OverBudget {
+            requested: bytes,
+            limit: self.inner.limit,
+        };
+        if bytes > self.inner.limit {
+            return Err(over_budget());
+        }
+
+        let deadline = Instant::now() + self.inner.timeout;
+        let mut used = self
+            .inner
+            .used
+            .lock()
+            .unwrap_or_else(|err| err.into_inner());
+        while *used + bytes > self.inner.limit {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if remaining.is_zero() {
+                return Err(over_budget());
+            }
+            used = self
+                .inner
+                .released
+                .wait_timeout(used, remaining)
+                .unwrap_or_else(|err| err.into_inner())
+                .0;
+        }
+        *used += bytes;
+        Ok(MemoryReservation {
+            budget: self.clone(),
+            bytes,
+        })
+    }
+}
+
+/// Bytes reserved in a `MemoryBudget`, released on drop.
+#[derive(Debug)]
+pub(crate) struct MemoryReservation {
+    budget: MemoryBudget,
+    bytes: u64,
+}
+
+impl Drop for MemoryReservation {
+    fn drop(&mut self) {
+        let inner = &self.budget.inner;
+        let mut used = inner.used.lock().unwrap_or_else(|err| err.into_inner());
+        *used -= self.bytes;
+        inner.released.notify_all();
+    }
+}
+
+/// Set the budget shared by every transcriber without one of its own, `None` removes it.
+pub fn set_global_budget(budget: Option<MemoryBudget>) {
+    *GLOBAL_BUDGET.lock().unwrap_or_else(|err| err.into_inner()) = budget;
+}
+
+/// The budget set with `set_global_budget`.
+pub fn global_budget() -> Option<MemoryBudget> {
+    GLOBAL_BUDGET
+        .lock()
+        .unwrap_or_else(|err| err.into_inner())
+        .clone()
+}
+
+/// A rough estimate of the memory one inference state of a model takes, in bytes: its key
+/// value caches and compute buffers, not the model weights shared by every state.
+///
+/// Unknown model types are assumed to be as large as the large model.
+pub fn estimate_state_bytes(model_type: &str) -> u64 {
+    const MIB: u64 = 1024 * 1024;
+    match model_type {
+        "tiny" => 200 * MIB,
+        "base" => 250 * MIB,
+        "small" => 400 * MIB,
+        "medium" => 600 * MIB,
+        _ => 1024 * MIB,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reservations_release_on_drop() {
+        let budget = MemoryBudget::new(100, Duration::ZERO);
+
+        let first = budget.reserve(60).unwrap();
+        assert_eq!(budget.in_use(), 60);
+        assert!(matches!(
+            budget.reserve(60),
+            Err(Error::OverBudget {
+                requested: 60,
+                limit: 100
+            })
+        ));
+        drop(first);
+
+        assert_eq!(budget.in_use(), 0);
+        assert!(budget.reserve(60).is_ok());
+        assert!(budget.reserve(101).is_err());
+    }
+
+    #[test]
+    fn test_reservation_released_when_panic_is_caught() {
+        let budget = MemoryBudget::new(100, Duration::ZERO);
+
+        let result = std::panic::catch_unwind(|| {
+            let _reservation = budget.reserve(100).unwrap();
+            panic!("inference failed");
+        });
+
+        assert!(result.is_err());
+        assert_eq!(budget.in_use(), 0);
+    }
+
+    #[test]
+    fn test_reserve_waits_for_release() {
+        let budget = MemoryBudget::new(100, Duration::from_secs(10));
+        let held = budget.reserve(100).unwrap();
+
+        let waiter = {
+            let budget = budget.clone();
+            std::thread::spawn(move || budget.reserve(100).map(|_| ()))
+        };
+        std::thread::sleep(Duration::from_millis(50));
+        drop(held);
+
+        assert!(waiter.join().unwrap().is_ok());
+    }
+}
@@ -1,35 +1,460 @@
+use std::collections::HashMap;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use sha2::{Digest, Sha256};
+
+use crate::error::Error;
+
+/// Where models are downloaded from unless mirrors are configured.
+const DEFAULT_MIRROR: &str = "https://huggingface.co/ggerganov/whisper.cpp/resolve/main";
+
+/// The most redirects followed while requesting a model.
+const MAX_REDIRECTS: usize = 10;
+
+#[derive(Debug, Clone)]
 pub struct ModelHandler {
-    model_name: String, // list of downloaded models
-    models_dir: String, // path to the models directory
+    model_name: String,  // list of downloaded models
+    models_dir: PathBuf, // path to the models directory
+    variant: Option<ModelVariant>,
 }
 
 const MODEL_MAP: phf::Map<&'static str, &'static str> = phf::phf_map! {
     "tiny" => "ggml-tiny",
+    "tiny.en" => "ggml-tiny.en",
     "base" => "ggml-base",
     "small" => "ggml-small",
     "medium" => "ggml-medium",
     "large" => "ggml-large",
+    // large-v3 and its turbo distillation take 128 mel bins instead of 80, whisper reads
+    // that from the model file
+    "large-v3" => "ggml-large-v3",
+    "large-v3-turbo" => "ggml-large-v3-turbo",
+    "turbo" => "ggml-large-v3-turbo",
 };
 
-impl ModelHandler {
-    pub async fn new(model_name: &str, models_dir: &str) -> ModelHandler {
-        let model_handler = ModelHandler {
-            model_name: MODEL_MAP
-                .get(&model_name.to_lowercase())
-                .copied()
-                .unwrap()
-                .to_string(),
-            models_dir: models_dir.to_string(),
+/// A file of a model, its full precision one or a quantization of it, see
+/// `ModelHandlerBuilder::variants`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ModelVariant {
+    quantization: Option<String>,
+    sha256: Option<String>,
+}
+
+impl ModelVariant {
+    /// The model's full precision file, such as "ggml-base.bin".
+    pub fn full() -> ModelVariant {
+        ModelVariant {
+            quantization: None,
+            sha256: None,
+        }
+    }
+
+    /// The model quantized as `quantization`, such as "q5_1" for "ggml-base-q5_1.bin".
+    pub fn quantized(quantization: &str) -> ModelVariant {
+        ModelVariant {
+            quantization: Some(quantization.to_string()),
+            sha256: None,
+        }
+    }
+
+    /// Verify downloads of this variant against `sha256`, hex encoded, instead of the
+    /// checksum the server reports.
+    pub fn with_sha256(mut self, sha256: &str) -> ModelVariant {
+        self.sha256 = Some(sha256.to_ascii_lowercase());
+        self
+    }
+
+    /// The quantization, `None` for the full precision file.
+    pub fn quantization(&self) -> Option<&str> {
+        self.quantization.as_deref()
+    }
+
+    /// The checksum downloads are verified against, when pinned.
+    pub fn sha256(&self) -> Option<&str> {
+        self.sha256.as_deref()
+    }
+
+    /// The variant's file name without extension, for the model file `base`.
+    fn file_stem(&self, base: &str) -> String {
+        match &self.quantization {
+            Some(quantization) => format!("{}-{}", base, quantization),
+            None => base.to_string(),
+        }
+    }
+}
+
+/// The progress of a model download, see `ModelHandlerBuilder::on_progress`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DownloadProgress {
+    /// The model's file name, such as "ggml-tiny.bin".
+    pub file_name: String,
+    /// The bytes downloaded so far.
+    pub downloaded: u64,
+    /// The size of the model, when the server reports it.
+    pub total: Option<u64>,
+}
+
+type ProgressCallback = Arc<dyn Fn(&DownloadProgress) + Send + Sync>;
+
+/// Settings shared by every model loaded through it: the models directory, the HTTP
+/// client, a progress callback and the mirrors models are downloaded from.
+///
+/// ```no_run
+/// # async fn run() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+/// use simple_transcribe_rs::model_handler::ModelHandlerBuilder;
+///
+/// let models = ModelHandlerBuilder::new()
+///     .models_dir("models/")
+///     .on_progress(|progress| println!("{} {}", progress.file_name, progress.downloaded));
+/// let tiny = models.load("tiny").await?;
+/// let base = models.load("base").await?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct ModelHandlerBuilder {
+    models_dir: PathBuf,
+    client: Option<reqwest::Client>,
+    on_progress: Option<ProgressCallback>,
+    mirrors: Vec<String>,
+    variants: HashMap<String, Vec<ModelVariant>>,
+}
+
+impl Default for ModelHandlerBuilder {
+    fn default() -> Self {
+        ModelHandlerBuilder {
+            models_dir: PathBuf::from("models/"),
+            client: None,
+            on_progress: None,
+            mirrors: Vec::new(),
+            variants: HashMap::new(),
+        }
+    }
+}
+
+impl ModelHandlerBuilder {
+    /// A builder downloading to "models/" from Hugging Face.
+    pub fn new() -> ModelHandlerBuilder {
+        ModelHandlerBuilder::default()
+    }
+
+    /// The directory models are downloaded to and loaded from.
+    pub fn models_dir(mut self, models_dir: impl AsRef<Path>) -> ModelHandlerBuilder {
+        self.models_dir = models_dir.as_ref().to_path_buf();
+        self
+    }
+
+    /// Download through `client`, for proxies, timeouts or custom TLS.
+    ///
+    /// Hugging Face reports the checksum of a model in the redirect to its storage, so a
+    /// client following redirects by itself skips verifying the download. The default client
+    /// doesn't.
+    pub fn http_client(mut self, client: reqwest::Client) -> ModelHandlerBuilder {
+        self.client = Some(client);
+        self
+    }
+
+    /// Call `on_progress` after every chunk of a model downloaded.
+    pub fn on_progress(
+        mut self,
+        on_progress: impl Fn(&DownloadProgress) + Send + Sync + 'static,
+    ) -> ModelHandlerBuilder {
+        self.on_progress = Some(Arc::new(on_progress));
+        self
+    }
+
+    /// Download from `base_url`, where models are found as `<base_url>/ggml-<name>.bin`.
+    /// Mirrors are tried in the order they were added until a download succeeds, and replace
+    /// Hugging Face.
+    pub fn mirror(mut self, base_url: &str) -> ModelHandlerBuilder {
+        self.mirrors
+            .push(base_url.trim_end_matches('/').to_string());
+        self
+    }
+
+    /// Download the model called `model_name` as the first of `variants` that is available,
+    /// in order of preference. A variant whose file the mirrors answer with 404 Not Found is
+    /// skipped for the next one, other failures fail the download. A variant already in the
+    /// models directory is loaded without any download, even when a preferred one isn't.
+    /// `ModelHandler::get_variant` tells which one was loaded.
+    ///
+    /// ```no_run
+    /// # async fn run() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    /// use simple_transcribe_rs::model_handler::{ModelHandlerBuilder, ModelVariant};
+    ///
+    /// let models = ModelHandlerBuilder::new().variants(
+    ///     "base",
+    ///     [ModelVariant::quantized("q5_1"), ModelVariant::quantized("q5_0")],
+    /// );
+    /// let base = models.load("base").await?;
+    /// println!("loaded {:?}", base.get_variant());
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn variants(
+        mut self,
+        model_name: &str,
+        variants: impl IntoIterator<Item = ModelVariant>,
+    ) -> ModelHandlerBuilder {
+        self.variants
+            .insert(model_name.to_lowercase(), variants.into_iter().collect());
+        self
+    }
+
+    /// Load the model called `model_name`, downloading it when it isn't in the models
+    /// directory yet.
+    pub async fn load(
+        &self,
+        model_name: &str,
+    ) -> Result<ModelHandler, Box<dyn std::error::Error + Send + Sync>> {
+        let on_progress = self.on_progress.clone();
+        self.plan(model_name)?
+            .execute(move |progress| {
+                if let Some(on_progress) = &on_progress {
+                    on_progress(progress);
+                }
+            })
+            .await
+    }
+
+    /// Resolve the model called `model_name` to its file and the URLs it is downloaded
+    /// from, without any network activity, so an app can ask before downloading it. See
+    /// `DownloadPlan`.
+    ///
+    /// With `variants` configured for the model, the plan is for the first variant already in
+    /// the models directory, or else the preferred one, falling back to the others.
+    pub fn plan(&self, model_name: &str) -> Result<DownloadPlan, Error> {
+        let key = model_name.to_lowercase();
+        let base = MODEL_MAP
+            .get(&key)
+            .ok_or_else(|| Error::UnknownModel(model_name.to_string()))?;
+        let mut candidates: Vec<ModelHandler> = match self.variants.get(&key) {
+            Some(variants) if !variants.is_empty() => variants
+                .iter()
+                .map(|variant| ModelHandler {
+                    model_name: variant.file_stem(base),
+                    models_dir: self.models_dir.clone(),
+                    variant: Some(variant.clone()),
+                })
+                .collect(),
+            _ => vec![ModelHandler {
+                model_name: base.to_string(),
+                models_dir: self.models_dir.clone(),
+                variant: None,
+            }],
+        };
+        let cached = candidates
+            .iter()
+            .position(ModelHandler::is_model_existing)
+            .unwrap_or(0);
+        let handler = candidates.remove(cached);
+        let fallbacks = if handler.is_model_existing() {
+            Vec::new()
+        } else {
+            candidates
+        };
+        let expected_size = std::fs::metadata(handler.model_path())
+            .ok()
+            .map(|metadata| metadata.len());
+        let mirrors = if self.mirrors.is_empty() {
+            &[DEFAULT_MIRROR.to_string()][..]
+        } else {
+            &self.mirrors[..]
         };
+        Ok(DownloadPlan {
+            source_urls: source_urls(mirrors, &handler),
+            mirrors: mirrors.to_vec(),
+            fallbacks,
+            cached: expected_size.is_some(),
+            expected_size,
+            client: self.client.clone(),
+            handler,
+        })
+    }
+}
+
+/// A model resolved to its file and download URLs but not fetched yet, see
+/// `ModelHandler::plan`.
+///
+/// ```no_run
+/// # async fn run() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+/// use simple_transcribe_rs::model_handler::ModelHandler;
+///
+/// let plan = ModelHandler::plan("large-v3", "models/")?.fetch_size().await?;
+/// if !plan.is_cached() {
+///     println!("This will download {:?} bytes, continue?", plan.expected_size());
+/// }
+/// let model = plan.execute(|progress| println!("{}", progress.downloaded)).await?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct DownloadPlan {
+    handler: ModelHandler,
+    source_urls: Vec<String>,
+    mirrors: Vec<String>,
+    // the variants to fall back to, in order, see `ModelHandlerBuilder::variants`
+    fallbacks: Vec<ModelHandler>,
+    expected_size: Option<u64>,
+    cached: bool,
+    client: Option<reqwest::Client>,
+}
+
+impl DownloadPlan {
+    /// The model's file name, such as "ggml-tiny.bin".
+    pub fn file_name(&self) -> String {
+        format!("{}.bin", self.handler.model_name)
+    }
+
+    /// The path the model is loaded from, and downloaded to when it isn't cached.
+    pub fn model_path(&self) -> PathBuf {
+        self.handler.model_path()
+    }
+
+    /// The SHA-256 recorded when the model file was downloaded, see `ModelHandler::sha256`.
+    pub(crate) fn recorded_sha256(&self) -> Option<String> {
+        self.handler.sha256()
+    }
+
+    /// The URLs the model is downloaded from, tried in order.
+    pub fn source_urls(&self) -> &[String] {
+        &self.source_urls
+    }
+
+    /// The size of the model in bytes: the size of the cached file, or what the server
+    /// reported to `fetch_size`. `None` when neither is known.
+    pub fn expected_size(&self) -> Option<u64> {
+        self.expected_size
+    }
 
+    /// Whether the model file is already in the models directory, so `execute` downloads
+    /// nothing.
+    pub fn is_cached(&self) -> bool {
+        self.cached
+    }
+
+    /// Ask the first source for the model's size with a `HEAD` request. Cached models skip
+    /// the request, and a server not reporting it leaves the size unknown.
+    pub async fn fetch_size(
+        mut self,
+    ) -> Result<DownloadPlan, Box<dyn std::error::Error + Send + Sync>> {
+        if !self.cached {
+            let client = self.client()?;
+            self.expected_size = fetch_size(&client, &self.source_urls[0]).await?;
+        }
+        Ok(self)
+    }
+
+    /// The variants tried, in order, when the planned one isn't found on the mirrors, see
+    /// `ModelHandlerBuilder::variants`.
+    pub fn fallback_variants(&self) -> Vec<&ModelVariant> {
+        self.fallbacks
+            .iter()
+            .filter_map(|handler| handler.variant.as_ref())
+            .collect()
+    }
+
+    /// Download the model unless it is cached, trying the sources in order and reporting
+    /// progress to `on_progress` after every chunk. When no source has the planned variant,
+    /// the fallback variants are tried in turn.
+    pub async fn execute(
+        self,
+        on_progress: impl Fn(&DownloadProgress) + Send + Sync,
+    ) -> Result<ModelHandler, Box<dyn std::error::Error + Send + Sync>> {
+        let model_handler = self.handler;
+        // the plan may be stale, the model could have been downloaded since
         if model_handler.is_model_existing() {
-            return model_handler;
+            return Ok(model_handler);
+        }
+        if !model_handler.is_writable() {
+            return Err(Box::new(Error::ReadOnlyModelsDir(
+                model_handler.model_path().display().to_string(),
+            )));
+        }
+
+        model_handler.setup_directory()?;
+        let client = match self.client {
+            Some(client) => client,
+            None => default_client()?,
+        };
+        let mut last_err = None;
+        let candidates = std::iter::once((model_handler, self.source_urls)).chain(
+            self.fallbacks.into_iter().map(|handler| {
+                let urls = source_urls(&self.mirrors, &handler);
+                (handler, urls)
+            }),
+        );
+        for (model_handler, urls) in candidates {
+            if model_handler.is_model_existing() {
+                return Ok(model_handler);
+            }
+            let mut not_found = false;
+            for url in &urls {
+                match model_handler
+                    .download_from(&client, url, Some(&on_progress))
+                    .await
+                {
+                    Ok(()) => return Ok(model_handler),
+                    Err(err) => {
+                        not_found |= is_not_found(err.as_ref());
+                        last_err = Some(err);
+                    }
+                }
+            }
+            if !not_found {
+                break;
+            }
+        }
+        Err(last_err.expect("there is at least one source"))
+    }
+
+    fn client(&self) -> reqwest::Result<reqwest::Client> {
+        match &self.client {
+            Some(client) => Ok(client.clone()),
+            None => default_client(),
         }
+    }
+}
 
-        let _ = model_handler.setup_directory();
-        let _ = model_handler.download_model().await;
+impl ModelHandler {
+    /// A builder for loading several models with the same settings.
+    pub fn builder() -> ModelHandlerBuilder {
+        ModelHandlerBuilder::new()
+    }
 
-        model_handler
+    /// Resolve the model called `model_name` in `models_dir` without downloading it, see
+    /// `DownloadPlan`. Use `ModelHandlerBuilder::plan` for mirrors or a custom client.
+    pub fn plan(model_name: &str, models_dir: impl AsRef<Path>) -> Result<DownloadPlan, Error> {
+        ModelHandler::builder()
+            .models_dir(models_dir)
+            .plan(model_name)
+    }
+
+    /// The variant loaded, `None` unless variants were configured with
+    /// `ModelHandlerBuilder::variants`.
+    pub fn get_variant(&self) -> Option<&ModelVariant> {
+        self.variant.as_ref()
+    }
+
+    /// Load the model called `model_name` from `models_dir`, downloading it when it isn't
+    /// there yet.
+    ///
+    /// # Panics
+    ///
+    /// With the error, when the model is unknown or can't be downloaded: on a checksum
+    /// mismatch, a read-only models directory without the model, or a network error.
+    /// `ModelHandlerBuilder::load` returns these errors instead.
+    pub async fn new(model_name: &str, models_dir: impl AsRef<Path>) -> ModelHandler {
+        let plan =
+            ModelHandler::plan(model_name, models_dir).unwrap_or_else(|err| panic!("{}", err));
+
+        // nothing is written for a model already there, so read-only volumes work
+        match plan.execute(|_| {}).await {
+            Ok(model_handler) => model_handler,
+            Err(err) => panic!("loading the {} model failed: {}", model_name, err),
+        }
     }
 
     /// setup the directory to which models will be downloaded.
@@ -39,44 +464,283 @@ impl ModelHandler {
     ///
     /// * `Void` - directory is setup.
     fn setup_directory(&self) -> Result<(), std::io::Error> {
-        let path = std::path::Path::new(&self.models_dir);
-        if !path.exists() {
-            let _ = std::fs::create_dir_all(path)?;
+        if !self.models_dir.exists() {
+            std::fs::create_dir_all(&self.models_dir)?;
         }
         Ok(())
     }
 
+    /// Whether models can be downloaded to the models directory, or to the directory it
+    /// would be created in when it doesn't exist yet. Read-only volumes, missing permissions
+    /// and the like make it `false`.
+    pub fn is_writable(&self) -> bool {
+        is_dir_writable(&self.models_dir)
+    }
+
     fn is_model_existing(&self) -> bool {
-        match std::fs::metadata(format!("{}/{}.bin", self.models_dir, self.model_name)) {
-            Ok(_) => true,
-            Err(_) => false,
-        }
+        std::fs::metadata(self.model_path()).is_ok()
     }
 
-    /// Download the specified model.
-    ///
-    /// # Arguments
-    ///
-    /// * `model` - The name of the model to download.
-    ///
-    /// # Returns
-    ///
-    /// * `Void` - The model is downloaded to the models directory.
-    async fn download_model(&self) -> Result<(), Box<dyn std::error::Error>> {
-        if !self.is_model_existing() {
-            self.setup_directory()?;
-        }
-        let base_url = "https://huggingface.co/ggerganov/whisper.cpp/resolve/main";
-        let response = reqwest::get(format!("{}/{}.bin", base_url, &self.model_name)).await?;
-        let mut file =
-            std::fs::File::create(format!("{}/{}.bin", &self.models_dir, &self.model_name))?;
-        let mut content = std::io::Cursor::new(response.bytes().await?);
-        std::io::copy(&mut content, &mut file)?;
+    /// Download the model from `url`, reporting progress to `on_progress`.
+    async fn download_from(
+        &self,
+        client: &reqwest::Client,
+        url: &str,
+        on_progress: Option<&(dyn Fn(&DownloadProgress) + Send + Sync)>,
+    ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let (mut response, expected_sha256) = fetch_model(client, url).await?;
+        let mut progress = DownloadProgress {
+            file_name: format!("{}.bin", self.model_name),
+            downloaded: 0,
+            total: response.content_length(),
+        };
+
+        // download next to the model and only move it in place once complete and verified, the
+        // partial file is removed if this future is dropped or fails
+        let model_path = self.model_path();
+        let part = PartFile::new(
+            self.models_dir
+                .join(format!("{}.bin.part", self.model_name)),
+        );
+        // hash the bytes as they are written so verifying needs no second read of the file
+        let mut file = ChecksumWriter::new(std::fs::File::create(&part.path)?);
+        while let Some(chunk) = response.chunk().await? {
+            file.write_all(&chunk)?;
+            progress.downloaded += chunk.len() as u64;
+            if let Some(on_progress) = on_progress {
+                on_progress(&progress);
+            }
+        }
+        let actual_sha256 = file.finish()?;
+
+        let pinned_sha256 = self
+            .variant
+            .as_ref()
+            .and_then(|variant| variant.sha256.clone());
+        if let Some(expected_sha256) = pinned_sha256.or(expected_sha256) {
+            if expected_sha256 != actual_sha256 {
+                return Err(Box::new(Error::ChecksumMismatch {
+                    expected: expected_sha256,
+                    actual: actual_sha256,
+                }));
+            }
+        }
+        part.persist(&model_path)?;
+        // recorded for manifests, so the model needn't be hashed again; a model without it
+        // just has no checksum in them
+        let _ = std::fs::write(self.sha256_path(), &actual_sha256);
         Ok(())
     }
 
+    /// The SHA-256 of the model file, hex encoded, as computed when the crate downloaded it.
+    /// `None` for model files put in the models directory otherwise, or changed since.
+    pub fn sha256(&self) -> Option<String> {
+        let modified = |path: &Path| std::fs::metadata(path).and_then(|meta| meta.modified());
+        let recorded = modified(&self.sha256_path()).ok()?;
+        if modified(&self.model_path()).ok()? > recorded {
+            return None;
+        }
+        let sha256 = std::fs::read_to_string(self.sha256_path()).ok()?;
+        let is_sha256 = sha256.len() == 64 && sha256.bytes().all(|b| b.is_ascii_hexdigit());
+        is_sha256.then_some(sha256)
+    }
+
+    /// The path of the file recording the SHA-256 of the downloaded model, next to it.
+    fn sha256_path(&self) -> PathBuf {
+        self.models_dir
+            .join(format!("{}.bin.sha256", self.model_name))
+    }
+
+    /// The path of the model file, as a string with anything not valid UTF-8 replaced, see
+    /// `model_path`.
     pub fn get_model_dir(&self) -> String {
-        format!("{}/{}.bin", &self.models_dir, &self.model_name)
+        self.model_path().to_string_lossy().into_owned()
+    }
+
+    /// The path of the model file.
+    pub fn model_path(&self) -> PathBuf {
+        self.models_dir.join(format!("{}.bin", self.model_name))
+    }
+}
+
+/// Whether files can be created in `dir`, or in the directory it would be created in when it
+/// doesn't exist yet.
+pub(crate) fn is_dir_writable(dir: &Path) -> bool {
+    let mut dir = dir;
+    while !dir.exists() {
+        dir = match dir.parent() {
+            Some(parent) if parent.as_os_str().is_empty() => Path::new("."),
+            Some(parent) => parent,
+            None => return false,
+        };
+    }
+    // permission bits can't tell read-only mounts or ACLs, so try writing instead
+    let probe = dir.join(format!(".write-probe-{}", std::process::id()));
+    match std::fs::File::create(&probe) {
+        Ok(_) => {
+            let _ = std::fs::remove_file(&probe);
+            true
+        }
+        Err(_) => false,
+    }
+}
+
+/// The URLs of the model file of `handler` on `mirrors`.
+fn source_urls(mirrors: &[String], handler: &ModelHandler) -> Vec<String> {
+    mirrors
+        .iter()
+        .map(|mirror| format!("{}/{}.bin", mirror, handler.model_name))
+        .collect()
+}
+
+/// Whether a download failed because the server has no such file.
+fn is_not_found(err: &(dyn std::error::Error + 'static)) -> bool {
+    err.downcast_ref::<reqwest::Error>()
+        .and_then(reqwest::Error::status)
+        == Some(reqwest::StatusCode::NOT_FOUND)
+}
+
+/// A client leaving redirects to `fetch_model`.
+fn default_client() -> reqwest::Result<reqwest::Client> {
+    reqwest::Client::builder()
+        .redirect(reqwest::redirect::Policy::none())
+        .build()
+}
+
+/// Request a model file, returning the response along with the SHA-256 the server reports.
+///
+/// Hugging Face answers with a redirect to its file storage and reports the SHA-256 of LFS
+/// files in the `X-Linked-Etag` header of that redirect, so redirects are followed manually.
+async fn fetch_model(
+    client: &reqwest::Client,
+    url: &str,
+) -> Result<(reqwest::Response, Option<String>), Box<dyn std::error::Error + Send + Sync>> {
+    let mut response = client.get(url).send().await?.error_for_status()?;
+    let mut expected_sha256 = None;
+    for _ in 0..MAX_REDIRECTS {
+        expected_sha256 = expected_sha256.or_else(|| sha256_from_headers(response.headers()));
+        let location = response
+            .headers()
+            .get(reqwest::header::LOCATION)
+            .and_then(|location| location.to_str().ok());
+        match location {
+            Some(location) if response.status().is_redirection() => {
+                let location = response.url().join(location)?;
+                response = client.get(location).send().await?.error_for_status()?;
+            }
+            _ => return Ok((response, expected_sha256)),
+        }
+    }
+    Err(format!("too many redirects requesting {}", url).into())
+}
+
+/// The size of the file at `url` as reported to a `HEAD` request, following redirects.
+///
+/// Hugging Face reports the size of LFS files in the `X-Linked-Size` header of its redirect.
+async fn fetch_size(
+    client: &reqwest::Client,
+    url: &str,
+) -> Result<Option<u64>, Box<dyn std::error::Error + Send + Sync>> {
+    let header = |response: &reqwest::Response, name| {
+        response
+            .headers()
+            .get(name)
+            .and_then(|value| value.to_str().ok()?.parse::<u64>().ok())
+    };
+    let mut response = client.head(url).send().await?.error_for_status()?;
+    for _ in 0..MAX_REDIRECTS {
+        if let Some(size) = header(&response, "x-linked-size") {
+            return Ok(Some(size));
+        }
+        let location = response
+            .headers()
+            .get(reqwest::header::LOCATION)
+            .and_then(|location| location.to_str().ok());
+        match location {
+            Some(location) if response.status().is_redirection() => {
+                let location = response.url().join(location)?;
+                response = client.head(location).send().await?.error_for_status()?;
+            }
+            // the body size of a HEAD response is always zero, so read the header itself
+            _ => return Ok(header(&response, "content-length")),
+        }
+    }
+    Err(format!("too many redirects requesting {}", url).into())
+}
+
+/// Read a SHA-256 digest from the `X-Linked-Etag` or `ETag` header, if either holds one.
+fn sha256_from_headers(headers: &reqwest::header::HeaderMap) -> Option<String> {
+    ["x-linked-etag", "etag"]
+        .iter()
+        .filter_map(|name| headers.get(*name)?.to_str().ok())
+        .map(|etag| {
+            etag.trim_start_matches("W/")
+                .trim_matches('"')
+                .to_ascii_lowercase()
+        })
+        .find(|etag| etag.len() == 64 && etag.chars().all(|c| c.is_ascii_hexdigit()))
+}
+
+/// A file being downloaded, removed when dropped unless it was persisted.
+struct PartFile {
+    path: PathBuf,
+    persisted: bool,
+}
+
+impl PartFile {
+    fn new(path: PathBuf) -> PartFile {
+        PartFile {
+            path,
+            persisted: false,
+        }
+    }
+
+    /// Move the file to its final path.
+    fn persist(mut self, path: &Path) -> std::io::Result<()> {
+        std::fs::rename(&self.path, path)?;
+        self.persisted = true;
+        Ok(())
+    }
+}
+
+impl Drop for PartFile {
+    fn drop(&mut self) {
+        if !self.persisted {
+            let _ = std::fs::remove_file(&self.path);
+        }
+    }
+}
+
+/// A writer computing the SHA-256 of everything written through it.
+struct ChecksumWriter<W: Write> {
+    inner: W,
+    hasher: Sha256,
+}
+
+impl<W: Write> ChecksumWriter<W> {
+    fn new(inner: W) -> ChecksumWriter<W> {
+        ChecksumWriter {
+            inner,
+            hasher: Sha256::new(),
+        }
+    }
+
+    /// Flush the inner writer and return the hex encoded digest.
+    fn finish(mut self) -> std::io::Result<String> {
+        self.inner.flush()?;
+        Ok(format!("{:x}", self.hasher.finalize()))
+    }
+}
+
+impl<W: Write> Write for ChecksumWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        self.hasher.update(&buf[..written]);
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()
     }
 }
 
@@ -86,6 +750,458 @@ mod tests {
 
     use super::*;
 
+    #[test]
+    fn test_part_file_removed_when_dropped() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("ggml-tiny.bin.part");
+        std::fs::write(&path, b"partial").unwrap();
+
+        drop(PartFile::new(path.clone()));
+
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn test_part_file_persisted() {
+        let dir = tempfile::tempdir().unwrap();
+        let part_path = dir.path().join("ggml-tiny.bin.part");
+        let model_path = dir.path().join("ggml-tiny.bin");
+        std::fs::write(&part_path, b"model").unwrap();
+
+        PartFile::new(part_path.clone())
+            .persist(&model_path)
+            .unwrap();
+
+        assert!(!part_path.exists());
+        assert_eq!(std::fs::read(&model_path).unwrap(), b"model");
+    }
+
+    #[tokio::test]
+    async fn test_part_file_removed_when_download_cancelled() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("ggml-tiny.bin.part");
+        let download = {
+            let path = path.clone();
+            async move {
+                let _part = PartFile::new(path.clone());
+                std::fs::write(&path, b"partial").unwrap();
+                std::future::pending::<()>().await;
+            }
+        };
+
+        let cancelled = tokio::time::timeout(std::time::Duration::from_millis(10), download).await;
+
+        assert!(cancelled.is_err());
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn test_checksum_writer_streaming_hash() {
+        let mut writer = ChecksumWriter::new(Vec::new());
+        writer.write_all(b"a").unwrap();
+        writer.write_all(b"bc").unwrap();
+
+        assert_eq!(
+            writer.finish().unwrap(),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+    }
+
+    #[test]
+    fn test_sha256_from_headers_prefers_linked_etag() {
+        let digest = "BA7816BF8F01CFEA414140DE5DAE2223B00361A396177A9CB410FF61F20015AD";
+        let mut headers = reqwest::header::HeaderMap::new();
+        headers.insert("etag", "\"not-a-digest\"".parse().unwrap());
+        headers.insert("x-linked-etag", format!("\"{}\"", digest).parse().unwrap());
+
+        assert_eq!(
+            sha256_from_headers(&headers),
+            Some(digest.to_ascii_lowercase())
+        );
+    }
+
+    #[test]
+    fn test_sha256_from_headers_without_digest() {
+        let mut headers = reqwest::header::HeaderMap::new();
+        headers.insert("etag", "W/\"1234-abcd\"".parse().unwrap());
+
+        assert_eq!(sha256_from_headers(&headers), None);
+    }
+
+    /// Serve `body` to one request on localhost, returning the server's URL and the request
+    /// line it receives.
+    fn serve_once(body: &'static [u8]) -> (String, std::sync::mpsc::Receiver<String>) {
+        use std::io::Read;
+
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let (sender, request_line) = std::sync::mpsc::channel();
+        std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut request = [0; 1024];
+            let read = stream.read(&mut request).unwrap();
+            let request = String::from_utf8_lossy(&request[..read]).into_owned();
+            let _ = sender.send(request.lines().next().unwrap_or_default().to_string());
+            let header = format!(
+                "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                body.len()
+            );
+            stream.write_all(header.as_bytes()).unwrap();
+            stream.write_all(body).unwrap();
+        });
+        (url, request_line)
+    }
+
+    /// Serve `files` to any number of requests on localhost, answering 404 Not Found for
+    /// other paths, returning the server's URL and the request lines it receives.
+    fn serve_files(
+        files: Vec<(&'static str, &'static [u8])>,
+    ) -> (String, std::sync::mpsc::Receiver<String>) {
+        use std::io::Read;
+
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let (sender, request_lines) = std::sync::mpsc::channel();
+        std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                let mut stream = stream.unwrap();
+                let mut request = [0; 1024];
+                let read = stream.read(&mut request).unwrap();
+                let request = String::from_utf8_lossy(&request[..read]).into_owned();
+                let line = request.lines().next().unwrap_or_default().to_string();
+                let path = line.split(' ').nth(1).unwrap_or_default().to_string();
+                let _ = sender.send(line);
+                let (status, body) = match files.iter().find(|(file, _)| *file == path) {
+                    Some((_, body)) => ("200 OK", *body),
+                    None => ("404 Not Found", &b""[..]),
+                };
+                let header = format!(
+                    "HTTP/1.1 {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                    status,
+                    body.len()
+                );
+                stream.write_all(header.as_bytes()).unwrap();
+                stream.write_all(body).unwrap();
+            }
+        });
+        (url, request_lines)
+    }
+
+    #[tokio::test]
+    async fn test_variants_fall_back_on_not_found() {
+        let dir = tempfile::tempdir().unwrap();
+        let (url, request_lines) = serve_files(vec![("/ggml-base-q5_0.bin", b"q5_0 bytes")]);
+        let builder = ModelHandler::builder()
+            .models_dir(dir.path())
+            .mirror(&url)
+            .variants(
+                "Base",
+                [
+                    ModelVariant::quantized("q5_1"),
+                    ModelVariant::quantized("q5_0")
+                        .with_sha256(&format!("{:x}", Sha256::digest(b"q5_0 bytes"))),
+                ],
+            );
+
+        let plan = builder.plan("base").unwrap();
+        assert_eq!(plan.file_name(), "ggml-base-q5_1.bin");
+        assert_eq!(
+            plan.fallback_variants(),
+            [&ModelVariant::quantized("q5_0")
+                .with_sha256(&format!("{:x}", Sha256::digest(b"q5_0 bytes")))]
+        );
+        let model = plan.execute(|_| {}).await.unwrap();
+
+        let requests: Vec<String> = request_lines.try_iter().collect();
+        assert_eq!(
+            requests,
+            [
+                "GET /ggml-base-q5_1.bin HTTP/1.1",
+                "GET /ggml-base-q5_0.bin HTTP/1.1"
+            ]
+        );
+        assert_eq!(model.get_variant().unwrap().quantization(), Some("q5_0"));
+        assert_eq!(model.model_path(), dir.path().join("ggml-base-q5_0.bin"));
+        assert_eq!(std::fs::read(model.model_path()).unwrap(), b"q5_0 bytes");
+        assert_eq!(
+            model.sha256(),
+            Some(format!("{:x}", Sha256::digest(b"q5_0 bytes")))
+        );
+
+        // the variant on disk is loaded from then on
+        let cached = builder.plan("base").unwrap();
+        assert!(cached.is_cached());
+        assert!(cached.fallback_variants().is_empty());
+        assert_eq!(cached.file_name(), "ggml-base-q5_0.bin");
+    }
+
+    #[tokio::test]
+    async fn test_variants_verify_their_own_checksum() {
+        let dir = tempfile::tempdir().unwrap();
+        let (url, _) = serve_files(vec![("/ggml-tiny-q8_0.bin", b"tampered")]);
+        let pinned = format!("{:x}", Sha256::digest(b"q8_0 bytes"));
+
+        let err = ModelHandler::builder()
+            .models_dir(dir.path())
+            .mirror(&url)
+            .variants(
+                "tiny",
+                [ModelVariant::quantized("q8_0").with_sha256(&pinned)],
+            )
+            .load("tiny")
+            .await
+            .unwrap_err();
+
+        assert!(matches!(
+            err.downcast_ref::<Error>(),
+            Some(Error::ChecksumMismatch { expected, .. }) if *expected == pinned
+        ));
+        assert!(!dir.path().join("ggml-tiny-q8_0.bin").exists());
+    }
+
+    /// A URL nothing listens on.
+    fn dead_url() -> String {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        format!("http://{}", listener.local_addr().unwrap())
+    }
+
+    #[tokio::test]
+    async fn test_builder_falls_back_to_next_mirror() {
+        let dir = tempfile::tempdir().unwrap();
+        let (url, request_line) = serve_once(b"model bytes");
+        let reported = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let builder = {
+            let reported = reported.clone();
+            ModelHandler::builder()
+                .models_dir(dir.path().to_str().unwrap())
+                .mirror(&dead_url())
+                .mirror(&format!("{}/", url))
+                .on_progress(move |progress| reported.lock().unwrap().push(progress.clone()))
+        };
+
+        let model = builder.load("tiny").await.unwrap();
+
+        assert_eq!(request_line.recv().unwrap(), "GET /ggml-tiny.bin HTTP/1.1");
+        assert_eq!(
+            std::fs::read(model.get_model_dir()).unwrap(),
+            b"model bytes"
+        );
+        let reported = reported.lock().unwrap();
+        assert_eq!(
+            reported.last(),
+            Some(&DownloadProgress {
+                file_name: "ggml-tiny.bin".to_string(),
+                downloaded: 11,
+                total: Some(11),
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn test_builder_downloads_to_long_unicode_path() {
+        let dir = tempfile::tempdir().unwrap();
+        // longer than the 260 characters Windows allows without a \\?\ prefix
+        let models_dir = (0..20).fold(dir.path().to_path_buf(), |path, i| {
+            path.join(format!("モデル ñ {}", i))
+        });
+        let (url, _) = serve_once(b"model bytes");
+
+        let model = ModelHandler::builder()
+            .models_dir(&models_dir)
+            .mirror(&url)
+            .load("tiny")
+            .await
+            .unwrap();
+
+        assert!(models_dir.as_os_str().len() > 260);
+        assert_eq!(model.model_path(), models_dir.join("ggml-tiny.bin"));
+        assert_eq!(std::fs::read(model.model_path()).unwrap(), b"model bytes");
+        assert!(!models_dir.join("ggml-tiny.bin.part").exists());
+    }
+
+    #[tokio::test]
+    async fn test_builder_loads_several_models() {
+        let dir = tempfile::tempdir().unwrap();
+        for file in ["ggml-tiny.bin", "ggml-base.bin"] {
+            std::fs::write(dir.path().join(file), b"model").unwrap();
+        }
+        let models_dir = dir.path().to_str().unwrap();
+        let builder = ModelHandler::builder()
+            .models_dir(models_dir)
+            .mirror(&dead_url());
+
+        let tiny = builder.load("tiny").await.unwrap();
+        let base = builder.load("Base").await.unwrap();
+
+        assert_eq!(
+            tiny.get_model_dir(),
+            format!("{}/ggml-tiny.bin", models_dir)
+        );
+        assert_eq!(
+            base.get_model_dir(),
+            format!("{}/ggml-base.bin", models_dir)
+        );
+        assert!(builder.load("small").await.is_err());
+        assert!(matches!(
+            builder.load("huge").await.unwrap_err().downcast_ref::<Error>(),
+            Some(Error::UnknownModel(name)) if name == "huge"
+        ));
+    }
+
+    /// A URL whose listener counts the connections made to it.
+    fn counting_url() -> (String, std::net::TcpListener) {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        listener.set_nonblocking(true).unwrap();
+        (
+            format!("http://{}", listener.local_addr().unwrap()),
+            listener,
+        )
+    }
+
+    #[tokio::test]
+    async fn test_plan_of_cached_model_touches_no_network() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("ggml-tiny.bin"), b"model").unwrap();
+        let (url, listener) = counting_url();
+        let builder = ModelHandler::builder().models_dir(dir.path()).mirror(&url);
+
+        let plan = builder.plan("Tiny").unwrap().fetch_size().await.unwrap();
+
+        assert!(plan.is_cached());
+        assert_eq!(plan.file_name(), "ggml-tiny.bin");
+        assert_eq!(plan.model_path(), dir.path().join("ggml-tiny.bin"));
+        assert_eq!(plan.source_urls(), [format!("{}/ggml-tiny.bin", url)]);
+        assert_eq!(plan.expected_size(), Some(5));
+        let model = plan
+            .execute(|_| panic!("nothing to download"))
+            .await
+            .unwrap();
+        assert_eq!(model.model_path(), dir.path().join("ggml-tiny.bin"));
+        assert!(listener.accept().is_err());
+        assert!(matches!(
+            builder.plan("huge"),
+            Err(Error::UnknownModel(name)) if name == "huge"
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_plan_defaults_and_fetch_size() {
+        let dir = tempfile::tempdir().unwrap();
+        let plan = ModelHandler::plan("large-v3", dir.path().join("models")).unwrap();
+        assert!(!plan.is_cached());
+        assert_eq!(plan.expected_size(), None);
+        assert_eq!(
+            plan.source_urls(),
+            [format!("{}/ggml-large-v3.bin", DEFAULT_MIRROR)]
+        );
+        assert!(!dir.path().join("models").exists());
+
+        let (url, request_line) = serve_once(b"model bytes");
+        let plan = ModelHandler::builder()
+            .models_dir(dir.path())
+            .mirror(&url)
+            .plan("tiny")
+            .unwrap()
+            .fetch_size()
+            .await
+            .unwrap();
+
+        assert_eq!(request_line.recv().unwrap(), "HEAD /ggml-tiny.bin HTTP/1.1");
+        assert_eq!(plan.expected_size(), Some(11));
+        assert!(!plan.model_path().exists());
+    }
+
+    #[tokio::test]
+    async fn test_execute_downloads_to_the_planned_path() {
+        let dir = tempfile::tempdir().unwrap();
+        let models_dir = dir.path().join("nested/models");
+        let (url, request_line) = serve_once(b"model bytes");
+        let plan = ModelHandler::builder()
+            .models_dir(&models_dir)
+            .mirror(&url)
+            .plan("base")
+            .unwrap();
+        let planned_path = plan.model_path();
+        let downloaded = Arc::new(std::sync::Mutex::new(0));
+
+        let model = {
+            let downloaded = downloaded.clone();
+            plan.execute(move |progress| *downloaded.lock().unwrap() = progress.downloaded)
+                .await
+                .unwrap()
+        };
+
+        assert_eq!(request_line.recv().unwrap(), "GET /ggml-base.bin HTTP/1.1");
+        assert_eq!(model.model_path(), planned_path);
+        assert_eq!(planned_path, models_dir.join("ggml-base.bin"));
+        assert_eq!(std::fs::read(&planned_path).unwrap(), b"model bytes");
+        assert_eq!(*downloaded.lock().unwrap(), 11);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_read_only_models_dir() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("ggml-tiny.bin"), b"model").unwrap();
+        std::fs::set_permissions(dir.path(), std::fs::Permissions::from_mode(0o555)).unwrap();
+        let builder = ModelHandler::builder()
+            .models_dir(dir.path().to_str().unwrap())
+            .mirror(&dead_url());
+
+        let tiny = builder.load("tiny").await;
+        // root ignores permission bits, there the directory stays writable
+        let read_only = std::fs::File::create(dir.path().join("probe")).is_err();
+        let missing = builder.load("base").await;
+        std::fs::set_permissions(dir.path(), std::fs::Permissions::from_mode(0o755)).unwrap();
+
+        let tiny = tiny.unwrap();
+        assert_eq!(tiny.is_writable(), !read_only);
+        if read_only {
+            assert!(matches!(
+                missing.unwrap_err().downcast_ref::<Error>(),
+                Some(Error::ReadOnlyModelsDir(path)) if path.ends_with("ggml-base.bin")
+            ));
+        }
+    }
+
+    #[test]
+    fn test_is_writable_for_missing_dir() {
+        let dir = tempfile::tempdir().unwrap();
+        let model = ModelHandler {
+            model_name: "ggml-tiny".to_string(),
+            models_dir: dir.path().join("a/b"),
+            variant: None,
+        };
+
+        assert!(model.is_writable());
+        assert!(!dir.path().join("a").exists());
+
+        let relative = ModelHandler {
+            model_name: "ggml-tiny".to_string(),
+            models_dir: PathBuf::from("not-created-models/"),
+            variant: None,
+        };
+        assert!(relative.is_writable());
+    }
+
+    #[test]
+    fn test_model_map_large_v3() {
+        assert_eq!(MODEL_MAP.get("large-v3"), Some(&"ggml-large-v3"));
+        assert_eq!(
+            MODEL_MAP.get("large-v3-turbo"),
+            Some(&"ggml-large-v3-turbo")
+        );
+        assert_eq!(MODEL_MAP.get("turbo"), MODEL_MAP.get("large-v3-turbo"));
+    }
+
+    #[tokio::test]
+    #[should_panic(expected = "unknown model 'huge'")]
+    async fn test_new_panics_with_the_error() {
+        ModelHandler::new("huge", "models").await;
+    }
+
     #[tokio::test]
     async fn test_check_model_exists_existent_path() {
         let path = std::path::Path::new("test_models/ggml-tiny.bin");
@@ -95,7 +1211,7 @@ mod tests {
 
         let test_model = model_handler::ModelHandler::new("tiny", "test_models/").await;
         let result = test_model.is_model_existing();
-        assert_eq!(result, true);
+        assert!(result);
     }
 
     #[tokio::test]
@@ -107,7 +1223,7 @@ mod tests {
 
         let test_model = model_handler::ModelHandler::new("tiny", "test_models/").await;
         let result = test_model.setup_directory();
-        assert_eq!(result.is_ok(), true);
+        assert!(result.is_ok());
         let _ = std::fs::remove_dir_all("test_models/");
     }
 
@@ -122,16 +1238,13 @@ mod tests {
 
         prep_test_dir();
 
-        let model_handler = model_handler::ModelHandler::new("tiny", "test_dir/").await;
+        let plan = model_handler::ModelHandler::plan("tiny", "test_dir/").unwrap();
 
-        let _result = model_handler.download_model().await;
+        let _result = plan.execute(|_| {}).await;
 
-        let is_file_existing = match std::fs::metadata("test_dir/ggml-tiny.bin") {
-            Ok(_) => true,
-            Err(_) => false,
-        };
+        let is_file_existing = std::fs::metadata("test_dir/ggml-tiny.bin").is_ok();
 
-        assert_eq!(is_file_existing, true);
+        assert!(is_file_existing);
 
         let _ = std::fs::remove_dir_all("test_dir/");
     }
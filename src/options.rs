@@ -0,0 +1,799 @@
+use crate::error::OptionViolation;
+use crate::language;
+use crate::system::{ContextInfo, STOCK_VOCAB_SIZES};
+
+/// Options controlling how the transcriber runs whisper.
+///
+/// Use `TranscribeOptions::default()` and override the fields you care about.
+///
+/// # Reproducibility
+///
+/// whisper does not expose a sampling seed. Instead it seeds the random generator of every
+/// decoder with a fixed value whenever a state is created, and the transcriber creates a
+/// fresh state for every call. Transcribing the same audio with the same model, options and
+/// thread count therefore gives identical output across runs.
+///
+/// The generator is only used when sampling at a temperature above zero: the temperature
+/// fallback on hard segments, and `best_of > 1` greedy sampling. Greedy decoding and beam
+/// search at temperature zero are deterministic regardless.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TranscribeOptions {
+    /// Suppress non-speech tokens such as "(wind blowing)" or "[music]".
+    ///
+    /// Defaults to `true`, since most users want clean speech text.
+    pub suppress_non_speech_tokens: bool,
+    /// The spoken language, as a whisper language code such as "en" or "ja". `None` lets
+    /// whisper detect it.
+    ///
+    /// Defaults to `Some("en")`.
+    pub language: Option<String>,
+    /// Transcribe files in the language their metadata declares (see
+    /// `audio_parser::AudioInfo::language`) instead of `language`, which stays the fallback
+    /// for files declaring none. A tag naming no language whisper knows falls back to
+    /// detecting the language instead, with a `Warning::InvalidLanguageMetadata`, or to
+    /// `language` on English-only models, which can't detect it. Only files are probed for
+    /// it: samples and streams always use `language`.
+    ///
+    /// Defaults to `false`.
+    pub use_metadata_language: bool,
+    /// Translate the speech to English instead of transcribing it.
+    ///
+    /// Defaults to `false`.
+    pub translate: bool,
+    /// Collect word level timestamps for every segment.
+    ///
+    /// Defaults to `false`.
+    pub word_timestamps: bool,
+    /// Collect the probability whisper gave every text token of a segment and the token's id,
+    /// see `TranscriberOutputSegment::get_token_probabilities`. Meant for calibrating
+    /// confidence scores, it adds two vectors per segment.
+    ///
+    /// Defaults to `false`.
+    pub token_probabilities: bool,
+    /// Collect up to this many hypotheses for every segment's text with their scores, see
+    /// `TranscriberOutputSegment::alternatives`. They are kept in the JSON export only.
+    ///
+    /// whisper 1.5.0 doesn't expose the candidates it discards, so every segment currently
+    /// gets a single alternative equal to its decoded text whatever the value, as long as it
+    /// is above zero.
+    ///
+    /// Defaults to `0`, collecting none.
+    pub n_best: usize,
+    /// Produce a single segment for the whole audio (or for every chunk, see `chunk_length`).
+    ///
+    /// whisper reports the end of its 30 second decoding window for that segment, so its
+    /// end timestamp is clamped to the real audio duration.
+    ///
+    /// Defaults to `false`.
+    pub single_segment: bool,
+    /// Strip the leading whitespace whisper puts in front of the first segment (and its first
+    /// word), so the transcript starts with a visible character. Spaces elsewhere are kept.
+    ///
+    /// Defaults to `false`.
+    pub strip_first_leading_space: bool,
+    /// Drop the segments whose text is empty or only whitespace, which whisper sometimes
+    /// emits, before the output is built. Their ids are recorded as dropped in the output's
+    /// `change_log`. Keep them to reconstruct whisper's output exactly.
+    ///
+    /// Defaults to `true`.
+    pub drop_empty_segments: bool,
+    /// Collapse runs of at least this many consecutive segments with the same text (ignoring
+    /// case, punctuation and spacing), the loops whisper falls into on bad audio, into one
+    /// segment spanning the run, without word timestamps or token probabilities. Every
+    /// collapsed run is flagged with a `Warning::RepetitionCollapsed` and recorded as merged
+    /// in the output's `change_log`. Below 3 repeats, phrases really said twice would be
+    /// collapsed too.
+    ///
+    /// Defaults to `None`, repetitions are kept.
+    pub collapse_repetitions: Option<usize>,
+    /// Normalize segment and word text to Unicode NFC.
+    ///
+    /// Defaults to `true`.
+    pub normalize_unicode: bool,
+    /// Convert Chinese text to a single script after transcription.
+    ///
+    /// Defaults to `None`, leaving whisper's output untouched.
+    #[cfg(feature = "chinese-conversion")]
+    pub chinese_variant: Option<ChineseVariant>,
+    /// What to do with segments whose timestamps are out of range, inverted or overlapping.
+    ///
+    /// Defaults to `TimestampPolicy::Repair`.
+    pub timestamp_policy: TimestampPolicy,
+    /// Sort segments by start time and start every segment no earlier than the previous one
+    /// ends, before `timestamp_policy` applies, see `TranscriberOutput::normalize_segments`.
+    ///
+    /// Defaults to `false`.
+    pub normalize_segments: bool,
+    /// The number of people expected to speak, a hint marking the segments after a pause
+    /// long enough to hand over as speaker turns (see
+    /// `TranscriberOutputSegment::is_speaker_turn`). The more speakers, the shorter the pause.
+    /// This is a heuristic on pauses, not diarization.
+    ///
+    /// Defaults to `None`, no turns are marked.
+    pub expected_speakers: Option<u32>,
+    /// Gate the noise of noisy recordings before inference, attenuating the parts of the
+    /// audio close to its estimated noise floor. Decoded audio is gated at its source rate,
+    /// before it is resampled.
+    ///
+    /// Defaults to `None`, the audio is left untouched.
+    pub denoise: Option<NoiseGate>,
+    /// Amplify quiet recordings by this many dB before inference. Loud moments that would
+    /// clip are bent back under full scale by a soft limiter, and how often it engaged is
+    /// reported as a `Warning::AudioLimited`. A gain of 0 dB or less only scales the audio,
+    /// bypassing the limiter. Must be finite.
+    ///
+    /// Applied after `denoise`, so the gate estimates the noise floor on the audio as given.
+    /// The audio isn't otherwise normalized, the gain is the last change to it before
+    /// inference.
+    ///
+    /// Defaults to `None`, the audio is left untouched.
+    pub gain_db: Option<f32>,
+    /// Warn with `Warning::PoorAudioQuality` when the audio's estimated signal-to-noise
+    /// ratio is below this many dB, see `quality::assess`. Estimated on the audio as given,
+    /// before `denoise`. Around 10 dB speech is hard to make out.
+    ///
+    /// Defaults to `None`, the quality isn't estimated.
+    pub min_snr_db: Option<f32>,
+    /// What to do when the audio holds no samples at all.
+    ///
+    /// Defaults to `EmptyAudioPolicy::Warn`.
+    pub empty_audio: EmptyAudioPolicy,
+    /// Audio shorter than this is padded with trailing silence before inference, since whisper
+    /// misbehaves on very short input. Timestamps are clamped back to the real audio length
+    /// and the padding is recorded as a `Warning::AudioPadded`.
+    ///
+    /// Defaults to one second.
+    #[serde(deserialize_with = "crate::config::deserialize_duration")]
+    pub min_duration: std::time::Duration,
+    /// Silence inserted between the files of `Transcriber::transcribe_concat`, so whisper
+    /// hears a boundary instead of running the last word of a file into the first of the
+    /// next. The gaps are taken out of the timestamps again. Around 500ms is enough.
+    ///
+    /// Defaults to zero, the files are joined directly.
+    #[serde(deserialize_with = "crate::config::deserialize_duration")]
+    pub concat_gap: std::time::Duration,
+    /// Fail files that aren't sampled at 16KHz with `Error::UnsupportedSampleRate` instead of
+    /// resampling them, to guarantee the audio reaches whisper exactly as recorded.
+    ///
+    /// Defaults to `false`, files at other rates are resampled.
+    pub strict_sample_rate: bool,
+    /// The longest segment whisper produces, in characters. whisper splits longer segments
+    /// using its token timestamps, which are enabled for it.
+    ///
+    /// Defaults to `None`, no limit.
+    pub max_segment_chars: Option<u32>,
+    /// With `max_segment_chars`, split segments between words instead of inside them.
+    ///
+    /// Defaults to `false`.
+    pub split_on_word: bool,
+    /// The most text tokens whisper generates for a segment before cutting it off, which
+    /// bounds the runaway repetition whisper can fall into on noisy audio.
+    ///
+    /// Defaults to `None`, whisper's own default of no limit.
+    pub max_tokens: Option<u32>,
+    /// The number of audio frames (of 20ms) whisper's encoder sees per window, `None` for
+    /// the model's full 1500 (30 seconds). Fewer frames encode faster at some accuracy
+    /// cost, mostly on speech near the end of the window.
+    ///
+    /// Defaults to `None`.
+    pub audio_ctx: Option<u32>,
+    /// Re-decode a window at higher temperatures when its text looks like a failure
+    /// (repetitive or improbable). Disabling it makes worst case windows much faster.
+    ///
+    /// Defaults to `true`.
+    pub temperature_fallback: bool,
+    /// Transcribe long audio in consecutive chunks of this length instead of in one pass.
+    ///
+    /// Defaults to `None`.
+    #[serde(deserialize_with = "crate::config::deserialize_optional_duration")]
+    pub chunk_length: Option<std::time::Duration>,
+    /// When transcribing in chunks, how far each cut between chunks may move from its
+    /// nominal position to land on the quietest 20ms of audio around it, instead of in the
+    /// middle of a word. At most half of `chunk_length`. The cuts made are reported by
+    /// `TranscriberOutput::get_chunk_boundaries`.
+    ///
+    /// Defaults to 15 seconds. Zero cuts every `chunk_length` exactly.
+    #[serde(deserialize_with = "crate::config::deserialize_duration")]
+    pub chunk_boundary_search: std::time::Duration,
+    /// When transcribing in chunks, the number of text tokens from the end of the previous
+    /// chunks used as the prompt for the next chunk, which keeps the text coherent across
+    /// chunk boundaries. `0` transcribes every chunk independently. Only used with
+    /// `ContextPolicy::Full`.
+    ///
+    /// Defaults to `64`.
+    pub context_tokens: usize,
+    /// How much previously decoded text whisper is prompted with, both between its internal
+    /// 30 second windows and between chunks.
+    ///
+    /// Defaults to `ContextPolicy::Full`.
+    pub context_policy: ContextPolicy,
+    /// The most tokens of previous text whisper may prompt a window with (whisper's
+    /// `n_max_text_ctx`), `None` keeps whisper's default. Whisper never uses more than half
+    /// of the model's 448 token text context.
+    ///
+    /// The prompt whisper is given (the carried over context of chunked transcription)
+    /// counts against the same budget: whisper keeps its last tokens and drops the rest.
+    ///
+    /// Defaults to `None`.
+    pub n_max_text_ctx: Option<u32>,
+    /// How inference is retried when whisper fails in a way a fresh state may fix.
+    ///
+    /// Defaults to a single attempt.
+    pub retry: RetryPolicy,
+    /// Annotate every segment with the index of the decode window (the chunk, in chunked
+    /// mode) it came from and the wall time spent decoding that window.
+    ///
+    /// Defaults to `false`.
+    pub segment_timing: bool,
+    /// Attach system and model information to the output (and its JSON export), useful
+    /// for bug reports.
+    ///
+    /// Defaults to `false`.
+    pub include_diagnostics: bool,
+    /// Let whisper print its realtime transcript, timestamps, special tokens and progress to
+    /// stdout while decoding. Only for debugging, since it mixes into the output of programs
+    /// writing their own to stdout.
+    ///
+    /// Defaults to `false`. Raw `FullParams` passed to `Transcriber::transcribe` keep their
+    /// own settings.
+    pub debug_print: bool,
+    /// Run even when the options are incompatible with the loaded model, instead of failing
+    /// with `Error::IncompatibleOptions`.
+    ///
+    /// Defaults to `false`.
+    pub skip_validation: bool,
+}
+
+/// How `TranscribeOptions::timestamp_policy` treats invalid segment timestamps.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub enum TimestampPolicy {
+    /// Repair the timestamps and record every repair as a `Warning::TimestampRepaired`.
+    Repair,
+    /// Fail the transcription with `Error::InvalidTimestamps`.
+    Strict,
+    /// Return the timestamps exactly as whisper produced them.
+    Keep,
+}
+
+/// The Chinese script `TranscribeOptions::chinese_variant` converts to.
+#[cfg(feature = "chinese-conversion")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub enum ChineseVariant {
+    Simplified,
+    Traditional,
+}
+
+/// How `TranscribeOptions::retry` retries inference after transient whisper failures, such as
+/// running out of memory or a flaky GPU driver.
+///
+/// Every retry runs on a fresh whisper state. Failures caused by the parameters or the audio
+/// are never retried, and the attempts are recorded as a `Warning::InferenceRetried`.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RetryPolicy {
+    /// The number of attempts, including the first one.
+    ///
+    /// Defaults to `1`, no retries.
+    pub max_attempts: u32,
+    /// Retry on the CPU when a GPU attempt fails.
+    ///
+    /// Defaults to `true`.
+    pub cpu_fallback: bool,
+    /// Halve the thread count on every retry.
+    ///
+    /// Defaults to `true`.
+    pub reduce_threads: bool,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy {
+            max_attempts: 1,
+            cpu_fallback: true,
+            reduce_threads: true,
+        }
+    }
+}
+
+/// The noise gate of `TranscribeOptions::denoise`.
+#[derive(Debug, Clone, Copy, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct NoiseGate {
+    /// How hard the noise is gated, from `0.0` (not at all) to `1.0`. Higher values gate
+    /// louder noise and attenuate it more, up to 30dB, at the risk of cutting quiet speech.
+    /// Values outside the range are clamped.
+    ///
+    /// Defaults to `0.5`.
+    pub aggressiveness: f32,
+}
+
+impl Default for NoiseGate {
+    fn default() -> Self {
+        NoiseGate {
+            aggressiveness: 0.5,
+        }
+    }
+}
+
+/// How `TranscribeOptions::empty_audio` treats audio without samples.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub enum EmptyAudioPolicy {
+    /// Return an empty transcription with a `Warning::NoAudio`.
+    Warn,
+    /// Fail with `Error::EmptyAudio`.
+    Error,
+}
+
+/// How much previously decoded text `TranscribeOptions::context_policy` carries into the
+/// next window. Carrying text keeps it coherent across windows, but a window that went wrong
+/// can derail the ones after it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub enum ContextPolicy {
+    /// Decode every window and chunk on its own.
+    None,
+    /// Carry as much as whisper keeps between its windows, and the last
+    /// `TranscribeOptions::context_tokens` tokens between chunks.
+    Full,
+    /// Carry only the last `n` tokens, between windows and between chunks.
+    LastNTokens(usize),
+}
+
+/// Which segment gives up time when two consecutive segments overlap, see
+/// `TranscriberOutput::resolve_overlaps`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Deserialize)]
+pub enum OverlapPolicy {
+    /// End the earlier segment where the later one starts.
+    TrimEarlier,
+    /// Start the later segment where the earlier one ends.
+    TrimLater,
+    /// Split the overlapping time at its midpoint.
+    SplitAtMidpoint,
+}
+
+impl Default for TranscribeOptions {
+    fn default() -> Self {
+        TranscribeOptions {
+            suppress_non_speech_tokens: true,
+            language: Some("en".to_string()),
+            use_metadata_language: false,
+            translate: false,
+            word_timestamps: false,
+            token_probabilities: false,
+            n_best: 0,
+            single_segment: false,
+            strip_first_leading_space: false,
+            drop_empty_segments: true,
+            collapse_repetitions: None,
+            normalize_unicode: true,
+            #[cfg(feature = "chinese-conversion")]
+            chinese_variant: None,
+            timestamp_policy: TimestampPolicy::Repair,
+            normalize_segments: false,
+            expected_speakers: None,
+            denoise: None,
+            gain_db: None,
+            min_snr_db: None,
+            empty_audio: EmptyAudioPolicy::Warn,
+            min_duration: std::time::Duration::from_secs(1),
+            concat_gap: std::time::Duration::ZERO,
+            strict_sample_rate: false,
+            max_segment_chars: None,
+            split_on_word: false,
+            max_tokens: None,
+            audio_ctx: None,
+            temperature_fallback: true,
+            chunk_length: None,
+            chunk_boundary_search: std::time::Duration::from_secs(15),
+            context_tokens: 64,
+            context_policy: ContextPolicy::Full,
+            n_max_text_ctx: None,
+            retry: RetryPolicy::default(),
+            segment_timing: false,
+            include_diagnostics: false,
+            debug_print: false,
+            skip_validation: false,
+        }
+    }
+}
+
+impl TranscribeOptions {
+    /// A preset for a quick, rough preview transcript, see `Transcriber::transcribe_fast`.
+    ///
+    /// Greedy decoding without temperature fallback or token timestamps, over half of the
+    /// encoder's audio context. Expect more mistakes than with the defaults, mostly in hard
+    /// to hear passages.
+    pub fn fast_preview() -> TranscribeOptions {
+        TranscribeOptions {
+            word_timestamps: false,
+            audio_ctx: Some(MAX_AUDIO_CTX / 2),
+            temperature_fallback: false,
+            ..Default::default()
+        }
+    }
+
+    /// Build the whisper parameters described by these options.
+    ///
+    /// # Returns
+    ///
+    /// * `FullParams` - greedy decoding parameters with the options applied.
+    pub fn to_full_params(&self) -> whisper_rs::FullParams<'_, '_> {
+        let mut params =
+            whisper_rs::FullParams::new(whisper_rs::SamplingStrategy::Greedy { best_of: 1 });
+        // whisper's defaults print progress and timestamps to stdout
+        params.set_print_special(self.debug_print);
+        params.set_print_progress(self.debug_print);
+        params.set_print_realtime(self.debug_print);
+        params.set_print_timestamps(self.debug_print);
+        params.set_suppress_non_speech_tokens(self.suppress_non_speech_tokens);
+        params.set_language(Some(self.language.as_deref().unwrap_or("auto")));
+        params.set_translate(self.translate);
+        params.set_token_timestamps(self.word_timestamps || self.max_segment_chars.is_some());
+        params.set_single_segment(self.single_segment);
+        if let Some(max_segment_chars) = self.max_segment_chars {
+            params.set_max_len(max_segment_chars.min(i32::MAX as u32) as i32);
+            params.set_split_on_word(self.split_on_word);
+        }
+        if let Some(max_tokens) = self.max_tokens {
+            params.set_max_tokens(max_tokens.min(i32::MAX as u32) as i32);
+        }
+        if let Some(audio_ctx) = self.audio_ctx {
+            params.set_audio_ctx(audio_ctx.min(i32::MAX as u32) as i32);
+        }
+        if !self.temperature_fallback {
+            params.set_temperature_inc(0.0);
+        }
+        let (no_context, n_max_text_ctx) = self.text_context_params();
+        params.set_no_context(no_context);
+        if let Some(n_max_text_ctx) = n_max_text_ctx {
+            params.set_n_max_text_ctx(n_max_text_ctx);
+        }
+        params
+    }
+
+    /// whisper's `no_context` and `n_max_text_ctx` parameters (`None` for whisper's default)
+    /// for the context policy.
+    pub(crate) fn text_context_params(&self) -> (bool, Option<i32>) {
+        let limit = |n: usize| n.min(i32::MAX as usize) as i32;
+        let policy_limit = match self.context_policy {
+            ContextPolicy::None => return (true, None),
+            ContextPolicy::Full => None,
+            ContextPolicy::LastNTokens(n) => Some(limit(n)),
+        };
+        let option_limit = self.n_max_text_ctx.map(|n| limit(n as usize));
+        let n_max_text_ctx = match (policy_limit, option_limit) {
+            (Some(a), Some(b)) => Some(a.min(b)),
+            (a, b) => a.or(b),
+        };
+        (false, n_max_text_ctx)
+    }
+
+    /// The number of trailing text tokens of the previous chunks to prompt a chunk with.
+    pub(crate) fn chunk_prompt_tokens(&self) -> usize {
+        match self.context_policy {
+            ContextPolicy::None => 0,
+            ContextPolicy::Full => self.context_tokens,
+            ContextPolicy::LastNTokens(n) => n,
+        }
+    }
+}
+
+/// The longest prompt whisper accepts, half of the models' 448 token text context.
+const MAX_PROMPT_TOKENS: usize = 224;
+/// The audio context of every whisper model, 30 seconds of 20ms frames.
+const MAX_AUDIO_CTX: u32 = 1500;
+
+impl TranscribeOptions {
+    /// Check these options against the capabilities of a loaded model.
+    ///
+    /// # Returns
+    ///
+    /// * `Vec<OptionViolation>` - every incompatibility found, empty when the options are valid.
+    pub fn validate(&self, context: &ContextInfo) -> Vec<OptionViolation> {
+        let mut violations = Vec::new();
+        if !context.multilingual {
+            if self.translate {
+                violations.push(OptionViolation::TranslateWithEnglishOnlyModel);
+            }
+            let language = self.language.as_deref().unwrap_or("auto");
+            if language != "en" {
+                violations.push(OptionViolation::LanguageWithEnglishOnlyModel(
+                    language.to_string(),
+                ));
+            }
+        }
+        if let Some(language) = &self.language {
+            if language != "auto" && language::language_name(language).is_none() {
+                violations.push(OptionViolation::UnknownLanguage(language.clone()));
+            }
+        }
+        if self.chunk_length.is_some() && self.chunk_prompt_tokens() > MAX_PROMPT_TOKENS {
+            violations.push(OptionViolation::ContextTokensTooLarge {
+                requested: self.chunk_prompt_tokens(),
+                max: MAX_PROMPT_TOKENS,
+            });
+        }
+        if self.chunk_length.is_some_and(|length| length.is_zero()) {
+            violations.push(OptionViolation::ZeroChunkLength);
+        }
+        if self.max_segment_chars == Some(0) {
+            violations.push(OptionViolation::ZeroMaxSegmentChars);
+        }
+        if self.max_tokens == Some(0) {
+            violations.push(OptionViolation::ZeroMaxTokens);
+        }
+        match self.audio_ctx {
+            Some(0) => violations.push(OptionViolation::ZeroAudioCtx),
+            Some(audio_ctx) if audio_ctx > MAX_AUDIO_CTX => {
+                violations.push(OptionViolation::AudioCtxTooLarge {
+                    requested: audio_ctx,
+                    max: MAX_AUDIO_CTX,
+                })
+            }
+            _ => {}
+        }
+        if self.gain_db.is_some_and(|gain_db| !gain_db.is_finite()) {
+            violations.push(OptionViolation::NonFiniteGainDb);
+        }
+        if !STOCK_VOCAB_SIZES.contains(&context.vocab_size) {
+            // the task token and the prompt's text tokens are told apart by id
+            let needs_stock = [
+                ("translate", self.translate),
+                ("chunk_length", self.chunk_length.is_some()),
+            ];
+            for (option, set) in needs_stock {
+                if set {
+                    violations.push(OptionViolation::RequiresStockVocabulary {
+                        option,
+                        vocab_size: context.vocab_size,
+                    });
+                }
+            }
+        }
+        violations
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::system::Backend;
+
+    fn context(multilingual: bool) -> ContextInfo {
+        ContextInfo {
+            model_type: "tiny".to_string(),
+            multilingual,
+            vocab_size: 51864,
+            backend: Backend::Cpu,
+        }
+    }
+
+    #[test]
+    fn test_validate_non_stock_vocabulary() {
+        let fine_tuned = ContextInfo {
+            vocab_size: 52000,
+            ..context(true)
+        };
+        let options = TranscribeOptions {
+            translate: true,
+            chunk_length: Some(std::time::Duration::from_secs(30)),
+            ..Default::default()
+        };
+
+        assert_eq!(
+            options.validate(&fine_tuned),
+            vec![
+                OptionViolation::RequiresStockVocabulary {
+                    option: "translate",
+                    vocab_size: 52000
+                },
+                OptionViolation::RequiresStockVocabulary {
+                    option: "chunk_length",
+                    vocab_size: 52000
+                },
+            ]
+        );
+        assert!(options.validate(&context(true)).is_empty());
+        assert!(TranscribeOptions::default()
+            .validate(&fine_tuned)
+            .is_empty());
+    }
+
+    #[test]
+    fn test_validate_default_options() {
+        assert!(TranscribeOptions::default()
+            .validate(&context(false))
+            .is_empty());
+        assert!(TranscribeOptions::default()
+            .validate(&context(true))
+            .is_empty());
+    }
+
+    #[test]
+    fn test_validate_english_only_model() {
+        let options = TranscribeOptions {
+            language: Some("ja".to_string()),
+            translate: true,
+            ..Default::default()
+        };
+
+        assert_eq!(
+            options.validate(&context(false)),
+            vec![
+                OptionViolation::TranslateWithEnglishOnlyModel,
+                OptionViolation::LanguageWithEnglishOnlyModel("ja".to_string()),
+            ]
+        );
+        assert!(options.validate(&context(true)).is_empty());
+    }
+
+    #[test]
+    fn test_validate_english_only_model_auto_detect() {
+        let options = TranscribeOptions {
+            language: None,
+            ..Default::default()
+        };
+
+        assert_eq!(
+            options.validate(&context(false)),
+            vec![OptionViolation::LanguageWithEnglishOnlyModel(
+                "auto".to_string()
+            )]
+        );
+    }
+
+    #[test]
+    fn test_validate_unknown_language_and_chunking() {
+        let options = TranscribeOptions {
+            language: Some("xx".to_string()),
+            chunk_length: Some(std::time::Duration::ZERO),
+            context_tokens: 500,
+            ..Default::default()
+        };
+
+        assert_eq!(
+            options.validate(&context(true)),
+            vec![
+                OptionViolation::UnknownLanguage("xx".to_string()),
+                OptionViolation::ContextTokensTooLarge {
+                    requested: 500,
+                    max: MAX_PROMPT_TOKENS
+                },
+                OptionViolation::ZeroChunkLength,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_language_violations_name_the_language() {
+        let options = TranscribeOptions {
+            language: Some("de".to_string()),
+            ..Default::default()
+        };
+
+        let violations = options.validate(&context(false));
+
+        assert_eq!(
+            violations[0].to_string(),
+            "language 'de' (German) needs a multilingual model"
+        );
+        assert!(options.validate(&context(true)).is_empty());
+    }
+
+    #[test]
+    fn test_default_suppresses_non_speech_tokens() {
+        let options = TranscribeOptions::default();
+        assert!(options.suppress_non_speech_tokens);
+    }
+
+    #[test]
+    fn test_validate_zero_limits() {
+        let capped = TranscribeOptions {
+            max_tokens: Some(32),
+            ..Default::default()
+        };
+        let zero = TranscribeOptions {
+            max_segment_chars: Some(0),
+            max_tokens: Some(0),
+            ..Default::default()
+        };
+
+        assert!(capped.validate(&context(true)).is_empty());
+        assert_eq!(
+            zero.validate(&context(true)),
+            vec![
+                OptionViolation::ZeroMaxSegmentChars,
+                OptionViolation::ZeroMaxTokens
+            ]
+        );
+    }
+
+    #[test]
+    fn test_validate_gain_db() {
+        let with = |gain_db| TranscribeOptions {
+            gain_db: Some(gain_db),
+            ..Default::default()
+        };
+
+        assert!(with(-6.0).validate(&context(true)).is_empty());
+        for gain_db in [f32::NAN, f32::INFINITY, f32::NEG_INFINITY] {
+            assert_eq!(
+                with(gain_db).validate(&context(true)),
+                vec![OptionViolation::NonFiniteGainDb]
+            );
+        }
+    }
+
+    #[test]
+    fn test_validate_audio_ctx() {
+        let with = |audio_ctx| TranscribeOptions {
+            audio_ctx,
+            ..Default::default()
+        };
+
+        assert!(TranscribeOptions::fast_preview()
+            .validate(&context(true))
+            .is_empty());
+        assert!(with(Some(1500)).validate(&context(true)).is_empty());
+        assert_eq!(
+            with(Some(0)).validate(&context(true)),
+            vec![OptionViolation::ZeroAudioCtx]
+        );
+        assert_eq!(
+            with(Some(1501)).validate(&context(true)),
+            vec![OptionViolation::AudioCtxTooLarge {
+                requested: 1501,
+                max: 1500
+            }]
+        );
+    }
+
+    #[test]
+    fn test_text_context_params() {
+        let with = |context_policy, n_max_text_ctx| TranscribeOptions {
+            context_policy,
+            n_max_text_ctx,
+            ..Default::default()
+        };
+
+        assert_eq!(
+            with(ContextPolicy::Full, None).text_context_params(),
+            (false, None)
+        );
+        assert_eq!(
+            with(ContextPolicy::Full, Some(32)).text_context_params(),
+            (false, Some(32))
+        );
+        assert_eq!(
+            with(ContextPolicy::None, Some(32)).text_context_params(),
+            (true, None)
+        );
+        assert_eq!(
+            with(ContextPolicy::LastNTokens(16), Some(32)).text_context_params(),
+            (false, Some(16))
+        );
+        assert_eq!(with(ContextPolicy::None, None).chunk_prompt_tokens(), 0);
+        assert_eq!(with(ContextPolicy::Full, None).chunk_prompt_tokens(), 64);
+        assert_eq!(
+            with(ContextPolicy::LastNTokens(8), None).chunk_prompt_tokens(),
+            8
+        );
+    }
+
+    #[test]
+    fn test_validate_chunk_prompt_from_context_policy() {
+        let options = TranscribeOptions {
+            chunk_length: Some(std::time::Duration::from_secs(30)),
+            context_policy: ContextPolicy::LastNTokens(300),
+            ..Default::default()
+        };
+
+        assert_eq!(
+            options.validate(&context(true)),
+            vec![OptionViolation::ContextTokensTooLarge {
+                requested: 300,
+                max: 224
+            }]
+        );
+    }
+}
@@ -0,0 +1,1163 @@
+use unicode_normalization::{is_nfc_quick, IsNormalized, UnicodeNormalization};
+
+use crate::options::OverlapPolicy;
+use crate::transcriber::{TranscriberOutputSegment, TranscriberOutputWord};
+use crate::warning::{TimestampIssue, Warning};
+
+/// Normalize segment and word text to Unicode NFC.
+///
+/// Words always start at a space (a starter character), so normalizing them one by one gives
+/// the same text as normalizing their concatenation, keeping words and segment text equal.
+pub(crate) fn normalize_unicode(segments: &mut [TranscriberOutputSegment]) {
+    for segment in segments {
+        normalize_nfc(&mut segment.text);
+        for word in &mut segment.words {
+            normalize_nfc(&mut word.text);
+        }
+    }
+}
+
+/// NFC normalize `text`, leaving text that already is untouched instead of copying it.
+fn normalize_nfc(text: &mut String) {
+    if is_nfc_quick(text.chars()) != IsNormalized::Yes {
+        *text = text.nfc().collect();
+    }
+}
+
+/// Convert the Han characters of segment and word text to the given Chinese script.
+///
+/// Non-Han characters and timestamps are left untouched. When the words make up the segment
+/// text, the segment text is rebuilt from the converted words so both stay equal.
+#[cfg(feature = "chinese-conversion")]
+pub(crate) fn convert_chinese(
+    segments: &mut [TranscriberOutputSegment],
+    variant: crate::options::ChineseVariant,
+) {
+    let target = match variant {
+        crate::options::ChineseVariant::Simplified => zhconv::Variant::ZhHans,
+        crate::options::ChineseVariant::Traditional => zhconv::Variant::ZhHant,
+    };
+    for segment in segments {
+        let words_match_text = !segment.words.is_empty()
+            && segment
+                .words
+                .iter()
+                .map(|w| w.text.as_str())
+                .collect::<String>()
+                == segment.text;
+        for word in &mut segment.words {
+            word.text = zhconv::zhconv(&word.text, target);
+        }
+        segment.text = if words_match_text {
+            segment.words.iter().map(|w| w.text.as_str()).collect()
+        } else {
+            zhconv::zhconv(&segment.text, target)
+        };
+    }
+}
+
+/// Strip the leading whitespace of the first segment and of its first word.
+pub(crate) fn strip_first_leading_space(segments: &mut [TranscriberOutputSegment]) {
+    if let Some(segment) = segments.first_mut() {
+        segment.text = segment.text.trim_start().to_string();
+        if let Some(word) = segment.words.first_mut() {
+            word.text = word.text.trim_start().to_string();
+        }
+    }
+}
+
+/// Report the segments whose text contains U+FFFD replacement characters.
+pub(crate) fn find_replacement_characters(segments: &[TranscriberOutputSegment]) -> Vec<Warning> {
+    segments
+        .iter()
+        .enumerate()
+        .filter_map(|(i, segment)| {
+            let count = segment.text.matches('\u{FFFD}').count();
+            (count > 0).then_some(Warning::ReplacementCharacters { segment: i, count })
+        })
+        .collect()
+}
+
+/// Repair invalid segment timestamps in place, returning every repair made.
+///
+/// Each segment is checked in order: a negative start is moved to zero, an inverted range is
+/// swapped, an end past `audio_duration` is clamped to it, and a start before the previous
+/// segment's end is moved to that end, so the earlier segment always wins an overlap.
+pub(crate) fn repair_timestamps(
+    segments: &mut [TranscriberOutputSegment],
+    audio_duration: Option<i64>,
+) -> Vec<(usize, TimestampIssue)> {
+    let mut repairs = Vec::new();
+    let mut previous_end = 0;
+    for (i, segment) in segments.iter_mut().enumerate() {
+        let (mut start, mut end) = (segment.start_timestamp, segment.end_timestamp);
+        if start < 0 {
+            repairs.push((i, TimestampIssue::NegativeStart { start }));
+            start = 0;
+        }
+        if end < start {
+            repairs.push((i, TimestampIssue::Inverted { start, end }));
+            std::mem::swap(&mut start, &mut end);
+        }
+        if let Some(audio_duration) = audio_duration {
+            if end > audio_duration {
+                repairs.push((
+                    i,
+                    TimestampIssue::EndPastAudio {
+                        end,
+                        audio_duration,
+                    },
+                ));
+                end = audio_duration;
+                start = start.min(audio_duration);
+            }
+        }
+        if start < previous_end {
+            repairs.push((
+                i,
+                TimestampIssue::Overlap {
+                    start,
+                    previous_end,
+                },
+            ));
+            start = previous_end;
+            end = end.max(start);
+        }
+        segment.start_timestamp = start;
+        segment.end_timestamp = end;
+        previous_end = end;
+    }
+    repairs
+}
+
+/// Remove the overlap between consecutive segments, see `TranscriberOutput::resolve_overlaps`.
+pub(crate) fn resolve_overlaps(segments: &mut [TranscriberOutputSegment], policy: OverlapPolicy) {
+    for i in 1..segments.len() {
+        let (before, after) = segments.split_at_mut(i);
+        let (earlier, later) = (&mut before[i - 1], &mut after[0]);
+        if later.start_timestamp >= earlier.end_timestamp {
+            continue;
+        }
+        match policy {
+            OverlapPolicy::TrimEarlier => {
+                earlier.end_timestamp = later.start_timestamp.max(earlier.start_timestamp);
+            }
+            OverlapPolicy::TrimLater => {
+                later.start_timestamp = earlier.end_timestamp;
+                later.end_timestamp = later.end_timestamp.max(later.start_timestamp);
+            }
+            OverlapPolicy::SplitAtMidpoint => {
+                let midpoint = ((later.start_timestamp + earlier.end_timestamp) / 2)
+                    .clamp(earlier.start_timestamp, later.end_timestamp);
+                earlier.end_timestamp = midpoint;
+                later.start_timestamp = midpoint;
+            }
+        }
+        clamp_words(earlier);
+        clamp_words(later);
+    }
+}
+
+/// Sort segments by start time and move overlapping starts to the previous end, see
+/// `TranscriberOutput::normalize_segments`.
+pub(crate) fn normalize_segments(segments: &mut [TranscriberOutputSegment]) {
+    sort_segments(segments);
+    resolve_overlaps(segments, OverlapPolicy::TrimLater);
+}
+
+/// Sort segments by start time, then end time, keeping the order of equal segments.
+pub(crate) fn sort_segments(segments: &mut [TranscriberOutputSegment]) {
+    segments.sort_by_key(|segment| (segment.start_timestamp, segment.end_timestamp));
+}
+
+/// Clamp segment and word end timestamps to `end`.
+pub(crate) fn clamp_ends(segments: &mut [TranscriberOutputSegment], end: i64) {
+    for segment in segments {
+        segment.start_timestamp = segment.start_timestamp.min(end);
+        segment.end_timestamp = segment.end_timestamp.min(end);
+        clamp_words(segment);
+    }
+}
+
+/// Split segments whose text does not `fit` between words, see
+/// `TranscriberOutput::split_segments`.
+///
+/// The pieces are timed by their words. Segments without word timings are split as if their
+/// words shared the segment's time in proportion to their length.
+pub(crate) fn split_segments(
+    segments: Vec<TranscriberOutputSegment>,
+    fits: impl Fn(&str) -> bool,
+) -> Vec<TranscriberOutputSegment> {
+    let mut split = Vec::with_capacity(segments.len());
+    for segment in segments {
+        if fits(&segment.text) {
+            split.push(segment);
+            continue;
+        }
+        let timed = !segment.words.is_empty();
+        let words = if timed {
+            segment.words.clone()
+        } else {
+            proportional_words(&segment)
+        };
+        let mut pieces: Vec<Vec<TranscriberOutputWord>> = Vec::new();
+        let mut piece: Vec<TranscriberOutputWord> = Vec::new();
+        for word in words {
+            let text: String = piece
+                .iter()
+                .chain(std::iter::once(&word))
+                .map(|w| w.text.as_str())
+                .collect();
+            if !piece.is_empty() && !fits(&text) {
+                pieces.push(std::mem::take(&mut piece));
+            }
+            piece.push(word);
+        }
+        pieces.push(piece);
+
+        let last = pieces.len() - 1;
+        for (i, piece) in pieces.into_iter().enumerate() {
+            let start = if i == 0 {
+                segment.start_timestamp
+            } else {
+                piece[0].start_timestamp
+            };
+            let end = if i == last {
+                segment.end_timestamp
+            } else {
+                piece[piece.len() - 1].end_timestamp
+            };
+            let mut new_segment = TranscriberOutputSegment {
+                start_timestamp: start,
+                end_timestamp: end,
+                text: piece.iter().map(|w| w.text.as_str()).collect(),
+                words: Vec::new(),
+                token_probabilities: Vec::new(),
+                token_ids: Vec::new(),
+                alternatives: Vec::new(),
+                speaker_turn: segment.speaker_turn && i == 0,
+                ..segment.clone()
+            };
+            if timed {
+                new_segment.words = piece;
+            }
+            split.push(new_segment);
+        }
+    }
+    split
+}
+
+/// The words of a segment, sharing its time in proportion to their length.
+fn proportional_words(segment: &TranscriberOutputSegment) -> Vec<TranscriberOutputWord> {
+    let mut texts: Vec<String> = Vec::new();
+    for (i, part) in segment.text.split(' ').enumerate() {
+        if i == 0 {
+            if !part.is_empty() {
+                texts.push(part.to_string());
+            }
+            continue;
+        }
+        match texts.last_mut() {
+            Some(last) if part.is_empty() => last.push(' '),
+            _ => texts.push(format!(" {}", part)),
+        }
+    }
+    let total = texts
+        .iter()
+        .map(|t| t.chars().count())
+        .sum::<usize>()
+        .max(1) as i64;
+    let duration = (segment.end_timestamp - segment.start_timestamp).max(0);
+    let mut elapsed = 0;
+    texts
+        .into_iter()
+        .map(|text| {
+            let start = segment.start_timestamp + duration * elapsed / total;
+            elapsed += text.chars().count() as i64;
+            let end = segment.start_timestamp + duration * elapsed / total;
+            TranscriberOutputWord::new(start, end, text)
+        })
+        .collect()
+}
+
+/// Move segments and their words `offset` centiseconds later.
+pub(crate) fn shift_segments(segments: &mut [TranscriberOutputSegment], offset: i64) {
+    for segment in segments {
+        segment.start_timestamp += offset;
+        segment.end_timestamp += offset;
+        for word in &mut segment.words {
+            word.start_timestamp += offset;
+            word.end_timestamp += offset;
+        }
+    }
+}
+
+/// Replace the segments overlapping `start..end` (in centiseconds) with `replacement`, timed
+/// from `start`, see `TranscriberOutput::splice`.
+///
+/// Segments straddling an edge keep their words outside the range, timed by their word
+/// timings or, without them, by sharing the segment's time in proportion to word length.
+pub(crate) fn splice(
+    segments: Vec<TranscriberOutputSegment>,
+    start: i64,
+    end: i64,
+    mut replacement: Vec<TranscriberOutputSegment>,
+) -> Vec<TranscriberOutputSegment> {
+    let mut spliced = Vec::with_capacity(segments.len() + replacement.len());
+    for segment in segments {
+        if segment.end_timestamp <= start || segment.start_timestamp >= end {
+            spliced.push(segment);
+            continue;
+        }
+        let timed = !segment.words.is_empty();
+        let words = if timed {
+            segment.words.clone()
+        } else {
+            proportional_words(&segment)
+        };
+        let before: Vec<TranscriberOutputWord> = words
+            .iter()
+            .filter(|word| word.end_timestamp <= start)
+            .cloned()
+            .collect();
+        let after: Vec<TranscriberOutputWord> = words
+            .into_iter()
+            .filter(|word| word.start_timestamp >= end)
+            .collect();
+        if segment.start_timestamp < start && !before.is_empty() {
+            let end = before[before.len() - 1].end_timestamp;
+            spliced.push(piece(&segment, before, segment.start_timestamp, end, timed));
+        }
+        if segment.end_timestamp > end && !after.is_empty() {
+            let start = after[0].start_timestamp;
+            let mut piece = piece(&segment, after, start, segment.end_timestamp, timed);
+            piece.speaker_turn = false;
+            spliced.push(piece);
+        }
+    }
+
+    shift_segments(&mut replacement, start);
+    for segment in &mut replacement {
+        segment.start_timestamp = segment.start_timestamp.clamp(start, end);
+        segment.end_timestamp = segment.end_timestamp.clamp(start, end);
+        clamp_words(segment);
+    }
+    spliced.extend(replacement);
+    sort_segments(&mut spliced);
+    spliced
+}
+
+/// The part of `segment` made of `words`, timed from `start` to `end`.
+fn piece(
+    segment: &TranscriberOutputSegment,
+    words: Vec<TranscriberOutputWord>,
+    start: i64,
+    end: i64,
+    timed: bool,
+) -> TranscriberOutputSegment {
+    TranscriberOutputSegment {
+        start_timestamp: start,
+        end_timestamp: end,
+        text: words.iter().map(|w| w.text.as_str()).collect(),
+        words: if timed { words } else { Vec::new() },
+        token_probabilities: Vec::new(),
+        token_ids: Vec::new(),
+        alternatives: Vec::new(),
+        ..segment.clone()
+    }
+}
+
+/// Merge consecutive segments at most `max_gap` centiseconds apart while the merged text
+/// still `fits`, see `TranscriberOutput::merge_segments`. Speaker turns are never merged
+/// into the segment before them, nor are segments from different sources.
+pub(crate) fn merge_segments(
+    segments: Vec<TranscriberOutputSegment>,
+    max_gap: i64,
+    fits: impl Fn(&str) -> bool,
+) -> Vec<TranscriberOutputSegment> {
+    let mut merged: Vec<TranscriberOutputSegment> = Vec::with_capacity(segments.len());
+    for segment in segments {
+        if let Some(previous) = merged.last_mut() {
+            let separator = if previous.text.ends_with(char::is_whitespace)
+                || segment.text.starts_with(char::is_whitespace)
+            {
+                ""
+            } else {
+                " "
+            };
+            let text = format!("{}{}{}", previous.text, separator, segment.text);
+            if segment.start_timestamp - previous.end_timestamp <= max_gap
+                && !segment.speaker_turn
+                && segment.source == previous.source
+                && fits(&text)
+            {
+                previous.text = text;
+                previous.end_timestamp = previous.end_timestamp.max(segment.end_timestamp);
+                previous.words.extend(segment.words);
+                previous
+                    .token_probabilities
+                    .extend(segment.token_probabilities);
+                previous.token_ids.extend(segment.token_ids);
+                // the hypotheses of either segment don't cover the merged text
+                previous.alternatives.clear();
+                continue;
+            }
+        }
+        merged.push(segment);
+    }
+    merged
+}
+
+/// Collapse every run of at least `min_repeats` consecutive segments with the same text, the
+/// loops whisper falls into on bad audio, into the run's first segment stretched over the
+/// whole run. Texts are compared ignoring case, punctuation and spacing, and segments from
+/// different sources never form a run. The words and tokens of the first repeat don't
+/// describe the stretched segment, so it has none, like split segments. Returns the index
+/// of every collapsed segment with the ids of the segments it was made of.
+pub(crate) fn collapse_repetitions(
+    segments: &mut Vec<TranscriberOutputSegment>,
+    min_repeats: usize,
+) -> Vec<(usize, Vec<u64>)> {
+    let mut collapsed = Vec::new();
+    let mut kept = Vec::with_capacity(segments.len());
+    let mut remaining = std::mem::take(segments).into_iter().peekable();
+    while let Some(first) = remaining.next() {
+        let key = repetition_key(&first.text);
+        let mut run = vec![first];
+        while let Some(next) = remaining
+            .next_if(|next| next.source == run[0].source && repetition_key(&next.text) == key)
+        {
+            run.push(next);
+        }
+        if run.len() < min_repeats.max(2) {
+            kept.extend(run);
+            continue;
+        }
+        let ids = run.iter().map(|segment| segment.id).collect();
+        let end = run.iter().map(|segment| segment.end_timestamp).max();
+        let mut segment = run.swap_remove(0);
+        segment.end_timestamp = end.unwrap_or(segment.end_timestamp);
+        segment.words.clear();
+        segment.token_probabilities.clear();
+        segment.token_ids.clear();
+        collapsed.push((kept.len(), ids));
+        kept.push(segment);
+    }
+    *segments = kept;
+    collapsed
+}
+
+/// The text of a segment as compared by `collapse_repetitions`: its letters and digits,
+/// lowercased. Text without any is compared as it is, so loops of `♪` still collapse.
+fn repetition_key(text: &str) -> String {
+    let key: String = text
+        .chars()
+        .filter(|c| c.is_alphanumeric())
+        .flat_map(char::to_lowercase)
+        .collect();
+    if key.is_empty() {
+        text.trim().to_string()
+    } else {
+        key
+    }
+}
+
+/// Whether `text` ends a sentence: its last character, past closing quotes and brackets, is
+/// sentence-final punctuation of a Latin, CJK, Arabic or Devanagari script.
+pub(crate) fn ends_sentence(text: &str) -> bool {
+    const CLOSING: &[char] = &['"', '\'', '”', '’', '»', ')', ']', '」', '』', '）'];
+    const FINAL: &[char] = &['.', '!', '?', '…', '。', '！', '？', '؟', '।'];
+    text.trim_end().trim_end_matches(CLOSING).ends_with(FINAL)
+}
+
+/// The shortest pause, in centiseconds, taken as a speaker turn among `expected_speakers`.
+///
+/// A single speaker only pauses this long between thoughts, while speakers of a panel hand
+/// over faster the more of them there are.
+pub(crate) fn speaker_turn_gap(expected_speakers: u32) -> i64 {
+    (200 / i64::from(expected_speakers.max(1))).max(30)
+}
+
+/// Mark the segments starting at least `min_gap` centiseconds after every earlier segment
+/// ended as speaker turns, see `TranscriberOutput::mark_speaker_turns`.
+pub(crate) fn mark_speaker_turns(segments: &mut [TranscriberOutputSegment], min_gap: i64) {
+    let mut previous_end: Option<i64> = None;
+    for segment in segments {
+        segment.speaker_turn =
+            previous_end.is_some_and(|end| segment.start_timestamp - end >= min_gap);
+        previous_end =
+            Some(previous_end.map_or(segment.end_timestamp, |end| end.max(segment.end_timestamp)));
+    }
+}
+
+/// Lengthen segments read faster than `max_chars_per_second` into the silence around them,
+/// see `TranscriberOutput::extend_for_reading_speed`.
+pub(crate) fn extend_for_reading_speed(
+    segments: &mut [TranscriberOutputSegment],
+    max_chars_per_second: f32,
+    audio_duration: Option<i64>,
+) {
+    for i in 0..segments.len() {
+        let chars = segments[i].text.trim().chars().count() as f32;
+        let needed = (chars / max_chars_per_second * 100.0).ceil() as i64;
+        let duration = segments[i].end_timestamp - segments[i].start_timestamp;
+        let mut missing = needed - duration;
+        if missing <= 0 {
+            continue;
+        }
+        let limit = match segments.get(i + 1) {
+            Some(next) => next.start_timestamp,
+            None => audio_duration.unwrap_or(i64::MAX),
+        };
+        let later = missing.min(limit - segments[i].end_timestamp).max(0);
+        segments[i].end_timestamp += later;
+        missing -= later;
+
+        let limit = if i == 0 {
+            0
+        } else {
+            segments[i - 1].end_timestamp
+        };
+        let earlier = missing.min(segments[i].start_timestamp - limit).max(0);
+        segments[i].start_timestamp -= earlier;
+    }
+}
+
+/// Clamp the word timings of a segment into the segment's range.
+fn clamp_words(segment: &mut TranscriberOutputSegment) {
+    let (start, end) = (segment.start_timestamp, segment.end_timestamp);
+    for word in &mut segment.words {
+        word.start_timestamp = word.start_timestamp.clamp(start, end);
+        word.end_timestamp = word.end_timestamp.clamp(start, end);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transcriber::TranscriberOutputWord;
+
+    fn turns(segments: &[TranscriberOutputSegment]) -> Vec<bool> {
+        segments.iter().map(|s| s.speaker_turn).collect()
+    }
+
+    #[test]
+    fn test_ends_sentence() {
+        for text in [
+            " Done.",
+            " Really?! ",
+            " He said \"stop.\"",
+            "好了。",
+            " (wait…)",
+        ] {
+            assert!(ends_sentence(text), "{:?}", text);
+        }
+        for text in ["", " and then", " Mr", " 3.5 percent", " so,"] {
+            assert!(!ends_sentence(text), "{:?}", text);
+        }
+    }
+
+    #[test]
+    fn test_more_speakers_mark_shorter_pauses_as_turns() {
+        // pauses of 0.2s, 0.6s and 2.5s
+        let mut segments = vec![
+            TranscriberOutputSegment::new(0, 100, " One."),
+            TranscriberOutputSegment::new(120, 200, " Two."),
+            TranscriberOutputSegment::new(260, 400, " Three."),
+            TranscriberOutputSegment::new(650, 800, " Four."),
+        ];
+
+        mark_speaker_turns(&mut segments, speaker_turn_gap(1));
+        assert_eq!(turns(&segments), vec![false, false, false, true]);
+
+        mark_speaker_turns(&mut segments, speaker_turn_gap(4));
+        assert_eq!(turns(&segments), vec![false, false, true, true]);
+
+        assert_eq!(speaker_turn_gap(0), speaker_turn_gap(1));
+        assert_eq!(speaker_turn_gap(100), 30);
+    }
+
+    #[test]
+    fn test_speaker_turns_survive_split_and_merge() {
+        let mut turn = TranscriberOutputSegment::new(300, 400, " Yes. Indeed.");
+        turn.speaker_turn = true;
+        let segments = vec![TranscriberOutputSegment::new(0, 100, " Hi."), turn];
+
+        let merged = merge_segments(segments, 500, |_| true);
+        assert_eq!(turns(&merged), vec![false, true]);
+
+        let split = split_segments(merged, |text| text.len() <= 6);
+        assert_eq!(turns(&split), vec![false, true, false]);
+    }
+
+    #[test]
+    fn test_normalize_unicode_composes_decomposed_text() {
+        let mut segments = vec![
+            TranscriberOutputSegment::new(0, 100, " Cafe\u{301} cre\u{300}me").with_words(vec![
+                TranscriberOutputWord::new(0, 50, " Cafe\u{301}"),
+                TranscriberOutputWord::new(50, 100, " cre\u{300}me"),
+            ]),
+        ];
+
+        normalize_unicode(&mut segments);
+
+        assert_eq!(segments[0].text, " Caf\u{e9} cr\u{e8}me");
+        let words: String = segments[0].words.iter().map(|w| w.text.as_str()).collect();
+        assert_eq!(words, segments[0].text);
+    }
+
+    #[test]
+    fn test_normalize_unicode_keeps_composed_text() {
+        let mut segments = vec![TranscriberOutputSegment::new(0, 100, " Caf\u{e9}")];
+
+        normalize_unicode(&mut segments);
+
+        assert_eq!(segments[0].text, " Caf\u{e9}");
+    }
+
+    #[cfg(feature = "chinese-conversion")]
+    #[test]
+    fn test_convert_chinese_to_traditional() {
+        let mut segments = vec![TranscriberOutputSegment::new(120, 340, " 软件測試 OK 123")
+            .with_words(vec![
+                TranscriberOutputWord::new(120, 200, " 软件"),
+                TranscriberOutputWord::new(200, 300, "測試"),
+                TranscriberOutputWord::new(300, 340, " OK 123"),
+            ])];
+
+        convert_chinese(&mut segments, crate::options::ChineseVariant::Traditional);
+
+        assert_eq!(segments[0].text, " 軟件測試 OK 123");
+        assert_eq!(segments[0].words[0].text, " 軟件");
+        assert_eq!(segments[0].start_timestamp, 120);
+        assert_eq!(segments[0].end_timestamp, 340);
+    }
+
+    #[cfg(feature = "chinese-conversion")]
+    #[test]
+    fn test_convert_chinese_to_simplified() {
+        let mut segments = vec![TranscriberOutputSegment::new(0, 100, " 軟件测试, done.")];
+
+        convert_chinese(&mut segments, crate::options::ChineseVariant::Simplified);
+
+        assert_eq!(segments[0].text, " 软件测试, done.");
+    }
+
+    #[test]
+    fn test_find_replacement_characters() {
+        let segments = vec![
+            TranscriberOutputSegment::new(0, 100, " fine"),
+            TranscriberOutputSegment::new(100, 200, " bro\u{FFFD}ken \u{FFFD}"),
+        ];
+
+        assert_eq!(
+            find_replacement_characters(&segments),
+            vec![Warning::ReplacementCharacters {
+                segment: 1,
+                count: 2
+            }]
+        );
+    }
+
+    fn timestamps(segments: &[TranscriberOutputSegment]) -> Vec<(i64, i64)> {
+        segments
+            .iter()
+            .map(|s| (s.start_timestamp, s.end_timestamp))
+            .collect()
+    }
+
+    #[test]
+    fn test_repair_timestamps_valid_segments_untouched() {
+        let mut segments = vec![
+            TranscriberOutputSegment::new(0, 100, " a"),
+            TranscriberOutputSegment::new(100, 200, " b"),
+        ];
+
+        assert!(repair_timestamps(&mut segments, Some(200)).is_empty());
+        assert_eq!(timestamps(&segments), vec![(0, 100), (100, 200)]);
+    }
+
+    #[test]
+    fn test_repair_timestamps_negative_start() {
+        let mut segments = vec![TranscriberOutputSegment::new(-5, 100, " a")];
+
+        let repairs = repair_timestamps(&mut segments, None);
+
+        assert_eq!(
+            repairs,
+            vec![(0, TimestampIssue::NegativeStart { start: -5 })]
+        );
+        assert_eq!(timestamps(&segments), vec![(0, 100)]);
+    }
+
+    #[test]
+    fn test_repair_timestamps_inverted() {
+        let mut segments = vec![TranscriberOutputSegment::new(150, 100, " a")];
+
+        let repairs = repair_timestamps(&mut segments, None);
+
+        assert_eq!(
+            repairs,
+            vec![(
+                0,
+                TimestampIssue::Inverted {
+                    start: 150,
+                    end: 100
+                }
+            )]
+        );
+        assert_eq!(timestamps(&segments), vec![(100, 150)]);
+    }
+
+    #[test]
+    fn test_repair_timestamps_end_past_audio() {
+        let mut segments = vec![
+            TranscriberOutputSegment::new(0, 100, " a"),
+            TranscriberOutputSegment::new(100, 300, " b"),
+            TranscriberOutputSegment::new(260, 280, " c"),
+        ];
+
+        let repairs = repair_timestamps(&mut segments, Some(250));
+
+        assert_eq!(
+            repairs,
+            vec![
+                (
+                    1,
+                    TimestampIssue::EndPastAudio {
+                        end: 300,
+                        audio_duration: 250
+                    }
+                ),
+                (
+                    2,
+                    TimestampIssue::EndPastAudio {
+                        end: 280,
+                        audio_duration: 250
+                    }
+                ),
+            ]
+        );
+        assert_eq!(
+            timestamps(&segments),
+            vec![(0, 100), (100, 250), (250, 250)]
+        );
+    }
+
+    #[test]
+    fn test_repair_timestamps_overlap() {
+        let mut segments = vec![
+            TranscriberOutputSegment::new(0, 120, " a"),
+            TranscriberOutputSegment::new(100, 200, " b"),
+            TranscriberOutputSegment::new(150, 180, " c"),
+        ];
+
+        let repairs = repair_timestamps(&mut segments, None);
+
+        assert_eq!(
+            repairs,
+            vec![
+                (
+                    1,
+                    TimestampIssue::Overlap {
+                        start: 100,
+                        previous_end: 120
+                    }
+                ),
+                (
+                    2,
+                    TimestampIssue::Overlap {
+                        start: 150,
+                        previous_end: 200
+                    }
+                ),
+            ]
+        );
+        assert_eq!(
+            timestamps(&segments),
+            vec![(0, 120), (120, 200), (200, 200)]
+        );
+    }
+
+    #[test]
+    fn test_repair_timestamps_multiple_issues_on_one_segment() {
+        let mut segments = vec![
+            TranscriberOutputSegment::new(0, 100, " a"),
+            TranscriberOutputSegment::new(400, 50, " b"),
+        ];
+
+        let repairs = repair_timestamps(&mut segments, Some(300));
+
+        assert_eq!(
+            repairs,
+            vec![
+                (
+                    1,
+                    TimestampIssue::Inverted {
+                        start: 400,
+                        end: 50
+                    }
+                ),
+                (
+                    1,
+                    TimestampIssue::EndPastAudio {
+                        end: 400,
+                        audio_duration: 300
+                    }
+                ),
+                (
+                    1,
+                    TimestampIssue::Overlap {
+                        start: 50,
+                        previous_end: 100
+                    }
+                ),
+            ]
+        );
+        assert_eq!(timestamps(&segments), vec![(0, 100), (100, 300)]);
+    }
+
+    #[test]
+    fn test_strip_first_leading_space() {
+        let mut segments = vec![
+            TranscriberOutputSegment::new(0, 100, " Hello there").with_words(vec![
+                TranscriberOutputWord::new(0, 50, " Hello"),
+                TranscriberOutputWord::new(50, 100, " there"),
+            ]),
+            TranscriberOutputSegment::new(100, 200, " again"),
+        ];
+
+        strip_first_leading_space(&mut segments);
+
+        assert_eq!(segments[0].text, "Hello there");
+        assert_eq!(segments[0].words[0].text, "Hello");
+        assert_eq!(segments[0].words[1].text, " there");
+        assert_eq!(segments[1].text, " again");
+    }
+
+    #[test]
+    fn test_clamp_ends_single_segment_window() {
+        let mut segments = vec![
+            TranscriberOutputSegment::new(0, 3000, " a b").with_words(vec![
+                TranscriberOutputWord::new(0, 200, " a"),
+                TranscriberOutputWord::new(200, 3000, " b"),
+            ]),
+        ];
+
+        clamp_ends(&mut segments, 400);
+
+        assert_eq!(timestamps(&segments), vec![(0, 400)]);
+        assert_eq!(word_timestamps(&segments[0]), vec![(0, 200), (200, 400)]);
+    }
+
+    fn overlapping_segments() -> Vec<TranscriberOutputSegment> {
+        vec![
+            TranscriberOutputSegment::new(0, 120, " a b").with_words(vec![
+                TranscriberOutputWord::new(0, 60, " a"),
+                TranscriberOutputWord::new(60, 120, " b"),
+            ]),
+            TranscriberOutputSegment::new(100, 200, " c d").with_words(vec![
+                TranscriberOutputWord::new(100, 150, " c"),
+                TranscriberOutputWord::new(150, 200, " d"),
+            ]),
+            TranscriberOutputSegment::new(200, 300, " e"),
+        ]
+    }
+
+    fn word_timestamps(segment: &TranscriberOutputSegment) -> Vec<(i64, i64)> {
+        segment
+            .words
+            .iter()
+            .map(|w| (w.start_timestamp, w.end_timestamp))
+            .collect()
+    }
+
+    #[test]
+    fn test_resolve_overlaps_trim_earlier() {
+        let mut segments = overlapping_segments();
+
+        resolve_overlaps(&mut segments, OverlapPolicy::TrimEarlier);
+
+        assert_eq!(
+            timestamps(&segments),
+            vec![(0, 100), (100, 200), (200, 300)]
+        );
+        assert_eq!(word_timestamps(&segments[0]), vec![(0, 60), (60, 100)]);
+        assert_eq!(word_timestamps(&segments[1]), vec![(100, 150), (150, 200)]);
+    }
+
+    #[test]
+    fn test_resolve_overlaps_trim_later() {
+        let mut segments = overlapping_segments();
+
+        resolve_overlaps(&mut segments, OverlapPolicy::TrimLater);
+
+        assert_eq!(
+            timestamps(&segments),
+            vec![(0, 120), (120, 200), (200, 300)]
+        );
+        assert_eq!(word_timestamps(&segments[0]), vec![(0, 60), (60, 120)]);
+        assert_eq!(word_timestamps(&segments[1]), vec![(120, 150), (150, 200)]);
+    }
+
+    #[test]
+    fn test_resolve_overlaps_split_at_midpoint() {
+        let mut segments = overlapping_segments();
+
+        resolve_overlaps(&mut segments, OverlapPolicy::SplitAtMidpoint);
+
+        assert_eq!(
+            timestamps(&segments),
+            vec![(0, 110), (110, 200), (200, 300)]
+        );
+        assert_eq!(word_timestamps(&segments[0]), vec![(0, 60), (60, 110)]);
+        assert_eq!(word_timestamps(&segments[1]), vec![(110, 150), (150, 200)]);
+    }
+
+    #[test]
+    fn test_resolve_overlaps_swallowed_segment() {
+        let mut segments = vec![
+            TranscriberOutputSegment::new(0, 300, " a"),
+            TranscriberOutputSegment::new(100, 200, " b"),
+        ];
+
+        resolve_overlaps(&mut segments, OverlapPolicy::TrimLater);
+
+        assert_eq!(timestamps(&segments), vec![(0, 300), (300, 300)]);
+    }
+
+    #[test]
+    fn test_normalize_segments_sorts_and_removes_overlaps() {
+        let mut segments = vec![
+            TranscriberOutputSegment::new(300, 400, " c"),
+            TranscriberOutputSegment::new(0, 250, " a"),
+            TranscriberOutputSegment::new(200, 280, " b"),
+            TranscriberOutputSegment::new(350, 380, " d"),
+        ];
+
+        normalize_segments(&mut segments);
+
+        assert_eq!(texts(&segments), vec![" a", " b", " c", " d"]);
+        assert_eq!(
+            timestamps(&segments),
+            vec![(0, 250), (250, 280), (300, 400), (400, 400)]
+        );
+        for pair in segments.windows(2) {
+            assert!(pair[1].start_timestamp >= pair[0].end_timestamp);
+        }
+    }
+
+    fn texts(segments: &[TranscriberOutputSegment]) -> Vec<&str> {
+        segments.iter().map(|s| s.text.as_str()).collect()
+    }
+
+    fn short(text: &str) -> bool {
+        text.trim().chars().count() <= 12
+    }
+
+    #[test]
+    fn test_split_segments_by_words() {
+        let segments = vec![
+            TranscriberOutputSegment::new(0, 300, " Hello there, my friend.").with_words(vec![
+                TranscriberOutputWord::new(0, 50, " Hello"),
+                TranscriberOutputWord::new(60, 120, " there,"),
+                TranscriberOutputWord::new(150, 200, " my"),
+                TranscriberOutputWord::new(200, 280, " friend."),
+            ]),
+            TranscriberOutputSegment::new(300, 400, " Bye."),
+        ];
+
+        let split = split_segments(segments, short);
+
+        assert_eq!(texts(&split), vec![" Hello there,", " my friend.", " Bye."]);
+        assert_eq!((split[0].start_timestamp, split[0].end_timestamp), (0, 120));
+        assert_eq!(
+            (split[1].start_timestamp, split[1].end_timestamp),
+            (150, 300)
+        );
+        assert_eq!(split[1].words.len(), 2);
+    }
+
+    #[test]
+    fn test_split_segments_without_words() {
+        let segments = vec![TranscriberOutputSegment::new(
+            0,
+            200,
+            " abcd efgh ijkl mnop",
+        )];
+
+        let split = split_segments(segments, |text| text.trim().chars().count() <= 10);
+
+        assert_eq!(texts(&split), vec![" abcd efgh", " ijkl mnop"]);
+        assert_eq!(split[0].start_timestamp, 0);
+        assert_eq!(split[0].end_timestamp, split[1].start_timestamp);
+        assert_eq!(split[1].end_timestamp, 200);
+        assert!(split.iter().all(|s| s.words.is_empty()));
+    }
+
+    fn spans(segments: &[TranscriberOutputSegment]) -> Vec<(i64, i64)> {
+        segments
+            .iter()
+            .map(|s| (s.start_timestamp, s.end_timestamp))
+            .collect()
+    }
+
+    #[test]
+    fn test_splice_splits_straddling_segments_by_words() {
+        let segments = vec![
+            TranscriberOutputSegment::new(0, 300, " One two three.").with_words(vec![
+                TranscriberOutputWord::new(0, 90, " One"),
+                TranscriberOutputWord::new(100, 190, " two"),
+                TranscriberOutputWord::new(210, 300, " three."),
+            ]),
+            TranscriberOutputSegment::new(300, 500, " Gone."),
+            TranscriberOutputSegment::new(500, 800, " Four five six.").with_words(vec![
+                TranscriberOutputWord::new(500, 590, " Four"),
+                TranscriberOutputWord::new(610, 700, " five"),
+                TranscriberOutputWord::new(700, 800, " six."),
+            ]),
+            TranscriberOutputSegment::new(800, 900, " Seven."),
+        ];
+        let replacement = vec![
+            TranscriberOutputSegment::new(0, 250, " New words").with_words(vec![
+                TranscriberOutputWord::new(0, 100, " New"),
+                TranscriberOutputWord::new(120, 250, " words"),
+            ]),
+            TranscriberOutputSegment::new(250, 420, " past the end"),
+        ];
+
+        let spliced = splice(segments, 200, 600, replacement);
+
+        assert_eq!(
+            texts(&spliced),
+            vec![
+                " One two",
+                " New words",
+                " past the end",
+                " five six.",
+                " Seven."
+            ]
+        );
+        assert_eq!(
+            spans(&spliced),
+            vec![(0, 190), (200, 450), (450, 600), (610, 800), (800, 900)]
+        );
+        assert_eq!(spliced[1].words[1].start_timestamp, 320);
+        assert_eq!(spliced[3].words.len(), 2);
+        assert!(spliced
+            .windows(2)
+            .all(|pair| pair[0].end_timestamp <= pair[1].start_timestamp));
+    }
+
+    #[test]
+    fn test_splice_without_words() {
+        let segments = vec![
+            TranscriberOutputSegment::new(0, 400, " abcd efgh ijkl mnop"),
+            TranscriberOutputSegment::new(400, 500, " qrst"),
+        ];
+
+        let spliced = splice(
+            segments,
+            150,
+            250,
+            vec![TranscriberOutputSegment::new(0, 100, " new")],
+        );
+
+        assert_eq!(texts(&spliced), vec![" abcd", " new", " mnop", " qrst"]);
+        assert_eq!(
+            spans(&spliced),
+            vec![(0, 100), (150, 250), (300, 400), (400, 500)]
+        );
+        assert!(spliced[0].words.is_empty());
+
+        // a segment covering the whole range keeps both of its ends
+        let covering = vec![TranscriberOutputSegment::new(
+            0,
+            400,
+            " abcd efgh ijkl mnop",
+        )];
+        let spliced = splice(covering, 100, 300, Vec::new());
+        assert_eq!(texts(&spliced), vec![" abcd", " mnop"]);
+    }
+
+    #[test]
+    fn test_merge_segments() {
+        let segments = vec![
+            TranscriberOutputSegment::new(0, 100, " One."),
+            TranscriberOutputSegment::new(120, 200, " Two."),
+            TranscriberOutputSegment::new(500, 600, " Three."),
+            TranscriberOutputSegment::new(600, 700, " Fourteen."),
+        ];
+
+        let merged = merge_segments(segments, 50, short);
+
+        assert_eq!(texts(&merged), vec![" One. Two.", " Three.", " Fourteen."]);
+        assert_eq!(
+            (merged[0].start_timestamp, merged[0].end_timestamp),
+            (0, 200)
+        );
+    }
+
+    #[test]
+    fn test_collapse_repetitions() {
+        let mut segments: Vec<TranscriberOutputSegment> = [
+            (0, 100, " Hello."),
+            (100, 200, " Thank you."),
+            (200, 300, " thank you"),
+            (300, 400, " Thank you!"),
+            (400, 500, " No."),
+            (500, 600, " No."),
+            (600, 700, " ♪"),
+            (700, 800, " ♪"),
+            (800, 900, " ♪"),
+        ]
+        .into_iter()
+        .enumerate()
+        .map(|(id, (start, end, text))| TranscriberOutputSegment {
+            id: id as u64,
+            words: vec![TranscriberOutputWord::new(start, end, text)],
+            token_probabilities: vec![0.9],
+            token_ids: vec![id as i32],
+            ..TranscriberOutputSegment::new(start, end, text)
+        })
+        .collect();
+
+        let collapsed = collapse_repetitions(&mut segments, 3);
+
+        assert_eq!(
+            texts(&segments),
+            vec![" Hello.", " Thank you.", " No.", " No.", " ♪"]
+        );
+        assert_eq!(collapsed, vec![(1, vec![1, 2, 3]), (4, vec![6, 7, 8])]);
+        assert_eq!(
+            (segments[1].start_timestamp, segments[1].end_timestamp),
+            (100, 400)
+        );
+        assert!(segments[1].words.is_empty());
+        assert!(segments[1].token_probabilities.is_empty() && segments[1].token_ids.is_empty());
+        assert_eq!(segments[0].words.len(), 1);
+        // a phrase said twice is no loop unless asked
+        collapse_repetitions(&mut segments, 2);
+        assert_eq!(
+            texts(&segments),
+            vec![" Hello.", " Thank you.", " No.", " ♪"]
+        );
+    }
+
+    #[test]
+    fn test_extend_for_reading_speed() {
+        let mut segments = vec![
+            TranscriberOutputSegment::new(100, 150, " Ten chars."),
+            TranscriberOutputSegment::new(200, 220, " Ten chars."),
+            TranscriberOutputSegment::new(400, 600, " Ten chars."),
+        ];
+
+        extend_for_reading_speed(&mut segments, 10.0, Some(650));
+
+        let timestamps: Vec<(i64, i64)> = segments
+            .iter()
+            .map(|s| (s.start_timestamp, s.end_timestamp))
+            .collect();
+        assert_eq!(timestamps, vec![(100, 200), (200, 300), (400, 600)]);
+    }
+}
@@ -0,0 +1,679 @@
+//! Checking that the environment is ready to transcribe, without loading a model or running
+//! inference, so users can tell what is wrong (and attach the report to bug reports). See
+//! `preflight`.
+
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use sha2::{Digest, Sha256};
+
+use crate::audio_parser::{self, WHISPER_SAMPLE_RATE};
+use crate::backend;
+use crate::memory;
+use crate::model_handler::{self, ModelHandler};
+use crate::system::{self, SystemInfo, SUPPORTED_N_MELS};
+
+const MIB: u64 = 1024 * 1024;
+
+/// What `preflight` checks.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PreflightConfig {
+    /// The model name, as given to `ModelHandler::new`, such as "tiny".
+    pub model: String,
+    pub models_dir: PathBuf,
+    /// An audio file to check the decoder reads, only its headers are probed.
+    pub sample_file: Option<PathBuf>,
+}
+
+impl PreflightConfig {
+    /// Check the model called `model` in `models_dir`, without a sample file.
+    pub fn new(model: &str, models_dir: impl AsRef<Path>) -> PreflightConfig {
+        PreflightConfig {
+            model: model.to_string(),
+            models_dir: models_dir.as_ref().to_path_buf(),
+            sample_file: None,
+        }
+    }
+
+    /// Also check that the audio file at `path` can be decoded.
+    pub fn sample_file(mut self, path: impl AsRef<Path>) -> PreflightConfig {
+        self.sample_file = Some(path.as_ref().to_path_buf());
+        self
+    }
+}
+
+/// The outcome of one check of a `PreflightReport`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize)]
+pub enum CheckStatus {
+    Ok,
+    /// Transcribing may work, but slowly, or not in every setup.
+    Warning,
+    /// Transcribing won't work until it is fixed.
+    Failed,
+    /// The check couldn't run, or had nothing to check.
+    Skipped,
+}
+
+/// One check of a `PreflightReport`.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+pub struct PreflightCheck {
+    /// What was checked: "model", "models_dir", "memory", "cpu", "gpu" or "sample_file".
+    pub name: &'static str,
+    pub status: CheckStatus,
+    /// What was found, and for problems how to fix them.
+    pub detail: String,
+}
+
+/// The result of `preflight`, printable for users and serializable for bug reports.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+pub struct PreflightReport {
+    pub checks: Vec<PreflightCheck>,
+    pub system: SystemInfo,
+}
+
+impl PreflightReport {
+    /// Whether no check failed.
+    pub fn is_ready(&self) -> bool {
+        self.checks
+            .iter()
+            .all(|check| check.status != CheckStatus::Failed)
+    }
+
+    /// The check called `name`.
+    pub fn check(&self, name: &str) -> Option<&PreflightCheck> {
+        self.checks.iter().find(|check| check.name == name)
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).expect("a preflight report always serializes")
+    }
+}
+
+impl fmt::Display for PreflightReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for check in &self.checks {
+            let status = match check.status {
+                CheckStatus::Ok => "ok",
+                CheckStatus::Warning => "warn",
+                CheckStatus::Failed => "FAIL",
+                CheckStatus::Skipped => "skip",
+            };
+            writeln!(f, "[{:>4}] {:<11} {}", status, check.name, check.detail)?;
+        }
+        let failed = self
+            .checks
+            .iter()
+            .filter(|check| check.status == CheckStatus::Failed)
+            .count();
+        match failed {
+            0 => write!(f, "ready to transcribe"),
+            failed => write!(f, "not ready: {} check(s) failed", failed),
+        }
+    }
+}
+
+/// Check everything a transcription with `config` needs, without loading the model or
+/// running inference: the model file, its header and checksum, the models directory, the
+/// memory the model needs against the memory available, the CPU features and GPU backends
+/// whisper was built with, and that the sample file, if any, can be decoded.
+///
+/// A model downloaded by the crate is hashed and compared to the checksum recorded then,
+/// which reads the whole file. Other model files are compared to the size of the stock
+/// model instead.
+pub fn preflight(config: &PreflightConfig) -> PreflightReport {
+    let system = system::system_info();
+    let model_bytes = model_file(config)
+        .ok()
+        .flatten()
+        .map(|path| file_size(&path));
+    let checks = vec![
+        check_model(config),
+        check_models_dir(&config.models_dir),
+        check_memory(&config.model, model_bytes, available_memory()),
+        check_cpu(&system),
+        check_gpu(&system),
+        check_sample_file(config.sample_file.as_deref()),
+    ];
+    PreflightReport { checks, system }
+}
+
+fn check(name: &'static str, status: CheckStatus, detail: String) -> PreflightCheck {
+    PreflightCheck {
+        name,
+        status,
+        detail,
+    }
+}
+
+/// The path of the model file, `None` when it isn't downloaded yet.
+fn model_file(config: &PreflightConfig) -> Result<Option<PathBuf>, String> {
+    let plan = ModelHandler::plan(&config.model, &config.models_dir).map_err(|err| {
+        format!(
+            "{}, known models are tiny, base, small, medium, large, large-v3 and turbo",
+            err
+        )
+    })?;
+    Ok(plan.is_cached().then(|| plan.model_path()))
+}
+
+fn file_size(path: &Path) -> u64 {
+    std::fs::metadata(path).map_or(0, |metadata| metadata.len())
+}
+
+/// The approximate size of the stock (full precision) model files, in bytes.
+fn stock_model_bytes(model: &str) -> Option<u64> {
+    let model = model.to_lowercase();
+    let mib = match model.trim_end_matches(".en") {
+        "tiny" => 75,
+        "base" => 142,
+        "small" => 466,
+        "medium" => 1533,
+        "large" | "large-v3" => 2951,
+        "large-v3-turbo" | "turbo" => 1549,
+        _ => return None,
+    };
+    Some(mib * MIB)
+}
+
+/// The hex encoded SHA-256 of the file at `path`.
+fn file_sha256(path: &Path) -> std::io::Result<String> {
+    let mut hasher = Sha256::new();
+    std::io::copy(&mut std::fs::File::open(path)?, &mut hasher)?;
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+fn check_model(config: &PreflightConfig) -> PreflightCheck {
+    let path = match model_file(config) {
+        Ok(Some(path)) => path,
+        Ok(None) => {
+            let plan = ModelHandler::plan(&config.model, &config.models_dir)
+                .expect("the model name was resolved");
+            let (status, action) = if model_handler::is_dir_writable(&config.models_dir) {
+                (CheckStatus::Warning, "it is downloaded on first use from")
+            } else {
+                (
+                    CheckStatus::Failed,
+                    "the directory is read-only, so it can't be downloaded from",
+                )
+            };
+            return check(
+                "model",
+                status,
+                format!(
+                    "{} is missing, {} {}",
+                    plan.model_path().display(),
+                    action,
+                    plan.source_urls()[0]
+                ),
+            );
+        }
+        Err(err) => return check("model", CheckStatus::Failed, err),
+    };
+    if let Err(err) = std::fs::File::open(&path) {
+        return check(
+            "model",
+            CheckStatus::Failed,
+            format!("{} can't be read: {}", path.display(), err),
+        );
+    }
+    let header = match backend::read_ggml_header(&path) {
+        Some(header) => header,
+        None => {
+            return check(
+                "model",
+                CheckStatus::Failed,
+                format!(
+                    "{} is not a ggml model file, delete it to download it again",
+                    path.display()
+                ),
+            )
+        }
+    };
+    if !SUPPORTED_N_MELS.contains(&header.n_mels) || header.n_mels != header.filterbank_n_mels {
+        return check(
+            "model",
+            CheckStatus::Failed,
+            format!(
+                "{} takes {} mel bands with a {} band filterbank, reconvert or download it again",
+                path.display(),
+                header.n_mels,
+                header.filterbank_n_mels
+            ),
+        );
+    }
+    let size = file_size(&path);
+    let plan =
+        ModelHandler::plan(&config.model, &config.models_dir).expect("the model name was resolved");
+    match plan.recorded_sha256() {
+        Some(recorded) => match file_sha256(&path) {
+            Ok(sha256) if sha256 == recorded => {}
+            Ok(_) => {
+                return check(
+                    "model",
+                    CheckStatus::Failed,
+                    format!(
+                        "{} changed since it was downloaded, its SHA-256 doesn't match, delete \
+                         it to download it again",
+                        path.display()
+                    ),
+                )
+            }
+            Err(err) => {
+                return check(
+                    "model",
+                    CheckStatus::Failed,
+                    format!("{} can't be read: {}", path.display(), err),
+                )
+            }
+        },
+        None => match stock_model_bytes(&config.model) {
+            // quantized models are smaller, but not this much
+            Some(stock) if size < stock / 4 => {
+                return check(
+                    "model",
+                    CheckStatus::Warning,
+                    format!(
+                        "{} is {} MiB where the stock model is about {} MiB, it may be \
+                         truncated, delete it to download it again",
+                        path.display(),
+                        size / MIB,
+                        stock / MIB
+                    ),
+                )
+            }
+            _ => {}
+        },
+    }
+    check(
+        "model",
+        CheckStatus::Ok,
+        format!(
+            "{} ({} MiB, {} mel bands)",
+            path.display(),
+            size / MIB,
+            header.n_mels
+        ),
+    )
+}
+
+fn check_models_dir(models_dir: &Path) -> PreflightCheck {
+    let writable = model_handler::is_dir_writable(models_dir);
+    if !models_dir.exists() {
+        return if writable {
+            check(
+                "models_dir",
+                CheckStatus::Ok,
+                format!("{} is created on first download", models_dir.display()),
+            )
+        } else {
+            check(
+                "models_dir",
+                CheckStatus::Failed,
+                format!(
+                    "{} doesn't exist and can't be created",
+                    models_dir.display()
+                ),
+            )
+        };
+    }
+    if let Err(err) = std::fs::read_dir(models_dir) {
+        return check(
+            "models_dir",
+            CheckStatus::Failed,
+            format!("{} can't be read: {}", models_dir.display(), err),
+        );
+    }
+    if writable {
+        check(
+            "models_dir",
+            CheckStatus::Ok,
+            format!("{} is readable and writable", models_dir.display()),
+        )
+    } else {
+        check(
+            "models_dir",
+            CheckStatus::Warning,
+            format!(
+                "{} is read-only, models in it load but no more can be downloaded",
+                models_dir.display()
+            ),
+        )
+    }
+}
+
+/// Compare the memory the weights (`model_bytes`, when downloaded, or else the size of the
+/// stock model) and one inference state take to the `available` memory.
+fn check_memory(model: &str, model_bytes: Option<u64>, available: Option<u64>) -> PreflightCheck {
+    // the model type whisper reports, large-v3 and turbo are large models
+    let model_type = model.to_lowercase();
+    let model_type = model_type.split('-').next().unwrap_or_default();
+    let weights_bytes = model_bytes.or_else(|| stock_model_bytes(model));
+    let needed = weights_bytes.unwrap_or_default() + memory::estimate_state_bytes(model_type);
+    let weights = match (model_bytes, weights_bytes) {
+        (Some(_), _) => "with the weights",
+        (None, Some(_)) => "with the stock model's weights",
+        (None, None) => "without the weights",
+    };
+    match available {
+        None => check(
+            "memory",
+            CheckStatus::Skipped,
+            format!(
+                "about {} MiB needed ({}), the available memory is unknown on this \
+                 platform",
+                needed / MIB,
+                weights
+            ),
+        ),
+        Some(available) if available < needed => check(
+            "memory",
+            CheckStatus::Warning,
+            format!(
+                "about {} MiB needed ({}) but only {} MiB available",
+                needed / MIB,
+                weights,
+                available / MIB
+            ),
+        ),
+        Some(available) => check(
+            "memory",
+            CheckStatus::Ok,
+            format!(
+                "about {} MiB needed ({}), {} MiB available",
+                needed / MIB,
+                weights,
+                available / MIB
+            ),
+        ),
+    }
+}
+
+/// The memory available to start new programs, as Linux reports it in `/proc/meminfo`.
+fn available_memory() -> Option<u64> {
+    let meminfo = std::fs::read_to_string("/proc/meminfo").ok()?;
+    let line = meminfo
+        .lines()
+        .find(|line| line.starts_with("MemAvailable:"))?;
+    let kib: u64 = line.split_whitespace().nth(1)?.parse().ok()?;
+    Some(kib * 1024)
+}
+
+fn check_cpu(system: &SystemInfo) -> PreflightCheck {
+    let features = [
+        ("AVX", system.avx),
+        ("AVX2", system.avx2),
+        ("AVX512", system.avx512),
+        ("FMA", system.fma),
+        ("F16C", system.f16c),
+        ("NEON", system.neon),
+    ];
+    let enabled: Vec<&str> = features
+        .iter()
+        .filter(|(_, enabled)| *enabled)
+        .map(|(name, _)| *name)
+        .collect();
+    let missing = system.unsupported_cpu_features();
+    if !missing.is_empty() {
+        return check(
+            "cpu",
+            CheckStatus::Failed,
+            format!(
+                "whisper was built with {} but this CPU lacks it, inference would crash, \
+                 rebuild without it",
+                missing.join(", ")
+            ),
+        );
+    }
+    if !system.avx2 && !system.neon {
+        return check(
+            "cpu",
+            CheckStatus::Warning,
+            format!(
+                "whisper was built without AVX2 or NEON ({}), inference will be slow",
+                if enabled.is_empty() {
+                    "no SIMD at all".to_string()
+                } else {
+                    enabled.join(", ")
+                }
+            ),
+        );
+    }
+    check("cpu", CheckStatus::Ok, enabled.join(", "))
+}
+
+fn check_gpu(system: &SystemInfo) -> PreflightCheck {
+    let backends: Vec<&str> = [
+        ("CUDA", system.cuda),
+        ("Metal", system.metal),
+        ("BLAS", system.blas),
+        ("Core ML", system.coreml),
+        ("OpenVINO", system.openvino),
+    ]
+    .iter()
+    .filter(|(_, enabled)| *enabled)
+    .map(|(name, _)| *name)
+    .collect();
+    let detail = if system.has_gpu() {
+        backends.join(", ")
+    } else if backends.is_empty() {
+        "no GPU backend compiled in, inference runs on the CPU".to_string()
+    } else {
+        format!(
+            "no GPU backend compiled in ({} only), inference runs on the CPU",
+            backends.join(", ")
+        )
+    };
+    check("gpu", CheckStatus::Ok, detail)
+}
+
+fn check_sample_file(sample_file: Option<&Path>) -> PreflightCheck {
+    let path = match sample_file {
+        Some(path) => path,
+        None => {
+            return check(
+                "sample_file",
+                CheckStatus::Skipped,
+                "no sample file given".to_string(),
+            )
+        }
+    };
+    let info = match audio_parser::try_probe_audio_info(path) {
+        Ok(info) => info,
+        Err(err) => {
+            return check(
+                "sample_file",
+                CheckStatus::Failed,
+                format!("{} can't be decoded: {}", path.display(), err),
+            )
+        }
+    };
+    let channels = info.channels.unwrap_or(1);
+    if channels > 2 {
+        return check(
+            "sample_file",
+            CheckStatus::Failed,
+            format!(
+                "{} has {} channels, convert it with {}",
+                path.display(),
+                channels,
+                audio_parser::ffmpeg_command(WHISPER_SAMPLE_RATE)
+            ),
+        );
+    }
+    let resampled = match info.sample_rate {
+        Some(sample_rate) if sample_rate != WHISPER_SAMPLE_RATE => {
+            format!(", resampled from {}Hz to 16KHz", sample_rate)
+        }
+        _ => String::new(),
+    };
+    check(
+        "sample_file",
+        CheckStatus::Ok,
+        format!(
+            "{} decodes, {} channel(s){}",
+            path.display(),
+            channels,
+            resampled
+        ),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util;
+
+    #[test]
+    fn test_preflight_missing_model() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = PreflightConfig::new("tiny", dir.path().join("models"));
+
+        let report = preflight(&config);
+
+        let model = report.check("model").unwrap();
+        assert_eq!(model.status, CheckStatus::Warning);
+        assert!(
+            model.detail.contains("ggml-tiny.bin is missing"),
+            "{}",
+            model.detail
+        );
+        assert!(model.detail.contains("huggingface.co"), "{}", model.detail);
+        assert_eq!(report.check("models_dir").unwrap().status, CheckStatus::Ok);
+        assert_eq!(
+            report.check("sample_file").unwrap().status,
+            CheckStatus::Skipped
+        );
+        assert!(!dir.path().join("models").exists());
+
+        let unknown = preflight(&PreflightConfig::new("huge", dir.path()));
+        assert_eq!(unknown.check("model").unwrap().status, CheckStatus::Failed);
+        assert!(!unknown.is_ready());
+    }
+
+    #[test]
+    fn test_preflight_model_integrity() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("ggml-base.bin");
+        let config = PreflightConfig::new("base", dir.path());
+
+        std::fs::write(&path, b"<html>rate limited</html> and then some").unwrap();
+        let model = preflight(&config).check("model").unwrap().clone();
+        assert_eq!(model.status, CheckStatus::Failed);
+        assert!(
+            model.detail.contains("not a ggml model"),
+            "{}",
+            model.detail
+        );
+
+        let mut header = 0x6767_6d6cu32.to_le_bytes().to_vec();
+        for value in [51865, 1500, 512, 8, 6, 448, 512, 8, 6, 80, 1, 80, 201] {
+            header.extend((value as u32).to_le_bytes());
+        }
+        std::fs::write(&path, &header).unwrap();
+        let model = preflight(&config).check("model").unwrap().clone();
+        assert_eq!(model.status, CheckStatus::Warning, "{}", model.detail);
+        assert!(
+            model.detail.contains("may be truncated"),
+            "{}",
+            model.detail
+        );
+
+        // the checksum recorded at download
+        let sha256_path = dir.path().join("ggml-base.bin.sha256");
+        std::fs::write(&sha256_path, format!("{:x}", Sha256::digest(&header))).unwrap();
+        let model = preflight(&config).check("model").unwrap().clone();
+        assert_eq!(model.status, CheckStatus::Ok, "{}", model.detail);
+        assert!(model.detail.contains("80 mel bands"));
+
+        std::fs::write(&sha256_path, format!("{:x}", Sha256::digest(b"other"))).unwrap();
+        let model = preflight(&config).check("model").unwrap().clone();
+        assert_eq!(model.status, CheckStatus::Failed);
+        assert!(
+            model.detail.contains("SHA-256 doesn't match"),
+            "{}",
+            model.detail
+        );
+    }
+
+    #[test]
+    fn test_preflight_unreadable_sample_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let garbage = dir.path().join("clip.mp3");
+        std::fs::write(&garbage, b"definitely not audio").unwrap();
+        let missing = dir.path().join("missing.wav");
+        let config = |sample: &Path| PreflightConfig::new("tiny", dir.path()).sample_file(sample);
+
+        for sample in [&garbage, &missing] {
+            let report = preflight(&config(sample));
+            let check = report.check("sample_file").unwrap();
+            assert_eq!(check.status, CheckStatus::Failed);
+            assert!(
+                check.detail.contains("can't be decoded"),
+                "{}",
+                check.detail
+            );
+            assert!(!report.is_ready());
+        }
+
+        let wav = dir.path().join("clip.wav");
+        test_util::write_wav(&wav, &test_util::silence(std::time::Duration::from_secs(1)));
+        let check = preflight(&config(&wav))
+            .check("sample_file")
+            .unwrap()
+            .clone();
+        assert_eq!(check.status, CheckStatus::Ok, "{}", check.detail);
+
+        let wav_8k = dir.path().join("clip-8k.wav");
+        test_util::write_pcm_wav(&wav_8k, 8000, 1, &[0; 8000]);
+        let check = preflight(&config(&wav_8k))
+            .check("sample_file")
+            .unwrap()
+            .clone();
+        assert_eq!(check.status, CheckStatus::Ok, "{}", check.detail);
+        assert!(
+            check.detail.contains("resampled from 8000Hz"),
+            "{}",
+            check.detail
+        );
+    }
+
+    #[test]
+    fn test_check_memory() {
+        assert_eq!(
+            check_memory("tiny", Some(75 * MIB), Some(8192 * MIB)).status,
+            CheckStatus::Ok
+        );
+        let low = check_memory("large-v3", None, Some(2048 * MIB));
+        assert_eq!(low.status, CheckStatus::Warning);
+        assert!(
+            low.detail
+                .contains("about 3975 MiB needed (with the stock model's weights)"),
+            "{}",
+            low.detail
+        );
+        assert!(check_memory("huge", None, None)
+            .detail
+            .contains("(without the weights)"));
+        assert_eq!(
+            check_memory("tiny", None, None).status,
+            CheckStatus::Skipped
+        );
+    }
+
+    #[test]
+    fn test_report_display_and_json() {
+        let dir = tempfile::tempdir().unwrap();
+        let report = preflight(&PreflightConfig::new("huge", dir.path()));
+
+        let text = report.to_string();
+        assert!(
+            text.contains("[FAIL] model       unknown model 'huge'"),
+            "{}",
+            text
+        );
+        assert!(text.ends_with("not ready: 1 check(s) failed"), "{}", text);
+        let json: serde_json::Value = serde_json::from_str(&report.to_json()).unwrap();
+        assert_eq!(json["checks"][0]["name"], "model");
+        assert_eq!(json["checks"][0]["status"], "Failed");
+    }
+}
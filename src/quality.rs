@@ -0,0 +1,112 @@
+//! A cheap estimate of how clean a recording is, to warn about noisy input before its
+//! transcript turns out poor, see `TranscribeOptions::min_snr_db`.
+//!
+//! The audio is band-passed to the speech band (300 to 3400 Hz) and cut into the frames the
+//! noise gate uses. The signal-to-noise ratio compares the level of the loudest frames,
+//! taken to hold speech, to the noise floor of the quietest ones. It is a rough figure: a
+//! recording of steady noise without pauses rates low whatever is said over it.
+
+use std::f32::consts::PI;
+
+use crate::audio_parser::WHISPER_SAMPLE_RATE;
+use crate::denoise::{frame_levels, percentile, NOISE_PERCENTILE};
+
+/// The share of frames louder than the level taken as the speech level.
+const SPEECH_PERCENTILE: f32 = 0.9;
+/// The ratio reported when the noise floor is digital silence.
+const MAX_SNR_DB: f32 = 60.0;
+/// The ratio at and above which the quality score is 1.
+const CLEAN_SNR_DB: f32 = 30.0;
+
+/// The estimated quality of a recording's audio.
+#[derive(Debug, Clone, Copy, PartialEq, serde::Serialize)]
+pub struct AudioQuality {
+    /// The estimated signal-to-noise ratio of the speech band in dB, at most 60.
+    pub snr_db: f32,
+}
+
+impl AudioQuality {
+    /// The quality between 0 and 1, scaling the signal-to-noise ratio linearly from 0 dB
+    /// (speech as loud as the noise) to 30 dB (clean speech).
+    pub fn score(&self) -> f32 {
+        (self.snr_db / CLEAN_SNR_DB).clamp(0.0, 1.0)
+    }
+}
+
+/// Estimate the quality of 16KHz mono `audio`, `None` when it is empty or silent.
+pub fn assess(audio: &[f32]) -> Option<AudioQuality> {
+    if audio.is_empty() {
+        return None;
+    }
+    let levels = frame_levels(&speech_band(audio), WHISPER_SAMPLE_RATE);
+    let speech = percentile(&levels, SPEECH_PERCENTILE);
+    let noise = percentile(&levels, NOISE_PERCENTILE);
+    if speech == 0.0 {
+        return None;
+    }
+    let snr_db = if noise == 0.0 {
+        MAX_SNR_DB
+    } else {
+        (20.0 * (speech / noise).log10()).min(MAX_SNR_DB)
+    };
+    Some(AudioQuality { snr_db })
+}
+
+/// `audio` through a first order high-pass at 300 Hz and low-pass at 3400 Hz.
+fn speech_band(audio: &[f32]) -> Vec<f32> {
+    let dt = 1.0 / WHISPER_SAMPLE_RATE as f32;
+    let high_rc = 1.0 / (2.0 * PI * 300.0);
+    let low_rc = 1.0 / (2.0 * PI * 3400.0);
+    let high_alpha = high_rc / (high_rc + dt);
+    let low_alpha = dt / (low_rc + dt);
+
+    let mut filtered = Vec::with_capacity(audio.len());
+    let (mut previous_in, mut high, mut low) = (audio[0], 0.0, 0.0);
+    for &sample in audio {
+        high = high_alpha * (high + sample - previous_in);
+        previous_in = sample;
+        low += low_alpha * (high - low);
+        filtered.push(low);
+    }
+    filtered
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::test_util;
+
+    /// A second of noise, a second of a 1 kHz tone at `level` over it, a second of noise.
+    fn recording(level: f32) -> Vec<f32> {
+        let mut audio = test_util::noise(Duration::from_secs(3), 0.01);
+        let tone = test_util::tone(1000.0, Duration::from_secs(1), level);
+        for (sample, tone) in audio[16000..32000].iter_mut().zip(tone) {
+            *sample += tone;
+        }
+        audio
+    }
+
+    #[test]
+    fn test_snr_tracks_the_signal_level() {
+        let clean = assess(&recording(0.5)).unwrap();
+        let noisy = assess(&recording(0.005)).unwrap();
+
+        assert!(clean.snr_db > 25.0, "{:?}", clean);
+        assert!(noisy.snr_db < 10.0, "{:?}", noisy);
+        assert!(clean.score() > noisy.score());
+        assert!((0.0..=1.0).contains(&noisy.score()));
+    }
+
+    #[test]
+    fn test_assess_edge_cases() {
+        assert_eq!(assess(&[]), None);
+        assert_eq!(assess(&[0.0; 16000]), None);
+
+        // speech between digitally silent pauses
+        let mut audio = vec![0.0; 48000];
+        audio[16000..32000].copy_from_slice(&test_util::tone(1000.0, Duration::from_secs(1), 0.5));
+        assert_eq!(assess(&audio).map(|q| q.snr_db), Some(MAX_SNR_DB));
+    }
+}
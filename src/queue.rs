@@ -0,0 +1,591 @@
+//! A bounded, prioritized queue of transcription jobs run by worker threads sharing
+//! transcribers, for services transcribing many clips. See `TranscriptionQueue`.
+
+use std::future::Future;
+use std::panic::{self, AssertUnwindSafe};
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::task::{Context, Poll, Waker};
+use std::thread::JoinHandle;
+
+use crate::audio_parser;
+use crate::batch::panic_message;
+use crate::error::Error;
+use crate::options::TranscribeOptions;
+use crate::transcriber::{Preprocess, Transcriber, TranscriberOutput};
+
+/// The audio of a `Job`.
+#[derive(Debug, Clone)]
+pub enum JobInput {
+    File(PathBuf),
+    /// An encoded audio file, with its MIME type as a hint for the container format.
+    Bytes {
+        data: Vec<u8>,
+        mime_type: Option<String>,
+    },
+    /// 16KHz mono samples.
+    Samples(Vec<f32>),
+}
+
+/// A transcription to run on a `TranscriptionQueue`.
+#[derive(Debug, Clone)]
+pub struct Job {
+    pub input: JobInput,
+    pub options: TranscribeOptions,
+    /// Jobs with a higher priority run first, jobs of equal priority in submission order.
+    ///
+    /// Defaults to `0`.
+    pub priority: i32,
+}
+
+impl Job {
+    /// A job transcribing `input` with the default options.
+    pub fn new(input: JobInput) -> Job {
+        Job {
+            input,
+            options: TranscribeOptions::default(),
+            priority: 0,
+        }
+    }
+
+    /// A job transcribing the audio file at `path`.
+    pub fn file(path: impl AsRef<Path>) -> Job {
+        Job::new(JobInput::File(path.as_ref().to_path_buf()))
+    }
+
+    /// A job transcribing an encoded audio file held in memory.
+    pub fn bytes(data: Vec<u8>, mime_type: Option<&str>) -> Job {
+        Job::new(JobInput::Bytes {
+            data,
+            mime_type: mime_type.map(str::to_string),
+        })
+    }
+
+    /// A job transcribing 16KHz mono samples.
+    pub fn samples(samples: Vec<f32>) -> Job {
+        Job::new(JobInput::Samples(samples))
+    }
+
+    pub fn with_options(mut self, options: TranscribeOptions) -> Job {
+        self.options = options;
+        self
+    }
+
+    pub fn with_priority(mut self, priority: i32) -> Job {
+        self.priority = priority;
+        self
+    }
+}
+
+/// What `TranscriptionQueue::submit` does when the queue is full.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FullQueuePolicy {
+    /// Fail the submission with `Error::QueueFull`.
+    Reject,
+    /// Wait for a queued job to start or be cancelled.
+    Block,
+}
+
+/// How a `TranscriptionQueue` runs its jobs.
+#[derive(Debug, Clone)]
+pub struct QueueOptions {
+    /// The number of worker threads, each running one job at a time. Raised to the number
+    /// of transcribers of a pool.
+    ///
+    /// Defaults to `1`.
+    pub workers: usize,
+    /// The most jobs waiting to start, running jobs not included.
+    ///
+    /// Defaults to `256`.
+    pub capacity: usize,
+    /// Defaults to `FullQueuePolicy::Block`.
+    pub when_full: FullQueuePolicy,
+}
+
+impl Default for QueueOptions {
+    fn default() -> Self {
+        QueueOptions {
+            workers: 1,
+            capacity: 256,
+            when_full: FullQueuePolicy::Block,
+        }
+    }
+}
+
+/// Where a job is in its life.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum JobStatus {
+    Queued,
+    Running,
+    /// The job ran, successfully or not.
+    Finished,
+    /// The job was cancelled before it started.
+    Cancelled,
+}
+
+/// Worker threads running transcription jobs, highest priority first, on shared
+/// transcribers.
+///
+/// Dropping the queue cancels the jobs that haven't started and waits for the running ones.
+pub struct TranscriptionQueue {
+    shared: Arc<Shared>,
+    workers: Vec<JoinHandle<()>>,
+}
+
+impl TranscriptionQueue {
+    /// A queue whose workers all share `transcriber`, inference states are per job.
+    pub fn new(transcriber: Arc<Transcriber>, options: QueueOptions) -> TranscriptionQueue {
+        TranscriptionQueue::with_pool(vec![transcriber], options)
+    }
+
+    /// A queue whose workers are spread over `transcribers`, such as one per GPU.
+    ///
+    /// # Panics
+    ///
+    /// When `transcribers` is empty.
+    pub fn with_pool(
+        transcribers: Vec<Arc<Transcriber>>,
+        options: QueueOptions,
+    ) -> TranscriptionQueue {
+        assert!(!transcribers.is_empty(), "a queue needs a transcriber");
+        let shared = Arc::new(Shared {
+            state: Mutex::new(QueueState {
+                pending: Vec::new(),
+                next_seq: 0,
+                closed: false,
+            }),
+            changed: Condvar::new(),
+            capacity: options.capacity.max(1),
+            when_full: options.when_full,
+        });
+        let workers = (0..options.workers.max(transcribers.len()))
+            .map(|i| {
+                let shared = shared.clone();
+                let transcriber = transcribers[i % transcribers.len()].clone();
+                std::thread::spawn(move || work(&shared, &transcriber))
+            })
+            .collect();
+        TranscriptionQueue { shared, workers }
+    }
+
+    /// Queue `job`, waiting for room or failing with `Error::QueueFull` when the queue is
+    /// full, depending on `QueueOptions::when_full`.
+    pub fn submit(&self, job: Job) -> Result<JobHandle, Error> {
+        let mut state = self.shared.lock();
+        while state.pending.len() >= self.shared.capacity {
+            match self.shared.when_full {
+                FullQueuePolicy::Reject => {
+                    return Err(Error::QueueFull {
+                        capacity: self.shared.capacity,
+                    })
+                }
+                FullQueuePolicy::Block => {
+                    state = self
+                        .shared
+                        .changed
+                        .wait(state)
+                        .unwrap_or_else(|err| err.into_inner());
+                }
+            }
+        }
+        let seq = state.next_seq;
+        state.next_seq += 1;
+        let job_state = Arc::new(JobState::default());
+        state.pending.push(Pending {
+            seq,
+            priority: job.priority,
+            job,
+            state: job_state.clone(),
+        });
+        self.shared.changed.notify_all();
+        Ok(JobHandle {
+            queue: self.shared.clone(),
+            seq,
+            state: job_state,
+        })
+    }
+
+    /// The number of jobs waiting to start.
+    pub fn len(&self) -> usize {
+        self.shared.lock().pending.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl Drop for TranscriptionQueue {
+    fn drop(&mut self) {
+        let pending = {
+            let mut state = self.shared.lock();
+            state.closed = true;
+            std::mem::take(&mut state.pending)
+        };
+        self.shared.changed.notify_all();
+        for pending in pending {
+            pending.state.cancel();
+        }
+        for worker in self.workers.drain(..) {
+            let _ = worker.join();
+        }
+    }
+}
+
+/// A submitted job: its status, its place in the queue and its result, waited for with
+/// `wait` or by awaiting the handle.
+pub struct JobHandle {
+    queue: Arc<Shared>,
+    seq: u64,
+    state: Arc<JobState>,
+}
+
+impl JobHandle {
+    pub fn status(&self) -> JobStatus {
+        self.state.lock().status
+    }
+
+    /// The number of queued jobs that start before this one, `None` once it left the queue.
+    pub fn position(&self) -> Option<usize> {
+        let state = self.queue.lock();
+        let job = state
+            .pending
+            .iter()
+            .find(|pending| pending.seq == self.seq)?;
+        Some(
+            state
+                .pending
+                .iter()
+                .filter(|pending| pending.runs_before(job))
+                .count(),
+        )
+    }
+
+    /// Take the job out of the queue if it didn't start yet, returning whether it did. Its
+    /// result is then `Error::JobCancelled`. Running jobs run to the end.
+    pub fn cancel(&self) -> bool {
+        let pending = {
+            let mut state = self.queue.lock();
+            match state
+                .pending
+                .iter()
+                .position(|pending| pending.seq == self.seq)
+            {
+                Some(index) => state.pending.remove(index),
+                None => return false,
+            }
+        };
+        self.queue.changed.notify_all();
+        pending.state.cancel();
+        true
+    }
+
+    /// Block until the job is done and return its result.
+    pub fn wait(self) -> Result<TranscriberOutput, Error> {
+        let mut progress = self.state.lock();
+        loop {
+            if let Some(result) = progress.result.take() {
+                return result;
+            }
+            progress = self
+                .state
+                .done
+                .wait(progress)
+                .unwrap_or_else(|err| err.into_inner());
+        }
+    }
+}
+
+impl Future for JobHandle {
+    type Output = Result<TranscriberOutput, Error>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let mut progress = self.state.lock();
+        match progress.result.take() {
+            Some(result) => Poll::Ready(result),
+            None => {
+                progress.waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+}
+
+struct Shared {
+    state: Mutex<QueueState>,
+    /// Notified when jobs are added to or leave the queue, and when it closes.
+    changed: Condvar,
+    capacity: usize,
+    when_full: FullQueuePolicy,
+}
+
+impl Shared {
+    fn lock(&self) -> MutexGuard<'_, QueueState> {
+        self.state.lock().unwrap_or_else(|err| err.into_inner())
+    }
+}
+
+struct QueueState {
+    pending: Vec<Pending>,
+    next_seq: u64,
+    closed: bool,
+}
+
+struct Pending {
+    seq: u64,
+    priority: i32,
+    job: Job,
+    state: Arc<JobState>,
+}
+
+impl Pending {
+    fn runs_before(&self, other: &Pending) -> bool {
+        (self.priority, other.seq) > (other.priority, self.seq)
+    }
+}
+
+#[derive(Default)]
+struct JobState {
+    progress: Mutex<JobProgress>,
+    done: Condvar,
+}
+
+struct JobProgress {
+    status: JobStatus,
+    result: Option<Result<TranscriberOutput, Error>>,
+    waker: Option<Waker>,
+}
+
+impl Default for JobProgress {
+    fn default() -> Self {
+        JobProgress {
+            status: JobStatus::Queued,
+            result: None,
+            waker: None,
+        }
+    }
+}
+
+impl JobState {
+    fn lock(&self) -> MutexGuard<'_, JobProgress> {
+        self.progress.lock().unwrap_or_else(|err| err.into_inner())
+    }
+
+    fn start(&self) {
+        self.lock().status = JobStatus::Running;
+    }
+
+    fn finish(&self, status: JobStatus, result: Result<TranscriberOutput, Error>) {
+        let waker = {
+            let mut progress = self.lock();
+            progress.status = status;
+            progress.result = Some(result);
+            progress.waker.take()
+        };
+        self.done.notify_all();
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+    }
+
+    fn cancel(&self) {
+        self.finish(JobStatus::Cancelled, Err(Error::JobCancelled));
+    }
+}
+
+/// Run the queue's jobs on `transcriber` until it closes.
+fn work(shared: &Shared, transcriber: &Transcriber) {
+    loop {
+        let pending = {
+            let mut state = shared.lock();
+            loop {
+                if state.closed {
+                    return;
+                }
+                let next = (0..state.pending.len()).reduce(|best, i| {
+                    if state.pending[i].runs_before(&state.pending[best]) {
+                        i
+                    } else {
+                        best
+                    }
+                });
+                match next {
+                    Some(index) => {
+                        let pending = state.pending.remove(index);
+                        // before the lock is released, so a job out of the queue never
+                        // shows as queued
+                        pending.state.start();
+                        break pending;
+                    }
+                    None => {
+                        state = shared
+                            .changed
+                            .wait(state)
+                            .unwrap_or_else(|err| err.into_inner())
+                    }
+                }
+            }
+        };
+        // there is room for another job
+        shared.changed.notify_all();
+        let result = run(transcriber, pending.job);
+        pending.state.finish(JobStatus::Finished, result);
+    }
+}
+
+/// Run `job`, turning panics and errors other than the crate's into `Error::JobFailed`.
+fn run(transcriber: &Transcriber, job: Job) -> Result<TranscriberOutput, Error> {
+    let result = panic::catch_unwind(AssertUnwindSafe(|| match job.input {
+        JobInput::File(path) => transcriber.transcribe_with_options(path, &job.options),
+        JobInput::Bytes { data, mime_type } => {
+            transcriber.check_options(&job.options)?;
+            let mut preprocess = Preprocess::new(&job.options);
+            let audio_data = audio_parser::decode_audio_bytes(
+                data,
+                mime_type.as_deref(),
+                &mut |audio, sample_rate| preprocess.apply(audio, sample_rate),
+            )?;
+            transcriber.run_preprocessed(audio_data, preprocess, &job.options)
+        }
+        JobInput::Samples(samples) => transcriber.transcribe_samples(&samples, &job.options),
+    }));
+    match result {
+        Ok(Ok(output)) => Ok(output),
+        Ok(Err(err)) => match err.downcast::<Error>() {
+            Ok(err) => Err(*err),
+            Err(err) => Err(Error::JobFailed(err.to_string())),
+        },
+        Err(payload) => Err(Error::JobFailed(panic_message(payload.as_ref()))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{MockBackend, MockSegment};
+    use std::time::Duration;
+
+    fn transcriber(latency: Duration) -> Arc<Transcriber> {
+        Arc::new(Transcriber::with_mock_backend(
+            MockBackend::new(vec![MockSegment::new(0, 100, " Hi.")]).with_latency(latency),
+        ))
+    }
+
+    /// A job whose audio is `seconds` long, to tell the jobs apart by their output.
+    fn job(seconds: usize) -> Job {
+        Job::samples(vec![0.0; 16000 * seconds])
+    }
+
+    /// Submit a job and wait until a worker took it, so the queue is empty.
+    fn start_blocker(queue: &TranscriptionQueue) -> JobHandle {
+        let blocker = queue.submit(job(1)).unwrap();
+        while blocker.status() == JobStatus::Queued {
+            std::thread::sleep(Duration::from_millis(1));
+        }
+        blocker
+    }
+
+    #[test]
+    fn test_jobs_run_highest_priority_first() {
+        let queue = TranscriptionQueue::new(
+            transcriber(Duration::from_millis(50)),
+            QueueOptions::default(),
+        );
+        let blocker = start_blocker(&queue);
+
+        let low = queue.submit(job(2).with_priority(-1)).unwrap();
+        let high = queue.submit(job(3).with_priority(5)).unwrap();
+        let mid = queue.submit(job(4)).unwrap();
+        let also_high = queue.submit(job(5).with_priority(5)).unwrap();
+
+        assert_eq!(
+            [&high, &also_high, &mid, &low].map(|handle| handle.position()),
+            [Some(0), Some(1), Some(2), Some(3)]
+        );
+        assert_eq!(queue.len(), 4);
+        assert!(blocker.wait().is_ok());
+        let output = low.wait().unwrap();
+        assert_eq!(output.get_audio_duration(), Some(200));
+        assert_eq!(
+            [&high, &also_high, &mid].map(|handle| handle.status()),
+            [JobStatus::Finished; 3]
+        );
+        assert_eq!(high.wait().unwrap().get_audio_duration(), Some(300));
+        assert!(queue.is_empty());
+    }
+
+    #[test]
+    fn test_cancelled_jobs_never_run() {
+        let queue = TranscriptionQueue::new(
+            transcriber(Duration::from_millis(50)),
+            QueueOptions::default(),
+        );
+        let blocker = start_blocker(&queue);
+        let cancelled = queue.submit(job(2)).unwrap();
+        let kept = queue.submit(job(3)).unwrap();
+
+        assert!(cancelled.cancel());
+        assert!(!cancelled.cancel());
+        assert!(!blocker.cancel());
+        assert_eq!(cancelled.status(), JobStatus::Cancelled);
+        assert_eq!(cancelled.position(), None);
+        assert_eq!(kept.position(), Some(0));
+        assert!(matches!(cancelled.wait(), Err(Error::JobCancelled)));
+        assert_eq!(kept.wait().unwrap().get_audio_duration(), Some(300));
+        assert!(blocker.wait().is_ok());
+    }
+
+    #[test]
+    fn test_full_queue_policies() {
+        let options = QueueOptions {
+            capacity: 1,
+            when_full: FullQueuePolicy::Reject,
+            ..QueueOptions::default()
+        };
+        let queue = TranscriptionQueue::new(transcriber(Duration::from_millis(50)), options);
+        let _blocker = start_blocker(&queue);
+        let _queued = queue.submit(job(1)).unwrap();
+        assert!(matches!(
+            queue.submit(job(1)),
+            Err(Error::QueueFull { capacity: 1 })
+        ));
+
+        let options = QueueOptions {
+            capacity: 1,
+            ..QueueOptions::default()
+        };
+        let queue = TranscriptionQueue::new(transcriber(Duration::from_millis(50)), options);
+        let _blocker = start_blocker(&queue);
+        let queued = queue.submit(job(1)).unwrap();
+        // waits for the queued job to start
+        let blocked = queue.submit(job(2)).unwrap();
+        assert_ne!(queued.status(), JobStatus::Queued);
+        assert_eq!(blocked.wait().unwrap().get_audio_duration(), Some(200));
+    }
+
+    #[tokio::test]
+    async fn test_job_handles_can_be_awaited() {
+        let queue = TranscriptionQueue::new(transcriber(Duration::ZERO), QueueOptions::default());
+        let handle = queue.submit(job(1)).unwrap();
+
+        let output = handle.await.unwrap();
+
+        assert_eq!(output.get_segments()[0].get_text(), " Hi.");
+    }
+
+    #[test]
+    fn test_dropping_the_queue_cancels_queued_jobs() {
+        let queue = TranscriptionQueue::new(
+            transcriber(Duration::from_millis(50)),
+            QueueOptions::default(),
+        );
+        let blocker = start_blocker(&queue);
+        let queued = queue.submit(job(1)).unwrap();
+
+        drop(queue);
+
+        assert!(blocker.wait().is_ok());
+        assert!(matches!(queued.wait(), Err(Error::JobCancelled)));
+    }
+}
@@ -0,0 +1,212 @@
+//! Transcribing audio straight from a URL, decoding it while it downloads.
+
+use std::io::Read;
+use std::sync::Arc;
+
+use tokio::sync::mpsc;
+
+use crate::audio_parser;
+use crate::batch::panic_message;
+use crate::options::TranscribeOptions;
+use crate::transcriber::{Preprocess, Transcriber, TranscriberOutput};
+
+/// The downloaded chunks buffered ahead of the decoder.
+const BUFFERED_CHUNKS: usize = 16;
+
+/// A blocking reader over the chunks of a download, fed from an async task.
+///
+/// A download error ends the stream like the end of the file does, the downloading side
+/// reports it.
+struct ChunkReader {
+    chunks: mpsc::Receiver<bytes::Bytes>,
+    current: bytes::Bytes,
+}
+
+impl Read for ChunkReader {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        while self.current.is_empty() {
+            match self.chunks.blocking_recv() {
+                Some(chunk) => self.current = chunk,
+                None => return Ok(0),
+            }
+        }
+        let n = buf.len().min(self.current.len());
+        buf[..n].copy_from_slice(&self.current.split_to(n));
+        Ok(n)
+    }
+}
+
+impl Transcriber {
+    /// Download and transcribe the audio at `url` using the given options.
+    ///
+    /// The audio is decoded on a blocking thread as its bytes arrive instead of after the
+    /// whole file is downloaded, so transcription starts as soon as the last bytes are
+    /// decoded. The `Content-Type` of the response is used as a hint for the container
+    /// format, which must be readable front to back (mp3, wav, ...).
+    ///
+    /// The transcription runs on a blocking thread too, keeping whisper off the async
+    /// runtime's workers, which is why the transcriber is taken behind an `Arc`.
+    pub async fn transcribe_url(
+        self: &Arc<Self>,
+        url: &str,
+        options: &TranscribeOptions,
+    ) -> Result<TranscriberOutput, Box<dyn std::error::Error + Send + Sync>> {
+        self.check_options(options)?;
+        let mut response = reqwest::get(url).await?.error_for_status()?;
+        let mime_type = response
+            .headers()
+            .get(reqwest::header::CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .map(|value| {
+                value
+                    .split(';')
+                    .next()
+                    .unwrap_or_default()
+                    .trim()
+                    .to_string()
+            });
+
+        let (sender, chunks) = mpsc::channel(BUFFERED_CHUNKS);
+        let mut preprocess = Preprocess::new(options);
+        let decoder = tokio::task::spawn_blocking(move || {
+            let reader = ChunkReader {
+                chunks,
+                current: bytes::Bytes::new(),
+            };
+            let decoded = audio_parser::decode_audio_stream(
+                reader,
+                mime_type.as_deref(),
+                &mut |audio, sample_rate| preprocess.apply(audio, sample_rate),
+            );
+            (decoded, preprocess)
+        });
+
+        let mut downloaded = Ok(());
+        loop {
+            match response.chunk().await {
+                Ok(Some(chunk)) => {
+                    if sender.send(chunk).await.is_err() {
+                        // the decoder stopped early, its result tells why
+                        break;
+                    }
+                }
+                Ok(None) => break,
+                Err(err) => {
+                    downloaded = Err(err);
+                    break;
+                }
+            }
+        }
+        drop(sender);
+
+        let (decoded, preprocess) = decoder.await.map_err(join_error)?;
+        // a failed download is what cut the decoded audio short
+        downloaded?;
+        let audio_data = decoded?;
+        let transcriber = Arc::clone(self);
+        let options = options.clone();
+        tokio::task::spawn_blocking(move || {
+            transcriber.run_preprocessed(audio_data, preprocess, &options)
+        })
+        .await
+        .map_err(join_error)?
+    }
+}
+
+/// The message of a blocking task that panicked or was cancelled.
+fn join_error(err: tokio::task::JoinError) -> String {
+    match err.try_into_panic() {
+        Ok(payload) => panic_message(payload.as_ref()),
+        Err(err) => err.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+    use std::net::TcpListener;
+
+    use super::*;
+    use crate::test_util::{self, MockBackend, MockSegment};
+
+    /// Serve `body` once over HTTP on localhost, in small chunks, returning the URL. When
+    /// `truncate` is set the connection is dropped halfway through the body.
+    fn serve_once(body: Vec<u8>, truncate: bool) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/speech.wav", listener.local_addr().unwrap());
+        std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut request = [0; 1024];
+            let _ = stream.read(&mut request);
+            let header = format!(
+                "HTTP/1.1 200 OK\r\nContent-Type: audio/wav\r\nContent-Length: {}\r\n\r\n",
+                body.len()
+            );
+            stream.write_all(header.as_bytes()).unwrap();
+            let sent = if truncate { body.len() / 2 } else { body.len() };
+            for chunk in body[..sent].chunks(4096) {
+                if stream.write_all(chunk).is_err() {
+                    return;
+                }
+            }
+        });
+        url
+    }
+
+    fn wav_bytes(seconds: u64) -> Vec<u8> {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("speech.wav");
+        test_util::write_wav(
+            &path,
+            &test_util::silence(std::time::Duration::from_secs(seconds)),
+        );
+        std::fs::read(path).unwrap()
+    }
+
+    fn mock_transcriber() -> Arc<Transcriber> {
+        Arc::new(Transcriber::with_mock_backend(MockBackend::new(vec![
+            MockSegment::new(0, 150, " Hi."),
+        ])))
+    }
+
+    #[tokio::test]
+    async fn test_transcribe_url_decodes_stream() {
+        let url = serve_once(wav_bytes(3), false);
+
+        let output = mock_transcriber()
+            .transcribe_url(&url, &TranscribeOptions::default())
+            .await
+            .unwrap();
+
+        assert_eq!(output.get_audio_duration(), Some(300));
+        assert_eq!(output.get_segments()[0].get_text(), " Hi.");
+    }
+
+    #[tokio::test]
+    async fn test_transcribe_url_reports_interrupted_download() {
+        let url = serve_once(wav_bytes(3), true);
+
+        let result = mock_transcriber()
+            .transcribe_url(&url, &TranscribeOptions::default())
+            .await;
+
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_chunk_reader_reads_across_chunks() {
+        let (sender, chunks) = mpsc::channel(4);
+        sender.try_send(bytes::Bytes::from_static(b"ab")).unwrap();
+        sender.try_send(bytes::Bytes::from_static(b"cde")).unwrap();
+        drop(sender);
+        let mut reader = ChunkReader {
+            chunks,
+            current: bytes::Bytes::new(),
+        };
+
+        let mut read = Vec::new();
+        reader.read_to_end(&mut read).unwrap();
+
+        assert_eq!(read, b"abcde");
+    }
+}
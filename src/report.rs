@@ -0,0 +1,273 @@
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::time::Instant;
+
+use sha2::{Digest, Sha256};
+
+use crate::audio_parser;
+use crate::error::Error;
+use crate::options::TranscribeOptions;
+use crate::transcriber::{Preprocess, Transcriber, TranscriberOutput};
+
+/// The version of the `JobReport` JSON schema, bumped whenever a field changes meaning or
+/// is removed.
+pub const JOB_REPORT_SCHEMA_VERSION: u32 = 1;
+
+/// A summary of one transcription job for log aggregation, see
+/// `Transcriber::transcribe_with_report`.
+///
+/// Every field is always present in the JSON, missing values are `null`.
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+pub struct JobReport {
+    pub schema_version: u32,
+    /// The path of the audio file, with anything not valid UTF-8 replaced.
+    pub input: String,
+    /// The hex encoded SHA-256 of the audio file, `None` when it could not be read.
+    pub input_sha256: Option<String>,
+    /// The duration of the audio, `None` when it was not decoded.
+    pub audio_duration_ms: Option<u64>,
+    /// The model type, such as "tiny".
+    pub model: String,
+    /// The `manifest::Manifest::digest` of the options on the loaded model, before the
+    /// audio's checksum is recorded in it, equal for equal options with the same model and
+    /// crate version.
+    pub options_digest: String,
+    pub timings: JobTimings,
+    /// The number of warnings of each `Warning::kind`.
+    pub warnings: BTreeMap<String, usize>,
+    pub status: JobStatus,
+    /// The error the job failed with.
+    pub error: Option<String>,
+}
+
+/// Where a transcription job spent its time, in milliseconds.
+#[derive(Debug, Clone, Default, PartialEq, Eq, serde::Serialize)]
+pub struct JobTimings {
+    /// Reading and decoding the audio file.
+    pub decode_audio_ms: u64,
+    /// Running the model and post-processing its output.
+    pub transcribe_ms: u64,
+    pub total_ms: u64,
+}
+
+/// Whether a transcription job succeeded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum JobStatus {
+    Success,
+    Failure,
+}
+
+impl JobReport {
+    /// The report as a single line of JSON.
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).expect("a job report always serializes")
+    }
+}
+
+impl Transcriber {
+    /// Transcribe an audio file using the given options, then call `on_complete` with a
+    /// `JobReport` of the job, whether it succeeded or not.
+    ///
+    /// Failing to decode the audio is reported (and returned) as an error instead of
+    /// panicking.
+    pub fn transcribe_with_report(
+        &self,
+        audio_path: impl AsRef<Path>,
+        options: &TranscribeOptions,
+        on_complete: impl FnOnce(&JobReport),
+    ) -> Result<TranscriberOutput, Box<dyn std::error::Error + Send + Sync>> {
+        let started = Instant::now();
+        let mut report = JobReport {
+            schema_version: JOB_REPORT_SCHEMA_VERSION,
+            input: audio_path.as_ref().display().to_string(),
+            input_sha256: None,
+            audio_duration_ms: None,
+            model: self.context_info().model_type,
+            options_digest: self.manifest(options, false).digest(),
+            timings: JobTimings::default(),
+            warnings: BTreeMap::new(),
+            status: JobStatus::Failure,
+            error: None,
+        };
+
+        let result = self.transcribe_timed(audio_path.as_ref(), options, &mut report);
+        match &result {
+            Ok(output) => {
+                report.status = JobStatus::Success;
+                for warning in output.get_warnings() {
+                    *report
+                        .warnings
+                        .entry(warning.kind().to_string())
+                        .or_default() += 1;
+                }
+            }
+            Err(err) => report.error = Some(err.to_string()),
+        }
+        report.timings.total_ms = started.elapsed().as_millis() as u64;
+        on_complete(&report);
+        result
+    }
+
+    fn transcribe_timed(
+        &self,
+        audio_path: &Path,
+        options: &TranscribeOptions,
+        report: &mut JobReport,
+    ) -> Result<TranscriberOutput, Box<dyn std::error::Error + Send + Sync>> {
+        let started = Instant::now();
+        let bytes = std::fs::read(audio_path).map_err(Error::Io)?;
+        report.input_sha256 = Some(format!("{:x}", Sha256::digest(&bytes)));
+        let mut preprocess = Preprocess::new(options);
+        let audio_data =
+            audio_parser::decode_audio_bytes(bytes, None, &mut |audio, sample_rate| {
+                preprocess.apply(audio, sample_rate)
+            })?;
+        report.audio_duration_ms = Some(audio_data.len() as u64 / 16);
+        report.timings.decode_audio_ms = started.elapsed().as_millis() as u64;
+
+        let started = Instant::now();
+        self.check_options(options)?;
+        let mut output = self.run_preprocessed(audio_data, preprocess, options);
+        report.timings.transcribe_ms = started.elapsed().as_millis() as u64;
+        if let Ok(Some(manifest)) = output.as_mut().map(|output| output.manifest.as_mut()) {
+            manifest.audio_sha256 = report.input_sha256.clone();
+        }
+        output
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::audio_parser::tests::write_test_wav;
+    use crate::test_util::{MockBackend, MockSegment};
+
+    const KEYS: [&str; 10] = [
+        "schema_version",
+        "input",
+        "input_sha256",
+        "audio_duration_ms",
+        "model",
+        "options_digest",
+        "timings",
+        "warnings",
+        "status",
+        "error",
+    ];
+
+    fn report_json(transcriber: &Transcriber, path: &std::path::Path) -> (bool, serde_json::Value) {
+        let mut line = None;
+        let result = transcriber.transcribe_with_report(
+            path.to_str().unwrap(),
+            &TranscribeOptions::default(),
+            |report| line = Some(report.to_json()),
+        );
+        let line = line.expect("on_complete was not called");
+        assert!(!line.contains('\n'));
+        (result.is_ok(), serde_json::from_str(&line).unwrap())
+    }
+
+    fn assert_schema(json: &serde_json::Value) {
+        let object = json.as_object().unwrap();
+        let mut keys: Vec<&str> = object.keys().map(String::as_str).collect();
+        keys.sort_unstable();
+        let mut expected = KEYS.to_vec();
+        expected.sort_unstable();
+        assert_eq!(keys, expected);
+        assert_eq!(json["schema_version"], JOB_REPORT_SCHEMA_VERSION);
+        assert_eq!(json["model"], "mock");
+        assert_eq!(json["options_digest"].as_str().unwrap().len(), 64);
+        for timing in ["decode_audio_ms", "transcribe_ms", "total_ms"] {
+            assert!(json["timings"][timing].is_u64(), "{}", timing);
+        }
+        assert!(json["warnings"].is_object());
+    }
+
+    #[test]
+    fn test_job_report_success() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("speech.wav");
+        write_test_wav(&path, 16000, 1, &[0; 16000 * 2]);
+        let transcriber = Transcriber::with_mock_backend(MockBackend::new(vec![MockSegment::new(
+            0, 300, " Hello.",
+        )]));
+
+        let (ok, json) = report_json(&transcriber, &path);
+
+        assert!(ok);
+        assert_schema(&json);
+        assert_eq!(json["status"], "success");
+        assert_eq!(json["error"], serde_json::Value::Null);
+        assert_eq!(json["audio_duration_ms"], 2000);
+        assert_eq!(json["input_sha256"].as_str().unwrap().len(), 64);
+        assert_eq!(json["warnings"]["TimestampRepaired"], 1);
+        assert_eq!(
+            json["options_digest"],
+            transcriber
+                .manifest(&TranscribeOptions::default(), false)
+                .digest()
+        );
+    }
+
+    #[test]
+    fn test_manifest_records_audio_sha256() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("speech.wav");
+        write_test_wav(&path, 16000, 1, &[0; 16000 * 2]);
+        let transcriber = Transcriber::with_mock_backend(MockBackend::new(vec![MockSegment::new(
+            0, 150, " Hello.",
+        )]));
+
+        let mut input_sha256 = None;
+        let output = transcriber
+            .transcribe_with_report(
+                path.to_str().unwrap(),
+                &TranscribeOptions::default(),
+                |report| input_sha256 = report.input_sha256.clone(),
+            )
+            .unwrap();
+
+        assert!(input_sha256.is_some());
+        assert_eq!(output.manifest().unwrap().audio_sha256, input_sha256);
+    }
+
+    #[test]
+    fn test_job_report_decode_failure() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("speech.wav");
+        write_test_wav(&path, 16000, 1, &[0; 16000 * 2]);
+        let transcriber = Transcriber::with_mock_backend(
+            MockBackend::new(Vec::new())
+                .failing_with(vec![whisper_rs::WhisperError::FailedToDecode]),
+        );
+
+        let (ok, json) = report_json(&transcriber, &path);
+
+        assert!(!ok);
+        assert_schema(&json);
+        assert_eq!(json["status"], "failure");
+        assert!(json["error"].is_string());
+        assert_eq!(json["audio_duration_ms"], 2000);
+    }
+
+    #[test]
+    fn test_job_report_audio_decode_failure() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("speech.wav");
+        std::fs::write(&path, b"not a wav file").unwrap();
+        let transcriber = Transcriber::with_mock_backend(MockBackend::new(Vec::new()));
+
+        let (ok, json) = report_json(&transcriber, &path);
+
+        assert!(!ok);
+        assert_schema(&json);
+        assert_eq!(json["status"], "failure");
+        assert!(json["error"]
+            .as_str()
+            .unwrap()
+            .contains("unsupported audio format"));
+        assert_eq!(json["audio_duration_ms"], serde_json::Value::Null);
+        assert!(json["input_sha256"].is_string());
+    }
+}
@@ -0,0 +1,249 @@
+//! Converting decoded audio to another sample rate, so files that aren't at whisper's 16KHz
+//! transcribe without converting them first.
+//!
+//! Every output sample is interpolated from the input with a Blackman windowed sinc,
+//! low-passed below the lower of the two Nyquist frequencies so downsampling doesn't alias.
+//! The rates are reduced to a ratio `up / down`, whose `up` distinct fractional offsets get
+//! their filter computed once.
+//!
+//! The sinc's length grows with the downsampling ratio, so high resolution recordings (96 or
+//! 192KHz) are first decimated by an integer factor. Only what would alias into the final
+//! passband needs filtering at that stage, which leaves a wide transition band and a short
+//! filter evaluated once per decimated sample. The sinc then finishes at a small ratio.
+
+/// The zero crossings of the sinc on each side of the filter, trading the sharpness of the
+/// low-pass for speed.
+const ZERO_CROSSINGS: f64 = 16.0;
+/// The cutoff as a share of the lower Nyquist frequency, leaving room for the transition
+/// band of the window.
+const ROLLOFF: f64 = 0.945;
+/// The downsampling ratio from which the audio is decimated before the sinc.
+const DECIMATION_RATIO: u32 = 4;
+/// The transition band of a Blackman window of `n` taps is about this many cycles per
+/// sample divided by `n`.
+const BLACKMAN_TRANSITION: f64 = 5.5;
+
+/// Resample `samples` from `from` Hz to `to` Hz, returning `ceil(len * to / from)` samples.
+pub(crate) fn resample(samples: &[f32], from: u32, to: u32) -> Vec<f32> {
+    if from == to {
+        return samples.to_vec();
+    }
+    if let Some(factor) = decimation_factor(from, to) {
+        let len = (samples.len() as u64 * to as u64).div_ceil(from as u64) as usize;
+        let mut resampled = resample(&decimate(samples, from, to, factor), from / factor, to);
+        // rounding up twice may give a sample more
+        resampled.truncate(len);
+        return resampled;
+    }
+    sinc_resample(samples, from, to)
+}
+
+/// The integer factor to decimate audio at `from` Hz by before resampling it to `to` Hz: the
+/// largest dividing `from` that keeps the decimated rate at least twice `to`, so the
+/// decimation filter has room for its transition band. `None` below `DECIMATION_RATIO`.
+fn decimation_factor(from: u32, to: u32) -> Option<u32> {
+    if from < to.saturating_mul(DECIMATION_RATIO) {
+        return None;
+    }
+    (2..=from / (2 * to))
+        .rev()
+        .find(|factor| from.is_multiple_of(*factor))
+}
+
+/// Keep every `factor`th sample of `samples` at `from` Hz, low-passed against what would
+/// alias into the passband of the final resampling to `to` Hz.
+fn decimate(samples: &[f32], from: u32, to: u32, factor: u32) -> Vec<f32> {
+    let factor = factor as usize;
+    // in cycles per input sample: the passband kept for `to`, and where the frequencies
+    // folding back into it start
+    let passband = ROLLOFF * to as f64 / 2.0 / from as f64;
+    let stopband = 1.0 / factor as f64 - passband;
+    let half_width = (BLACKMAN_TRANSITION / (stopband - passband) / 2.0).ceil() as usize;
+    // `kernel` takes the cutoff relative to the Nyquist frequency
+    let cutoff = passband + stopband;
+    let weights: Vec<f64> = (0..=2 * half_width)
+        .map(|tap| {
+            let x = tap as f64 - half_width as f64;
+            kernel(x, cutoff, half_width as f64 + 1.0)
+        })
+        .collect();
+    let sum: f64 = weights.iter().sum();
+    let filter: Vec<f32> = weights.iter().map(|weight| (weight / sum) as f32).collect();
+
+    (0..samples.len().div_ceil(factor))
+        .map(|m| {
+            convolve(
+                samples,
+                &filter,
+                (m * factor) as isize - half_width as isize,
+            )
+        })
+        .collect()
+}
+
+/// Resample with the windowed sinc alone, see the module documentation.
+fn sinc_resample(samples: &[f32], from: u32, to: u32) -> Vec<f32> {
+    let divisor = gcd(from, to);
+    let (up, down) = ((to / divisor) as usize, (from / divisor) as usize);
+    // in cycles per input sample, relative to the input's Nyquist frequency
+    let cutoff = ROLLOFF * (up as f64 / down as f64).min(1.0);
+    let half_width = (ZERO_CROSSINGS / cutoff).ceil() as usize;
+    // the filter of the output samples `phase / up` input samples past an input sample,
+    // weighting the `2 * half_width` input samples around it
+    let filters: Vec<Vec<f32>> = (0..up)
+        .map(|phase| {
+            let offset = half_width as f64 - 1.0 + phase as f64 / up as f64;
+            let weights: Vec<f64> = (0..2 * half_width)
+                .map(|tap| kernel(tap as f64 - offset, cutoff, half_width as f64))
+                .collect();
+            // unity gain, so constant audio stays constant
+            let sum: f64 = weights.iter().sum();
+            weights.iter().map(|weight| (weight / sum) as f32).collect()
+        })
+        .collect();
+
+    let len = (samples.len() as u64 * up as u64).div_ceil(down as u64) as usize;
+    (0..len)
+        .map(|n| {
+            let position = n * down;
+            let start = (position / up) as isize - (half_width as isize - 1);
+            convolve(samples, &filters[position % up], start)
+        })
+        .collect()
+}
+
+/// The sum of `filter` weighting the samples from index `start` on.
+fn convolve(samples: &[f32], filter: &[f32], start: isize) -> f32 {
+    if start >= 0 && start as usize + filter.len() <= samples.len() {
+        let window = &samples[start as usize..start as usize + filter.len()];
+        window.iter().zip(filter).map(|(x, w)| x * w).sum()
+    } else {
+        // the edges, reading silence past both ends of the audio
+        filter
+            .iter()
+            .enumerate()
+            .filter_map(|(tap, w)| {
+                let index = usize::try_from(start + tap as isize).ok()?;
+                samples.get(index).map(|x| x * w)
+            })
+            .sum()
+    }
+}
+
+/// The windowed sinc low-pass at `x` input samples from its center.
+fn kernel(x: f64, cutoff: f64, half_width: f64) -> f64 {
+    if x.abs() >= half_width {
+        return 0.0;
+    }
+    let sinc = if x == 0.0 {
+        1.0
+    } else {
+        let t = std::f64::consts::PI * cutoff * x;
+        t.sin() / t
+    };
+    let phase = std::f64::consts::PI * x / half_width;
+    let window = 0.42 + 0.5 * phase.cos() + 0.08 * (2.0 * phase).cos();
+    cutoff * sinc * window
+}
+
+fn gcd(mut a: u32, mut b: u32) -> u32 {
+    while b != 0 {
+        (a, b) = (b, a % b);
+    }
+    a
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sine(frequency: f64, sample_rate: u32, seconds: f64) -> Vec<f32> {
+        let len = (sample_rate as f64 * seconds) as usize;
+        (0..len)
+            .map(|i| {
+                (2.0 * std::f64::consts::PI * frequency * i as f64 / sample_rate as f64).sin()
+                    as f32
+            })
+            .collect()
+    }
+
+    /// The largest difference to `expected`, leaving out the edges the filter reads
+    /// silence past.
+    fn max_error(actual: &[f32], expected: &[f32]) -> f32 {
+        assert_eq!(actual.len(), expected.len());
+        let edge = 200;
+        actual[edge..actual.len() - edge]
+            .iter()
+            .zip(&expected[edge..expected.len() - edge])
+            .map(|(a, b)| (a - b).abs())
+            .fold(0.0, f32::max)
+    }
+
+    fn rms(samples: &[f32]) -> f32 {
+        (samples.iter().map(|x| x * x).sum::<f32>() / samples.len() as f32).sqrt()
+    }
+
+    #[test]
+    fn test_resample_keeps_tones_below_nyquist() {
+        for from in [48000, 44100, 22050, 8000] {
+            let resampled = resample(&sine(440.0, from, 1.0), from, 16000);
+            let error = max_error(&resampled, &sine(440.0, 16000, 1.0));
+            assert!(error < 1e-3, "{}Hz: {}", from, error);
+        }
+    }
+
+    #[test]
+    fn test_resample_filters_what_the_target_rate_cannot_hold() {
+        let resampled = resample(&sine(12000.0, 48000, 1.0), 48000, 16000);
+
+        assert_eq!(resampled.len(), 16000);
+        assert!(rms(&resampled[200..15800]) < 1e-3);
+    }
+
+    #[test]
+    fn test_decimation_factor() {
+        assert_eq!(decimation_factor(48000, 16000), None);
+        assert_eq!(decimation_factor(8000, 16000), None);
+        assert_eq!(decimation_factor(64000, 16000), Some(2));
+        assert_eq!(decimation_factor(88200, 16000), Some(2));
+        assert_eq!(decimation_factor(96000, 16000), Some(3));
+        assert_eq!(decimation_factor(176400, 16000), Some(5));
+        assert_eq!(decimation_factor(192000, 16000), Some(6));
+        assert_eq!(decimation_factor(96001, 16000), None);
+    }
+
+    #[test]
+    fn test_resample_high_resolution_audio() {
+        for from in [192000, 176400, 96000] {
+            let resampled = resample(&sine(440.0, from, 1.0), from, 16000);
+            let error = max_error(&resampled, &sine(440.0, 16000, 1.0));
+            assert!(error < 1e-3, "{}Hz: {}", from, error);
+
+            // a tone that would fold onto 5KHz in the decimated audio, and one above the
+            // final Nyquist frequency
+            let decimated_rate = from / decimation_factor(from, 16000).unwrap();
+            for tone in [decimated_rate as f64 - 5000.0, 9000.0] {
+                let resampled = resample(&sine(tone, from, 1.0), from, 16000);
+                assert_eq!(resampled.len(), 16000);
+                let level = rms(&resampled[200..15800]);
+                assert!(level < 2e-3, "{}Hz at {}Hz: {}", tone, from, level);
+            }
+        }
+        assert_eq!(resample(&[0.5; 192001], 192000, 16000).len(), 16001);
+    }
+
+    #[test]
+    fn test_resample_lengths_and_identity() {
+        let samples = sine(440.0, 44100, 0.5);
+        assert_eq!(resample(&samples, 44100, 44100), samples);
+        assert_eq!(resample(&samples, 44100, 16000).len(), 8000);
+        assert_eq!(resample(&[0.5; 3], 48000, 16000).len(), 1);
+        assert!(resample(&[], 48000, 16000).is_empty());
+
+        let constant = resample(&[0.25; 8000], 8000, 16000);
+        assert_eq!(constant.len(), 16000);
+        assert!(constant[100..15900]
+            .iter()
+            .all(|sample| (sample - 0.25).abs() < 1e-6));
+    }
+}
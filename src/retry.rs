@@ -0,0 +1,201 @@
+use whisper_rs::WhisperError;
+
+use crate::options::RetryPolicy;
+use crate::system::Backend;
+use crate::warning::Warning;
+
+/// The configuration of one inference attempt.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Attempt {
+    /// Starting from 1.
+    pub(crate) number: u32,
+    /// The thread count to run with, `None` keeps the parameters' own.
+    pub(crate) n_threads: Option<i32>,
+    pub(crate) use_gpu: bool,
+}
+
+/// Whether an error may go away when the same inference is retried on a fresh state.
+///
+/// Errors caused by the input or the parameters (thread count, mel bands, invalid strings)
+/// fail the same way every time.
+pub(crate) fn is_retryable(err: &WhisperError) -> bool {
+    matches!(
+        err,
+        WhisperError::InitError
+            | WhisperError::FailedToCreateState
+            | WhisperError::UnableToCalculateSpectrogram
+            | WhisperError::UnableToCalculateEvaluation
+            | WhisperError::FailedToEncode
+            | WhisperError::FailedToDecode
+            | WhisperError::GenericError(_)
+    )
+}
+
+/// Run `infer` until it succeeds, it fails with an error that is not retryable, or the
+/// policy runs out of attempts.
+///
+/// # Returns
+///
+/// * The result of the last attempt, with a `Warning::InferenceRetried` describing the
+///   attempts when there was more than one.
+pub(crate) fn with_retries<T>(
+    policy: &RetryPolicy,
+    use_gpu: bool,
+    mut infer: impl FnMut(&Attempt) -> Result<T, WhisperError>,
+) -> Result<(T, Option<Warning>), WhisperError> {
+    let mut attempt = Attempt {
+        number: 1,
+        n_threads: None,
+        use_gpu,
+    };
+    let mut errors = Vec::new();
+    loop {
+        match infer(&attempt) {
+            Ok(value) => {
+                let backend = if attempt.use_gpu {
+                    Backend::Gpu
+                } else {
+                    Backend::Cpu
+                };
+                let warning = (attempt.number > 1).then_some(Warning::InferenceRetried {
+                    attempts: attempt.number,
+                    errors,
+                    n_threads: attempt.n_threads,
+                    backend,
+                });
+                return Ok((value, warning));
+            }
+            Err(err) if is_retryable(&err) && attempt.number < policy.max_attempts => {
+                errors.push(err.to_string());
+                attempt = next_attempt(policy, &attempt);
+            }
+            Err(err) => return Err(err),
+        }
+    }
+}
+
+fn next_attempt(policy: &RetryPolicy, attempt: &Attempt) -> Attempt {
+    let n_threads = if policy.reduce_threads {
+        let current = attempt.n_threads.unwrap_or_else(default_n_threads);
+        Some((current / 2).max(1))
+    } else {
+        attempt.n_threads
+    };
+    Attempt {
+        number: attempt.number + 1,
+        n_threads,
+        use_gpu: attempt.use_gpu && !policy.cpu_fallback,
+    }
+}
+
+/// whisper's default thread count, min(4, available parallelism).
+fn default_n_threads() -> i32 {
+    std::thread::available_parallelism()
+        .map(|n| n.get())
+        .unwrap_or(1)
+        .min(4) as i32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn policy(max_attempts: u32) -> RetryPolicy {
+        RetryPolicy {
+            max_attempts,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_with_retries_succeeds_first_time() {
+        let (value, warning) = with_retries(&policy(3), false, |_| Ok(7)).unwrap();
+
+        assert_eq!(value, 7);
+        assert_eq!(warning, None);
+    }
+
+    #[test]
+    fn test_with_retries_recovers_from_transient_failures() {
+        let mut attempts = Vec::new();
+
+        let (value, warning) = with_retries(&policy(3), true, |attempt| {
+            attempts.push(attempt.clone());
+            if attempt.number < 3 {
+                Err(WhisperError::FailedToEncode)
+            } else {
+                Ok("text")
+            }
+        })
+        .unwrap();
+
+        assert_eq!(value, "text");
+        assert_eq!(attempts.len(), 3);
+        assert!(attempts[0].use_gpu && attempts[0].n_threads.is_none());
+        assert!(!attempts[1].use_gpu);
+        assert!(attempts[2].n_threads <= attempts[1].n_threads);
+        assert!(attempts[2].n_threads >= Some(1));
+        match warning {
+            Some(Warning::InferenceRetried {
+                attempts,
+                errors,
+                backend,
+                ..
+            }) => {
+                assert_eq!(attempts, 3);
+                assert_eq!(errors.len(), 2);
+                assert_eq!(backend, Backend::Cpu);
+            }
+            other => panic!("unexpected warning {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_with_retries_gives_up_after_max_attempts() {
+        let mut count = 0;
+
+        let result: Result<((), _), _> = with_retries(&policy(2), false, |_| {
+            count += 1;
+            Err(WhisperError::GenericError(-6))
+        });
+
+        assert!(matches!(result, Err(WhisperError::GenericError(-6))));
+        assert_eq!(count, 2);
+    }
+
+    #[test]
+    fn test_with_retries_does_not_retry_parameter_errors() {
+        let mut count = 0;
+
+        let result: Result<((), _), _> = with_retries(&policy(3), false, |_| {
+            count += 1;
+            Err(WhisperError::InvalidThreadCount)
+        });
+
+        assert!(matches!(result, Err(WhisperError::InvalidThreadCount)));
+        assert_eq!(count, 1);
+    }
+
+    #[test]
+    fn test_next_attempt_keeps_configuration_when_disabled() {
+        let policy = RetryPolicy {
+            max_attempts: 2,
+            cpu_fallback: false,
+            reduce_threads: false,
+        };
+        let first = Attempt {
+            number: 1,
+            n_threads: None,
+            use_gpu: true,
+        };
+
+        assert_eq!(
+            next_attempt(&policy, &first),
+            Attempt {
+                number: 2,
+                n_threads: None,
+                use_gpu: true,
+            }
+        );
+    }
+}
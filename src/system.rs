@@ -0,0 +1,172 @@
+/// The CPU features and acceleration backends whisper was built with.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+pub struct SystemInfo {
+    pub avx: bool,
+    pub avx2: bool,
+    pub avx512: bool,
+    pub fma: bool,
+    pub f16c: bool,
+    pub neon: bool,
+    pub metal: bool,
+    pub blas: bool,
+    pub cuda: bool,
+    pub coreml: bool,
+    pub openvino: bool,
+    /// whisper's system info string, as printed by whisper itself.
+    pub raw: String,
+}
+
+/// The backend a model was loaded on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize)]
+pub enum Backend {
+    Cpu,
+    Gpu,
+}
+
+/// Information about the model loaded by a `Transcriber`.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+pub struct ContextInfo {
+    /// The model type, such as "tiny" or "large".
+    pub model_type: String,
+    pub multilingual: bool,
+    pub vocab_size: i32,
+    pub backend: Backend,
+}
+
+/// The vocabulary sizes of stock whisper models: English-only, multilingual, and large-v3
+/// (which added a language).
+pub const STOCK_VOCAB_SIZES: [i32; 3] = [51864, 51865, 51866];
+
+/// The numbers of mel bands whisper models take: 80, and 128 from large-v3 on.
+pub const SUPPORTED_N_MELS: [i32; 2] = [80, 128];
+
+/// The hyperparameters a model file reports, see `Transcriber::hyperparameters`.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+pub struct ModelHyperparameters {
+    /// The model type whisper derives from the number of audio layers, such as "tiny".
+    pub model_type: String,
+    pub n_vocab: i32,
+    pub n_audio_ctx: i32,
+    pub n_audio_state: i32,
+    pub n_audio_head: i32,
+    pub n_audio_layer: i32,
+    pub n_text_ctx: i32,
+    pub n_text_state: i32,
+    pub n_text_head: i32,
+    pub n_text_layer: i32,
+    pub n_mels: i32,
+    /// The type of the weights, 0 for f32, 1 for f16 and higher for quantized types.
+    pub ftype: i32,
+}
+
+impl ModelHyperparameters {
+    /// Whether the vocabulary is as large as a stock whisper vocabulary. whisper derives the
+    /// ids of its special tokens (end of text, languages, tasks, timestamps) from the
+    /// vocabulary size, so they are wrong for fine-tuned models with other vocabularies.
+    pub fn has_stock_vocabulary(&self) -> bool {
+        STOCK_VOCAB_SIZES.contains(&self.n_vocab)
+    }
+}
+
+/// Diagnostics attached to a `TranscriberOutput` when
+/// `TranscribeOptions::include_diagnostics` is enabled, so bug reports carry them.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+pub struct Diagnostics {
+    pub system: SystemInfo,
+    pub context: ContextInfo,
+}
+
+/// Report the CPU features and acceleration backends whisper was built with.
+pub fn system_info() -> SystemInfo {
+    parse_system_info(whisper_rs::print_system_info())
+}
+
+impl SystemInfo {
+    /// Whether a GPU backend is compiled in.
+    pub fn has_gpu(&self) -> bool {
+        self.cuda || self.metal
+    }
+
+    /// The x86 instruction sets whisper was built with that the CPU running it lacks.
+    ///
+    /// whisper.cpp picks its SIMD code paths when it is compiled, not at runtime, so on such
+    /// a CPU inference crashes with an illegal instruction. whisper.cpp has to be built with
+    /// its `WHISPER_NO_AVX`, `WHISPER_NO_AVX2`, `WHISPER_NO_FMA` or `WHISPER_NO_F16C` CMake
+    /// options to run there.
+    #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+    pub fn unsupported_cpu_features(&self) -> Vec<&'static str> {
+        let features = [
+            ("AVX", self.avx, is_x86_feature_detected!("avx")),
+            ("AVX2", self.avx2, is_x86_feature_detected!("avx2")),
+            ("AVX512", self.avx512, is_x86_feature_detected!("avx512f")),
+            ("FMA", self.fma, is_x86_feature_detected!("fma")),
+            ("F16C", self.f16c, is_x86_feature_detected!("f16c")),
+        ];
+        features
+            .iter()
+            .filter(|(_, built, detected)| *built && !*detected)
+            .map(|(name, _, _)| *name)
+            .collect()
+    }
+
+    /// The x86 instruction sets whisper was built with that the CPU running it lacks, none
+    /// on other architectures.
+    #[cfg(not(any(target_arch = "x86", target_arch = "x86_64")))]
+    pub fn unsupported_cpu_features(&self) -> Vec<&'static str> {
+        Vec::new()
+    }
+}
+
+/// Parse whisper's `AVX = 1 | AVX2 = 0 | ...` system info string.
+fn parse_system_info(raw: &str) -> SystemInfo {
+    let flag = |name: &str| {
+        raw.split('|').any(|entry| {
+            let mut parts = entry.splitn(2, '=');
+            parts.next().map(str::trim) == Some(name) && parts.next().map(str::trim) == Some("1")
+        })
+    };
+    SystemInfo {
+        avx: flag("AVX"),
+        avx2: flag("AVX2"),
+        avx512: flag("AVX512"),
+        fma: flag("FMA"),
+        f16c: flag("F16C"),
+        neon: flag("NEON"),
+        metal: flag("METAL"),
+        blas: flag("BLAS"),
+        cuda: flag("CUDA"),
+        coreml: flag("COREML"),
+        openvino: flag("OPENVINO"),
+        raw: raw.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_system_info() {
+        let info = parse_system_info(
+            "AVX = 1 | AVX2 = 1 | AVX512 = 0 | FMA = 1 | NEON = 0 | ARM_FMA = 0 | METAL = 0 | \
+             F16C = 1 | FP16_VA = 0 | WASM_SIMD = 0 | BLAS = 0 | SSE3 = 1 | SSSE3 = 1 | \
+             VSX = 0 | CUDA = 1 | COREML = 0 | OPENVINO = 0 | ",
+        );
+
+        assert!(info.avx && info.avx2 && info.fma && info.f16c && info.cuda);
+        assert!(!info.avx512 && !info.neon && !info.metal && !info.blas);
+        assert!(info.has_gpu());
+    }
+
+    #[test]
+    fn test_system_info_populated() {
+        let info = system_info();
+
+        assert!(info.raw.contains("AVX = "));
+        assert!(!info.has_gpu());
+        // these tests run, so the CPU has what whisper was built with
+        assert!(info.unsupported_cpu_features().is_empty());
+        let without_simd = parse_system_info("AVX = 0 | AVX2 = 0 | FMA = 0 | F16C = 0 |");
+        assert!(without_simd.unsupported_cpu_features().is_empty());
+    }
+}
@@ -0,0 +1,613 @@
+//! Test helpers for code built on `Transcriber`, needing neither a model nor the network.
+//! Enabled by the `test-util` feature.
+//!
+//! `MockBackend` is a scripted stand-in for the whisper model, and `MockTranscriber` hands out
+//! the transcripts of such a script. `silence`, `tone` and `noise` synthesize deterministic
+//! 16KHz mono audio and `speech_clip` is a bundled recording, which `write_wav` saves for the
+//! file based APIs.
+//!
+//! ```ignore
+//! use simple_transcribe_rs::test_util::{self, MockSegment, MockTranscriber};
+//!
+//! let mock = MockTranscriber::new(vec![MockSegment::new(0, 150, " Hello world.")]);
+//! assert_eq!(mock.output().to_plain_text(Default::default()), "Hello world.");
+//!
+//! test_util::write_wav("clip.wav", &test_util::tone(440.0, Duration::from_secs(1), 0.5));
+//! let output = mock.transcriber().transcribe_with_options("clip.wav", &Default::default());
+//! ```
+
+use std::collections::VecDeque;
+use std::os::raw::c_int;
+use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use whisper_rs::{FullParams, WhisperError};
+
+use crate::backend::{InferenceBackend, InferenceState, TokenData};
+use crate::callbacks::Hooks;
+use crate::options::TranscribeOptions;
+use crate::system::ModelHyperparameters;
+use crate::transcriber::{Transcriber, TranscriberOutput};
+
+/// The sample rate of the synthesized audio.
+const SAMPLE_RATE: u32 = 16000;
+
+/// The first two seconds of the recording the crate's own tests transcribe
+/// (`src/test_data/test.mp3`), decoded to 16KHz mono little endian f32 samples. Its source
+/// and licence are noted in `src/test_data/speech_2s_16k_f32le.raw.LICENSE`.
+const SPEECH_CLIP: &[u8] = include_bytes!("test_data/speech_2s_16k_f32le.raw");
+
+/// `duration` of 16KHz mono silence.
+pub fn silence(duration: Duration) -> Vec<f32> {
+    vec![0.0; samples_in(duration)]
+}
+
+/// `duration` of a 16KHz mono sine wave at `frequency` Hz peaking at `amplitude`.
+pub fn tone(frequency: f32, duration: Duration, amplitude: f32) -> Vec<f32> {
+    (0..samples_in(duration))
+        .map(|i| {
+            let t = i as f32 / SAMPLE_RATE as f32;
+            amplitude * (2.0 * std::f32::consts::PI * frequency * t).sin()
+        })
+        .collect()
+}
+
+/// `duration` of 16KHz mono white noise peaking at `amplitude`, the same on every call.
+pub fn noise(duration: Duration, amplitude: f32) -> Vec<f32> {
+    let mut state = 0x2545_f491u32;
+    (0..samples_in(duration))
+        .map(|_| {
+            state = state.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+            (state as f32 / u32::MAX as f32 * 2.0 - 1.0) * amplitude
+        })
+        .collect()
+}
+
+/// Two seconds of 16KHz mono English speech, the start of the sentence "By what he has
+/// said and done".
+pub fn speech_clip() -> Vec<f32> {
+    SPEECH_CLIP
+        .chunks_exact(4)
+        .map(|bytes| f32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+        .collect()
+}
+
+/// Save 16KHz mono samples as a 16-bit PCM wav file, clipping samples outside -1..=1.
+pub fn write_wav(path: impl AsRef<Path>, samples: &[f32]) {
+    let samples: Vec<i16> = samples
+        .iter()
+        .map(|sample| (sample.clamp(-1.0, 1.0) * i16::MAX as f32).round() as i16)
+        .collect();
+    write_pcm_wav(path.as_ref(), SAMPLE_RATE, 1, &samples);
+}
+
+/// Save interleaved 16-bit PCM samples as a wav file.
+pub(crate) fn write_pcm_wav(path: &Path, sample_rate: u32, channels: u16, samples: &[i16]) {
+    let data_len = (samples.len() * 2) as u32;
+    let mut bytes = Vec::with_capacity(44 + data_len as usize);
+    bytes.extend_from_slice(b"RIFF");
+    bytes.extend_from_slice(&(36 + data_len).to_le_bytes());
+    bytes.extend_from_slice(b"WAVEfmt ");
+    bytes.extend_from_slice(&16u32.to_le_bytes());
+    bytes.extend_from_slice(&1u16.to_le_bytes());
+    bytes.extend_from_slice(&channels.to_le_bytes());
+    bytes.extend_from_slice(&sample_rate.to_le_bytes());
+    bytes.extend_from_slice(&(sample_rate * channels as u32 * 2).to_le_bytes());
+    bytes.extend_from_slice(&(channels * 2).to_le_bytes());
+    bytes.extend_from_slice(&16u16.to_le_bytes());
+    bytes.extend_from_slice(b"data");
+    bytes.extend_from_slice(&data_len.to_le_bytes());
+    for sample in samples {
+        bytes.extend_from_slice(&sample.to_le_bytes());
+    }
+    std::fs::write(path, bytes).unwrap();
+}
+
+/// Save `samples` like `write_wav`, in a file declaring `language` in the `ILNG` tag of an
+/// INFO list, as a fixture for `TranscribeOptions::use_metadata_language`.
+pub fn write_wav_with_language(path: impl AsRef<Path>, samples: &[f32], language: &str) {
+    let path = path.as_ref();
+    write_wav(path, samples);
+    let mut bytes = std::fs::read(path).unwrap();
+
+    let mut value = language.as_bytes().to_vec();
+    value.push(0);
+    let mut ilng = b"ILNG".to_vec();
+    ilng.extend_from_slice(&(value.len() as u32).to_le_bytes());
+    ilng.extend_from_slice(&value);
+    if value.len() % 2 == 1 {
+        ilng.push(0);
+    }
+    let mut list = b"LIST".to_vec();
+    list.extend_from_slice(&(4 + ilng.len() as u32).to_le_bytes());
+    list.extend_from_slice(b"INFO");
+    list.extend_from_slice(&ilng);
+
+    let riff_len = u32::from_le_bytes(bytes[4..8].try_into().unwrap()) + list.len() as u32;
+    bytes[4..8].copy_from_slice(&riff_len.to_le_bytes());
+    bytes.splice(36..36, list);
+    std::fs::write(path, bytes).unwrap();
+}
+
+fn samples_in(duration: Duration) -> usize {
+    (duration.as_secs_f64() * SAMPLE_RATE as f64).round() as usize
+}
+
+/// The end of text token of whisper's multilingual vocabulary.
+const TOKEN_EOT: c_int = 50257;
+
+/// A segment a `MockBackend` decodes, timed in centiseconds from the start of the audio
+/// an inference run is given (the chunk, in chunked mode).
+#[derive(Debug, Clone, PartialEq)]
+pub struct MockSegment {
+    pub start: i64,
+    pub end: i64,
+    pub text: String,
+    /// The probability of each word's token, words past its end have probability 1.
+    pub probabilities: Vec<f32>,
+}
+
+impl MockSegment {
+    pub fn new(start: i64, end: i64, text: impl Into<String>) -> MockSegment {
+        MockSegment {
+            start,
+            end,
+            text: text.into(),
+            probabilities: Vec::new(),
+        }
+    }
+
+    /// Give the tokens of the segment's words the given probabilities, in order.
+    pub fn with_probabilities(mut self, probabilities: Vec<f32>) -> MockSegment {
+        self.probabilities = probabilities;
+        self
+    }
+
+    /// One token per word, sharing the segment's time evenly, after a special token as
+    /// whisper's segments start with.
+    fn tokens(&self) -> Vec<(c_int, String, TokenData)> {
+        // the space before a word belongs to it, like in whisper's tokens
+        let mut words: Vec<String> = Vec::new();
+        for (i, part) in self.text.split(' ').enumerate() {
+            if part.is_empty() {
+                continue;
+            }
+            let space = if i > 0 { " " } else { "" };
+            words.push(format!("{}{}", space, part));
+        }
+
+        let duration = (self.end - self.start).max(0);
+        let n = words.len().max(1) as i64;
+        let mut tokens = vec![(
+            TOKEN_EOT + 1,
+            "[_BEG_]".to_string(),
+            TokenData {
+                t0: self.start,
+                t1: self.start,
+                p: 1.0,
+            },
+        )];
+        for (i, word) in words.into_iter().enumerate() {
+            let i = i as i64;
+            tokens.push((
+                i as c_int,
+                word,
+                TokenData {
+                    t0: self.start + duration * i / n,
+                    t1: self.start + duration * (i + 1) / n,
+                    p: self.probabilities.get(i as usize).copied().unwrap_or(1.0),
+                },
+            ));
+        }
+        tokens
+    }
+}
+
+/// A model decoding scripted segments, see `Transcriber::with_mock_backend`.
+pub struct MockBackend {
+    runs: Vec<Vec<MockSegment>>,
+    failures: Mutex<VecDeque<WhisperError>>,
+    completed_runs: AtomicUsize,
+    multilingual: bool,
+    vocab_size: Option<c_int>,
+    n_mels: c_int,
+    filterbank_n_mels: Option<c_int>,
+    latency: Duration,
+    token_timestamps: bool,
+}
+
+impl MockBackend {
+    /// The memory a mock inference state counts against a `memory::MemoryBudget`, in bytes.
+    pub const STATE_MEMORY_BYTES: u64 = 1024 * 1024;
+
+    /// A model decoding `segments` in every inference run.
+    pub fn new(segments: Vec<MockSegment>) -> MockBackend {
+        MockBackend::with_runs(vec![segments])
+    }
+
+    /// A model decoding `runs[i]` in its i-th successful inference run, the last run repeats.
+    ///
+    /// Chunked transcriptions run once per chunk.
+    pub fn with_runs(runs: Vec<Vec<MockSegment>>) -> MockBackend {
+        MockBackend {
+            runs,
+            failures: Mutex::new(VecDeque::new()),
+            completed_runs: AtomicUsize::new(0),
+            multilingual: true,
+            vocab_size: None,
+            n_mels: 80,
+            filterbank_n_mels: Some(80),
+            latency: Duration::ZERO,
+            token_timestamps: true,
+        }
+    }
+
+    /// Fail the next inference runs with `errors`, one per run, before decoding the script.
+    pub fn failing_with(self, errors: Vec<WhisperError>) -> MockBackend {
+        MockBackend {
+            failures: Mutex::new(errors.into()),
+            ..self
+        }
+    }
+
+    /// Take `latency` for every inference run, like a real model would.
+    pub fn with_latency(self, latency: Duration) -> MockBackend {
+        MockBackend { latency, ..self }
+    }
+
+    /// Leave token timings uncomputed, as whisper builds without token timestamps do.
+    pub fn without_token_timestamps(self) -> MockBackend {
+        MockBackend {
+            token_timestamps: false,
+            ..self
+        }
+    }
+
+    /// Behave like an English-only model.
+    pub fn english_only(self) -> MockBackend {
+        MockBackend {
+            multilingual: false,
+            ..self
+        }
+    }
+
+    /// Report a vocabulary of `vocab_size` tokens, like a fine-tuned model with a non stock
+    /// vocabulary.
+    pub fn with_vocab_size(self, vocab_size: c_int) -> MockBackend {
+        MockBackend {
+            vocab_size: Some(vocab_size),
+            ..self
+        }
+    }
+
+    /// Take `n_mels` mel bands, shipping a filterbank of `filterbank_n_mels` bands (`None`
+    /// for an unknown filterbank). Unlike a real model, inference runs whatever they are.
+    pub fn with_mel_bands(self, n_mels: c_int, filterbank_n_mels: Option<c_int>) -> MockBackend {
+        MockBackend {
+            n_mels,
+            filterbank_n_mels,
+            ..self
+        }
+    }
+}
+
+impl InferenceBackend for MockBackend {
+    fn create_state(&self, _use_gpu: bool) -> Result<Box<dyn InferenceState + '_>, WhisperError> {
+        Ok(Box::new(MockState {
+            backend: self,
+            segments: &[],
+        }))
+    }
+
+    fn model_type(&self) -> String {
+        "mock".to_string()
+    }
+
+    fn is_multilingual(&self) -> bool {
+        self.multilingual
+    }
+
+    fn n_vocab(&self) -> c_int {
+        match self.vocab_size {
+            Some(vocab_size) => vocab_size,
+            None if self.multilingual => 51865,
+            None => 51864,
+        }
+    }
+
+    #[cfg(feature = "mel-spectrogram")]
+    fn n_mels(&self) -> c_int {
+        self.n_mels
+    }
+
+    fn token_eot(&self) -> c_int {
+        TOKEN_EOT
+    }
+
+    /// The hyperparameters of the tiny model.
+    fn hyperparameters(&self) -> ModelHyperparameters {
+        ModelHyperparameters {
+            model_type: self.model_type(),
+            n_vocab: self.n_vocab(),
+            n_audio_ctx: 1500,
+            n_audio_state: 384,
+            n_audio_head: 6,
+            n_audio_layer: 4,
+            n_text_ctx: 448,
+            n_text_state: 384,
+            n_text_head: 6,
+            n_text_layer: 4,
+            n_mels: self.n_mels,
+            ftype: 1,
+        }
+    }
+
+    fn mel_filterbank_n_mels(&self) -> Option<c_int> {
+        self.filterbank_n_mels
+    }
+
+    fn state_memory_bytes(&self) -> u64 {
+        MockBackend::STATE_MEMORY_BYTES
+    }
+}
+
+struct MockState<'a> {
+    backend: &'a MockBackend,
+    segments: &'a [MockSegment],
+}
+
+impl MockState<'_> {
+    fn segment(&self, segment: c_int) -> Result<&MockSegment, WhisperError> {
+        usize::try_from(segment)
+            .ok()
+            .and_then(|i| self.segments.get(i))
+            .ok_or(WhisperError::GenericError(-1))
+    }
+
+    fn token(
+        &self,
+        segment: c_int,
+        token: c_int,
+    ) -> Result<(c_int, String, TokenData), WhisperError> {
+        let mut tokens = self.segment(segment)?.tokens();
+        usize::try_from(token)
+            .ok()
+            .filter(|&j| j < tokens.len())
+            .map(|j| tokens.swap_remove(j))
+            .ok_or(WhisperError::GenericError(-1))
+    }
+}
+
+impl InferenceState for MockState<'_> {
+    /// Report every scripted segment, with the progress after it, to `hooks` like whisper
+    /// does, aborting once a hook failed.
+    fn full(
+        &mut self,
+        _params: FullParams<'_, '_>,
+        _audio: &[f32],
+        hooks: &mut Hooks<'_, '_>,
+    ) -> Result<(), WhisperError> {
+        if let Some(err) = self.backend.failures.lock().unwrap().pop_front() {
+            return Err(err);
+        }
+        std::thread::sleep(self.backend.latency);
+        let run = self.backend.completed_runs.fetch_add(1, Ordering::SeqCst);
+        let runs = &self.backend.runs;
+        self.segments = runs
+            .get(run)
+            .or_else(|| runs.last())
+            .map(Vec::as_slice)
+            .unwrap_or_default();
+        let n_segments = self.segments.len().max(1);
+        for (i, segment) in self.segments.iter().enumerate() {
+            hooks.segment(segment.start, segment.end, &segment.text);
+            hooks.progress(((i + 1) * 100 / n_segments) as i32);
+            if hooks.failed() {
+                // whisper fails runs its abort callback stopped
+                return Err(WhisperError::GenericError(-6));
+            }
+        }
+        Ok(())
+    }
+
+    fn n_segments(&self) -> Result<c_int, WhisperError> {
+        Ok(self.segments.len() as c_int)
+    }
+
+    fn segment_text(&self, segment: c_int) -> Result<String, WhisperError> {
+        Ok(self.segment(segment)?.text.clone())
+    }
+
+    fn segment_text_into(&self, segment: c_int, text: &mut String) -> Result<(), WhisperError> {
+        text.push_str(&self.segment(segment)?.text);
+        Ok(())
+    }
+
+    fn segment_t0(&self, segment: c_int) -> Result<i64, WhisperError> {
+        Ok(self.segment(segment)?.start)
+    }
+
+    fn segment_t1(&self, segment: c_int) -> Result<i64, WhisperError> {
+        Ok(self.segment(segment)?.end)
+    }
+
+    fn n_tokens(&self, segment: c_int) -> Result<c_int, WhisperError> {
+        Ok(self.segment(segment)?.tokens().len() as c_int)
+    }
+
+    fn token_id(&self, segment: c_int, token: c_int) -> Result<c_int, WhisperError> {
+        Ok(self.token(segment, token)?.0)
+    }
+
+    fn token_text(&self, segment: c_int, token: c_int) -> Result<String, WhisperError> {
+        Ok(self.token(segment, token)?.1)
+    }
+
+    fn token_data(&self, segment: c_int, token: c_int) -> Result<TokenData, WhisperError> {
+        let data = self.token(segment, token)?.2;
+        if self.backend.token_timestamps {
+            Ok(data)
+        } else {
+            Ok(TokenData {
+                t0: -1,
+                t1: -1,
+                ..data
+            })
+        }
+    }
+
+    /// Always English.
+    fn lang_id(&self) -> Result<c_int, WhisperError> {
+        Ok(0)
+    }
+}
+
+/// A transcriber whose transcripts are scripted, to test code consuming `TranscriberOutput`s
+/// such as exporters and pipelines.
+///
+/// Transcripts go through the transcriber's usual post processing, so they look like real
+/// ones: warnings, manifest and the options' effects included.
+pub struct MockTranscriber {
+    transcriber: Transcriber,
+    audio_duration: Duration,
+}
+
+impl MockTranscriber {
+    /// A transcriber decoding `segments`, as if from audio lasting until the last one ends.
+    pub fn new(segments: Vec<MockSegment>) -> MockTranscriber {
+        let end = segments
+            .iter()
+            .map(|segment| segment.end)
+            .max()
+            .unwrap_or(0);
+        MockTranscriber {
+            audio_duration: Duration::from_millis(end.max(0) as u64 * 10),
+            transcriber: Transcriber::with_mock_backend(MockBackend::new(segments)),
+        }
+    }
+
+    /// Script the transcript of audio lasting `audio_duration` instead.
+    pub fn with_audio_duration(self, audio_duration: Duration) -> MockTranscriber {
+        MockTranscriber {
+            audio_duration,
+            ..self
+        }
+    }
+
+    /// The scripted transcript with the default options.
+    pub fn output(&self) -> TranscriberOutput {
+        self.output_with_options(&TranscribeOptions::default())
+    }
+
+    /// The scripted transcript, post processed according to `options`.
+    pub fn output_with_options(&self, options: &TranscribeOptions) -> TranscriberOutput {
+        self.transcriber
+            .transcribe_samples(&silence(self.audio_duration), options)
+            .expect("a scripted transcription cannot fail")
+    }
+
+    /// The underlying transcriber, to transcribe files or streams with the script.
+    pub fn transcriber(&self) -> &Transcriber {
+        &self.transcriber
+    }
+}
+
+impl Transcriber {
+    /// A transcriber running on a scripted model instead of whisper.
+    pub fn with_mock_backend(backend: MockBackend) -> Transcriber {
+        Transcriber {
+            backend: Box::new(backend),
+            use_gpu: false,
+            memory_budget: None,
+            model_sha256: None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::audio_parser;
+    use crate::export::TextSeparator;
+    use crate::warning::Warning;
+
+    fn rms(samples: &[f32]) -> f32 {
+        (samples.iter().map(|sample| sample * sample).sum::<f32>() / samples.len() as f32).sqrt()
+    }
+
+    #[test]
+    fn test_synthesized_audio() {
+        let silence = silence(Duration::from_millis(1500));
+        let tone = tone(100.0, Duration::from_secs(1), 0.5);
+
+        assert_eq!(silence.len(), 24000);
+        assert!(silence.iter().all(|&sample| sample == 0.0));
+        assert_eq!(tone.len(), 16000);
+        assert!((rms(&tone) - 0.5 / 2f32.sqrt()).abs() < 1e-3);
+        // a 100Hz sine crosses zero upwards once per period
+        let upward_crossings = tone
+            .windows(2)
+            .filter(|w| w[0] < 0.0 && w[1] >= 0.0)
+            .count();
+        assert_eq!(upward_crossings, 99);
+    }
+
+    #[test]
+    fn test_speech_clip_is_the_start_of_the_test_recording() {
+        let clip = speech_clip();
+
+        assert_eq!(clip.len(), 32000);
+        assert!(rms(&clip) > 0.05);
+        assert_eq!(
+            clip[..],
+            audio_parser::parse_audio_file("src/test_data/test.mp3")[..32000]
+        );
+    }
+
+    #[test]
+    fn test_write_wav_round_trips() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("tone.wav");
+        let tone = tone(440.0, Duration::from_millis(250), 0.5);
+
+        write_wav(&path, &tone);
+
+        let decoded = audio_parser::parse_audio_file(&path);
+        assert_eq!(decoded.len(), tone.len());
+        assert!(decoded
+            .iter()
+            .zip(&tone)
+            .all(|(decoded, original)| (decoded - original).abs() < 1e-4));
+    }
+
+    #[test]
+    fn test_mock_transcriber_outputs_script() {
+        let mock = MockTranscriber::new(vec![
+            MockSegment::new(0, 150, " Hello world."),
+            MockSegment::new(150, 300, " Bye."),
+        ]);
+        let options = TranscribeOptions {
+            min_duration: Duration::from_secs(5),
+            ..Default::default()
+        };
+
+        let output = mock.output();
+        let padded = mock.output_with_options(&options);
+
+        assert_eq!(
+            output.to_plain_text(TextSeparator::Space),
+            "Hello world. Bye."
+        );
+        assert_eq!(output.get_audio_duration(), Some(300));
+        assert!(output.get_warnings().is_empty());
+        assert!(output.manifest().is_some());
+        assert!(matches!(
+            padded.get_warnings()[..],
+            [Warning::AudioPadded { duration: 300, .. }]
+        ));
+        let longer = MockTranscriber::new(Vec::new()).with_audio_duration(Duration::from_secs(2));
+        assert_eq!(longer.output().get_audio_duration(), Some(200));
+    }
+}
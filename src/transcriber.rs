@@ -1,25 +1,659 @@
+use std::borrow::Cow;
+use std::path::Path;
+
 use crate::audio_parser;
+use crate::backend::{InferenceBackend, InferenceState, WhisperBackend};
+use crate::batch::SourceRef;
+use crate::callbacks::{Callbacks, Hooks};
+use crate::change_log::{Change, ChangeLog};
+use crate::checkpoint::{self, Checkpoint, CheckpointFile, CheckpointHeader};
+use crate::data_uri;
+use crate::denoise;
+use crate::error::Error;
+use crate::export;
+use crate::gain;
+use crate::manifest::{Manifest, ModelManifest};
+use crate::memory::{self, MemoryBudget};
 use crate::model_handler;
+use crate::options::{
+    EmptyAudioPolicy, NoiseGate, OverlapPolicy, RetryPolicy, TimestampPolicy, TranscribeOptions,
+};
+use crate::postprocess;
+use crate::quality;
+use crate::retry;
+use crate::system::{
+    self, Backend, ContextInfo, Diagnostics, ModelHyperparameters, STOCK_VOCAB_SIZES,
+    SUPPORTED_N_MELS,
+};
+use crate::warning::Warning;
+
+/// Where the language of a transcript came from, see `TranscriberOutput::get_language`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize)]
+pub enum LanguageSource {
+    /// `TranscribeOptions::language`.
+    Options,
+    /// The file's metadata, see `TranscribeOptions::use_metadata_language`.
+    Metadata,
+    /// whisper's language detection.
+    Detected,
+}
+
+/// Audio to transcribe, either a file or 16KHz mono samples.
+#[derive(Debug, Clone, Copy)]
+pub enum AudioInput<'a> {
+    File(&'a Path),
+    Samples(&'a [f32]),
+}
+
+impl<'a> From<&'a Path> for AudioInput<'a> {
+    fn from(path: &'a Path) -> Self {
+        AudioInput::File(path)
+    }
+}
+
+impl<'a> From<&'a std::path::PathBuf> for AudioInput<'a> {
+    fn from(path: &'a std::path::PathBuf) -> Self {
+        AudioInput::File(path)
+    }
+}
+
+impl<'a> From<&'a str> for AudioInput<'a> {
+    fn from(path: &'a str) -> Self {
+        AudioInput::File(Path::new(path))
+    }
+}
+
+impl<'a> From<&'a [f32]> for AudioInput<'a> {
+    fn from(samples: &'a [f32]) -> Self {
+        AudioInput::Samples(samples)
+    }
+}
+
+impl<'a> From<&'a Vec<f32>> for AudioInput<'a> {
+    fn from(samples: &'a Vec<f32>) -> Self {
+        AudioInput::Samples(samples)
+    }
+}
 
-#[derive(Debug)]
+#[derive(Debug, Default, serde::Serialize)]
 pub struct TranscriberOutput {
-    segments: Vec<TranscriberOutputSegment>,
+    pub(crate) segments: Vec<TranscriberOutputSegment>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) audio_duration: Option<i64>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub(crate) warnings: Vec<Warning>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) diagnostics: Option<Diagnostics>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) language: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) language_source: Option<LanguageSource>,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub(crate) translated: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) manifest: Option<Manifest>,
+    #[serde(skip_serializing_if = "ChangeLog::is_empty")]
+    pub(crate) change_log: ChangeLog,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub(crate) chunk_boundaries: Vec<i64>,
+    /// The id the next new segment gets.
+    #[serde(skip)]
+    pub(crate) next_id: u64,
 }
 
 impl TranscriberOutput {
+    /// An output of `segments`, numbered in order from 0.
+    pub(crate) fn new(mut segments: Vec<TranscriberOutputSegment>) -> TranscriberOutput {
+        let next_id = number_segments(&mut segments);
+        TranscriberOutput::numbered(segments, next_id)
+    }
+
+    /// An output of `segments` already numbered, whose new segments get ids from `next_id`.
+    fn numbered(segments: Vec<TranscriberOutputSegment>, next_id: u64) -> TranscriberOutput {
+        TranscriberOutput {
+            next_id,
+            change_log: ChangeLog::default(),
+            segments,
+            audio_duration: None,
+            warnings: Vec::new(),
+            diagnostics: None,
+            language: None,
+            language_source: None,
+            translated: false,
+            manifest: None,
+            chunk_boundaries: Vec::new(),
+        }
+    }
+
+    pub(crate) fn with_audio_duration(mut self, audio_duration: i64) -> TranscriberOutput {
+        self.audio_duration = Some(audio_duration);
+        self
+    }
+
     pub fn get_segments(&self) -> &Vec<TranscriberOutputSegment> {
         &self.segments
     }
+
+    /// Duration of the transcribed audio in centiseconds, when known.
+    pub fn get_audio_duration(&self) -> Option<i64> {
+        self.audio_duration
+    }
+
+    /// Non fatal issues noticed while transcribing.
+    pub fn get_warnings(&self) -> &Vec<Warning> {
+        &self.warnings
+    }
+
+    /// The whisper code of the spoken language, such as "en", as given in the options or
+    /// detected by whisper. `None` when nothing was decoded.
+    pub fn get_language(&self) -> Option<&str> {
+        self.language.as_deref()
+    }
+
+    /// Where the language came from, `None` when there is none.
+    pub fn get_language_source(&self) -> Option<LanguageSource> {
+        self.language_source
+    }
+
+    /// The centiseconds a chunked transcription (see `TranscribeOptions::chunk_length`) cut
+    /// the audio at, empty when it wasn't chunked.
+    pub fn get_chunk_boundaries(&self) -> &[i64] {
+        &self.chunk_boundaries
+    }
+
+    /// Whether the text was translated to English.
+    pub fn is_translated(&self) -> bool {
+        self.translated
+    }
+
+    /// System and model information, only populated when
+    /// `TranscribeOptions::include_diagnostics` is enabled.
+    pub fn get_diagnostics(&self) -> Option<&Diagnostics> {
+        self.diagnostics.as_ref()
+    }
+
+    /// How the transcript was produced: versions, model and options. `None` only for
+    /// outputs not produced by a `Transcriber`.
+    pub fn manifest(&self) -> Option<&Manifest> {
+        self.manifest.as_ref()
+    }
+
+    /// The segments whose text contains `query`, in order, to find where a phrase is said.
+    /// With `case_insensitive`, both are compared lowercased. An empty query matches no
+    /// segment.
+    pub fn search(&self, query: &str, case_insensitive: bool) -> Vec<&TranscriberOutputSegment> {
+        if query.is_empty() {
+            return Vec::new();
+        }
+        if !case_insensitive {
+            return self
+                .segments
+                .iter()
+                .filter(|segment| segment.text.contains(query))
+                .collect();
+        }
+        let query = query.to_lowercase();
+        self.segments
+            .iter()
+            .filter(|segment| segment.text.to_lowercase().contains(&query))
+            .collect()
+    }
+
+    /// How post processing replaced and removed segments since the transcription, to follow
+    /// segments by their ids.
+    pub fn change_log(&self) -> &ChangeLog {
+        &self.change_log
+    }
+
+    /// Keep only the segments `keep` returns `true` for, recording the others as dropped.
+    pub fn retain_segments(&mut self, mut keep: impl FnMut(&TranscriberOutputSegment) -> bool) {
+        let mut dropped = Vec::new();
+        self.segments.retain(|segment| {
+            let kept = keep(segment);
+            if !kept {
+                dropped.push(segment.id);
+            }
+            kept
+        });
+        self.change_log.record(Change::Dropped { ids: dropped });
+    }
+
+    /// A new segment id, higher than every id before it.
+    fn new_id(&mut self) -> u64 {
+        self.next_id += 1;
+        self.next_id - 1
+    }
+
+    /// Give the segments new ids, returning them.
+    fn renumber<'s>(
+        &mut self,
+        segments: impl Iterator<Item = &'s mut TranscriberOutputSegment>,
+    ) -> Vec<u64> {
+        segments
+            .map(|segment| {
+                segment.id = self.new_id();
+                segment.id
+            })
+            .collect()
+    }
+
+    /// Remove the overlap between consecutive segments using the given policy.
+    ///
+    /// Segments are expected in start order, as the transcriber returns them. Word timings
+    /// of a trimmed segment are clamped into its new range.
+    pub fn resolve_overlaps(&mut self, policy: OverlapPolicy) {
+        postprocess::resolve_overlaps(&mut self.segments, policy);
+    }
+
+    /// Sort the segments by start time and start each no earlier than the previous one
+    /// ends, so they are in order and never overlap, as subtitle formats require.
+    ///
+    /// A start moved past its segment's end also moves the end, word timings are clamped
+    /// into the new range.
+    pub fn normalize_segments(&mut self) {
+        postprocess::normalize_segments(&mut self.segments);
+    }
+
+    /// The time covered by at least one segment, in milliseconds. Overlapping segments are
+    /// only counted once.
+    pub fn speech_duration_ms(&self) -> u64 {
+        let mut intervals: Vec<(i64, i64)> = self
+            .segments
+            .iter()
+            .map(|s| (s.start_timestamp.max(0), s.end_timestamp.max(0)))
+            .filter(|(start, end)| start < end)
+            .collect();
+        intervals.sort_unstable();
+        let mut covered = 0;
+        let mut reached = 0;
+        for (start, end) in intervals {
+            if end > reached {
+                covered += end - start.max(reached);
+                reached = end;
+            }
+        }
+        (covered * 10) as u64
+    }
+
+    /// The fraction of the audio covered by segments, between 0 and 1, as a speech density
+    /// metric. Overlapping segments are only counted once and time past `total_audio_ms` is
+    /// ignored.
+    ///
+    /// # Arguments
+    ///
+    /// * `total_audio_ms` - the duration of the audio. Use `get_audio_duration` (which is in
+    ///   centiseconds) times 10 for the transcribed audio itself.
+    pub fn speech_ratio(&self, total_audio_ms: u64) -> f64 {
+        if total_audio_ms == 0 {
+            return 0.0;
+        }
+        let clamped = TranscriberOutput::new(
+            self.segments
+                .iter()
+                .map(|s| {
+                    let end = (total_audio_ms / 10) as i64;
+                    TranscriberOutputSegment::new(
+                        s.start_timestamp.min(end),
+                        s.end_timestamp.min(end),
+                        "",
+                    )
+                })
+                .collect(),
+        );
+        clamped.speech_duration_ms() as f64 / total_audio_ms as f64
+    }
+
+    /// Split the segments whose text does not fit in `max_lines` lines of `max_line_chars`
+    /// characters, wrapped as `SubtitleOptions::max_line_chars` wraps cues, between words.
+    ///
+    /// The pieces are timed by their word timings when the segment has them, otherwise by
+    /// sharing the segment's time in proportion to their length.
+    pub fn split_segments(&mut self, max_line_chars: usize, max_lines: usize) {
+        let segments = std::mem::take(&mut self.segments);
+        let mut split = postprocess::split_segments(segments, |text| {
+            export::fits_lines(text, max_line_chars, max_lines)
+        });
+        // the pieces of a split segment follow each other, all with its id
+        let mut i = 0;
+        while i < split.len() {
+            let parent = split[i].id;
+            let pieces = split[i..].iter().take_while(|s| s.id == parent).count();
+            if pieces > 1 {
+                let into = self.renumber(split[i..i + pieces].iter_mut());
+                self.change_log.record(Change::Split { parent, into });
+            }
+            i += pieces;
+        }
+        self.segments = split;
+    }
+
+    /// Merge consecutive segments at most `max_gap` apart, as long as the merged text fits in
+    /// `max_lines` lines of `max_line_chars` characters.
+    pub fn merge_segments(
+        &mut self,
+        max_gap: std::time::Duration,
+        max_line_chars: usize,
+        max_lines: usize,
+    ) {
+        let segments = std::mem::take(&mut self.segments);
+        let ids: Vec<u64> = segments.iter().map(|s| s.id).collect();
+        let mut merged =
+            postprocess::merge_segments(segments, (max_gap.as_millis() / 10) as i64, |text| {
+                export::fits_lines(text, max_line_chars, max_lines)
+            });
+        // a merged segment keeps the id of its first source, and holds the ones up to the
+        // next segment's
+        let mut next = 0;
+        for i in 0..merged.len() {
+            let start = next;
+            next += 1;
+            while next < ids.len() && merged.get(i + 1).map(|s| s.id) != Some(ids[next]) {
+                next += 1;
+            }
+            if next - start > 1 {
+                let into = self.new_id();
+                merged[i].id = into;
+                let sources = ids[start..next].to_vec();
+                self.change_log.record(Change::Merged { sources, into });
+            }
+        }
+        self.segments = merged;
+    }
+
+    /// Add the segments of `other`, a transcription of audio starting `time_offset_ms` into
+    /// this one's, with their timestamps shifted by the offset, to stitch together the
+    /// transcriptions of chunks split by hand.
+    ///
+    /// The offset is rounded to whole centiseconds, the resolution of timestamps. Segments
+    /// are kept sorted by start, `other`'s warnings are added, and the audio duration grows
+    /// to cover `other`'s audio. A missing language is taken from `other`.
+    pub fn append(&mut self, mut other: TranscriberOutput, time_offset_ms: i64) {
+        let offset = (time_offset_ms as f64 / 10.0).round() as i64;
+        postprocess::shift_segments(&mut other.segments, offset);
+        let ids = self.renumber(other.segments.iter_mut());
+        self.change_log.record(Change::Inserted { ids });
+        self.segments.append(&mut other.segments);
+        postprocess::sort_segments(&mut self.segments);
+        self.warnings.append(&mut other.warnings);
+        if let Some(duration) = other.audio_duration {
+            self.audio_duration = Some(self.audio_duration.unwrap_or(0).max(offset + duration));
+        }
+        if self.language.is_none() {
+            self.language = other.language;
+            self.language_source = other.language_source;
+        }
+    }
+
+    /// Replace the segments overlapping `range` with `replacement`, a transcription of just
+    /// the audio in `range` such as `Transcriber::retranscribe_range` returns, whose
+    /// timestamps start from the start of the range.
+    ///
+    /// Segments straddling an edge of the range are cut down to their words outside of it,
+    /// timed by their word timings when they have them and by sharing their time in
+    /// proportion to word length otherwise. The replacement is kept within the range and
+    /// its warnings are added to the output's. Segments stay sorted, and those that didn't
+    /// overlap before don't overlap after.
+    pub fn splice(
+        &mut self,
+        range: std::ops::Range<std::time::Duration>,
+        replacement: TranscriberOutput,
+    ) {
+        let start = (range.start.as_millis() / 10) as i64;
+        let end = ((range.end.as_millis() / 10) as i64).max(start);
+        let overlapping: Vec<u64> = self
+            .segments
+            .iter()
+            .filter(|s| s.end_timestamp > start && s.start_timestamp < end)
+            .map(|s| s.id)
+            .collect();
+        let mut inserted = replacement.segments;
+        let ids = self.renumber(inserted.iter_mut());
+        self.change_log.record(Change::Inserted { ids });
+
+        let segments = std::mem::take(&mut self.segments);
+        let mut spliced = postprocess::splice(segments, start, end, inserted);
+        let mut dropped = Vec::new();
+        for parent in overlapping {
+            let pieces: Vec<&mut TranscriberOutputSegment> =
+                spliced.iter_mut().filter(|s| s.id == parent).collect();
+            if pieces.is_empty() {
+                dropped.push(parent);
+            } else {
+                let into = self.renumber(pieces.into_iter());
+                self.change_log.record(Change::Split { parent, into });
+            }
+        }
+        self.change_log.record(Change::Dropped { ids: dropped });
+        self.segments = spliced;
+        self.warnings.extend(replacement.warnings);
+    }
+
+    /// Mark the segments following a pause long enough for another of `expected_speakers`
+    /// speakers to take over as speaker turns, clearing earlier marks.
+    pub fn mark_speaker_turns(&mut self, expected_speakers: u32) {
+        postprocess::mark_speaker_turns(
+            &mut self.segments,
+            postprocess::speaker_turn_gap(expected_speakers),
+        );
+    }
+
+    /// Lengthen segments that would have to be read faster than `max_chars_per_second`, first
+    /// into the silence after them and then into the silence before them. Segments never grow
+    /// into their neighbours, so a segment without enough silence around it stays too fast.
+    pub fn extend_for_reading_speed(&mut self, max_chars_per_second: f32) {
+        postprocess::extend_for_reading_speed(
+            &mut self.segments,
+            max_chars_per_second,
+            self.audio_duration,
+        );
+    }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
 pub struct TranscriberOutputSegment {
-    start_timestamp: i64,
-    end_timestamp: i64,
-    text: String,
+    #[serde(default)]
+    pub(crate) id: u64,
+    pub(crate) start_timestamp: i64,
+    pub(crate) end_timestamp: i64,
+    pub(crate) text: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub(crate) words: Vec<TranscriberOutputWord>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub(crate) token_probabilities: Vec<f32>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub(crate) token_ids: Vec<i32>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub(crate) alternatives: Vec<TranscriberOutputAlternative>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) window_index: Option<usize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) decode_time_ms: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) seek: Option<i64>,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub(crate) speaker_turn: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) source: Option<SourceRef>,
 }
 
 impl TranscriberOutputSegment {
+    pub(crate) fn new(
+        start_timestamp: i64,
+        end_timestamp: i64,
+        text: impl Into<String>,
+    ) -> TranscriberOutputSegment {
+        TranscriberOutputSegment {
+            id: 0,
+            start_timestamp,
+            end_timestamp,
+            text: text.into(),
+            words: Vec::new(),
+            token_probabilities: Vec::new(),
+            token_ids: Vec::new(),
+            alternatives: Vec::new(),
+            window_index: None,
+            decode_time_ms: None,
+            seek: None,
+            speaker_turn: false,
+            source: None,
+        }
+    }
+
+    /// The segment emptied for reuse with new timestamps, keeping the allocations of its text
+    /// and words.
+    fn recycle(mut self, start_timestamp: i64, end_timestamp: i64) -> TranscriberOutputSegment {
+        let mut text = std::mem::take(&mut self.text);
+        text.clear();
+        let mut words = std::mem::take(&mut self.words);
+        words.clear();
+        let mut token_probabilities = std::mem::take(&mut self.token_probabilities);
+        token_probabilities.clear();
+        let mut token_ids = std::mem::take(&mut self.token_ids);
+        token_ids.clear();
+        TranscriberOutputSegment {
+            text,
+            words,
+            token_probabilities,
+            token_ids,
+            ..TranscriberOutputSegment::new(start_timestamp, end_timestamp, String::new())
+        }
+    }
+
+    pub(crate) fn with_words(
+        mut self,
+        words: Vec<TranscriberOutputWord>,
+    ) -> TranscriberOutputSegment {
+        self.words = words;
+        self
+    }
+
+    /// The segment's id, unique within its output and kept through post processing that
+    /// leaves the segment whole, see `TranscriberOutput::change_log`.
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    pub fn get_start_timestamp(&self) -> &i64 {
+        &self.start_timestamp
+    }
+
+    pub fn get_end_timestamp(&self) -> &i64 {
+        &self.end_timestamp
+    }
+
+    /// Index of the segment's first sample in the 16KHz audio it was transcribed from,
+    /// exact since timestamps are whole centiseconds of 160 samples. Slice a segment's audio
+    /// with `&audio[start..end.min(audio.len())]`, the end can reach past the last sample of
+    /// audio that isn't a whole number of centiseconds long.
+    pub fn get_start_sample(&self) -> usize {
+        timestamp_to_sample(self.start_timestamp)
+    }
+
+    /// Index one past the segment's last sample in the 16KHz audio, see `get_start_sample`.
+    pub fn get_end_sample(&self) -> usize {
+        timestamp_to_sample(self.end_timestamp)
+    }
+
+    pub fn get_text(&self) -> &str {
+        &self.text
+    }
+
+    /// Word level timings, only populated when `TranscribeOptions::word_timestamps` is enabled.
+    pub fn get_words(&self) -> &Vec<TranscriberOutputWord> {
+        &self.words
+    }
+
+    /// The probability of every text token of the segment in order, only populated when
+    /// `TranscribeOptions::token_probabilities` is enabled. Special tokens such as timestamps
+    /// are left out.
+    ///
+    /// Merged segments keep the tokens of both, while `TranscriberOutput::split_segments`
+    /// clears them from the segments it splits, since it can't tell which piece a token
+    /// belongs to.
+    pub fn get_token_probabilities(&self) -> &[f32] {
+        &self.token_probabilities
+    }
+
+    /// The vocabulary ids of the segment's text tokens, in the order of
+    /// `get_token_probabilities` and populated along with them.
+    pub fn get_token_ids(&self) -> &[i32] {
+        &self.token_ids
+    }
+
+    /// Up to `TranscribeOptions::n_best` hypotheses for the segment's text, best first, only
+    /// populated when that option is set.
+    ///
+    /// whisper 1.5.0 discards the candidates of its beam search and `best_of` sampling, so
+    /// this is currently always a single alternative holding the decoded text. It is the
+    /// text as decoded, before `normalize_unicode` and the other text post processing.
+    /// Merged and split segments have none.
+    pub fn alternatives(&self) -> &[TranscriberOutputAlternative] {
+        &self.alternatives
+    }
+
+    /// Index of the decode window (the chunk, in chunked mode) the segment came from, only
+    /// populated when `TranscribeOptions::segment_timing` is enabled. Without chunking these
+    /// are whisper's 30 second windows, the ones starting at each `get_seek`.
+    pub fn get_window_index(&self) -> Option<usize> {
+        self.window_index
+    }
+
+    /// Wall time in milliseconds spent decoding the segment's window, only populated when
+    /// `TranscribeOptions::segment_timing` is enabled.
+    pub fn get_decode_time_ms(&self) -> Option<u64> {
+        self.decode_time_ms
+    }
+
+    /// Start in centiseconds of the 30 second window whisper decoded the segment in, the
+    /// `seek` of OpenAI's reference implementation. whisper.cpp doesn't report its windows, so
+    /// they are rebuilt from the segments: a window starts where the last one's segments
+    /// ended, and a segment that would end past it starts the next. `None` for segments that
+    /// weren't decoded, such as those loaded from JSON of older versions.
+    pub fn get_seek(&self) -> Option<i64> {
+        self.seek
+    }
+
+    /// Whether the segment follows a pause long enough to likely start another speaker's
+    /// turn, see `TranscribeOptions::expected_speakers`.
+    pub fn is_speaker_turn(&self) -> bool {
+        self.speaker_turn
+    }
+
+    /// The input the segment was transcribed from, only populated by APIs transcribing
+    /// several inputs, such as `Transcriber::transcribe_concat`.
+    pub fn get_source(&self) -> Option<&SourceRef> {
+        self.source.as_ref()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct TranscriberOutputWord {
+    pub(crate) start_timestamp: i64,
+    pub(crate) end_timestamp: i64,
+    pub(crate) text: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) probability: Option<f32>,
+}
+
+impl TranscriberOutputWord {
+    pub(crate) fn new(
+        start_timestamp: i64,
+        end_timestamp: i64,
+        text: impl Into<String>,
+    ) -> TranscriberOutputWord {
+        TranscriberOutputWord {
+            start_timestamp,
+            end_timestamp,
+            text: text.into(),
+            probability: None,
+        }
+    }
+
+    pub(crate) fn with_probability(mut self, probability: f32) -> TranscriberOutputWord {
+        self.probability = Some(probability);
+        self
+    }
+
     pub fn get_start_timestamp(&self) -> &i64 {
         &self.start_timestamp
     }
@@ -28,114 +662,3218 @@ impl TranscriberOutputSegment {
         &self.end_timestamp
     }
 
+    /// The word text, including the leading space whisper attaches to it.
+    pub fn get_text(&self) -> &str {
+        &self.text
+    }
+
+    /// The mean probability whisper gave the word's tokens, between 0 and 1.
+    pub fn get_probability(&self) -> Option<f32> {
+        self.probability
+    }
+}
+
+/// A hypothesis for the text of a segment, see `TranscriberOutputSegment::alternatives`.
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct TranscriberOutputAlternative {
+    pub(crate) text: String,
+    pub(crate) score: f32,
+}
+
+impl TranscriberOutputAlternative {
     pub fn get_text(&self) -> &str {
         &self.text
     }
+
+    /// The mean probability whisper gave the hypothesis' text tokens, between 0 and 1, and 0
+    /// without text tokens.
+    pub fn get_score(&self) -> f32 {
+        self.score
+    }
+}
+
+/// Number of audio samples per whisper timestamp unit (centisecond) at 16KHz.
+pub(crate) const SAMPLES_PER_CENTISECOND: usize = 160;
+
+/// The sample index at a timestamp in centiseconds, negative timestamps at the first sample.
+fn timestamp_to_sample(timestamp: i64) -> usize {
+    timestamp.max(0) as usize * SAMPLES_PER_CENTISECOND
 }
 
+/// A loaded whisper model, transcribing audio with it.
+///
+/// A transcriber is `Send + Sync`, share one between threads (such as in the state of a web
+/// server) behind an `Arc`. The model is loaded once and shared by all transcriptions, while
+/// every transcription creates its own inference state, so concurrent calls run in parallel
+/// and each holds a state's memory (see `memory::MemoryBudget`) while it runs. Outputs are
+/// `Send + Sync` and options `Send`, as are the boxed errors the transcription methods
+/// return, so they can be returned from async handlers and sent between threads.
 pub struct Transcriber {
-    ctx: whisper_rs::WhisperContext,
+    pub(crate) backend: Box<dyn InferenceBackend>,
+    pub(crate) use_gpu: bool,
+    pub(crate) memory_budget: Option<MemoryBudget>,
+    /// The SHA-256 of the model file recorded at download, see `ModelHandler::sha256`.
+    pub(crate) model_sha256: Option<String>,
 }
 
 impl Transcriber {
     pub fn new(model: model_handler::ModelHandler) -> Transcriber {
+        let backend = if whisper_rs::WhisperContextParameters::default().use_gpu {
+            Backend::Gpu
+        } else {
+            Backend::Cpu
+        };
+        Transcriber::new_with_backend(model, backend)
+    }
+
+    /// Load the model on the given backend. `Backend::Cpu` keeps inference off the GPU even
+    /// when a GPU backend is compiled in, such as on a machine whose GPU driver crashes.
+    /// `Backend::Gpu` is what `new` loads on, and runs on the CPU when no GPU backend is
+    /// compiled in.
+    ///
+    /// The backend is the only choice whisper makes at runtime: the CPU instruction sets it
+    /// uses are fixed when whisper.cpp is compiled, see `SystemInfo::unsupported_cpu_features`.
+    ///
+    /// # Panics
+    ///
+    /// When the model fails to load, or when whisper was built with instruction sets this CPU
+    /// lacks, which would otherwise crash the process on the first inference.
+    pub fn new_with_backend(model: model_handler::ModelHandler, backend: Backend) -> Transcriber {
+        let missing = system::system_info().unsupported_cpu_features();
+        if !missing.is_empty() {
+            panic!(
+                "whisper was built with {} but this CPU lacks it, rebuild whisper.cpp without it",
+                missing.join(", ")
+            );
+        }
+        let use_gpu = backend == Backend::Gpu;
         Transcriber {
-            ctx: whisper_rs::WhisperContext::new_with_params(
-                &model.get_model_dir(),
-                whisper_rs::WhisperContextParameters::default(),
-            )
-            .expect("failed to load model"),
+            backend: Box::new(
+                WhisperBackend::load(&model.model_path(), use_gpu).expect("failed to load model"),
+            ),
+            use_gpu,
+            memory_budget: None,
+            model_sha256: model.sha256(),
+        }
+    }
+
+    /// Count this transcriber's inference states against `budget` instead of the global
+    /// budget (see `memory::set_global_budget`), so transcriptions wait for room in it or
+    /// fail with `Error::OverBudget`.
+    pub fn with_memory_budget(mut self, budget: MemoryBudget) -> Transcriber {
+        self.memory_budget = Some(budget);
+        self
+    }
+
+    /// Report the loaded model's type, vocabulary and the backend it runs on.
+    ///
+    /// The backend is `Gpu` when the GPU was requested and a GPU backend is compiled in.
+    pub fn context_info(&self) -> ContextInfo {
+        let backend = if self.use_gpu && system::system_info().has_gpu() {
+            Backend::Gpu
+        } else {
+            Backend::Cpu
+        };
+        ContextInfo {
+            model_type: self.backend.model_type(),
+            multilingual: self.is_multilingual(),
+            vocab_size: self.backend.n_vocab(),
+            backend,
+        }
+    }
+
+    /// Whether the loaded model transcribes languages other than English, as whisper reports
+    /// it. English-only models (the `.en` ones) only take `"en"`, the others any of
+    /// `language::supported_languages`.
+    pub fn is_multilingual(&self) -> bool {
+        self.backend.is_multilingual()
+    }
+
+    /// The hyperparameters the loaded model reports.
+    pub fn hyperparameters(&self) -> ModelHyperparameters {
+        self.backend.hyperparameters()
+    }
+
+    /// Check that whisper can preprocess audio for the loaded model: that it takes 80 or 128
+    /// mel bands (large-v3 and its fine-tunes take 128), and that the mel filterbank in its
+    /// file has as many. whisper computes spectrograms with the file's filterbank, so a
+    /// mismatch, typically from a model converted with an outdated script, makes it decode
+    /// garbage. Every transcription runs this check first.
+    pub fn check_mel_bands(&self) -> Result<(), Error> {
+        let n_mels = self.backend.hyperparameters().n_mels;
+        if !SUPPORTED_N_MELS.contains(&n_mels) {
+            return Err(Error::UnsupportedMelBands(n_mels));
+        }
+        match self.backend.mel_filterbank_n_mels() {
+            Some(filterbank_n_mels) if filterbank_n_mels != n_mels => {
+                Err(Error::MelBandsMismatch {
+                    n_mels,
+                    filterbank_n_mels,
+                })
+            }
+            _ => Ok(()),
         }
     }
 
+    /// The manifest of a run with `options`.
+    pub(crate) fn manifest(
+        &self,
+        options: &TranscribeOptions,
+        custom_whisper_params: bool,
+    ) -> Manifest {
+        let model_path = self.backend.model_path();
+        let model = ModelManifest {
+            model_type: self.backend.model_type(),
+            file_name: model_path
+                .and_then(Path::file_name)
+                .map(|name| name.to_string_lossy().into_owned()),
+            sha256: self.model_sha256.clone(),
+        };
+        Manifest::new(model, options, custom_whisper_params)
+    }
+
     pub fn transcribe(
         &self,
-        audio_path: &str,
+        audio_path: impl AsRef<Path>,
         whisper_params: Option<whisper_rs::FullParams>,
-    ) -> Result<TranscriberOutput, Box<dyn std::error::Error>> {
-        let audio_data = audio_parser::parse_audio_file(audio_path);
+    ) -> Result<TranscriberOutput, Box<dyn std::error::Error + Send + Sync>> {
+        let options = TranscribeOptions::default();
 
-        let mut state: whisper_rs::WhisperState =
-            self.ctx.create_state().expect("Failed to create state");
-        let params: whisper_rs::FullParams = match whisper_params {
-            Some(whisper_params) => whisper_params,
-            None => {
-                whisper_rs::FullParams::new(whisper_rs::SamplingStrategy::Greedy { best_of: 1 })
-            }
-        };
+        self.run(
+            audio_parser::try_parse_audio_file(audio_path)?,
+            whisper_params,
+            &options,
+        )
+    }
 
-        state
-            .full(params, &audio_data[..])
-            .expect("failed to run the model");
+    /// Transcribe an audio file using the given options instead of raw whisper parameters.
+    ///
+    /// # Arguments
+    ///
+    /// * `audio_path` - path to the audio file to transcribe.
+    /// * `options` - the options used to configure whisper.
+    pub fn transcribe_with_options(
+        &self,
+        audio_path: impl AsRef<Path>,
+        options: &TranscribeOptions,
+    ) -> Result<TranscriberOutput, Box<dyn std::error::Error + Send + Sync>> {
+        self.transcribe_file(
+            audio_path.as_ref(),
+            options,
+            |audio_data, preprocessed, options| {
+                self.run_preprocessed(audio_data, preprocessed, options)
+            },
+        )
+    }
 
-        let mut start_timestamp;
-        let mut end_timestamp;
-        // fetch the results
-        let num_segments = state
-            .full_n_segments()
-            .expect("failed to get number of segments");
-        let mut output_segments = Vec::with_capacity(num_segments as usize);
-        for i in 0..num_segments {
-            let segment: String = state
-                .full_get_segment_text(i)
-                .expect("failed to get segment");
-            start_timestamp = state
-                .full_get_segment_t0(i)
-                .expect("failed to get segment start timestamp");
-            end_timestamp = state
-                .full_get_segment_t1(i)
-                .expect("failed to get segment end timestamp");
-            output_segments.push(TranscriberOutputSegment {
-                start_timestamp,
-                end_timestamp,
-                text: segment,
-            });
-        }
+    /// Quickly transcribe an audio file into a rough preview, trading accuracy for speed
+    /// with `TranscribeOptions::fast_preview`. Re-run with `transcribe_with_options` for
+    /// the full quality transcript.
+    pub fn transcribe_fast(
+        &self,
+        audio_path: impl AsRef<Path>,
+    ) -> Result<TranscriberOutput, Box<dyn std::error::Error + Send + Sync>> {
+        self.transcribe_with_options(audio_path, &TranscribeOptions::fast_preview())
+    }
 
-        Ok(TranscriberOutput {
-            segments: output_segments,
-        })
+    /// Transcribe audio sent as a `data:audio/...;base64,...` URI, such as one produced by a
+    /// browser upload.
+    ///
+    /// The MIME type of the URI is used as a hint for the audio container format.
+    pub fn transcribe_data_uri(
+        &self,
+        uri: &str,
+    ) -> Result<TranscriberOutput, Box<dyn std::error::Error + Send + Sync>> {
+        self.transcribe_data_uri_with_options(uri, &TranscribeOptions::default())
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use crate::model_handler;
+    /// Transcribe audio sent as a base64 data URI using the given options.
+    pub fn transcribe_data_uri_with_options(
+        &self,
+        uri: &str,
+        options: &TranscribeOptions,
+    ) -> Result<TranscriberOutput, Box<dyn std::error::Error + Send + Sync>> {
+        self.check_options(options)?;
+        let data_uri = data_uri::DataUri::parse(uri)?;
+        let mut preprocess = Preprocess::new(options);
+        let audio_data = audio_parser::decode_audio_bytes(
+            data_uri.payload,
+            data_uri.mime_type.as_deref(),
+            &mut |audio, sample_rate| preprocess.apply(audio, sample_rate),
+        )?;
+        self.run_preprocessed(audio_data, preprocess, options)
+    }
 
-    use super::*;
+    /// Compute the log-mel spectrogram whisper sees for an audio file, with the number of mel
+    /// bands the loaded model uses. See `mel::log_mel_spectrogram`. Fails like
+    /// `audio_parser::try_parse_audio_file` on audio that can't be decoded.
+    #[cfg(feature = "mel-spectrogram")]
+    pub fn mel_spectrogram(&self, audio_path: impl AsRef<Path>) -> Result<Vec<Vec<f32>>, Error> {
+        let audio_data = audio_parser::try_parse_audio_file(audio_path)?;
+        Ok(crate::mel::log_mel_spectrogram(
+            &audio_data,
+            self.backend.n_mels() as usize,
+        ))
+    }
 
-    #[tokio::test]
-    async fn component_test_happy_case() {
-        let expected_result = " By what he has said and done, a man judges himself by what he is willing to do, by what he might have said, or might have done, a judgment that is necessarily hapered, but only by the scope and limits of his imagination, but by the ever-changing measure of his doubt and self-esteem.";
+    /// Transcribe 16KHz mono samples using the given options.
+    ///
+    /// # Arguments
+    ///
+    /// * `samples` - the audio, as 16KHz mono f32 samples.
+    /// * `options` - the options used to configure whisper.
+    pub fn transcribe_samples(
+        &self,
+        samples: &[f32],
+        options: &TranscribeOptions,
+    ) -> Result<TranscriberOutput, Box<dyn std::error::Error + Send + Sync>> {
+        self.check_options(options)?;
+        self.run(samples.to_vec(), None, options)
+    }
 
-        let tiny_model_handler = model_handler::ModelHandler::new("Tiny", "models").await;
-        let whisper_wrp = Transcriber::new(tiny_model_handler);
+    /// Transcribe only the audio in `range`, such as a stretch that was edited, to
+    /// `TranscriberOutput::splice` into the transcript of the whole audio.
+    ///
+    /// The timestamps of the returned output start from the start of the range. A range
+    /// reaching past the end of the audio is cut short.
+    ///
+    /// # Arguments
+    ///
+    /// * `audio` - an audio file, or 16KHz mono samples.
+    /// * `range` - the time range to transcribe, from the start of the audio.
+    /// * `options` - the options used to configure whisper.
+    pub fn retranscribe_range<'a>(
+        &self,
+        audio: impl Into<AudioInput<'a>>,
+        range: std::ops::Range<std::time::Duration>,
+        options: &TranscribeOptions,
+    ) -> Result<TranscriberOutput, Box<dyn std::error::Error + Send + Sync>> {
+        let window = |samples: &[f32]| {
+            let sample = |time: std::time::Duration| {
+                ((time.as_secs_f64() * audio_parser::WHISPER_SAMPLE_RATE as f64) as usize)
+                    .min(samples.len())
+            };
+            let start = sample(range.start);
+            samples[start..sample(range.end).max(start)].to_vec()
+        };
+        match audio.into() {
+            AudioInput::File(path) => {
+                self.transcribe_file(path, options, |audio_data, preprocessed, options| {
+                    self.run_preprocessed(window(&audio_data), preprocessed, options)
+                })
+            }
+            AudioInput::Samples(samples) => {
+                self.check_options(options)?;
+                self.run(window(samples), None, options)
+            }
+        }
+    }
 
-        let result = whisper_wrp
-            .transcribe("src/test_data/test.mp3", None)
-            .unwrap();
+    /// Transcribe 16KHz mono samples into `out`, reusing the allocations of the segments it
+    /// already holds, for services transcribing many short clips.
+    ///
+    /// The segments' text is rewritten in place, so after the first few calls extracting
+    /// segments allocates nothing per segment (whisper-rs itself still copies each segment's
+    /// text once). Word timestamps are extracted as usual. The samples are only copied when
+    /// they are shorter than `TranscribeOptions::min_duration` and need padding. Decode files
+    /// into a reused buffer with `audio_parser::parse_audio_file_into`.
+    ///
+    /// On error `out` is left empty.
+    pub fn transcribe_into(
+        &self,
+        samples: &[f32],
+        options: &TranscribeOptions,
+        out: &mut TranscriberOutput,
+    ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let mut spare = std::mem::take(&mut out.segments);
+        *out = TranscriberOutput::default();
+        self.check_options(options)?;
+        *out = self.run_into(
+            Cow::Borrowed(samples),
+            None,
+            options,
+            Checkpoint::default(),
+            &mut |_| {},
+            &mut Callbacks::new(),
+            None,
+            &mut spare,
+        )?;
+        Ok(())
+    }
 
-        let mut result_text:String = String::new();
-        for segment in result.get_segments() {
-            result_text.push_str(segment.get_text());
+    /// Transcribe an audio file, resuming from a checkpoint of an earlier, interrupted run.
+    ///
+    /// With `TranscribeOptions::chunk_length` set, `on_checkpoint` is called after every
+    /// chunk with the progress so far, save it (see `Checkpoint::save`) to resume from it
+    /// later. The returned output holds the checkpoint's segments followed by the new ones.
+    ///
+    /// # Arguments
+    ///
+    /// * `audio_path` - path to the audio file.
+    /// * `options` - the options to transcribe with, which should match the ones the
+    ///   checkpoint was made with.
+    /// * `resume_from` - the checkpoint to resume from, `None` starts from the beginning.
+    /// * `on_checkpoint` - called with the progress after every chunk.
+    pub fn transcribe_resumable(
+        &self,
+        audio_path: impl AsRef<Path>,
+        options: &TranscribeOptions,
+        resume_from: Option<Checkpoint>,
+        mut on_checkpoint: impl FnMut(&Checkpoint),
+    ) -> Result<TranscriberOutput, Box<dyn std::error::Error + Send + Sync>> {
+        self.transcribe_file(
+            audio_path.as_ref(),
+            options,
+            |audio_data, preprocessed, options| {
+                self.run_from(
+                    audio_data,
+                    None,
+                    options,
+                    resume_from.unwrap_or_default(),
+                    &mut on_checkpoint,
+                    &mut Callbacks::new(),
+                    Some(preprocessed),
+                )
+            },
+        )
+    }
+
+    /// Transcribe an audio file like `transcribe_resumable`, persisting the progress to the
+    /// checkpoint file at `checkpoint_path` after every chunk, so a transcription interrupted
+    /// by a crash can be continued with `Transcriber::resume`. The file is replaced if it
+    /// exists.
+    ///
+    /// Only chunked transcriptions (see `TranscribeOptions::chunk_length`) make progress
+    /// worth recording, without chunks the file only records how the transcription started.
+    pub fn transcribe_with_checkpoint_file(
+        &self,
+        audio_path: impl AsRef<Path>,
+        options: &TranscribeOptions,
+        checkpoint_path: impl AsRef<Path>,
+    ) -> Result<TranscriberOutput, Box<dyn std::error::Error + Send + Sync>> {
+        let audio_path = std::fs::canonicalize(audio_path)?;
+        let header = CheckpointHeader {
+            audio_sha256: checkpoint::file_sha256(&audio_path)?,
+            audio_path,
+            manifest_digest: self.manifest(options, false).digest(),
+        };
+        let file = CheckpointFile::create(checkpoint_path.as_ref(), &header)?;
+        self.continue_checkpoint_file(&header.audio_path, options, file, Checkpoint::default())
+    }
+
+    /// Continue the transcription recorded in the checkpoint file at `checkpoint_path` by
+    /// `transcribe_with_checkpoint_file`, skipping the chunks it completed. The result is the
+    /// output the transcription would have had without the interruption.
+    ///
+    /// `options` must be the ones the transcription started with. Fails with
+    /// `Error::InvalidCheckpoint` when they or the model differ, or when the audio file
+    /// changed since.
+    pub fn resume(
+        &self,
+        checkpoint_path: impl AsRef<Path>,
+        options: &TranscribeOptions,
+    ) -> Result<TranscriberOutput, Box<dyn std::error::Error + Send + Sync>> {
+        let (file, header, progress) = CheckpointFile::resume(checkpoint_path.as_ref())?;
+        if header.manifest_digest != self.manifest(options, false).digest() {
+            return Err(Box::new(Error::InvalidCheckpoint(
+                "made with other options or another model".to_string(),
+            )));
         }
+        if checkpoint::file_sha256(&header.audio_path)? != header.audio_sha256 {
+            return Err(Box::new(Error::InvalidCheckpoint(format!(
+                "{} changed since it was made",
+                header.audio_path.display()
+            ))));
+        }
+        self.continue_checkpoint_file(&header.audio_path, options, file, progress)
+    }
 
-        assert_eq!(expected_result, result_text);
+    /// Transcribe an audio file from `progress` on, appending every chunk to `file`.
+    fn continue_checkpoint_file(
+        &self,
+        audio_path: &Path,
+        options: &TranscribeOptions,
+        mut file: CheckpointFile,
+        progress: Checkpoint,
+    ) -> Result<TranscriberOutput, Box<dyn std::error::Error + Send + Sync>> {
+        let mut failure = None;
+        let output =
+            self.transcribe_file(audio_path, options, |audio_data, preprocessed, options| {
+                self.run_from(
+                    audio_data,
+                    None,
+                    options,
+                    progress,
+                    &mut |checkpoint| {
+                        if failure.is_none() {
+                            failure = file.append(checkpoint).err();
+                        }
+                    },
+                    &mut Callbacks::new(),
+                    Some(preprocessed),
+                )
+            })?;
+        match failure {
+            Some(err) => Err(Box::new(err)),
+            None => Ok(output),
+        }
+    }
 
-        let _ = std::fs::remove_dir_all("models/");
+    /// Transcribe an audio file using the given options, reporting progress and new segments
+    /// to `callbacks` while whisper runs.
+    ///
+    /// A panicking callback stops the transcription, which then fails with
+    /// `Error::CallbackPanicked`, and so does a full segment channel with
+    /// `Backpressure::Fail` (`Error::SegmentChannelFull`). The transcriber stays usable.
+    pub fn transcribe_with_callbacks(
+        &self,
+        audio_path: impl AsRef<Path>,
+        options: &TranscribeOptions,
+        mut callbacks: Callbacks<'_>,
+    ) -> Result<TranscriberOutput, Box<dyn std::error::Error + Send + Sync>> {
+        self.transcribe_file(
+            audio_path.as_ref(),
+            options,
+            |audio_data, preprocessed, options| {
+                self.run_from(
+                    audio_data,
+                    None,
+                    options,
+                    Checkpoint::default(),
+                    &mut |_| {},
+                    &mut callbacks,
+                    Some(preprocessed),
+                )
+            },
+        )
     }
 
-    #[tokio::test]
-    #[should_panic]
-    async fn component_test_48k() {
-        let tiny_model_handler = model_handler::ModelHandler::new("Tiny", "models").await;
-        let whisper_wrp = Transcriber::new(tiny_model_handler);
+    /// Check `options` and `run` them over the audio file, decoded and `Preprocess`ed, in the
+    /// language the file declares when `TranscribeOptions::use_metadata_language` is set.
+    fn transcribe_file(
+        &self,
+        audio_path: &Path,
+        options: &TranscribeOptions,
+        run: impl FnOnce(
+            Vec<f32>,
+            Preprocess,
+            &TranscribeOptions,
+        ) -> Result<TranscriberOutput, Box<dyn std::error::Error + Send + Sync>>,
+    ) -> Result<TranscriberOutput, Box<dyn std::error::Error + Send + Sync>> {
+        let tag = if options.use_metadata_language {
+            audio_parser::try_probe_audio_info(audio_path)?.language
+        } else {
+            None
+        };
+        let (language, from_metadata) = match tag.as_deref().map(crate::language::whisper_code) {
+            Some(Some(language)) => (Some(language.to_string()), true),
+            // an invalid tag leaves the language to detection, which English-only models lack
+            Some(None) if self.is_multilingual() => (None, false),
+            _ => (options.language.clone(), false),
+        };
 
-        whisper_wrp
-            .transcribe("src/test_data/test_48k_mono.mp3", None)
-            .unwrap();
+        let options = TranscribeOptions {
+            language,
+            ..options.clone()
+        };
+        self.check_options(&options)?;
+        let mut preprocess = Preprocess::new(&options);
+        let (audio_data, rate_warning) = audio_parser::decode_audio_file(
+            audio_path,
+            options.strict_sample_rate,
+            &mut |audio, sample_rate| preprocess.apply(audio, sample_rate),
+        )?;
+        let mut output = run(audio_data, preprocess, &options)?;
+        output.warnings.extend(rate_warning);
+        if from_metadata && output.language.is_some() {
+            output.language_source = Some(LanguageSource::Metadata);
+        }
+        if let (false, Some(tag)) = (from_metadata, tag) {
+            output
+                .warnings
+                .push(Warning::InvalidLanguageMetadata { tag });
+        }
+        Ok(output)
+    }
+
+    /// Fail with `Error::IncompatibleOptions` unless the options suit the loaded model or
+    /// validation is skipped.
+    pub(crate) fn check_options(&self, options: &TranscribeOptions) -> Result<(), Error> {
+        if options.skip_validation {
+            return Ok(());
+        }
+        let violations = options.validate(&self.context_info());
+        if violations.is_empty() {
+            Ok(())
+        } else {
+            Err(Error::IncompatibleOptions(violations))
+        }
     }
 
+    /// Transcribe the audio, `params` overrides the whisper parameters built from `options`.
+    ///
+    /// Retries (see `TranscribeOptions::retry`) need fresh parameters for every attempt, so
+    /// they are disabled when `params` is given.
+    pub(crate) fn run(
+        &self,
+        audio_data: Vec<f32>,
+        params: Option<whisper_rs::FullParams<'_, '_>>,
+        options: &TranscribeOptions,
+    ) -> Result<TranscriberOutput, Box<dyn std::error::Error + Send + Sync>> {
+        self.run_from(
+            audio_data,
+            params,
+            options,
+            Checkpoint::default(),
+            &mut |_| {},
+            &mut Callbacks::new(),
+            None,
+        )
+    }
+
+    /// Like `run`, for audio the `preprocessed` checks and noise gate already ran over while
+    /// it was decoded.
+    pub(crate) fn run_preprocessed(
+        &self,
+        audio_data: Vec<f32>,
+        preprocessed: Preprocess,
+        options: &TranscribeOptions,
+    ) -> Result<TranscriberOutput, Box<dyn std::error::Error + Send + Sync>> {
+        self.run_from(
+            audio_data,
+            None,
+            options,
+            Checkpoint::default(),
+            &mut |_| {},
+            &mut Callbacks::new(),
+            Some(preprocessed),
+        )
+    }
+
+    /// Like `run`, skipping the audio before the checkpoint, reporting progress to
+    /// `on_checkpoint` after every chunk and running `callbacks` during inference. Audio
+    /// `preprocessed` while it was decoded isn't checked and gated again.
+    #[allow(clippy::too_many_arguments)]
+    fn run_from(
+        &self,
+        audio_data: Vec<f32>,
+        params: Option<whisper_rs::FullParams<'_, '_>>,
+        options: &TranscribeOptions,
+        resume_from: Checkpoint,
+        on_checkpoint: &mut dyn FnMut(&Checkpoint),
+        callbacks: &mut Callbacks<'_>,
+        preprocessed: Option<Preprocess>,
+    ) -> Result<TranscriberOutput, Box<dyn std::error::Error + Send + Sync>> {
+        self.run_into(
+            Cow::Owned(audio_data),
+            params,
+            options,
+            resume_from,
+            on_checkpoint,
+            callbacks,
+            preprocessed,
+            &mut Vec::new(),
+        )
+    }
+
+    /// Like `run_from`, reusing the `spare` segments for the decoded ones.
+    #[allow(clippy::too_many_arguments)]
+    fn run_into(
+        &self,
+        mut audio_data: Cow<'_, [f32]>,
+        params: Option<whisper_rs::FullParams<'_, '_>>,
+        options: &TranscribeOptions,
+        resume_from: Checkpoint,
+        on_checkpoint: &mut dyn FnMut(&Checkpoint),
+        callbacks: &mut Callbacks<'_>,
+        preprocessed: Option<Preprocess>,
+        spare: &mut Vec<TranscriberOutputSegment>,
+    ) -> Result<TranscriberOutput, Box<dyn std::error::Error + Send + Sync>> {
+        self.check_mel_bands()?;
+        let manifest = self.manifest(options, params.is_some());
+        if let Some(mut output) = screen_empty_audio(&audio_data, options)? {
+            output.manifest = Some(manifest);
+            return Ok(output);
+        }
+        resume_from.check(audio_data.len())?;
+        // a run holds a single state at a time, retries and chunks replace it
+        let _reservation = match self.memory_budget.clone().or_else(memory::global_budget) {
+            Some(budget) => Some(budget.reserve(self.backend.state_memory_bytes())?),
+            None => None,
+        };
+
+        let mut warnings = match preprocessed {
+            Some(preprocessed) => preprocessed.warnings,
+            None => {
+                let mut preprocess = Preprocess::new(options);
+                preprocess.check_quality(&audio_data, audio_parser::WHISPER_SAMPLE_RATE);
+                // borrowed samples are only copied to be gated
+                if let Some(gate) = &options.denoise {
+                    denoise::apply_noise_gate(
+                        audio_data.to_mut(),
+                        gate,
+                        audio_parser::WHISPER_SAMPLE_RATE,
+                    );
+                }
+                preprocess.warnings
+            }
+        };
+        if let Some(gain_db) = options.gain_db {
+            let limited = gain::apply_gain(audio_data.to_mut(), gain_db);
+            if limited > 0 {
+                warnings.push(Warning::AudioLimited {
+                    gain_db,
+                    limited_samples: limited,
+                    total_samples: audio_data.len(),
+                });
+            }
+        }
+
+        let audio_duration = (audio_data.len() / SAMPLES_PER_CENTISECOND) as i64;
+        let vocab_size = self.backend.n_vocab();
+        if !STOCK_VOCAB_SIZES.contains(&vocab_size) {
+            warnings.push(Warning::NonStockVocabulary { vocab_size });
+        }
+        let min_samples = options.min_duration.as_millis() as usize * 16;
+        // borrowed samples are only copied to be padded
+        let padded =
+            audio_data.len() < min_samples && pad_with_silence(audio_data.to_mut(), min_samples);
+        if padded {
+            warnings.push(Warning::AudioPadded {
+                duration: audio_duration,
+                padded_duration: (min_samples / SAMPLES_PER_CENTISECOND) as i64,
+            });
+        }
+
+        let mut language = options
+            .language
+            .clone()
+            .filter(|language| language != "auto");
+        let language_source = if language.is_some() {
+            LanguageSource::Options
+        } else {
+            LanguageSource::Detected
+        };
+        let mut chunk_boundaries = Vec::new();
+        let mut output_segments = match options.chunk_length {
+            Some(chunk_length) => {
+                let progress = self.decode_chunked(
+                    &audio_data,
+                    chunk_length,
+                    options,
+                    resume_from,
+                    on_checkpoint,
+                    callbacks,
+                    &mut warnings,
+                    &mut language,
+                    spare,
+                )?;
+                chunk_boundaries = progress.boundaries;
+                progress.segments
+            }
+            None if resume_from.sample_offset >= audio_data.len() => resume_from.segments,
+            None => {
+                let offset = resume_from.sample_offset;
+                let policy = match params {
+                    Some(_) => RetryPolicy::default(),
+                    None => options.retry.clone(),
+                };
+                let mut params = params;
+                let mut hooks =
+                    Hooks::for_window(callbacks, (offset / SAMPLES_PER_CENTISECOND) as i64, 0, 1)
+                        .numbered_from(resume_from.segments.len());
+                let started = std::time::Instant::now();
+                let (state, warning) = retry::with_retries(&policy, self.use_gpu, |attempt| {
+                    let mut state = self.backend.create_state(attempt.use_gpu)?;
+                    let mut params = match params.take() {
+                        Some(params) => params,
+                        None => options.to_full_params(),
+                    };
+                    if let Some(n_threads) = attempt.n_threads {
+                        params.set_n_threads(n_threads);
+                    }
+                    hooks.start_run();
+                    let result = state.full(params, &audio_data[offset..], &mut hooks);
+                    if hooks.failed() {
+                        // the run was aborted by a callback, retrying would not help
+                        return Ok(state);
+                    }
+                    result?;
+                    Ok(state)
+                })?;
+                if let Some(failure) = hooks.take_failure() {
+                    return Err(Box::new(failure));
+                }
+                warnings.extend(warning);
+                if language.is_none() {
+                    language = detected_language(state.as_ref());
+                }
+                let mut segments = self.collect_segments(
+                    state.as_ref(),
+                    options,
+                    offset..audio_data.len(),
+                    &mut warnings,
+                    spare,
+                );
+                if options.segment_timing {
+                    annotate_window(&mut segments, resume_from.windows, started.elapsed());
+                    // one run decodes all the 30 second windows, numbered like their seeks
+                    let run_start = (offset / SAMPLES_PER_CENTISECOND) as i64;
+                    let windows = seek_windows(&segments, run_start);
+                    for (segment, (_, window)) in segments.iter_mut().zip(windows) {
+                        segment.window_index = Some(resume_from.windows + window);
+                    }
+                }
+                if resume_from.segments.is_empty() {
+                    segments
+                } else {
+                    let mut resumed = resume_from.segments;
+                    resumed.extend(segments);
+                    resumed
+                }
+            }
+        };
+        let mut next_id = number_segments(&mut output_segments);
+        let mut empty_segments = Vec::new();
+        if options.drop_empty_segments {
+            output_segments.retain(|segment| {
+                let empty = segment.text.trim().is_empty();
+                if empty {
+                    empty_segments.push(segment.id);
+                }
+                !empty
+            });
+        }
+        let mut collapsed = Vec::new();
+        if let Some(min_repeats) = options.collapse_repetitions {
+            for (segment, sources) in
+                postprocess::collapse_repetitions(&mut output_segments, min_repeats)
+            {
+                output_segments[segment].id = next_id;
+                warnings.push(Warning::RepetitionCollapsed {
+                    segment,
+                    repeats: sources.len(),
+                });
+                collapsed.push(Change::Merged {
+                    sources,
+                    into: next_id,
+                });
+                next_id += 1;
+            }
+        }
+        if padded {
+            // hide the padding from callers
+            postprocess::clamp_ends(&mut output_segments, audio_duration);
+        }
+
+        if options.normalize_unicode {
+            postprocess::normalize_unicode(&mut output_segments);
+        }
+        #[cfg(feature = "chinese-conversion")]
+        if let Some(variant) = options.chinese_variant {
+            postprocess::convert_chinese(&mut output_segments, variant);
+        }
+        if options.strip_first_leading_space {
+            postprocess::strip_first_leading_space(&mut output_segments);
+        }
+
+        if options.normalize_segments {
+            postprocess::normalize_segments(&mut output_segments);
+        }
+        if options.timestamp_policy != TimestampPolicy::Keep {
+            let repairs =
+                postprocess::repair_timestamps(&mut output_segments, Some(audio_duration));
+            if let Some(&(segment, issue)) = repairs.first() {
+                if options.timestamp_policy == TimestampPolicy::Strict {
+                    return Err(Box::new(Error::InvalidTimestamps { segment, issue }));
+                }
+            }
+            warnings.extend(
+                repairs
+                    .into_iter()
+                    .map(|(segment, issue)| Warning::TimestampRepaired { segment, issue }),
+            );
+        }
+        warnings.extend(postprocess::find_replacement_characters(&output_segments));
+        if let Some(expected_speakers) = options.expected_speakers {
+            postprocess::mark_speaker_turns(
+                &mut output_segments,
+                postprocess::speaker_turn_gap(expected_speakers),
+            );
+        }
+        let dropped = callbacks.dropped_segments();
+        if dropped > 0 {
+            warnings.push(Warning::SegmentsDropped { count: dropped });
+        }
+
+        let mut output = TranscriberOutput::numbered(output_segments, next_id)
+            .with_audio_duration(audio_duration);
+        output.change_log.record(Change::Dropped {
+            ids: empty_segments,
+        });
+        for change in collapsed {
+            output.change_log.record(change);
+        }
+        output.warnings = warnings;
+        output.language_source = language.is_some().then_some(language_source);
+        output.language = language;
+        output.translated = options.translate;
+        output.manifest = Some(manifest);
+        output.chunk_boundaries = chunk_boundaries
+            .into_iter()
+            .map(|sample| (sample / SAMPLES_PER_CENTISECOND) as i64)
+            .collect();
+        if options.include_diagnostics {
+            output.diagnostics = Some(Diagnostics {
+                system: system::system_info(),
+                context: self.context_info(),
+            });
+        }
+        Ok(output)
+    }
+
+    /// Transcribe the audio in consecutive chunks of about `chunk_length`, cut at the quietest
+    /// point within `options.chunk_boundary_search`, prompting each chunk with the trailing
+    /// text tokens decoded before it, see `options.context_policy`.
+    ///
+    /// Every chunk is retried on its own according to `options.retry`, a fresh state is used
+    /// for retries while successful chunks share theirs.
+    ///
+    /// Chunking starts at the checkpoint's sample offset, with its segments and prompt, and
+    /// `on_checkpoint` is called after every chunk. `language` is set to the language
+    /// detected in the first chunk when it is `None`. Decoded segments reuse the `spare` ones.
+    /// Returns the progress at the end, with every segment and cut.
+    #[allow(clippy::too_many_arguments)]
+    fn decode_chunked(
+        &self,
+        audio_data: &[f32],
+        chunk_length: std::time::Duration,
+        options: &TranscribeOptions,
+        resume_from: Checkpoint,
+        on_checkpoint: &mut dyn FnMut(&Checkpoint),
+        callbacks: &mut Callbacks<'_>,
+        warnings: &mut Vec<Warning>,
+        language: &mut Option<String>,
+        spare: &mut Vec<TranscriberOutputSegment>,
+    ) -> Result<Checkpoint, Box<dyn std::error::Error + Send + Sync>> {
+        let chunk_samples = (chunk_length.as_millis() as usize * 16).max(1);
+        let search_samples = options.chunk_boundary_search.as_millis() as usize * 16;
+        let mut progress = Checkpoint {
+            total_samples: Some(audio_data.len()),
+            ..resume_from
+        };
+        let mut reusable_state = None;
+        let ranges = chunk_ranges(
+            audio_data,
+            progress.sample_offset..audio_data.len(),
+            chunk_samples,
+            search_samples,
+        );
+        let n_ranges = ranges.len();
+        for (i, range) in ranges.into_iter().enumerate() {
+            let mut hooks = Hooks::for_window(
+                callbacks,
+                (range.start / SAMPLES_PER_CENTISECOND) as i64,
+                i,
+                n_ranges,
+            )
+            .numbered_from(progress.segments.len());
+            let window = progress.windows;
+            let prompt = &progress.prompt;
+            let started = std::time::Instant::now();
+            let (state, warning) = retry::with_retries(&options.retry, self.use_gpu, |attempt| {
+                let mut state = match reusable_state.take() {
+                    Some(state) if attempt.number == 1 => state,
+                    _ => self.backend.create_state(attempt.use_gpu)?,
+                };
+                let mut params = options.to_full_params();
+                // the prompt replaces whisper's own history, which would otherwise grow
+                // unbounded
+                params.set_no_context(true);
+                params.set_tokens(prompt);
+                if let Some(n_threads) = attempt.n_threads {
+                    params.set_n_threads(n_threads);
+                }
+                hooks.start_run();
+                let result = state.full(params, &audio_data[range.clone()], &mut hooks);
+                if hooks.failed() {
+                    return Ok(state);
+                }
+                result?;
+                Ok(state)
+            })?;
+            if let Some(failure) = hooks.take_failure() {
+                return Err(Box::new(failure));
+            }
+            warnings.extend(warning);
+            if language.is_none() {
+                *language = detected_language(state.as_ref());
+            }
+
+            let end = range.end;
+            let mut chunk_segments =
+                self.collect_segments(state.as_ref(), options, range, warnings, spare);
+            if options.segment_timing {
+                annotate_window(&mut chunk_segments, window, started.elapsed());
+            }
+            progress.segments.extend(chunk_segments);
+            progress.prompt.extend(self.text_tokens(state.as_ref()));
+            let excess = progress
+                .prompt
+                .len()
+                .saturating_sub(options.chunk_prompt_tokens());
+            progress.prompt.drain(..excess);
+            progress.sample_offset = end;
+            if end < audio_data.len() {
+                progress.boundaries.push(end);
+            }
+            progress.windows += 1;
+            on_checkpoint(&progress);
+            reusable_state = Some(state);
+        }
+        Ok(progress)
+    }
+
+    /// Collect the segments of the last `full` run over the `samples` range of the audio,
+    /// shifting their timestamps to the start of the range. Segments are taken from `spare`
+    /// while it has any, reusing their allocations.
+    ///
+    /// Segments whose token timings are unavailable are kept without words, with a
+    /// `Warning::WordTimestampsUnavailable`.
+    fn collect_segments(
+        &self,
+        state: &dyn InferenceState,
+        options: &TranscribeOptions,
+        samples: std::ops::Range<usize>,
+        warnings: &mut Vec<Warning>,
+        spare: &mut Vec<TranscriberOutputSegment>,
+    ) -> Vec<TranscriberOutputSegment> {
+        let offset = (samples.start / SAMPLES_PER_CENTISECOND) as i64;
+        let num_segments = state
+            .n_segments()
+            .expect("failed to get number of segments");
+        let mut output_segments = Vec::with_capacity(num_segments as usize);
+        let mut untimed_segments = 0;
+        for i in 0..num_segments {
+            let start_timestamp = state
+                .segment_t0(i)
+                .expect("failed to get segment start timestamp")
+                + offset;
+            let end_timestamp = state
+                .segment_t1(i)
+                .expect("failed to get segment end timestamp")
+                + offset;
+            let mut output_segment = match spare.pop() {
+                Some(segment) => segment.recycle(start_timestamp, end_timestamp),
+                None => {
+                    TranscriberOutputSegment::new(start_timestamp, end_timestamp, String::new())
+                }
+            };
+            state
+                .segment_text_into(i, &mut output_segment.text)
+                .expect("failed to get segment");
+            if options.token_probabilities {
+                self.extract_token_probabilities(
+                    state,
+                    i,
+                    &mut output_segment.token_ids,
+                    &mut output_segment.token_probabilities,
+                );
+            }
+            if options.n_best > 0 {
+                // whisper keeps no other candidate than the decoded one
+                let mut probabilities = Vec::new();
+                self.extract_token_probabilities(state, i, &mut Vec::new(), &mut probabilities);
+                let score = match probabilities.len() {
+                    0 => 0.0,
+                    n => probabilities.iter().sum::<f32>() / n as f32,
+                };
+                output_segment.alternatives = vec![TranscriberOutputAlternative {
+                    text: output_segment.text.clone(),
+                    score,
+                }];
+            }
+            if options.word_timestamps {
+                match self.extract_words(state, i) {
+                    Some(mut words) => {
+                        for word in &mut words {
+                            word.start_timestamp += offset;
+                            word.end_timestamp += offset;
+                        }
+                        output_segment = output_segment.with_words(words);
+                    }
+                    None => untimed_segments += 1,
+                }
+            }
+            output_segments.push(output_segment);
+        }
+        if untimed_segments > 0 {
+            warnings.push(Warning::WordTimestampsUnavailable {
+                segments: untimed_segments,
+            });
+        }
+        if options.single_segment {
+            let end = (samples.end / SAMPLES_PER_CENTISECOND) as i64;
+            postprocess::clamp_ends(&mut output_segments, end);
+        }
+        assign_seeks(&mut output_segments, offset);
+        output_segments
+    }
+
+    /// The ids of the text (non special) tokens of the last `full` run, in order.
+    fn text_tokens(&self, state: &dyn InferenceState) -> Vec<std::os::raw::c_int> {
+        let num_segments = state.n_segments().unwrap_or(0);
+        let mut tokens = Vec::new();
+        for i in 0..num_segments {
+            for j in 0..state.n_tokens(i).unwrap_or(0) {
+                if let Ok(id) = state.token_id(i, j) {
+                    if id < self.backend.token_eot() {
+                        tokens.push(id);
+                    }
+                }
+            }
+        }
+        tokens
+    }
+
+    /// Append the ids and probabilities of the text tokens of a segment to `ids` and
+    /// `probabilities`, skipping tokens whose data can't be read.
+    fn extract_token_probabilities(
+        &self,
+        state: &dyn InferenceState,
+        segment: i32,
+        ids: &mut Vec<std::os::raw::c_int>,
+        probabilities: &mut Vec<f32>,
+    ) {
+        for j in 0..state.n_tokens(segment).unwrap_or(0) {
+            let id = match state.token_id(segment, j) {
+                Ok(id) if id < self.backend.token_eot() => id,
+                _ => continue,
+            };
+            if let Ok(data) = state.token_data(segment, j) {
+                ids.push(id);
+                probabilities.push(data.p);
+            }
+        }
+    }
+
+    /// Collect the timed text tokens of a segment and group them into words, `None` when
+    /// the token timings can't be read or were not computed (whisper leaves them at -1).
+    fn extract_words(
+        &self,
+        state: &dyn InferenceState,
+        segment: i32,
+    ) -> Option<Vec<TranscriberOutputWord>> {
+        let num_tokens = state.n_tokens(segment).unwrap_or(0);
+        let mut tokens = Vec::with_capacity(num_tokens as usize);
+        for j in 0..num_tokens {
+            let is_special = match state.token_id(segment, j) {
+                Ok(id) => id >= self.backend.token_eot(),
+                Err(_) => true,
+            };
+            if is_special {
+                continue;
+            }
+            let data = state.token_data(segment, j).ok()?;
+            if data.t0 < 0 || data.t1 < 0 {
+                return None;
+            }
+            let text = state.token_text(segment, j).unwrap_or_default();
+            tokens.push((text, data.t0, data.t1, data.p));
+        }
+        Some(group_tokens_into_words(&tokens))
+    }
+}
+
+/// Number `segments` in order from 0, the ids they get when transcribed, returning the next
+/// id.
+fn number_segments(segments: &mut [TranscriberOutputSegment]) -> u64 {
+    for (id, segment) in segments.iter_mut().enumerate() {
+        segment.id = id as u64;
+    }
+    segments.len() as u64
+}
+
+/// The `min_snr_db` check and `denoise` gate of `TranscribeOptions`, run over decoded audio
+/// at its source rate, before it is resampled to 16KHz. Audio given as 16KHz samples goes
+/// through them in `Transcriber::run`.
+pub(crate) struct Preprocess {
+    min_snr_db: Option<f32>,
+    denoise: Option<NoiseGate>,
+    /// The warnings of the checks so far.
+    warnings: Vec<Warning>,
+}
+
+impl Preprocess {
+    pub(crate) fn new(options: &TranscribeOptions) -> Preprocess {
+        Preprocess {
+            min_snr_db: options.min_snr_db,
+            denoise: options.denoise,
+            warnings: Vec::new(),
+        }
+    }
+
+    /// Check the quality of `audio`, mono samples at `sample_rate`, then gate its noise in
+    /// place.
+    pub(crate) fn apply(&mut self, audio: &mut [f32], sample_rate: u32) {
+        self.check_quality(audio, sample_rate);
+        if let Some(gate) = &self.denoise {
+            denoise::apply_noise_gate(audio, gate, sample_rate);
+        }
+    }
+
+    /// Warn with `Warning::PoorAudioQuality` when `audio` is noisier than `min_snr_db`
+    /// allows. `quality::assess` rates 16KHz audio, audio at other rates is resampled for it.
+    fn check_quality(&mut self, audio: &[f32], sample_rate: u32) {
+        let Some(min_snr_db) = self.min_snr_db else {
+            return;
+        };
+        let quality = if sample_rate == audio_parser::WHISPER_SAMPLE_RATE {
+            quality::assess(audio)
+        } else {
+            quality::assess(&crate::resample::resample(
+                audio,
+                sample_rate,
+                audio_parser::WHISPER_SAMPLE_RATE,
+            ))
+        };
+        if let Some(quality) = quality {
+            if quality.snr_db < min_snr_db {
+                self.warnings.push(Warning::PoorAudioQuality {
+                    snr_db: quality.snr_db,
+                    min_snr_db,
+                });
+            }
+        }
+    }
+}
+
+/// Handle audio too short to hold a single timestamp unit according to the empty audio
+/// policy, returning the empty transcription to use instead of running whisper on it.
+///
+/// Silent audio is not empty, it is transcribed like any other.
+fn screen_empty_audio(
+    audio_data: &[f32],
+    options: &TranscribeOptions,
+) -> Result<Option<TranscriberOutput>, Error> {
+    if audio_data.len() >= SAMPLES_PER_CENTISECOND {
+        return Ok(None);
+    }
+    match options.empty_audio {
+        EmptyAudioPolicy::Warn => {
+            let mut output = TranscriberOutput::new(Vec::new()).with_audio_duration(0);
+            output.warnings.push(Warning::NoAudio);
+            Ok(Some(output))
+        }
+        EmptyAudioPolicy::Error => Err(Error::EmptyAudio),
+    }
+}
+
+/// Record the decode window segments came from and the wall time decoding it took.
+fn annotate_window(
+    segments: &mut [TranscriberOutputSegment],
+    window: usize,
+    decode_time: std::time::Duration,
+) {
+    for segment in segments {
+        segment.window_index = Some(window);
+        segment.decode_time_ms = Some(decode_time.as_millis() as u64);
+    }
+}
+
+/// Length in centiseconds of the windows whisper decodes, 30 seconds of mel frames.
+const WINDOW_CENTISECONDS: i64 = 3000;
+
+/// Set the `seek` of the segments of a `full` run starting at `run_start`, in centiseconds,
+/// see `seek_windows`.
+fn assign_seeks(segments: &mut [TranscriberOutputSegment], run_start: i64) {
+    let windows = seek_windows(segments, run_start);
+    for (segment, (seek, _)) in segments.iter_mut().zip(windows) {
+        segment.seek = Some(seek);
+    }
+}
+
+/// The start and index of the window each segment of a `full` run starting at `run_start`
+/// was decoded in. whisper moves its window to the end of the last segment it decoded in
+/// it, or by a whole window when that decoded nothing, so a segment ending past the current
+/// window opens the next one, after as many empty windows as fit before it. A window never
+/// starts past the start of the segment it opens.
+fn seek_windows(segments: &[TranscriberOutputSegment], run_start: i64) -> Vec<(i64, usize)> {
+    let mut seek = run_start;
+    let mut window = 0;
+    let mut last_end = run_start;
+    let mut windows = Vec::with_capacity(segments.len());
+    for segment in segments {
+        while segment.end_timestamp > seek + WINDOW_CENTISECONDS && seek < segment.start_timestamp {
+            seek = if last_end > seek {
+                last_end
+            } else {
+                seek + WINDOW_CENTISECONDS
+            }
+            .min(segment.start_timestamp);
+            window += 1;
+        }
+        windows.push((seek, window));
+        last_end = segment.end_timestamp;
+    }
+    windows
+}
+
+/// The whisper code of the language of the state's last run.
+fn detected_language(state: &dyn InferenceState) -> Option<String> {
+    let id = state.lang_id().ok()?;
+    whisper_rs::get_lang_str(id).map(str::to_string)
+}
+
+/// Pad the audio with trailing silence up to `min_samples`, returning whether it was padded.
+fn pad_with_silence(audio_data: &mut Vec<f32>, min_samples: usize) -> bool {
+    if audio_data.len() >= min_samples {
+        return false;
+    }
+    audio_data.resize(min_samples, 0.0);
+    true
+}
+
+/// 20ms of 16KHz audio, the steps in which chunk boundaries are searched.
+const BOUNDARY_FRAME_SAMPLES: usize = 320;
+
+/// Split the `samples` range of `audio` into consecutive ranges of about `chunk_samples`
+/// samples. Every cut is moved to the quietest frame within `search_samples` (at most half a
+/// chunk) of where a fixed chunk length would put it, the nearest of equally quiet ones.
+fn chunk_ranges(
+    audio: &[f32],
+    samples: std::ops::Range<usize>,
+    chunk_samples: usize,
+    search_samples: usize,
+) -> Vec<std::ops::Range<usize>> {
+    let search_frames = search_samples.min(chunk_samples / 2) / BOUNDARY_FRAME_SAMPLES;
+    let mut ranges = Vec::new();
+    let mut start = samples.start;
+    while start < samples.end {
+        let nominal = start + chunk_samples;
+        let end = if nominal >= samples.end {
+            samples.end
+        } else {
+            // candidates from the nominal cut outwards, so ties go to the nearest
+            (1..=search_frames)
+                .flat_map(|frame| {
+                    let offset = frame * BOUNDARY_FRAME_SAMPLES;
+                    [nominal.checked_sub(offset), Some(nominal + offset)]
+                })
+                .flatten()
+                .filter(|&cut| cut > start && cut < samples.end)
+                .fold((nominal, frame_energy(audio, nominal)), |quietest, cut| {
+                    let energy = frame_energy(audio, cut);
+                    if energy < quietest.1 {
+                        (cut, energy)
+                    } else {
+                        quietest
+                    }
+                })
+                .0
+        };
+        ranges.push(start..end);
+        start = end;
+    }
+    ranges
+}
+
+/// The energy of the frame of `audio` centered on `sample`.
+fn frame_energy(audio: &[f32], sample: usize) -> f32 {
+    let from = sample
+        .saturating_sub(BOUNDARY_FRAME_SAMPLES / 2)
+        .min(audio.len());
+    let to = (sample + BOUNDARY_FRAME_SAMPLES / 2).min(audio.len());
+    audio[from..to].iter().map(|x| x * x).sum()
+}
+
+/// Merge whisper tokens (text, t0, t1, probability) into words, a token starting with a space
+/// begins a new word. A word's probability is the mean of its tokens' probabilities.
+fn group_tokens_into_words(tokens: &[(String, i64, i64, f32)]) -> Vec<TranscriberOutputWord> {
+    let mut words: Vec<TranscriberOutputWord> = Vec::new();
+    let mut token_counts: Vec<usize> = Vec::new();
+    for (text, t0, t1, p) in tokens {
+        match (words.last_mut(), token_counts.last_mut()) {
+            (Some(word), Some(count)) if !text.starts_with(' ') => {
+                word.text.push_str(text);
+                word.end_timestamp = *t1.max(&word.end_timestamp);
+                *word.probability.get_or_insert(0.0) += p;
+                *count += 1;
+            }
+            _ => {
+                words
+                    .push(TranscriberOutputWord::new(*t0, *t1, text.as_str()).with_probability(*p));
+                token_counts.push(1);
+            }
+        }
+    }
+    for (word, count) in words.iter_mut().zip(token_counts) {
+        if let Some(probability) = &mut word.probability {
+            *probability /= count as f32;
+        }
+    }
+    words
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::model_handler;
+    use crate::test_util::{MockBackend, MockSegment};
+
+    use super::*;
+
+    fn texts(output: &TranscriberOutput) -> Vec<&str> {
+        output.get_segments().iter().map(|s| s.get_text()).collect()
+    }
+
+    /// The lowercase words of `text`, without punctuation, to compare transcripts that may
+    /// differ in casing and punctuation.
+    fn normalized_words(text: &str) -> Vec<String> {
+        text.split_whitespace()
+            .map(|word| {
+                word.chars()
+                    .filter(|c| c.is_alphanumeric() || *c == '\'')
+                    .flat_map(char::to_lowercase)
+                    .collect::<String>()
+            })
+            .filter(|word| !word.is_empty())
+            .collect()
+    }
+
+    /// The number of words inserted, deleted or substituted between `expected` and `actual`.
+    fn word_errors(expected: &[String], actual: &[String]) -> usize {
+        let mut previous: Vec<usize> = (0..=actual.len()).collect();
+        for (i, expected_word) in expected.iter().enumerate() {
+            let mut current = vec![i + 1];
+            for (j, actual_word) in actual.iter().enumerate() {
+                let substitution = previous[j] + usize::from(expected_word != actual_word);
+                current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+            }
+            previous = current;
+        }
+        previous[actual.len()]
+    }
+
+    #[test]
+    fn test_word_errors() {
+        let expected = normalized_words(" By what he has said, and done.");
+        assert_eq!(expected, ["by", "what", "he", "has", "said", "and", "done"]);
+        assert_eq!(word_errors(&expected, &expected), 0);
+        assert_eq!(
+            word_errors(&expected, &normalized_words("by what she has said and")),
+            2
+        );
+    }
+
+    #[test]
+    fn test_transcribe_into_replaces_previous_output() {
+        let transcriber = Transcriber::with_mock_backend(MockBackend::with_runs(vec![
+            vec![
+                MockSegment::new(0, 50, " A much longer first transcript."),
+                MockSegment::new(50, 100, " Second."),
+            ],
+            vec![MockSegment::new(0, 80, " Short.")],
+        ]));
+        let options = TranscribeOptions {
+            segment_timing: true,
+            ..Default::default()
+        };
+        let mut out = TranscriberOutput::default();
+
+        transcriber
+            .transcribe_into(&[0.0; 16000], &options, &mut out)
+            .unwrap();
+        transcriber
+            .transcribe_into(&[0.0; 8000], &options, &mut out)
+            .unwrap();
+
+        assert_eq!(texts(&out), vec![" Short."]);
+        assert_eq!(out.get_segments()[0].get_end_timestamp(), &50);
+        assert_eq!(out.get_audio_duration(), Some(50));
+        assert_eq!(out.get_segments()[0].get_window_index(), Some(0));
+        assert!(matches!(
+            out.get_warnings()[..],
+            [Warning::AudioPadded { duration: 50, .. }]
+        ));
+    }
+
+    #[test]
+    fn test_speech_ratio_counts_overlaps_once() {
+        let output = TranscriberOutput::new(vec![
+            TranscriberOutputSegment::new(0, 200, " One."),
+            TranscriberOutputSegment::new(100, 300, " Two."),
+            TranscriberOutputSegment::new(500, 600, " Three."),
+            TranscriberOutputSegment::new(550, 580, " Inside."),
+            TranscriberOutputSegment::new(900, 1200, " Past the end."),
+        ]);
+
+        assert_eq!(output.speech_duration_ms(), 7000);
+        assert!((output.speech_ratio(10000) - 0.5).abs() < 1e-9);
+        assert_eq!(output.speech_ratio(0), 0.0);
+        assert_eq!(TranscriberOutput::new(Vec::new()).speech_ratio(1000), 0.0);
+    }
+
+    #[cfg(feature = "mel-spectrogram")]
+    #[test]
+    fn test_mel_spectrogram_of_undecodable_audio_is_an_error() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("tone.wav");
+        crate::test_util::write_wav(
+            &path,
+            &crate::test_util::tone(440.0, std::time::Duration::from_secs(1), 0.5),
+        );
+        let transcriber = Transcriber::with_mock_backend(MockBackend::new(Vec::new()));
+
+        let mel = transcriber.mel_spectrogram(&path).unwrap();
+        assert!(!mel.is_empty() && mel.iter().all(|band| band.len() == 100));
+        assert!(matches!(
+            transcriber.mel_spectrogram(dir.path().join("missing.wav")),
+            Err(Error::Io(_))
+        ));
+        std::fs::write(&path, b"not audio").unwrap();
+        assert!(matches!(
+            transcriber.mel_spectrogram(&path),
+            Err(Error::UnsupportedFormat(_))
+        ));
+    }
+
+    #[test]
+    fn test_mel_band_mismatches_are_rejected() {
+        let transcribe = |backend: MockBackend| {
+            Transcriber::with_mock_backend(backend)
+                .transcribe_samples(&[0.0; 16000], &TranscribeOptions::default())
+        };
+        let segments = || vec![MockSegment::new(0, 100, " Hi.")];
+
+        let large_v3 = MockBackend::new(segments()).with_mel_bands(128, Some(128));
+        assert_eq!(transcribe(large_v3).unwrap().get_segments().len(), 1);
+        assert!(transcribe(MockBackend::new(segments()).with_mel_bands(128, None)).is_ok());
+
+        let err =
+            transcribe(MockBackend::new(segments()).with_mel_bands(128, Some(80))).unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<Error>(),
+            Some(Error::MelBandsMismatch {
+                n_mels: 128,
+                filterbank_n_mels: 80
+            })
+        ));
+        let err =
+            transcribe(MockBackend::new(segments()).with_mel_bands(64, Some(64))).unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<Error>(),
+            Some(Error::UnsupportedMelBands(64))
+        ));
+    }
+
+    #[test]
+    fn test_non_stock_vocabulary_reported() {
+        let stock = Transcriber::with_mock_backend(MockBackend::new(Vec::new()));
+        let fine_tuned =
+            Transcriber::with_mock_backend(MockBackend::new(Vec::new()).with_vocab_size(52000));
+
+        assert!(stock.hyperparameters().has_stock_vocabulary());
+        assert_eq!(fine_tuned.hyperparameters().n_vocab, 52000);
+        assert!(!fine_tuned.hyperparameters().has_stock_vocabulary());
+        let output = fine_tuned
+            .transcribe_samples(&[0.0; 16000], &TranscribeOptions::default())
+            .unwrap();
+        assert_eq!(
+            output.get_warnings(),
+            &vec![Warning::NonStockVocabulary { vocab_size: 52000 }]
+        );
+        let translate = TranscribeOptions {
+            translate: true,
+            ..Default::default()
+        };
+        let err = fine_tuned
+            .transcribe_samples(&[0.0; 16000], &translate)
+            .unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<Error>(),
+            Some(Error::IncompatibleOptions(violations))
+                if violations[0] == crate::error::OptionViolation::RequiresStockVocabulary {
+                    option: "translate",
+                    vocab_size: 52000,
+                }
+        ));
+    }
+
+    #[test]
+    fn test_mock_transcribe_samples_extracts_segments_and_words() {
+        let transcriber = Transcriber::with_mock_backend(MockBackend::new(vec![
+            MockSegment::new(0, 100, " Hello world."),
+            MockSegment::new(100, 200, " Bye."),
+        ]));
+        let options = TranscribeOptions {
+            word_timestamps: true,
+            ..Default::default()
+        };
+
+        let output = transcriber
+            .transcribe_samples(&[0.0; 32000], &options)
+            .unwrap();
+
+        assert_eq!(texts(&output), vec![" Hello world.", " Bye."]);
+        assert_eq!(output.get_audio_duration(), Some(200));
+        assert!(output.get_warnings().is_empty());
+        let words = output.get_segments()[0].get_words();
+        assert_eq!(
+            words,
+            &vec![
+                TranscriberOutputWord::new(0, 50, " Hello").with_probability(1.0),
+                TranscriberOutputWord::new(50, 100, " world.").with_probability(1.0),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_token_probabilities_collected_when_enabled() {
+        let transcriber = Transcriber::with_mock_backend(MockBackend::new(vec![
+            MockSegment::new(0, 100, " Hello world.").with_probabilities(vec![0.9, 0.25]),
+            MockSegment::new(100, 200, " Bye.").with_probabilities(vec![0.5]),
+        ]));
+        let options = TranscribeOptions {
+            token_probabilities: true,
+            ..Default::default()
+        };
+
+        let mut output = transcriber
+            .transcribe_samples(&[0.0; 32000], &options)
+            .unwrap();
+        let default = transcriber
+            .transcribe_samples(&[0.0; 32000], &TranscribeOptions::default())
+            .unwrap();
+
+        let segments = output.get_segments();
+        assert_eq!(segments[0].get_token_probabilities(), &[0.9, 0.25]);
+        assert_eq!(segments[0].get_token_ids(), &[0, 1]);
+        assert_eq!(segments[1].get_token_probabilities(), &[0.5]);
+        assert!(output
+            .to_json()
+            .contains(r#""token_probabilities":[0.9,0.25]"#));
+        assert!(default.get_segments()[0]
+            .get_token_probabilities()
+            .is_empty());
+        assert!(!default.to_json().contains(r#""token_probabilities":["#));
+
+        output.merge_segments(std::time::Duration::from_secs(1), 80, 1);
+        assert_eq!(
+            output.get_segments()[0].get_token_probabilities(),
+            &[0.9, 0.25, 0.5]
+        );
+        assert_eq!(output.get_segments()[0].get_token_ids(), &[0, 1, 0]);
+        output.split_segments(8, 1);
+        assert!(output
+            .get_segments()
+            .iter()
+            .all(|segment| segment.get_token_probabilities().is_empty()
+                && segment.get_token_ids().is_empty()));
+    }
+
+    #[test]
+    fn test_n_best_falls_back_to_the_decoded_text() {
+        let transcriber = Transcriber::with_mock_backend(MockBackend::new(vec![
+            MockSegment::new(0, 100, " Hello world.").with_probabilities(vec![0.9, 0.3]),
+            MockSegment::new(100, 200, " Bye.").with_probabilities(vec![0.5]),
+        ]));
+        let options = TranscribeOptions {
+            n_best: 3,
+            ..Default::default()
+        };
+
+        let mut output = transcriber
+            .transcribe_samples(&[0.0; 32000], &options)
+            .unwrap();
+        let default = transcriber
+            .transcribe_samples(&[0.0; 32000], &TranscribeOptions::default())
+            .unwrap();
+
+        let alternatives = output.get_segments()[0].alternatives();
+        assert_eq!(alternatives.len(), 1);
+        assert_eq!(alternatives[0].get_text(), " Hello world.");
+        assert!((alternatives[0].get_score() - 0.6).abs() < 1e-6);
+        assert!(output
+            .to_json()
+            .contains(r#""alternatives":[{"text":" Bye.","score":0.5}]"#));
+        assert!(!output.to_srt().contains("score"));
+        assert!(default.get_segments()[0].alternatives().is_empty());
+        assert!(!default.to_json().contains("alternatives"));
+
+        output.merge_segments(std::time::Duration::from_secs(1), 80, 1);
+        assert!(output.get_segments()[0].alternatives().is_empty());
+
+        let params =
+            whisper_rs::FullParams::new(whisper_rs::SamplingStrategy::Greedy { best_of: 5 });
+        let sampled = transcriber
+            .run(vec![0.0; 32000], Some(params), &options)
+            .unwrap();
+        for segment in sampled.get_segments() {
+            assert_eq!(segment.alternatives().len(), 1);
+            assert_eq!(segment.alternatives()[0].get_text(), segment.get_text());
+        }
+    }
+
+    #[test]
+    fn test_min_snr_db_warns_on_noisy_audio() {
+        let transcriber = Transcriber::with_mock_backend(MockBackend::new(vec![MockSegment::new(
+            0, 100, " Hi.",
+        )]));
+        let options = TranscribeOptions {
+            min_snr_db: Some(10.0),
+            ..Default::default()
+        };
+        let mut clean = crate::test_util::silence(std::time::Duration::from_secs(1));
+        clean.extend(crate::test_util::speech_clip());
+
+        let noisy = transcriber
+            .transcribe_samples(
+                &crate::test_util::noise(std::time::Duration::from_secs(2), 0.5),
+                &options,
+            )
+            .unwrap();
+        let clean = transcriber.transcribe_samples(&clean, &options).unwrap();
+
+        assert!(matches!(
+            noisy.get_warnings()[..],
+            [Warning::PoorAudioQuality { snr_db, min_snr_db: 10.0 }] if snr_db < 10.0
+        ));
+        assert!(
+            clean.get_warnings().is_empty(),
+            "{:?}",
+            clean.get_warnings()
+        );
+    }
+
+    #[test]
+    fn test_min_snr_db_rates_files_before_they_are_gated() {
+        let transcriber = Transcriber::with_mock_backend(MockBackend::new(vec![MockSegment::new(
+            0, 100, " Hi.",
+        )]));
+        // two seconds of 48KHz noise with a loud tone in the middle
+        let mut samples = crate::test_util::noise(std::time::Duration::from_secs(6), 0.05);
+        for (i, sample) in samples[32000..64000].iter_mut().enumerate() {
+            *sample += 0.5 * (2.0 * std::f32::consts::PI * 1000.0 * i as f32 / 48000.0).sin();
+        }
+        let samples: Vec<i16> = samples.iter().map(|s| (s * 32767.0) as i16).collect();
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("noisy.wav");
+        crate::test_util::write_pcm_wav(&path, 48000, 1, &samples);
+        let snr_db = |options: &TranscribeOptions| match transcriber
+            .transcribe_with_options(&path, options)
+            .unwrap()
+            .get_warnings()[..]
+        {
+            [Warning::PoorAudioQuality { snr_db, .. }] => snr_db,
+            ref warnings => panic!("{:?}", warnings),
+        };
+        let options = TranscribeOptions {
+            min_snr_db: Some(60.0),
+            ..Default::default()
+        };
+
+        let ungated = snr_db(&options);
+        let gated = snr_db(&TranscribeOptions {
+            denoise: Some(NoiseGate::default()),
+            ..options.clone()
+        });
+
+        assert_eq!(gated, ungated);
+        let at_16k = audio_parser::parse_audio_file(&path);
+        assert_eq!(Some(ungated), quality::assess(&at_16k).map(|q| q.snr_db));
+    }
+
+    #[test]
+    fn test_gain_db_reports_limiting() {
+        let transcriber = Transcriber::with_mock_backend(MockBackend::new(vec![MockSegment::new(
+            0, 100, " Hi.",
+        )]));
+        let options = |gain_db| TranscribeOptions {
+            gain_db: Some(gain_db),
+            ..Default::default()
+        };
+        let quiet: Vec<f32> = (0..16000).map(|i| 0.01 * (i as f32 * 0.05).sin()).collect();
+
+        let gentle = transcriber
+            .transcribe_samples(&quiet, &options(12.0))
+            .unwrap();
+        let loud = transcriber
+            .transcribe_samples(&quiet, &options(60.0))
+            .unwrap();
+
+        assert!(gentle.get_warnings().is_empty());
+        assert!(matches!(
+            loud.get_warnings()[..],
+            [Warning::AudioLimited { limited_samples, total_samples: 16000, .. }]
+                if limited_samples > 0
+        ));
+    }
+
+    #[test]
+    fn test_transcribe_fast_uses_the_preview_preset() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("speech.wav");
+        crate::test_util::write_wav(&path, &crate::test_util::speech_clip());
+        let transcriber = Transcriber::with_mock_backend(MockBackend::new(vec![MockSegment::new(
+            0, 100, " Hi.",
+        )]));
+
+        let output = transcriber.transcribe_fast(&path).unwrap();
+
+        assert_eq!(texts(&output), vec![" Hi."]);
+        let options = &output.manifest().unwrap().options;
+        assert_eq!(options["audio_ctx"], 750);
+        assert_eq!(options["temperature_fallback"], false);
+    }
+
+    #[test]
+    fn test_memory_budget_serializes_transcriptions() {
+        let latency = std::time::Duration::from_millis(200);
+        let budget = MemoryBudget::new(
+            MockBackend::STATE_MEMORY_BYTES,
+            std::time::Duration::from_secs(10),
+        );
+        let started = std::time::Instant::now();
+
+        let handles: Vec<_> = (0..2)
+            .map(|_| {
+                let budget = budget.clone();
+                std::thread::spawn(move || {
+                    let transcriber = Transcriber::with_mock_backend(
+                        MockBackend::new(vec![MockSegment::new(0, 100, " Hi.")])
+                            .with_latency(latency),
+                    )
+                    .with_memory_budget(budget);
+                    transcriber
+                        .transcribe_samples(&[0.0; 16000], &TranscribeOptions::default())
+                        .is_ok()
+                })
+            })
+            .collect();
+
+        for handle in handles {
+            assert!(handle.join().unwrap());
+        }
+        assert!(started.elapsed() >= latency * 2);
+        assert_eq!(budget.in_use(), 0);
+    }
+
+    #[test]
+    fn test_memory_budget_fails_fast() {
+        let budget = MemoryBudget::new(MockBackend::STATE_MEMORY_BYTES, std::time::Duration::ZERO);
+        let _held = budget.reserve(1).unwrap();
+        let transcriber =
+            Transcriber::with_mock_backend(MockBackend::new(Vec::new())).with_memory_budget(budget);
+
+        let err = transcriber
+            .transcribe_samples(&[0.0; 16000], &TranscribeOptions::default())
+            .unwrap_err();
+
+        assert!(matches!(
+            err.downcast_ref::<Error>(),
+            Some(Error::OverBudget { .. })
+        ));
+    }
+
+    fn assert_callback_panicked(
+        result: Result<TranscriberOutput, Box<dyn std::error::Error + Send + Sync>>,
+    ) {
+        match result.unwrap_err().downcast_ref::<Error>() {
+            Some(Error::CallbackPanicked(message)) => assert_eq!(message, "callback failed"),
+            other => panic!("unexpected error {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_mock_callbacks_report_progress_and_segments() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("speech.wav");
+        crate::audio_parser::tests::write_test_wav(&path, 16000, 1, &[0; 32000]);
+        let transcriber = Transcriber::with_mock_backend(MockBackend::new(vec![
+            MockSegment::new(0, 100, " One."),
+            MockSegment::new(100, 200, " Two."),
+        ]));
+        let mut progress = Vec::new();
+        let mut segments = Vec::new();
+        let partial_text = std::sync::Arc::new(std::sync::Mutex::new(String::new()));
+
+        let output = transcriber
+            .transcribe_with_callbacks(
+                path.to_str().unwrap(),
+                &TranscribeOptions::default(),
+                Callbacks::new()
+                    .on_progress(|p| progress.push(p))
+                    .on_segment(|s| segments.push(s.get_text().to_string()))
+                    .partial_text(partial_text.clone()),
+            )
+            .unwrap();
+
+        assert_eq!(progress, vec![50, 100]);
+        assert_eq!(segments, texts(&output));
+        assert_eq!(
+            *partial_text.lock().unwrap(),
+            output.to_plain_text(crate::export::TextSeparator::Space)
+        );
+    }
+
+    #[test]
+    fn test_mock_panicking_callbacks_fail_the_transcription() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("speech.wav");
+        crate::audio_parser::tests::write_test_wav(&path, 16000, 1, &[0; 32000]);
+        let path = path.to_str().unwrap();
+        let transcriber = Transcriber::with_mock_backend(MockBackend::new(vec![MockSegment::new(
+            0, 100, " Hi.",
+        )]));
+        let chunked = TranscribeOptions {
+            chunk_length: Some(std::time::Duration::from_secs(1)),
+            ..Default::default()
+        };
+
+        assert_callback_panicked(transcriber.transcribe_with_callbacks(
+            path,
+            &TranscribeOptions::default(),
+            Callbacks::new().on_progress(|_| panic!("callback failed")),
+        ));
+        assert_callback_panicked(transcriber.transcribe_with_callbacks(
+            path,
+            &chunked,
+            Callbacks::new().on_segment(|_| panic!("callback failed")),
+        ));
+
+        let output = transcriber
+            .transcribe_with_callbacks(path, &TranscribeOptions::default(), Callbacks::new())
+            .unwrap();
+        assert_eq!(texts(&output), vec![" Hi."]);
+    }
+
+    fn channel_transcriber() -> Transcriber {
+        Transcriber::with_mock_backend(MockBackend::new(
+            (0..5)
+                .map(|i| MockSegment::new(i * 100, i * 100 + 100, format!(" Segment {}.", i)))
+                .collect(),
+        ))
+    }
+
+    /// Five seconds of silence, written as a WAV file in `dir`.
+    fn channel_audio(dir: &tempfile::TempDir) -> std::path::PathBuf {
+        let path = dir.path().join("speech.wav");
+        crate::test_util::write_wav(
+            &path,
+            &crate::test_util::silence(std::time::Duration::from_secs(5)),
+        );
+        path
+    }
+
+    fn segment_texts(segments: Vec<TranscriberOutputSegment>) -> Vec<String> {
+        segments.iter().map(|s| s.get_text().to_string()).collect()
+    }
+
+    #[test]
+    fn test_segment_channel_block_paces_inference() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = channel_audio(&dir);
+        let transcriber = channel_transcriber();
+        let (callbacks, receiver) =
+            Callbacks::new().segment_channel(1, crate::callbacks::Backpressure::Block);
+
+        let (output, received) = std::thread::scope(|scope| {
+            let consumer = scope.spawn(move || {
+                let mut received = Vec::new();
+                for segment in receiver {
+                    std::thread::sleep(std::time::Duration::from_millis(20));
+                    received.push(segment);
+                }
+                received
+            });
+            let started = std::time::Instant::now();
+            let output = transcriber
+                .transcribe_with_callbacks(&path, &TranscribeOptions::default(), callbacks)
+                .unwrap();
+            // inference waited for the consumer to take all but the last two segments
+            assert!(started.elapsed() >= std::time::Duration::from_millis(60));
+            (output, consumer.join().unwrap())
+        });
+
+        assert_eq!(segment_texts(received), texts(&output));
+        assert!(output.get_warnings().is_empty());
+    }
+
+    #[test]
+    fn test_segment_channel_drop_oldest_warns() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = channel_audio(&dir);
+        let transcriber = channel_transcriber();
+        let (callbacks, receiver) =
+            Callbacks::new().segment_channel(2, crate::callbacks::Backpressure::DropOldest);
+
+        // the consumer only reads once the transcription is over
+        let output = transcriber
+            .transcribe_with_callbacks(&path, &TranscribeOptions::default(), callbacks)
+            .unwrap();
+
+        assert_eq!(receiver.dropped(), 3);
+        assert_eq!(
+            segment_texts(receiver.collect()),
+            vec![" Segment 3.", " Segment 4."]
+        );
+        assert_eq!(output.get_segments().len(), 5);
+        assert_eq!(
+            output.get_warnings(),
+            &vec![Warning::SegmentsDropped { count: 3 }]
+        );
+    }
+
+    #[test]
+    fn test_segment_channel_fail_aborts_transcription() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = channel_audio(&dir);
+        let transcriber = channel_transcriber();
+        let (callbacks, receiver) =
+            Callbacks::new().segment_channel(2, crate::callbacks::Backpressure::Fail);
+
+        let result =
+            transcriber.transcribe_with_callbacks(&path, &TranscribeOptions::default(), callbacks);
+
+        match result.unwrap_err().downcast_ref::<Error>() {
+            Some(Error::SegmentChannelFull { capacity: 2 }) => {}
+            other => panic!("unexpected error {:?}", other),
+        }
+        assert_eq!(receiver.count(), 2);
+    }
+
+    #[test]
+    fn test_mock_normalize_segments_option() {
+        let transcriber = Transcriber::with_mock_backend(MockBackend::new(vec![
+            MockSegment::new(50, 100, " Two."),
+            MockSegment::new(0, 80, " One."),
+        ]));
+        let options = TranscribeOptions {
+            normalize_segments: true,
+            ..Default::default()
+        };
+
+        let output = transcriber
+            .transcribe_samples(&[0.0; 16000], &options)
+            .unwrap();
+
+        assert_eq!(texts(&output), vec![" One.", " Two."]);
+        assert_eq!(output.get_segments()[1].get_start_timestamp(), &80);
+        assert!(output.get_warnings().is_empty());
+    }
+
+    #[test]
+    fn test_mock_transcribe_long_unicode_path() {
+        use crate::audio_parser::tests::write_test_wav;
+
+        let dir = tempfile::tempdir().unwrap();
+        let audio_dir = (0..10).fold(dir.path().to_path_buf(), |path, i| {
+            path.join(format!("会議の録音 ñandú {}", i))
+        });
+        std::fs::create_dir_all(&audio_dir).unwrap();
+        let path = audio_dir.join("インタビュー – 1.wav");
+        write_test_wav(&path, 16000, 1, &[0; 16000]);
+        let transcriber = Transcriber::with_mock_backend(MockBackend::new(vec![MockSegment::new(
+            0, 100, " Hi.",
+        )]));
+
+        let output = transcriber
+            .transcribe_with_options(&path, &TranscribeOptions::default())
+            .unwrap();
+
+        assert!(path.as_os_str().len() > 260);
+        assert_eq!(texts(&output), vec![" Hi."]);
+        let transcript = audio_dir.join("インタビュー – 1.srt");
+        output.save(&transcript).unwrap();
+        assert!(std::fs::read_to_string(transcript).unwrap().contains("Hi."));
+    }
+
+    #[test]
+    fn test_mock_strict_sample_rate_opts_out_of_resampling() {
+        use crate::audio_parser::tests::write_test_wav;
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("phone.wav");
+        write_test_wav(&path, 8000, 1, &[0; 16000]);
+        let transcriber = Transcriber::with_mock_backend(MockBackend::new(Vec::new()));
+
+        let output = transcriber
+            .transcribe_with_options(&path, &TranscribeOptions::default())
+            .unwrap();
+        assert_eq!(output.get_audio_duration(), Some(200));
+
+        let strict = TranscribeOptions {
+            strict_sample_rate: true,
+            ..TranscribeOptions::default()
+        };
+        let err = transcriber
+            .transcribe_with_options(&path, &strict)
+            .unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<Error>(),
+            Some(Error::UnsupportedSampleRate {
+                sample_rate: 8000,
+                required: 16000
+            })
+        ));
+        assert!(err.to_string().contains("ffmpeg -i"));
+    }
+
+    #[test]
+    fn test_mock_unsupported_format_lists_the_supported_ones() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("notes.txt");
+        std::fs::write(&path, "not audio at all, just some text").unwrap();
+        let transcriber = Transcriber::with_mock_backend(MockBackend::new(Vec::new()));
+
+        let err = transcriber
+            .transcribe_with_options(&path, &TranscribeOptions::default())
+            .unwrap_err();
+
+        match err.downcast_ref::<Error>() {
+            Some(Error::UnsupportedFormat(input)) => assert!(input.ends_with("notes.txt")),
+            other => panic!("expected UnsupportedFormat, got {:?}", other),
+        }
+        let message = err.to_string();
+        assert!(message.contains("mp3"));
+        assert!(message.contains("ffmpeg -i"));
+    }
+
+    #[test]
+    fn test_mock_expected_speakers_marks_turns() {
+        let transcriber = Transcriber::with_mock_backend(MockBackend::new(vec![
+            MockSegment::new(0, 100, " Welcome."),
+            MockSegment::new(160, 300, " Thanks."),
+        ]));
+        let options = |expected_speakers| TranscribeOptions {
+            expected_speakers,
+            ..Default::default()
+        };
+
+        let panel = transcriber
+            .transcribe_samples(&[0.0; 48000], &options(Some(4)))
+            .unwrap();
+        let monologue = transcriber
+            .transcribe_samples(&[0.0; 48000], &options(Some(1)))
+            .unwrap();
+
+        assert!(panel.get_segments()[1].is_speaker_turn());
+        assert!(panel.to_json().contains(r#""speaker_turn":true"#));
+        assert!(!monologue.get_segments()[1].is_speaker_turn());
+    }
+
+    #[test]
+    fn test_mock_chunked_context_policy_bounds_prompt() {
+        let options = TranscribeOptions {
+            chunk_length: Some(std::time::Duration::from_secs(1)),
+            context_policy: crate::options::ContextPolicy::LastNTokens(2),
+            ..Default::default()
+        };
+        let transcriber = Transcriber::with_mock_backend(MockBackend::new(vec![MockSegment::new(
+            0,
+            100,
+            " One two three.",
+        )]));
+        let mut prompts = Vec::new();
+
+        let output = transcriber
+            .run_from(
+                vec![0.0; 48000],
+                None,
+                &options,
+                Checkpoint::default(),
+                &mut |checkpoint| prompts.push(checkpoint.prompt.len()),
+                &mut Callbacks::new(),
+                None,
+            )
+            .unwrap();
+
+        assert_eq!(output.get_segments().len(), 3);
+        assert_eq!(prompts, vec![2, 2, 2]);
+    }
+
+    #[test]
+    fn test_empty_segments_are_dropped_by_default() {
+        let transcriber = Transcriber::with_mock_backend(MockBackend::new(vec![
+            MockSegment::new(0, 100, " Hi."),
+            MockSegment::new(100, 150, "  "),
+            MockSegment::new(150, 200, ""),
+            MockSegment::new(200, 300, " Bye."),
+        ]));
+        let samples = vec![0.0; 16000 * 3];
+
+        let dropped = transcriber
+            .transcribe_samples(&samples, &TranscribeOptions::default())
+            .unwrap();
+        let kept = transcriber
+            .transcribe_samples(
+                &samples,
+                &TranscribeOptions {
+                    drop_empty_segments: false,
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+
+        let ids: Vec<u64> = dropped.get_segments().iter().map(|s| s.id()).collect();
+        assert_eq!(ids, vec![0, 3]);
+        assert_eq!(
+            dropped.change_log().changes(),
+            [Change::Dropped { ids: vec![1, 2] }]
+        );
+        assert_eq!(kept.get_segments().len(), 4);
+        assert!(kept.change_log().is_empty());
+    }
+
+    #[test]
+    fn test_mock_collapse_repetitions() {
+        let transcriber = Transcriber::with_mock_backend(MockBackend::new(vec![
+            MockSegment::new(0, 100, " Hi."),
+            MockSegment::new(100, 200, " Thank you."),
+            MockSegment::new(200, 300, " Thank you."),
+            MockSegment::new(300, 400, " Thank you."),
+        ]));
+        let options = TranscribeOptions {
+            collapse_repetitions: Some(3),
+            ..Default::default()
+        };
+
+        let output = transcriber
+            .transcribe_samples(&vec![0.0; 16000 * 5], &options)
+            .unwrap();
+
+        assert_eq!(texts(&output), vec![" Hi.", " Thank you."]);
+        assert_eq!(output.get_segments()[1].get_end_timestamp(), &400);
+        assert_eq!(
+            output.get_warnings()[..],
+            [Warning::RepetitionCollapsed {
+                segment: 1,
+                repeats: 3
+            }]
+        );
+        assert_eq!(
+            output.change_log().origins(output.get_segments()[1].id()),
+            vec![1, 2, 3]
+        );
+    }
+
+    #[test]
+    fn test_search_segments() {
+        let output = TranscriberOutput::new(vec![
+            TranscriberOutputSegment::new(0, 100, " The Quick brown fox."),
+            TranscriberOutputSegment::new(100, 200, " Jumps over."),
+            TranscriberOutputSegment::new(200, 300, " Straße, quick!"),
+        ]);
+        let starts = |segments: Vec<&TranscriberOutputSegment>| -> Vec<i64> {
+            segments.iter().map(|s| s.start_timestamp).collect()
+        };
+
+        assert_eq!(starts(output.search("quick", true)), vec![0, 200]);
+        assert_eq!(starts(output.search("quick", false)), vec![200]);
+        assert_eq!(starts(output.search("STRASSE", true)), Vec::<i64>::new());
+        assert_eq!(starts(output.search("STRAẞE", true)), vec![200]);
+        assert!(output.search("", true).is_empty());
+    }
+
+    #[test]
+    fn test_append_shifts_the_other_output() {
+        let mut output =
+            TranscriberOutput::new(vec![TranscriberOutputSegment::new(0, 150, " One.")])
+                .with_audio_duration(300);
+        let mut other = TranscriberOutput::new(vec![
+            TranscriberOutputSegment::new(0, 100, " Two.")
+                .with_words(vec![TranscriberOutputWord::new(20, 100, " Two.")]),
+            TranscriberOutputSegment::new(100, 250, " Three."),
+        ])
+        .with_audio_duration(300);
+        other.language = Some("de".to_string());
+        other.warnings.push(Warning::NoAudio);
+
+        output.append(other, 3004);
+
+        let spans: Vec<(i64, i64)> = output
+            .get_segments()
+            .iter()
+            .map(|s| (s.start_timestamp, s.end_timestamp))
+            .collect();
+        assert_eq!(spans, vec![(0, 150), (300, 400), (400, 550)]);
+        assert_eq!(output.get_segments()[1].get_words()[0].start_timestamp, 320);
+        assert_eq!(output.get_audio_duration(), Some(600));
+        assert_eq!(output.get_language(), Some("de"));
+        assert_eq!(output.get_warnings().len(), 1);
+    }
+
+    #[test]
+    fn test_retranscribe_range_and_splice() {
+        let transcriber = Transcriber::with_mock_backend(MockBackend::new(vec![MockSegment::new(
+            10, 120, " Fixed.",
+        )]));
+        let samples = vec![0.0; 16000 * 10];
+        let seconds = std::time::Duration::from_secs;
+        let mut output = TranscriberOutput::new(vec![
+            TranscriberOutputSegment::new(0, 150, " Before."),
+            TranscriberOutputSegment::new(200, 350, " Noise."),
+            TranscriberOutputSegment::new(400, 500, " After."),
+        ]);
+
+        let range = std::time::Duration::from_millis(2000)..std::time::Duration::from_millis(3500);
+        let replacement = transcriber
+            .retranscribe_range(&samples, range.clone(), &TranscribeOptions::default())
+            .unwrap();
+        assert_eq!(replacement.get_audio_duration(), Some(150));
+        output.splice(range, replacement);
+
+        let segments: Vec<(i64, i64, &str)> = output
+            .get_segments()
+            .iter()
+            .map(|s| (s.start_timestamp, s.end_timestamp, s.get_text()))
+            .collect();
+        assert_eq!(
+            segments,
+            vec![
+                (0, 150, " Before."),
+                (210, 320, " Fixed."),
+                (400, 500, " After.")
+            ]
+        );
+
+        let tail = transcriber
+            .retranscribe_range(
+                &samples,
+                seconds(9)..seconds(20),
+                &TranscribeOptions::default(),
+            )
+            .unwrap();
+        assert_eq!(tail.get_audio_duration(), Some(100));
+    }
+
+    #[test]
+    fn test_mock_records_language() {
+        let transcriber = Transcriber::with_mock_backend(MockBackend::new(vec![MockSegment::new(
+            0, 100, " Hi.",
+        )]));
+        let samples = [0.0; 16000];
+
+        let given = transcriber
+            .transcribe_samples(&samples, &TranscribeOptions::default())
+            .unwrap();
+        let detected = transcriber
+            .transcribe_samples(
+                &samples,
+                &TranscribeOptions {
+                    language: None,
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+
+        assert_eq!(given.get_language(), Some("en"));
+        assert_eq!(given.get_language_source(), Some(LanguageSource::Options));
+        assert_eq!(detected.get_language(), Some("en"));
+        assert_eq!(
+            detected.get_language_source(),
+            Some(LanguageSource::Detected)
+        );
+        assert!(!detected.is_translated());
+    }
+
+    #[test]
+    fn test_metadata_language_seeds_whisper() {
+        let transcriber = Transcriber::with_mock_backend(MockBackend::new(vec![MockSegment::new(
+            0, 100, " Hallo.",
+        )]));
+        let dir = tempfile::tempdir().unwrap();
+        let tagged = dir.path().join("tagged.wav");
+        crate::test_util::write_wav_with_language(&tagged, &[0.0; 16000], "ger");
+        let garbage = dir.path().join("garbage.wav");
+        crate::test_util::write_wav_with_language(&garbage, &[0.0; 16000], "%%Klingon%%");
+        let options = TranscribeOptions {
+            use_metadata_language: true,
+            ..Default::default()
+        };
+
+        let output = transcriber
+            .transcribe_with_options(&tagged, &options)
+            .unwrap();
+        assert_eq!(output.get_language(), Some("de"));
+        assert_eq!(output.get_language_source(), Some(LanguageSource::Metadata));
+        assert_eq!(output.manifest().unwrap().options["language"], "de");
+        assert!(output.to_json().contains(r#""language_source":"Metadata""#));
+
+        // an invalid tag falls back to detection, not to the options' language
+        let output = transcriber
+            .transcribe_with_options(&garbage, &options)
+            .unwrap();
+        assert_eq!(output.get_language(), Some("en"));
+        assert_eq!(output.get_language_source(), Some(LanguageSource::Detected));
+        assert_eq!(
+            output.manifest().unwrap().options["language"],
+            serde_json::Value::Null
+        );
+        assert_eq!(
+            output.get_warnings(),
+            &vec![Warning::InvalidLanguageMetadata {
+                tag: "%%Klingon%%".to_string()
+            }]
+        );
+
+        // off by default
+        let output = transcriber
+            .transcribe_with_options(&tagged, &TranscribeOptions::default())
+            .unwrap();
+        assert_eq!(output.get_language(), Some("en"));
+    }
+
+    #[test]
+    fn test_metadata_language_is_validated_against_the_model() {
+        let transcriber =
+            Transcriber::with_mock_backend(MockBackend::new(Vec::new()).english_only());
+        assert!(!transcriber.is_multilingual());
+        let dir = tempfile::tempdir().unwrap();
+        let tagged = dir.path().join("tagged.wav");
+        crate::test_util::write_wav_with_language(&tagged, &[0.0; 16000], "de");
+        let options = TranscribeOptions {
+            use_metadata_language: true,
+            ..Default::default()
+        };
+
+        let err = transcriber
+            .transcribe_with_options(&tagged, &options)
+            .unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<Error>(),
+            Some(Error::IncompatibleOptions(violations))
+                if violations[0] == crate::error::OptionViolation::LanguageWithEnglishOnlyModel(
+                    "de".to_string()
+                )
+        ));
+
+        // without detection, an invalid tag falls back to the options' language
+        let garbage = dir.path().join("garbage.wav");
+        crate::test_util::write_wav_with_language(&garbage, &[0.0; 16000], "%%Klingon%%");
+        let output = transcriber
+            .transcribe_with_options(&garbage, &options)
+            .unwrap();
+        assert_eq!(output.get_language_source(), Some(LanguageSource::Options));
+        assert_eq!(output.get_warnings().len(), 1);
+    }
+
+    #[test]
+    fn test_mock_word_timestamps_fall_back_to_segments() {
+        let transcriber = Transcriber::with_mock_backend(
+            MockBackend::new(vec![
+                MockSegment::new(0, 100, " Hello world."),
+                MockSegment::new(100, 200, " Bye."),
+            ])
+            .without_token_timestamps(),
+        );
+        let options = TranscribeOptions {
+            word_timestamps: true,
+            ..Default::default()
+        };
+
+        let output = transcriber
+            .transcribe_samples(&[0.0; 32000], &options)
+            .unwrap();
+
+        assert_eq!(texts(&output), vec![" Hello world.", " Bye."]);
+        assert!(output
+            .get_segments()
+            .iter()
+            .all(|s| s.get_words().is_empty()));
+        assert_eq!(
+            output.get_warnings(),
+            &vec![Warning::WordTimestampsUnavailable { segments: 2 }]
+        );
+    }
+
+    #[test]
+    fn test_mock_chunked_offsets_timestamps_and_resumes() {
+        let runs = vec![
+            vec![MockSegment::new(0, 500, " One.")],
+            vec![MockSegment::new(0, 300, " Two.")],
+        ];
+        let options = TranscribeOptions {
+            chunk_length: Some(std::time::Duration::from_secs(10)),
+            ..Default::default()
+        };
+        let audio = vec![0.0; 240000];
+        let transcriber = Transcriber::with_mock_backend(MockBackend::with_runs(runs.clone()));
+        let mut checkpoints = Vec::new();
+
+        let output = transcriber
+            .run_from(
+                audio.clone(),
+                None,
+                &options,
+                Checkpoint::default(),
+                &mut |checkpoint| checkpoints.push(checkpoint.clone()),
+                &mut Callbacks::new(),
+                None,
+            )
+            .unwrap();
+
+        let timestamps: Vec<(i64, i64)> = output
+            .get_segments()
+            .iter()
+            .map(|s| (*s.get_start_timestamp(), *s.get_end_timestamp()))
+            .collect();
+        assert_eq!(timestamps, vec![(0, 500), (1000, 1300)]);
+        let offsets: Vec<usize> = checkpoints.iter().map(|c| c.get_sample_offset()).collect();
+        assert_eq!(offsets, vec![160000, 240000]);
+
+        let transcriber = Transcriber::with_mock_backend(MockBackend::new(runs[1].clone()));
+        let resumed = transcriber
+            .run_from(
+                audio,
+                None,
+                &options,
+                checkpoints[0].clone(),
+                &mut |_| {},
+                &mut Callbacks::new(),
+                None,
+            )
+            .unwrap();
+
+        assert_eq!(texts(&resumed), texts(&output));
+        assert_eq!(resumed.get_segments()[1].get_start_timestamp(), &1000);
+    }
+
+    #[test]
+    fn test_mock_resume_checkpoint_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let audio = dir.path().join("long.wav");
+        crate::test_util::write_wav(&audio, &vec![0.0; 5 * 160000]);
+        let checkpoint = dir.path().join("long.checkpoint");
+        let runs: Vec<Vec<MockSegment>> = (0..5)
+            .map(|i| vec![MockSegment::new(0, 500, format!(" Chunk {}.", i))])
+            .collect();
+        let options = TranscribeOptions {
+            chunk_length: Some(std::time::Duration::from_secs(10)),
+            ..Default::default()
+        };
+
+        let straight = Transcriber::with_mock_backend(MockBackend::with_runs(runs.clone()))
+            .transcribe_with_checkpoint_file(&audio, &options, &checkpoint)
+            .unwrap();
+        let contents = std::fs::read_to_string(&checkpoint).unwrap();
+        let lines: Vec<&str> = contents.lines().collect();
+        assert_eq!(lines.len(), 6);
+        // the process died after two chunks, while writing the third
+        let killed = format!(
+            "{}\n{}\n{}\n{}",
+            lines[0],
+            lines[1],
+            lines[2],
+            &lines[3][..20]
+        );
+        std::fs::write(&checkpoint, killed).unwrap();
+
+        let transcriber =
+            Transcriber::with_mock_backend(MockBackend::with_runs(runs[2..].to_vec()));
+        let resumed = transcriber.resume(&checkpoint, &options).unwrap();
+
+        assert_eq!(resumed.to_json(), straight.to_json());
+        assert_eq!(std::fs::read_to_string(&checkpoint).unwrap(), contents);
+
+        let other_options = TranscribeOptions {
+            language: Some("de".to_string()),
+            ..options.clone()
+        };
+        let err = transcriber.resume(&checkpoint, &other_options).unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<Error>(),
+            Some(Error::InvalidCheckpoint(_))
+        ));
+        crate::test_util::write_wav(&audio, &vec![0.5; 5 * 160000]);
+        let err = transcriber.resume(&checkpoint, &options).unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<Error>(),
+            Some(Error::InvalidCheckpoint(_))
+        ));
+    }
+
+    #[test]
+    fn test_mock_retries_transient_failures() {
+        let segments = vec![MockSegment::new(0, 100, " Hello.")];
+        let options = TranscribeOptions {
+            retry: RetryPolicy {
+                max_attempts: 2,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+
+        let flaky = Transcriber::with_mock_backend(
+            MockBackend::new(segments.clone())
+                .failing_with(vec![whisper_rs::WhisperError::FailedToEncode]),
+        );
+        let output = flaky.transcribe_samples(&[0.0; 16000], &options).unwrap();
+
+        assert_eq!(texts(&output), vec![" Hello."]);
+        assert!(matches!(
+            output.get_warnings().as_slice(),
+            [Warning::InferenceRetried { attempts: 2, .. }]
+        ));
+
+        let misconfigured = Transcriber::with_mock_backend(
+            MockBackend::new(segments)
+                .failing_with(vec![whisper_rs::WhisperError::InvalidThreadCount]),
+        );
+        assert!(misconfigured
+            .transcribe_samples(&[0.0; 16000], &options)
+            .is_err());
+    }
+
+    #[test]
+    fn test_mock_repairs_timestamps_past_audio() {
+        let transcriber = Transcriber::with_mock_backend(MockBackend::new(vec![MockSegment::new(
+            0, 500, " Hello.",
+        )]));
+
+        let output = transcriber
+            .transcribe_samples(&[0.0; 16000], &TranscribeOptions::default())
+            .unwrap();
+
+        assert_eq!(output.get_segments()[0].get_end_timestamp(), &100);
+        assert!(matches!(
+            output.get_warnings().as_slice(),
+            [Warning::TimestampRepaired { segment: 0, .. }]
+        ));
+    }
+
+    #[test]
+    fn test_mock_validates_options_against_model() {
+        let transcriber =
+            Transcriber::with_mock_backend(MockBackend::new(Vec::new()).english_only());
+        let options = TranscribeOptions {
+            language: Some("de".to_string()),
+            ..Default::default()
+        };
+
+        let err = transcriber
+            .transcribe_samples(&[0.0; 16000], &options)
+            .unwrap_err();
+
+        assert!(matches!(
+            err.downcast_ref::<Error>(),
+            Some(Error::IncompatibleOptions(_))
+        ));
+    }
+
+    #[test]
+    fn test_mock_output_exports_srt() {
+        let transcriber = Transcriber::with_mock_backend(MockBackend::new(vec![MockSegment::new(
+            0, 150, " Hello.",
+        )]));
+
+        let output = transcriber
+            .transcribe_samples(&[0.0; 32000], &TranscribeOptions::default())
+            .unwrap();
+
+        assert_eq!(
+            output.to_srt(),
+            "1\n00:00:00,000 --> 00:00:01,500\nHello.\n\n"
+        );
+    }
+
+    #[test]
+    fn test_group_tokens_into_words() {
+        let tokens = vec![
+            (" Hel".to_string(), 0, 10, 0.5),
+            ("lo".to_string(), 10, 20, 1.0),
+            (" world".to_string(), 25, 40, 0.25),
+            (".".to_string(), 40, 42, 0.75),
+        ];
+
+        let words = group_tokens_into_words(&tokens);
+
+        assert_eq!(
+            words,
+            vec![
+                TranscriberOutputWord::new(0, 20, " Hello").with_probability(0.75),
+                TranscriberOutputWord::new(25, 42, " world.").with_probability(0.5),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_screen_empty_audio_warns() {
+        let output = screen_empty_audio(&[], &TranscribeOptions::default())
+            .unwrap()
+            .unwrap();
+
+        assert!(output.get_segments().is_empty());
+        assert_eq!(output.get_audio_duration(), Some(0));
+        assert_eq!(output.get_warnings(), &vec![Warning::NoAudio]);
+    }
+
+    #[test]
+    fn test_screen_empty_audio_errors() {
+        let options = TranscribeOptions {
+            empty_audio: EmptyAudioPolicy::Error,
+            ..Default::default()
+        };
+
+        assert!(matches!(
+            screen_empty_audio(&[0.0; 100], &options),
+            Err(Error::EmptyAudio)
+        ));
+    }
+
+    #[test]
+    fn test_screen_empty_audio_lets_silence_through() {
+        assert!(
+            screen_empty_audio(&[0.0; 16000], &TranscribeOptions::default())
+                .unwrap()
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn test_segment_sample_positions() {
+        let audio: Vec<f32> = (0..48000).map(|i| i as f32).collect();
+        let segment = TranscriberOutputSegment::new(150, 275, " Hi.");
+
+        assert_eq!(segment.get_start_sample(), 24000);
+        assert_eq!(segment.get_end_sample(), 44000);
+        let clip = &audio[segment.get_start_sample()..segment.get_end_sample()];
+        assert_eq!((clip[0], clip.len()), (24000.0, 20000));
+        assert_eq!(
+            TranscriberOutputSegment::new(-3, 0, "").get_start_sample(),
+            0
+        );
+    }
+
+    #[test]
+    fn test_assign_seeks_rebuilds_windows() {
+        let mut segments = vec![
+            TranscriberOutputSegment::new(500, 1200, " a"),
+            TranscriberOutputSegment::new(1200, 2800, " b"),
+            TranscriberOutputSegment::new(2900, 3600, " c"),
+            TranscriberOutputSegment::new(3600, 5600, " d"),
+            TranscriberOutputSegment::new(9000, 9500, " e"),
+        ];
+
+        assign_seeks(&mut segments, 500);
+
+        let seeks: Vec<_> = segments.iter().map(|s| s.get_seek().unwrap()).collect();
+        // c ends past the first window, which ended with b. The window after d, from 5600 to
+        // 8600, decoded nothing, so e opens the one after it
+        assert_eq!(seeks, [500, 500, 2800, 2800, 8600]);
+        let windows: Vec<_> = seek_windows(&segments, 500)
+            .into_iter()
+            .map(|(_, window)| window)
+            .collect();
+        assert_eq!(windows, [0, 0, 1, 1, 3]);
+
+        // a segment longer than a window opens one at its start
+        let mut segments = vec![
+            TranscriberOutputSegment::new(0, 500, " a"),
+            TranscriberOutputSegment::new(600, 4000, " b"),
+        ];
+        assign_seeks(&mut segments, 0);
+        let seeks: Vec<_> = segments.iter().map(|s| s.get_seek().unwrap()).collect();
+        assert_eq!(seeks, [0, 600]);
+    }
+
+    #[test]
+    fn test_annotate_window() {
+        let mut segments = vec![TranscriberOutputSegment::new(0, 100, " a")];
+
+        annotate_window(&mut segments, 3, std::time::Duration::from_millis(250));
+
+        assert_eq!(segments[0].get_window_index(), Some(3));
+        assert_eq!(segments[0].get_decode_time_ms(), Some(250));
+        assert!(TranscriberOutputSegment::new(0, 100, " a")
+            .get_window_index()
+            .is_none());
+    }
+
+    #[test]
+    fn test_pad_with_silence() {
+        let mut short = vec![0.5; 6400];
+        let mut long = vec![0.5; 16000];
+
+        assert!(pad_with_silence(&mut short, 16000));
+        assert!(!pad_with_silence(&mut long, 16000));
+        assert_eq!(short.len(), 16000);
+        assert_eq!(short[6399], 0.5);
+        assert_eq!(short[6400], 0.0);
+        assert_eq!(long.len(), 16000);
+    }
+
+    #[tokio::test]
+    async fn component_test_sub_second_clip() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("short.wav");
+        let tone: Vec<i16> = (0..6400)
+            .map(|i| ((i as f32 * 0.1).sin() * 8000.0) as i16)
+            .collect();
+        crate::audio_parser::tests::write_test_wav(&path, 16000, 1, &tone);
+
+        let tiny_model_handler = model_handler::ModelHandler::new("Tiny", "models").await;
+        let whisper_wrp = Transcriber::new(tiny_model_handler);
+        let result = whisper_wrp
+            .transcribe(path.to_str().unwrap(), None)
+            .unwrap();
+
+        assert_eq!(result.get_audio_duration(), Some(40));
+        for segment in result.get_segments() {
+            assert!(*segment.get_end_timestamp() <= 40);
+        }
+        assert!(result.get_warnings().contains(&Warning::AudioPadded {
+            duration: 40,
+            padded_duration: 100
+        }));
+
+        let _ = std::fs::remove_dir_all("models/");
+    }
+
+    #[test]
+    fn test_chunk_ranges() {
+        let audio = [0.0; 10];
+        assert_eq!(chunk_ranges(&audio, 0..10, 4, 0), vec![0..4, 4..8, 8..10]);
+        assert_eq!(chunk_ranges(&audio, 0..8, 4, 0), vec![0..4, 4..8]);
+        assert_eq!(chunk_ranges(&audio, 6..10, 4, 0), vec![6..10]);
+        assert!(chunk_ranges(&audio, 0..0, 4, 0).is_empty());
+        assert!(chunk_ranges(&audio, 10..10, 4, 0).is_empty());
+    }
+
+    #[test]
+    fn test_chunk_ranges_cut_in_pauses() {
+        use crate::test_util::{silence, tone};
+        use std::time::Duration;
+
+        let speech = |seconds: f32| tone(440.0, Duration::from_secs_f32(seconds), 0.5);
+        // pauses at 11.5-12.3s and 23.0-23.6s, off the nominal cuts every 10s
+        let mut audio = speech(11.5);
+        audio.extend(silence(Duration::from_millis(800)));
+        audio.extend(speech(10.7));
+        audio.extend(silence(Duration::from_millis(600)));
+        audio.extend(speech(6.4));
+        let pauses = [184_000..196_800, 368_000..377_600];
+
+        let ranges = chunk_ranges(&audio, 0..audio.len(), 160_000, 48_000);
+
+        assert_eq!(ranges.len(), 3);
+        assert_eq!(ranges[2].end, audio.len());
+        for (range, pause) in ranges.iter().zip(&pauses) {
+            assert!(
+                pause.contains(&range.end),
+                "{:?} outside {:?}",
+                range,
+                pause
+            );
+        }
+        for pair in ranges.windows(2) {
+            assert_eq!(pair[0].end, pair[1].start);
+        }
+        let fixed = chunk_ranges(&audio, 0..audio.len(), 160_000, 0);
+        assert_eq!(
+            fixed,
+            vec![0..160_000, 160_000..320_000, 320_000..audio.len()]
+        );
+    }
+
+    #[test]
+    fn test_mock_chunk_boundaries_reported() {
+        use crate::test_util::{silence, tone};
+        use std::time::Duration;
+
+        let mut audio = tone(440.0, Duration::from_millis(11500), 0.5);
+        audio.extend(silence(Duration::from_millis(800)));
+        audio.extend(tone(440.0, Duration::from_millis(5000), 0.5));
+        let runs = vec![
+            vec![MockSegment::new(0, 1100, " One.")],
+            vec![MockSegment::new(0, 500, " Two.")],
+        ];
+        let options = TranscribeOptions {
+            chunk_length: Some(std::time::Duration::from_secs(10)),
+            chunk_boundary_search: std::time::Duration::from_secs(3),
+            ..Default::default()
+        };
+        let transcriber = Transcriber::with_mock_backend(MockBackend::with_runs(runs));
+        let mut checkpoints = Vec::new();
+
+        let output = transcriber
+            .run_from(
+                audio,
+                None,
+                &options,
+                Checkpoint::default(),
+                &mut |checkpoint| checkpoints.push(checkpoint.clone()),
+                &mut Callbacks::new(),
+                None,
+            )
+            .unwrap();
+
+        let boundaries = output.get_chunk_boundaries();
+        assert_eq!(boundaries.len(), 1);
+        assert!((1150..1230).contains(&boundaries[0]), "{:?}", boundaries);
+        assert_eq!(
+            *output.get_segments()[1].get_start_timestamp(),
+            boundaries[0]
+        );
+        assert_eq!(checkpoints[0].boundaries, checkpoints[1].boundaries);
+    }
+
+    #[tokio::test]
+    async fn component_test_n_best() {
+        let tiny_model_handler = model_handler::ModelHandler::new("Tiny", "models").await;
+        let whisper_wrp = Transcriber::new(tiny_model_handler);
+        let options = TranscribeOptions {
+            n_best: 3,
+            ..Default::default()
+        };
+        // five sampled candidates per segment, of which whisper keeps the best
+        let params =
+            whisper_rs::FullParams::new(whisper_rs::SamplingStrategy::Greedy { best_of: 5 });
+        let audio = audio_parser::try_parse_audio_file("src/test_data/test.mp3").unwrap();
+
+        let result = whisper_wrp.run(audio, Some(params), &options).unwrap();
+
+        assert!(!result.get_segments().is_empty());
+        for segment in result.get_segments() {
+            let alternatives = segment.alternatives();
+            assert_eq!(alternatives.len(), 1);
+            assert_eq!(alternatives[0].get_text(), segment.get_text());
+            assert!((0.0..=1.0).contains(&alternatives[0].get_score()));
+        }
+
+        let _ = std::fs::remove_dir_all("models/");
+    }
+
+    #[tokio::test]
+    async fn component_test_tiny_hyperparameters() {
+        let tiny_model_handler = model_handler::ModelHandler::new("Tiny", "models").await;
+        let whisper_wrp = Transcriber::new(tiny_model_handler);
+
+        assert_eq!(
+            whisper_wrp.hyperparameters(),
+            ModelHyperparameters {
+                model_type: "tiny".to_string(),
+                n_vocab: 51865,
+                n_audio_ctx: 1500,
+                n_audio_state: 384,
+                n_audio_head: 6,
+                n_audio_layer: 4,
+                n_text_ctx: 448,
+                n_text_state: 384,
+                n_text_head: 6,
+                n_text_layer: 4,
+                n_mels: 80,
+                ftype: 1,
+            }
+        );
+        assert!(whisper_wrp.hyperparameters().has_stock_vocabulary());
+
+        let _ = std::fs::remove_dir_all("models/");
+    }
+
+    #[tokio::test]
+    async fn component_test_happy_case() {
+        let expected_result = " By what he has said and done, a man judges himself by what he is willing to do, by what he might have said, or might have done, a judgment that is necessarily hapered, but only by the scope and limits of his imagination, but by the ever-changing measure of his doubt and self-esteem.";
+
+        let tiny_model_handler = model_handler::ModelHandler::new("Tiny", "models").await;
+        let whisper_wrp = Transcriber::new(tiny_model_handler);
+
+        let result = whisper_wrp
+            .transcribe("src/test_data/test.mp3", None)
+            .unwrap();
+
+        let mut result_text: String = String::new();
+        for segment in result.get_segments() {
+            result_text.push_str(segment.get_text());
+        }
+
+        assert_eq!(expected_result, result_text);
+
+        let _ = std::fs::remove_dir_all("models/");
+    }
+
+    /// Set in the copy of the test binary `child_stdout` runs.
+    const STDOUT_CHILD: &str = "SIMPLE_TRANSCRIBE_STDOUT_CHILD";
+    const STDOUT_BEGIN: &str = "<<stdout begins>>";
+    const STDOUT_END: &str = "<<stdout ends>>";
+
+    /// What `f` writes to stdout given what `setup` returns, including the writes of whisper's
+    /// C code, which bypass the test harness' capture. It runs in a copy of the test binary
+    /// running only `test`, so the output of tests running in parallel can't mix in. In that
+    /// copy this runs `setup` and `f` and returns `None`.
+    #[cfg(unix)]
+    fn child_stdout<T>(test: &str, setup: impl FnOnce() -> T, f: impl FnOnce(T)) -> Option<String> {
+        use std::io::Write;
+
+        if std::env::var_os(STDOUT_CHILD).is_some() {
+            let input = setup();
+            print!("{}", STDOUT_BEGIN);
+            std::io::stdout().flush().unwrap();
+            f(input);
+            std::io::stdout().flush().unwrap();
+            // SAFETY: flushing every C stream takes no pointer
+            unsafe { libc::fflush(std::ptr::null_mut()) };
+            print!("{}", STDOUT_END);
+            std::io::stdout().flush().unwrap();
+            return None;
+        }
+        let output = std::process::Command::new(std::env::current_exe().unwrap())
+            .args([test, "--exact", "--nocapture"])
+            .env(STDOUT_CHILD, "1")
+            .output()
+            .unwrap();
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        assert!(output.status.success(), "{}", stdout);
+        let begin = stdout.find(STDOUT_BEGIN).unwrap() + STDOUT_BEGIN.len();
+        let end = stdout.rfind(STDOUT_END).unwrap();
+        Some(stdout[begin..end].to_string())
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn component_test_prints_nothing_by_default() {
+        let stdout = child_stdout(
+            "transcriber::tests::component_test_prints_nothing_by_default",
+            || {
+                let runtime = tokio::runtime::Runtime::new().unwrap();
+                let tiny_model_handler =
+                    runtime.block_on(model_handler::ModelHandler::new("Tiny", "models"));
+                Transcriber::new(tiny_model_handler)
+            },
+            |whisper_wrp| {
+                whisper_wrp
+                    .transcribe("src/test_data/test.mp3", None)
+                    .unwrap();
+            },
+        );
+
+        if let Some(stdout) = stdout {
+            assert_eq!(stdout, "");
+        }
+    }
+
+    #[tokio::test]
+    #[ignore = "downloads the 1.6 GB large-v3-turbo model"]
+    async fn component_test_large_v3_turbo() {
+        let model_handler = model_handler::ModelHandler::new("large-v3-turbo", "models").await;
+        let whisper_wrp = Transcriber::new(model_handler);
+        let options = TranscribeOptions {
+            word_timestamps: true,
+            ..Default::default()
+        };
+
+        let result = whisper_wrp
+            .transcribe_with_options("src/test_data/test.mp3", &options)
+            .unwrap();
+
+        let text: String = result.get_segments().iter().map(|s| s.get_text()).collect();
+        assert!(text.contains("man judges himself"));
+        let audio_duration = result.get_audio_duration().unwrap();
+        for segment in result.get_segments() {
+            assert!(segment.get_start_timestamp() <= segment.get_end_timestamp());
+            assert!(*segment.get_end_timestamp() <= audio_duration);
+            for word in segment.get_words() {
+                assert!(word.get_start_timestamp() >= segment.get_start_timestamp());
+                assert!(word.get_end_timestamp() <= segment.get_end_timestamp());
+            }
+        }
+
+        let _ = std::fs::remove_dir_all("models/");
+    }
+
+    #[tokio::test]
+    async fn component_test_panicking_callback() {
+        let tiny_model_handler = model_handler::ModelHandler::new("Tiny", "models").await;
+        let whisper_wrp = Transcriber::new(tiny_model_handler);
+
+        let result = whisper_wrp.transcribe_with_callbacks(
+            "src/test_data/test.mp3",
+            &TranscribeOptions::default(),
+            Callbacks::new().on_segment(|_| panic!("callback failed")),
+        );
+
+        assert_callback_panicked(result);
+        assert!(whisper_wrp
+            .transcribe("src/test_data/test.mp3", None)
+            .is_ok());
+
+        let _ = std::fs::remove_dir_all("models/");
+    }
+
+    #[tokio::test]
+    async fn component_test_reproducible() {
+        let tiny_model_handler = model_handler::ModelHandler::new("Tiny", "models").await;
+        let whisper_wrp = Transcriber::new(tiny_model_handler);
+
+        let transcribe = || {
+            let result = whisper_wrp
+                .transcribe("src/test_data/test.mp3", None)
+                .unwrap();
+            result.to_json()
+        };
+
+        assert_eq!(transcribe(), transcribe());
+
+        let _ = std::fs::remove_dir_all("models/");
+    }
+
+    #[tokio::test]
+    async fn component_test_chunked() {
+        let tiny_model_handler = model_handler::ModelHandler::new("Tiny", "models").await;
+        let whisper_wrp = Transcriber::new(tiny_model_handler);
+        let options = TranscribeOptions {
+            chunk_length: Some(std::time::Duration::from_secs(10)),
+            segment_timing: true,
+            ..Default::default()
+        };
+
+        let result = whisper_wrp
+            .transcribe_with_options("src/test_data/test.mp3", &options)
+            .unwrap();
+
+        let result_text: String = result.get_segments().iter().map(|s| s.get_text()).collect();
+        assert!(result_text.contains("a man judges himself"));
+        for pair in result.get_segments().windows(2) {
+            assert!(pair[0].get_end_timestamp() <= pair[1].get_start_timestamp());
+            assert!(pair[0].get_window_index() <= pair[1].get_window_index());
+        }
+        assert!(result
+            .get_segments()
+            .iter()
+            .all(|s| s.get_window_index().is_some() && s.get_decode_time_ms().is_some()));
+
+        let _ = std::fs::remove_dir_all("models/");
+    }
+
+    #[tokio::test]
+    async fn component_test_resume_from_checkpoint() {
+        let tiny_model_handler = model_handler::ModelHandler::new("Tiny", "models").await;
+        let whisper_wrp = Transcriber::new(tiny_model_handler);
+        let options = TranscribeOptions {
+            chunk_length: Some(std::time::Duration::from_secs(10)),
+            ..Default::default()
+        };
+        let mut checkpoints = Vec::new();
+
+        let full = whisper_wrp
+            .transcribe_resumable("src/test_data/test.mp3", &options, None, |checkpoint| {
+                checkpoints.push(checkpoint.clone())
+            })
+            .unwrap();
+        let resumed = whisper_wrp
+            .transcribe_resumable(
+                "src/test_data/test.mp3",
+                &options,
+                Some(checkpoints[0].clone()),
+                |_| {},
+            )
+            .unwrap();
+
+        assert!(checkpoints.len() > 1);
+        assert_eq!(checkpoints[0].get_sample_offset(), 160000);
+        let texts = |output: &TranscriberOutput| -> Vec<String> {
+            output
+                .get_segments()
+                .iter()
+                .map(|s| s.get_text().to_string())
+                .collect()
+        };
+        assert_eq!(texts(&resumed), texts(&full));
+
+        let _ = std::fs::remove_dir_all("models/");
+    }
+
+    #[tokio::test]
+    async fn component_test_single_segment_ends_at_audio_end() {
+        let tiny_model_handler = model_handler::ModelHandler::new("Tiny", "models").await;
+        let whisper_wrp = Transcriber::new(tiny_model_handler);
+        let options = TranscribeOptions {
+            single_segment: true,
+            ..Default::default()
+        };
+
+        let result = whisper_wrp
+            .transcribe_with_options("src/test_data/test.mp3", &options)
+            .unwrap();
+
+        assert_eq!(result.get_segments().len(), 1);
+        assert!(
+            *result.get_segments()[0].get_end_timestamp() <= result.get_audio_duration().unwrap()
+        );
+
+        let _ = std::fs::remove_dir_all("models/");
+    }
+
+    #[tokio::test]
+    async fn component_test_context_info() {
+        let tiny_model_handler = model_handler::ModelHandler::new("Tiny", "models").await;
+        let whisper_wrp = Transcriber::new(tiny_model_handler);
+
+        let info = whisper_wrp.context_info();
+
+        assert_eq!(info.model_type, "tiny");
+        assert!(info.multilingual);
+        assert!(info.vocab_size > 0);
+        assert_eq!(info.backend, Backend::Cpu);
+
+        let _ = std::fs::remove_dir_all("models/");
+    }
+
+    #[tokio::test]
+    async fn component_test_is_multilingual() {
+        let tiny_en = model_handler::ModelHandler::new("tiny.en", "models").await;
+        assert!(!Transcriber::new(tiny_en).is_multilingual());
+        let tiny = model_handler::ModelHandler::new("tiny", "models").await;
+        assert!(Transcriber::new(tiny).is_multilingual());
+
+        let _ = std::fs::remove_dir_all("models/");
+    }
+
+    #[tokio::test]
+    async fn component_test_data_uri() {
+        use base64::Engine;
+
+        let tiny_model_handler = model_handler::ModelHandler::new("Tiny", "models").await;
+        let whisper_wrp = Transcriber::new(tiny_model_handler);
+        let audio = std::fs::read("src/test_data/test.mp3").unwrap();
+        let uri = format!(
+            "data:audio/mpeg;base64,{}",
+            base64::engine::general_purpose::STANDARD.encode(audio)
+        );
+
+        let from_uri = whisper_wrp.transcribe_data_uri(&uri).unwrap();
+        let from_file = whisper_wrp
+            .transcribe("src/test_data/test.mp3", None)
+            .unwrap();
+
+        assert_eq!(from_uri.to_json(), from_file.to_json());
+
+        let _ = std::fs::remove_dir_all("models/");
+    }
+
+    #[tokio::test]
+    async fn component_test_48k() {
+        let expected_result = " By what he has said and done, a man judges himself by what he is willing to do, by what he might have said, or might have done, a judgment that is necessarily hapered, but only by the scope and limits of his imagination, but by the ever-changing measure of his doubt and self-esteem.";
+
+        let tiny_model_handler = model_handler::ModelHandler::new("Tiny", "models").await;
+        let whisper_wrp = Transcriber::new(tiny_model_handler);
+
+        let result = whisper_wrp
+            .transcribe("src/test_data/test_48k_mono.mp3", None)
+            .unwrap();
+
+        let mut result_text: String = String::new();
+        for segment in result.get_segments() {
+            result_text.push_str(segment.get_text());
+        }
+        // resampling doesn't give whisper the 16KHz fixture's exact samples, so the words are
+        // compared, allowing a few in the 50 to differ
+        let expected_words = normalized_words(expected_result);
+        let errors = word_errors(&expected_words, &normalized_words(&result_text));
+        assert!(errors <= 3, "{} word errors: {}", errors, result_text);
+
+        let _ = std::fs::remove_dir_all("models/");
+    }
 
     #[tokio::test]
     async fn component_test_16k_stereo() {
@@ -148,7 +3886,7 @@ mod tests {
             .transcribe("src/test_data/test_16k_stereo.mp3", None)
             .unwrap();
 
-        let mut result_text:String = String::new();
+        let mut result_text: String = String::new();
         for segment in result.get_segments() {
             result_text.push_str(segment.get_text());
         }
@@ -0,0 +1,194 @@
+use std::fmt;
+
+use crate::system::Backend;
+
+/// A non fatal issue noticed while transcribing, recorded on the `TranscriberOutput`.
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+pub enum Warning {
+    /// A segment's text contains U+FFFD replacement characters, usually caused by whisper
+    /// emitting invalid UTF-8.
+    ReplacementCharacters { segment: usize, count: usize },
+    /// The audio holds no samples, so nothing was transcribed.
+    NoAudio,
+    /// The audio was shorter than `TranscribeOptions::min_duration` and was padded with
+    /// silence before inference, durations in centiseconds.
+    AudioPadded { duration: i64, padded_duration: i64 },
+    /// Inference failed and was retried, see `TranscribeOptions::retry`. `n_threads` and
+    /// `backend` are the configuration of the successful attempt, `n_threads` is `None` when
+    /// whisper's default was kept.
+    InferenceRetried {
+        attempts: u32,
+        errors: Vec<String>,
+        n_threads: Option<i32>,
+        backend: Backend,
+    },
+    /// A segment's timestamps were invalid and have been repaired.
+    TimestampRepaired {
+        segment: usize,
+        issue: TimestampIssue,
+    },
+    /// Word timestamps were requested but the model or whisper build did not provide token
+    /// timings for `segments` segments, which were kept with their segment timestamps only.
+    WordTimestampsUnavailable { segments: usize },
+    /// The model's vocabulary isn't a stock whisper vocabulary, so special tokens may be
+    /// misread and the text may be garbled, see `ModelHyperparameters::has_stock_vocabulary`.
+    NonStockVocabulary { vocab_size: i32 },
+    /// The audio's estimated signal-to-noise ratio is below
+    /// `TranscribeOptions::min_snr_db`, the transcript may be poor.
+    PoorAudioQuality { snr_db: f32, min_snr_db: f32 },
+    /// The receiver of the segment channel fell behind and `count` segments were dropped
+    /// from it, see `callbacks::Backpressure::DropOldest`. The transcript still has them.
+    SegmentsDropped { count: usize },
+    /// The file's metadata declares a language whisper doesn't know, so the language was
+    /// detected instead (or `TranscribeOptions::language` used, on English-only models), see
+    /// `TranscribeOptions::use_metadata_language`.
+    InvalidLanguageMetadata { tag: String },
+    /// The file's header declares a sample rate in Hz other than the one its audio decodes
+    /// at. The decoded rate was trusted.
+    SampleRateMismatch { declared: u32, decoded: u32 },
+    /// `TranscribeOptions::gain_db` would have clipped `limited_samples` of the
+    /// `total_samples` samples, which the limiter bent back under full scale.
+    AudioLimited {
+        gain_db: f32,
+        limited_samples: usize,
+        total_samples: usize,
+    },
+    /// whisper repeated the text of segment `segment` in `repeats` consecutive segments, a
+    /// loop it falls into on bad audio, which were collapsed into that segment, see
+    /// `TranscribeOptions::collapse_repetitions`.
+    RepetitionCollapsed { segment: usize, repeats: usize },
+}
+
+impl Warning {
+    /// The name of the warning's variant, such as `"NoAudio"`, as it appears in JSON.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Warning::ReplacementCharacters { .. } => "ReplacementCharacters",
+            Warning::NoAudio => "NoAudio",
+            Warning::AudioPadded { .. } => "AudioPadded",
+            Warning::InferenceRetried { .. } => "InferenceRetried",
+            Warning::TimestampRepaired { .. } => "TimestampRepaired",
+            Warning::WordTimestampsUnavailable { .. } => "WordTimestampsUnavailable",
+            Warning::NonStockVocabulary { .. } => "NonStockVocabulary",
+            Warning::PoorAudioQuality { .. } => "PoorAudioQuality",
+            Warning::SegmentsDropped { .. } => "SegmentsDropped",
+            Warning::InvalidLanguageMetadata { .. } => "InvalidLanguageMetadata",
+            Warning::SampleRateMismatch { .. } => "SampleRateMismatch",
+            Warning::AudioLimited { .. } => "AudioLimited",
+            Warning::RepetitionCollapsed { .. } => "RepetitionCollapsed",
+        }
+    }
+}
+
+/// A problem with a segment's timestamps, all values in centiseconds.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize)]
+pub enum TimestampIssue {
+    /// The segment starts before zero, repaired by starting it at zero.
+    NegativeStart { start: i64 },
+    /// The segment ends before it starts, repaired by swapping start and end.
+    Inverted { start: i64, end: i64 },
+    /// The segment ends after the audio does, repaired by clamping it to the audio duration.
+    EndPastAudio { end: i64, audio_duration: i64 },
+    /// The segment starts before the previous one ends, repaired by starting it where the
+    /// previous one ends (and extending its end to that point if needed).
+    Overlap { start: i64, previous_end: i64 },
+}
+
+impl fmt::Display for Warning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Warning::ReplacementCharacters { segment, count } => write!(
+                f,
+                "segment {} contains {} replacement character(s)",
+                segment, count
+            ),
+            Warning::NoAudio => write!(f, "the audio holds no samples"),
+            Warning::AudioPadded {
+                duration,
+                padded_duration,
+            } => write!(
+                f,
+                "audio of {} centiseconds padded with silence to {}",
+                duration, padded_duration
+            ),
+            Warning::InferenceRetried {
+                attempts, errors, ..
+            } => write!(
+                f,
+                "inference succeeded after {} attempts ({})",
+                attempts,
+                errors.join("; ")
+            ),
+            Warning::TimestampRepaired { segment, issue } => {
+                write!(f, "segment {}: repaired {}", segment, issue)
+            }
+            Warning::WordTimestampsUnavailable { segments } => write!(
+                f,
+                "no token timings for {} segment(s), kept without word timestamps",
+                segments
+            ),
+            Warning::NonStockVocabulary { vocab_size } => write!(
+                f,
+                "the model's vocabulary of {} tokens isn't a stock whisper vocabulary",
+                vocab_size
+            ),
+            Warning::PoorAudioQuality { snr_db, min_snr_db } => write!(
+                f,
+                "the audio is very noisy ({:.1} dB signal-to-noise, below {:.1} dB), the \
+                 transcript may be poor",
+                snr_db, min_snr_db
+            ),
+            Warning::SegmentsDropped { count } => {
+                write!(f, "{} segment(s) dropped from the segment channel", count)
+            }
+            Warning::InvalidLanguageMetadata { tag } => write!(
+                f,
+                "the file declares the unknown language '{}', ignored",
+                tag
+            ),
+            Warning::SampleRateMismatch { declared, decoded } => write!(
+                f,
+                "the header declares {}Hz but the audio decodes at {}Hz, trusted the audio",
+                declared, decoded
+            ),
+            Warning::AudioLimited {
+                gain_db,
+                limited_samples,
+                total_samples,
+            } => write!(
+                f,
+                "the limiter engaged on {:.2}% of the samples with {} dB of gain",
+                100.0 * *limited_samples as f32 / *total_samples as f32,
+                gain_db
+            ),
+            Warning::RepetitionCollapsed { segment, repeats } => write!(
+                f,
+                "segment {}: the text repeated {} times in a row, collapsed",
+                segment, repeats
+            ),
+        }
+    }
+}
+
+impl fmt::Display for TimestampIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TimestampIssue::NegativeStart { start } => write!(f, "negative start {}", start),
+            TimestampIssue::Inverted { start, end } => {
+                write!(f, "end {} before start {}", end, start)
+            }
+            TimestampIssue::EndPastAudio {
+                end,
+                audio_duration,
+            } => write!(f, "end {} past the audio duration {}", end, audio_duration),
+            TimestampIssue::Overlap {
+                start,
+                previous_end,
+            } => write!(
+                f,
+                "start {} before the previous segment's end {}",
+                start, previous_end
+            ),
+        }
+    }
+}
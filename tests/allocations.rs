@@ -0,0 +1,71 @@
+//! Allocation counts of transcriptions, in their own test binary so the counting allocator
+//! doesn't replace the allocator of the library's tests.
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+
+use simple_transcribe_rs::options::TranscribeOptions;
+use simple_transcribe_rs::test_util::{MockBackend, MockSegment};
+use simple_transcribe_rs::transcriber::{Transcriber, TranscriberOutput};
+
+/// Counts the allocations of each thread, so tests running in parallel don't skew each
+/// other's counts.
+struct CountingAllocator;
+
+thread_local! {
+    static ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+}
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let _ = ALLOCATIONS.try_with(|count| count.set(count.get() + 1));
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+fn count_allocations(f: impl FnOnce()) -> usize {
+    let before = ALLOCATIONS.with(Cell::get);
+    f();
+    ALLOCATIONS.with(Cell::get) - before
+}
+
+#[test]
+fn test_transcribe_into_allocates_nothing_per_segment() {
+    let audio = vec![0.0; 48000];
+    let options = TranscribeOptions::default();
+    // allocations of a transcription reusing its output and of a fresh one
+    let allocations = |n_segments: i64| {
+        let segments = (0..n_segments)
+            .map(|i| MockSegment::new(i * 5, i * 5 + 5, " Hello there."))
+            .collect();
+        let transcriber = Transcriber::with_mock_backend(MockBackend::new(segments));
+        let mut out = TranscriberOutput::default();
+        transcriber
+            .transcribe_into(&audio, &options, &mut out)
+            .unwrap();
+        let reused = count_allocations(|| {
+            transcriber
+                .transcribe_into(&audio, &options, &mut out)
+                .unwrap()
+        });
+        assert_eq!(out.get_segments().len(), n_segments as usize);
+        let fresh = count_allocations(|| {
+            transcriber.transcribe_samples(&audio, &options).unwrap();
+        });
+        (reused, fresh)
+    };
+
+    let (reused_few, fresh_few) = allocations(2);
+    let (reused_many, fresh_many) = allocations(50);
+
+    assert_eq!(reused_many, reused_few);
+    assert!(fresh_many >= fresh_few + 48, "{} {}", fresh_few, fresh_many);
+    assert!(reused_many < fresh_many);
+}